@@ -0,0 +1,143 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use nt_apiset::{synthetic_map, ApiSetMap, SYNTHETIC_MAP_NAMESPACE_ENTRIES};
+
+/// Seed of the synthetic API Set Map, fixed to make all runs comparable.
+const SEED: u64 = 0x4170_6953_6574;
+
+fn namespace_entry_names(map: &ApiSetMap) -> Vec<String> {
+    map.namespace_entries()
+        .unwrap()
+        .map(|namespace_entry| namespace_entry.name().unwrap().to_string().unwrap())
+        .collect()
+}
+
+fn bench_lookup(c: &mut Criterion) {
+    let bytes = synthetic_map(SEED);
+    let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+    let names = namespace_entry_names(&map);
+    let hit = &names[names.len() / 2];
+    let import_names = names
+        .iter()
+        .map(|name| format!("{name}.dll"))
+        .collect::<Vec<_>>();
+
+    println!(
+        "Synthetic API Set Map: {} Namespace Entries, {} bytes",
+        SYNTHETIC_MAP_NAMESPACE_ENTRIES,
+        bytes.len()
+    );
+
+    c.bench_function("find_namespace_entry hit", |b| {
+        b.iter(|| map.find_namespace_entry(black_box(hit)).unwrap().unwrap())
+    });
+
+    c.bench_function("find_namespace_entry miss", |b| {
+        b.iter(|| map.find_namespace_entry(black_box("api-ms-win-core-missing-l1-1-0")))
+    });
+
+    c.bench_function("find_namespace_entry_unverified hit", |b| {
+        b.iter(|| {
+            map.find_namespace_entry_unverified(black_box(hit))
+                .unwrap()
+                .unwrap()
+        })
+    });
+
+    c.bench_function("resolve batch", |b| {
+        b.iter(|| {
+            for import_name in &import_names {
+                black_box(map.resolve(black_box(import_name), Some("kernel32.dll")));
+            }
+        })
+    });
+
+    c.bench_function("iterate all entries", |b| {
+        b.iter(|| {
+            for namespace_entry in map.namespace_entries().unwrap() {
+                black_box(namespace_entry.name().unwrap());
+
+                for value_entry in namespace_entry.value_entries().unwrap() {
+                    black_box(value_entry.value().unwrap());
+                }
+            }
+        })
+    });
+
+    c.bench_function("validate", |b| b.iter(|| map.validate().unwrap()));
+
+    c.bench_function("parse", |b| {
+        b.iter_batched(
+            || bytes.as_slice(),
+            |bytes| ApiSetMap::try_from_apiset_section_bytes(black_box(bytes)).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+/// Compares [`ApiSetMap::find_namespace_entry`] and [`ApiSetMap::resolve`] with their [`ApiSetIndex`](nt_apiset::ApiSetIndex) counterparts
+/// by looking up every Namespace Entry of `map`.
+fn bench_index_for(c: &mut Criterion, label: &str, map: &ApiSetMap) {
+    let names = namespace_entry_names(map);
+    let import_names = names
+        .iter()
+        .map(|name| format!("{name}.dll"))
+        .collect::<Vec<_>>();
+    let index = map.build_index().unwrap();
+
+    let mut group = c.benchmark_group(format!("index {label}"));
+
+    group.bench_function("find_namespace_entry all", |b| {
+        b.iter(|| {
+            for name in &names {
+                black_box(map.find_namespace_entry(black_box(name)));
+            }
+        })
+    });
+
+    group.bench_function("ApiSetIndex::get all", |b| {
+        b.iter(|| {
+            for name in &names {
+                black_box(index.get(black_box(name)));
+            }
+        })
+    });
+
+    group.bench_function("resolve all", |b| {
+        b.iter(|| {
+            for import_name in &import_names {
+                black_box(map.resolve(black_box(import_name), Some("kernel32.dll")));
+            }
+        })
+    });
+
+    group.bench_function("ApiSetIndex::resolve all", |b| {
+        b.iter(|| {
+            for import_name in &import_names {
+                black_box(index.resolve(black_box(import_name), Some("kernel32.dll")));
+            }
+        })
+    });
+
+    group.bench_function("build_index", |b| b.iter(|| map.build_index().unwrap()));
+
+    group.finish();
+}
+
+/// Benchmarks the [`ApiSetIndex`](nt_apiset::ApiSetIndex) against the synthetic API Set Map,
+/// and additionally against the `apisetschema.dll` given in the `NT_APISET_BENCH_DLL` environment variable.
+fn bench_index(c: &mut Criterion) {
+    let bytes = synthetic_map(SEED);
+    let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+    bench_index_for(c, "synthetic", &map);
+
+    #[cfg(feature = "pelite")]
+    if let Some(path) = std::env::var_os("NT_APISET_BENCH_DLL") {
+        let dll = std::fs::read(&path).unwrap();
+        let pe_file = pelite::PeFile::from_bytes(&dll).unwrap();
+        let map = ApiSetMap::try_from_pe_file(pe_file).unwrap();
+        bench_index_for(c, "apisetschema.dll", &map);
+    }
+}
+
+criterion_group!(benches, bench_lookup, bench_index);
+criterion_main!(benches);
@@ -0,0 +1,73 @@
+//! Demonstrates that nt-apiset works in a `no_std` environment without `pelite`.
+//!
+//! Build it via `cargo build` in this directory.
+//! This produces a static library exporting a single `nostd_lookup` function, which resolves an API Set
+//! against an embedded `.apiset` section and formats the result into a fixed-size buffer.
+//!
+//! The embedded section is synthetic and only contains a handful of entries.
+
+#![no_std]
+
+use core::fmt::{self, Write};
+
+use nt_apiset::{ApiSetMap, Result};
+
+static APISET_SECTION: &[u8] = include_bytes!("nostd_lookup.apiset");
+
+/// A [`fmt::Write`] implementation that writes into a fixed-size buffer.
+struct FixedBuffer {
+    bytes: [u8; 128],
+    length: usize,
+}
+
+impl Write for FixedBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.length + s.len();
+        let target = self.bytes.get_mut(self.length..end).ok_or(fmt::Error)?;
+        target.copy_from_slice(s.as_bytes());
+        self.length = end;
+
+        Ok(())
+    }
+}
+
+fn lookup(buffer: &mut FixedBuffer) -> Result<Option<()>> {
+    let map = ApiSetMap::try_from_apiset_section_bytes(APISET_SECTION)?;
+
+    let namespace_entry = match map.find_namespace_entry("api-ms-win-core-sysinfo-l1-1-0") {
+        Some(namespace_entry) => namespace_entry?,
+        None => return Ok(None),
+    };
+    let value_entry = match namespace_entry.value_entries()?.next() {
+        Some(value_entry) => value_entry,
+        None => return Ok(None),
+    };
+
+    let name = namespace_entry.name()?;
+    let default_value = value_entry.value()?;
+
+    if write!(buffer, "{name} -> {default_value}").is_err() {
+        return Ok(None);
+    }
+
+    Ok(Some(()))
+}
+
+/// Returns the length of the formatted resolution result, or zero if the lookup failed.
+#[no_mangle]
+pub extern "C" fn nostd_lookup() -> usize {
+    let mut buffer = FixedBuffer {
+        bytes: [0; 128],
+        length: 0,
+    };
+
+    match lookup(&mut buffer) {
+        Ok(Some(())) => buffer.length,
+        _ => 0,
+    }
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {}
+}
@@ -0,0 +1,427 @@
+use std::fs;
+
+use anyhow::{bail, Result};
+use nt_apiset::{ApiSetMap, ApiSetNamespaceEntry, HexdumpGutter, HexdumpOptions};
+use pelite::PeFile;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+/// Number of entries skipped by PageUp and PageDown.
+const PAGE_SIZE: usize = 10;
+
+struct App<'a> {
+    map: ApiSetMap<'a>,
+    /// Offsets of all Namespace Entries, to get the index of a search match for the hexdump.
+    offsets: Vec<usize>,
+    filter: String,
+    matches: Vec<nt_apiset::Result<ApiSetNamespaceEntry<'a>>>,
+    list_state: ListState,
+    show_hexdump: bool,
+}
+
+impl<'a> App<'a> {
+    fn new(map: ApiSetMap<'a>) -> Self {
+        let offsets = match map.namespace_entries() {
+            Ok(namespace_entries) => namespace_entries.map(|entry| entry.offset()).collect(),
+            Err(_) => Vec::new(),
+        };
+
+        let mut app = Self {
+            map,
+            offsets,
+            filter: String::new(),
+            matches: Vec::new(),
+            list_state: ListState::default(),
+            show_hexdump: false,
+        };
+        app.update_matches();
+        app
+    }
+
+    /// Handles a key press and returns `false` if the explorer shall be closed.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Esc if self.filter.is_empty() => return false,
+            KeyCode::Esc => {
+                self.filter.clear();
+                self.update_matches();
+            }
+            KeyCode::Tab => self.show_hexdump = !self.show_hexdump,
+            KeyCode::Up => self.move_selection(-1),
+            KeyCode::Down => self.move_selection(1),
+            KeyCode::PageUp => self.move_selection(-(PAGE_SIZE as isize)),
+            KeyCode::PageDown => self.move_selection(PAGE_SIZE as isize),
+            KeyCode::Home => self.move_selection(isize::MIN),
+            KeyCode::End => self.move_selection(isize::MAX),
+            KeyCode::Backspace => {
+                self.filter.pop();
+                self.update_matches();
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.filter.push(c);
+                self.update_matches();
+            }
+            _ => (),
+        }
+
+        true
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        if let Some(selected) = self.list_state.selected() {
+            let last = self.matches.len() - 1;
+            let selected = if delta < 0 {
+                selected.saturating_sub(delta.unsigned_abs())
+            } else {
+                selected.saturating_add(delta as usize).min(last)
+            };
+            self.list_state.select(Some(selected));
+        }
+    }
+
+    fn update_matches(&mut self) {
+        self.matches = match self.map.find_namespace_entries_containing(&self.filter) {
+            Ok(matches) => matches.collect(),
+            Err(e) => vec![Err(e)],
+        };
+
+        let selected = if self.matches.is_empty() {
+            None
+        } else {
+            Some(0)
+        };
+        self.list_state.select(selected);
+    }
+
+    fn selected(&self) -> Option<&nt_apiset::Result<ApiSetNamespaceEntry<'a>>> {
+        self.list_state
+            .selected()
+            .and_then(|selected| self.matches.get(selected))
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
+        let [left, right] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(frame.area());
+        let [filter_area, list_area] =
+            Layout::vertical([Constraint::Length(3), Constraint::Min(0)]).areas(left);
+
+        let filter = Paragraph::new(self.filter.as_str()).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Filter (Esc: clear/quit) "),
+        );
+        frame.render_widget(filter, filter_area);
+
+        let items = self
+            .matches
+            .iter()
+            .map(|result| match result.as_ref().map(|entry| entry.name()) {
+                Ok(Ok(name)) => ListItem::new(name.to_string_lossy()),
+                Ok(Err(e)) => ListItem::new(error_line(&e)),
+                Err(e) => ListItem::new(error_line(e)),
+            })
+            .collect::<Vec<_>>();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(format!(
+                " Namespace Entries ({}/{}) ",
+                self.matches.len(),
+                self.map.count()
+            )))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> ");
+        frame.render_stateful_widget(list, list_area, &mut self.list_state);
+
+        let (title, lines) = if self.show_hexdump {
+            (" Hexdump (Tab: details) ", self.hexdump_lines())
+        } else {
+            (" Details (Tab: hexdump) ", self.detail_lines())
+        };
+        let details =
+            Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title));
+        frame.render_widget(details, right);
+    }
+
+    fn detail_lines(&self) -> Vec<Line<'static>> {
+        let namespace_entry = match self.selected() {
+            Some(Ok(namespace_entry)) => namespace_entry,
+            Some(Err(e)) => return vec![error_line(e)],
+            None => return vec![Line::from("No matching Namespace Entry")],
+        };
+
+        let mut lines = Vec::new();
+
+        match namespace_entry.name() {
+            Ok(name) => lines.push(Line::from(format!("Name:        {name}"))),
+            Err(e) => lines.push(error_line(&e)),
+        }
+
+        let offset = namespace_entry.offset();
+        lines.push(Line::from(match self.map.to_rva(offset) {
+            Some(rva) => format!("Offset:      {offset:#x} (RVA {rva:#x})"),
+            None => format!("Offset:      {offset:#x}"),
+        }));
+        lines.push(Line::from(format!(
+            "Flags:       {:?} ({:#x})",
+            namespace_entry.flags(),
+            namespace_entry.raw_flags()
+        )));
+
+        match namespace_entry.hashed_name() {
+            // API Set Maps before version 6 have no hash table.
+            Ok(hashed_name) if self.map.version() >= 6 => {
+                let hash = self.map.hash_name(&hashed_name.to_string_lossy());
+                lines.push(Line::from(format!("Hashed name: {hashed_name}")));
+                lines.push(Line::from(format!("Hash:        {hash:#010x}")));
+            }
+            Ok(_) => (),
+            Err(e) => lines.push(error_line(&e)),
+        }
+
+        lines.push(Line::from(""));
+        lines.push(Line::from("Value Entries:"));
+
+        let value_entries = match namespace_entry.value_entries() {
+            Ok(value_entries) => value_entries,
+            Err(e) => {
+                lines.push(error_line(&e));
+                return lines;
+            }
+        };
+
+        for value_entry in value_entries {
+            let importing_module = match value_entry.name() {
+                Ok(name) if name.is_empty() => String::from("(default)"),
+                Ok(name) => name.to_string_lossy(),
+                Err(e) => {
+                    lines.push(error_line(&e));
+                    continue;
+                }
+            };
+            let host_module = match value_entry.value() {
+                Ok(value) => value.to_string_lossy(),
+                Err(e) => {
+                    lines.push(error_line(&e));
+                    continue;
+                }
+            };
+
+            lines.push(Line::from(format!(
+                "  [{}] {importing_module} -> {host_module} (offset {:#x}, flags {:#x})",
+                value_entry.array_index(),
+                value_entry.offset(),
+                value_entry.raw_flags()
+            )));
+        }
+
+        lines
+    }
+
+    fn hexdump_lines(&self) -> Vec<Line<'static>> {
+        let namespace_entry = match self.selected() {
+            Some(Ok(namespace_entry)) => namespace_entry,
+            Some(Err(e)) => return vec![error_line(e)],
+            None => return vec![Line::from("No matching Namespace Entry")],
+        };
+
+        let index = match self.offsets.binary_search(&namespace_entry.offset()) {
+            Ok(index) => index,
+            Err(_) => return vec![Line::from("Namespace Entry not found in the entry array")],
+        };
+
+        let options = HexdumpOptions::new()
+            .bytes_per_line(8)
+            .gutter(HexdumpGutter::Utf16)
+            .namespace_entry(index);
+        let mut hexdump = String::new();
+
+        match self.map.annotate_hexdump(&mut hexdump, &options) {
+            Ok(()) => hexdump
+                .lines()
+                .map(|line| Line::from(line.to_owned()))
+                .collect(),
+            Err(e) => vec![error_line(&e)],
+        }
+    }
+}
+
+fn error_line(error: &nt_apiset::NtApiSetError) -> Line<'static> {
+    Line::styled(format!("⚠ {error}"), Style::default().fg(Color::Red))
+}
+
+fn run(terminal: &mut DefaultTerminal, app: &mut App) -> Result<()> {
+    loop {
+        terminal.draw(|frame| app.render(frame))?;
+
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press && !app.handle_key(key) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    let args = std::env::args().collect::<Vec<_>>();
+
+    if args.len() != 2 {
+        println!("Usage: apiset_explorer <FILENAME>");
+        println!("Example: apiset_explorer C:\\Windows\\system32\\apisetschema.dll");
+        println!();
+        println!("FILENAME may also contain the raw bytes of an .apiset section, which are parsed leniently.");
+        bail!("Aborted");
+    }
+
+    let bytes = fs::read(&args[1])?;
+    let map = match PeFile::from_bytes(&bytes) {
+        Ok(pe_file) => ApiSetMap::try_from_pe_file(pe_file)?,
+        Err(_) => ApiSetMap::try_from_apiset_section_bytes_lenient(&bytes)?,
+    };
+
+    let mut app = App::new(map);
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, &mut app);
+    ratatui::restore();
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nt_apiset::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder, ApiSetNamespaceEntryFlags};
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    fn build() -> Vec<u8> {
+        ApiSetMapBuilder::new()
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "api-ms-win-core-bar-l1-1-0",
+                    ApiSetNamespaceEntryFlags::SEALED,
+                )
+                .add_value_entry("", "bar.dll"),
+            )
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "api-ms-win-core-foo-l1-1-0",
+                    ApiSetNamespaceEntryFlags::SEALED,
+                )
+                .add_value_entry("", "foo.dll")
+                .add_value_entry("kernel32.dll", "kernelbase.dll"),
+            )
+            .build()
+            .unwrap()
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn render(app: &mut App) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(160, 20)).unwrap();
+        terminal.draw(|frame| app.render(frame)).unwrap();
+
+        let buffer = terminal.backend().buffer();
+        let mut screen = String::new();
+
+        for y in 0..buffer.area.height {
+            for x in 0..buffer.area.width {
+                screen.push_str(buffer[(x, y)].symbol());
+            }
+            screen.push('\n');
+        }
+
+        screen
+    }
+
+    #[test]
+    fn test_filter() {
+        let bytes = build();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let mut app = App::new(map);
+        assert_eq!(app.matches.len(), 2);
+
+        for c in "FOO".chars() {
+            assert!(app.handle_key(key(KeyCode::Char(c))));
+        }
+        assert_eq!(app.filter, "FOO");
+        assert_eq!(app.matches.len(), 1);
+        assert_eq!(app.list_state.selected(), Some(0));
+
+        assert!(app.handle_key(key(KeyCode::Char('x'))));
+        assert!(app.matches.is_empty());
+        assert_eq!(app.list_state.selected(), None);
+        assert!(render(&mut app).contains("No matching Namespace Entry"));
+
+        assert!(app.handle_key(key(KeyCode::Backspace)));
+        assert_eq!(app.matches.len(), 1);
+
+        // Esc first clears the filter and then closes the explorer.
+        assert!(app.handle_key(key(KeyCode::Esc)));
+        assert!(app.filter.is_empty());
+        assert_eq!(app.matches.len(), 2);
+        assert!(!app.handle_key(key(KeyCode::Esc)));
+    }
+
+    #[test]
+    fn test_selection() {
+        let bytes = build();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let mut app = App::new(map);
+
+        app.handle_key(key(KeyCode::Up));
+        assert_eq!(app.list_state.selected(), Some(0));
+        app.handle_key(key(KeyCode::Down));
+        assert_eq!(app.list_state.selected(), Some(1));
+        app.handle_key(key(KeyCode::Down));
+        assert_eq!(app.list_state.selected(), Some(1));
+        app.handle_key(key(KeyCode::Home));
+        assert_eq!(app.list_state.selected(), Some(0));
+        app.handle_key(key(KeyCode::PageDown));
+        assert_eq!(app.list_state.selected(), Some(1));
+
+        assert!(!app.handle_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)));
+    }
+
+    #[test]
+    fn test_render() {
+        let bytes = build();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let mut app = App::new(map);
+        app.handle_key(key(KeyCode::End));
+
+        let screen = render(&mut app);
+        assert!(screen.contains("Namespace Entries (2/2)"));
+        assert!(screen.contains("> api-ms-win-core-foo-l1-1-0"));
+        assert!(screen.contains("Hashed name: api-ms-win-core-foo-l1-1"));
+        assert!(screen.contains("[0] (default) -> foo.dll"));
+        assert!(screen.contains("[1] kernel32.dll -> kernelbase.dll"));
+
+        app.handle_key(key(KeyCode::Tab));
+        let screen = render(&mut app);
+        assert!(screen.contains("Hexdump (Tab: details)"));
+        assert!(screen.contains("namespace[1].header"));
+    }
+
+    #[test]
+    fn test_render_corrupted() {
+        let mut bytes = build();
+
+        // Point the name of the first Namespace Entry beyond the end of the section.
+        let namespace_entry_offset = u32::from_le_bytes(bytes[16..20].try_into().unwrap()) as usize;
+        let name_offset = namespace_entry_offset + 4;
+        bytes[name_offset..name_offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let mut app = App::new(map);
+
+        let screen = render(&mut app);
+        assert!(screen.contains("⚠ Tried to read the name"));
+    }
+}
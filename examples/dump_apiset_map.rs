@@ -1,35 +1,415 @@
+use std::fmt::Write as _;
 use std::fs;
+use std::process;
 
-use anyhow::{bail, Result};
-use nt_apiset::ApiSetMap;
+use anyhow::{anyhow, bail, Context, Result};
+use nt_apiset::{ApiSetMap, ApiSetNamespaceEntry, NtApiSetError};
 use pelite::pe64::PeFile;
 
-fn main() -> Result<()> {
-    let args = std::env::args().collect::<Vec<_>>();
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Csv,
+    Json,
+    Text,
+}
+
+struct Options {
+    filename: String,
+    format: Format,
+    filter: Option<String>,
+    name: Option<String>,
+    stats: bool,
+}
+
+struct DumpedNamespaceEntry {
+    offset: usize,
+    name: String,
+    flags: u32,
+    value_entries: Vec<DumpedValueEntry>,
+}
+
+struct DumpedValueEntry {
+    offset: usize,
+    importing_module: String,
+    host_module: String,
+    flags: u32,
+}
+
+/// A malformed entry encountered while dumping, along with the byte offset of that entry.
+struct DumpError {
+    offset: usize,
+    error: NtApiSetError,
+}
+
+impl From<NtApiSetError> for DumpError {
+    /// Takes the byte offset from errors of the search iterators, which can only fail to read a name.
+    fn from(error: NtApiSetError) -> Self {
+        let offset = match error {
+            NtApiSetError::EntryNameOutOfBounds { entry_offset, .. } => entry_offset,
+            _ => 0,
+        };
+
+        Self { offset, error }
+    }
+}
+
+fn print_usage() {
+    println!("Usage: dump_apiset_map [OPTIONS] <FILENAME>");
+    println!();
+    println!("Options:");
+    println!("  --format <json|csv|text>  Output format (default: text)");
+    println!("  --filter <TEXT>           Only dump Namespace Entries whose names contain TEXT");
+    println!("  --name <APISET>           Only dump a single API Set, fail if it doesn't exist");
+    println!("  --stats                   Print the header fields and entry counts instead of the entries");
+    println!();
+    println!("Example: dump_apiset_map --format json C:\\Windows\\system32\\apisetschema.dll");
+}
+
+fn parse_options() -> Result<Options> {
+    let mut args = std::env::args().skip(1);
+    let mut filename = None;
+    let mut format = Format::Text;
+    let mut filter = None;
+    let mut name = None;
+    let mut stats = false;
+
+    while let Some(arg) = args.next() {
+        let mut value = |option: &str| {
+            args.next()
+                .ok_or_else(|| anyhow!("Missing value for {option}"))
+        };
 
-    if args.len() != 2 {
-        println!("Usage: dump_apiset_map <FILENAME>");
-        println!("Example: dump_apiset_map C:\\Windows\\system32\\apisetschema.dll");
-        bail!("Aborted");
+        match arg.as_str() {
+            "--format" => {
+                format = match value("--format")?.as_str() {
+                    "csv" => Format::Csv,
+                    "json" => Format::Json,
+                    "text" => Format::Text,
+                    other => bail!("Unknown format \"{other}\""),
+                }
+            }
+            "--filter" => filter = Some(value("--filter")?),
+            "--name" => name = Some(value("--name")?),
+            "--stats" => stats = true,
+            _ if arg.starts_with("--") => bail!("Unknown option \"{arg}\""),
+            _ if filename.is_none() => filename = Some(arg),
+            _ => bail!("Unexpected argument \"{arg}\""),
+        }
     }
 
-    let filename = &args[1];
+    let filename = filename.ok_or_else(|| anyhow!("Missing filename"))?;
 
-    let dll = fs::read(filename)?;
-    let pe_file = PeFile::from_bytes(&dll)?;
-    let map = ApiSetMap::try_from_pe64(pe_file)?;
+    Ok(Options {
+        filename,
+        format,
+        filter,
+        name,
+        stats,
+    })
+}
+
+fn dump_namespace_entry(
+    namespace_entry: &ApiSetNamespaceEntry,
+) -> Result<DumpedNamespaceEntry, DumpError> {
+    let namespace_entry_error = |error| DumpError {
+        offset: namespace_entry.offset(),
+        error,
+    };
+
+    let name = namespace_entry.name().map_err(namespace_entry_error)?;
+    let value_entries = namespace_entry
+        .value_entries()
+        .map_err(namespace_entry_error)?;
 
-    for namespace_entry in map.namespace_entries()? {
-        println!("● Namespace Entry: \"{}\"", namespace_entry.name()?);
+    let mut dumped_value_entries = Vec::new();
 
-        for value_entry in namespace_entry.value_entries()? {
+    for value_entry in value_entries {
+        let value_entry_error = |error| DumpError {
+            offset: value_entry.offset(),
+            error,
+        };
+
+        dumped_value_entries.push(DumpedValueEntry {
+            offset: value_entry.offset(),
+            importing_module: value_entry
+                .name()
+                .map_err(value_entry_error)?
+                .to_string_lossy(),
+            host_module: value_entry
+                .value()
+                .map_err(value_entry_error)?
+                .to_string_lossy(),
+            flags: value_entry.raw_flags(),
+        });
+    }
+
+    Ok(DumpedNamespaceEntry {
+        offset: namespace_entry.offset(),
+        name: name.to_string_lossy(),
+        flags: namespace_entry.raw_flags(),
+        value_entries: dumped_value_entries,
+    })
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::from("\"");
+
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped.push('"');
+    escaped
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn print_stats(map: &ApiSetMap, format: Format) -> Result<()> {
+    let entry_stats = map.stats()?;
+    let stats = [
+        ("version", u64::from(map.version())),
+        ("size", u64::from(map.size())),
+        ("flags", u64::from(map.flags().bits())),
+        ("count", u64::from(map.count())),
+        ("hash_factor", u64::from(map.hash_factor())),
+        ("namespace_entries", entry_stats.namespace_entries() as u64),
+        (
+            "sealed_namespace_entries",
+            entry_stats.sealed_namespace_entries() as u64,
+        ),
+        (
+            "extension_namespace_entries",
+            entry_stats.extension_namespace_entries() as u64,
+        ),
+        ("value_entries", entry_stats.value_entries() as u64),
+        ("max_value_entries", entry_stats.max_value_entries() as u64),
+        ("hash_entries", entry_stats.hash_entries() as u64),
+        ("errors", entry_stats.errors() as u64),
+    ];
+
+    match format {
+        Format::Csv => {
+            println!("field,value");
+            for (field, value) in stats {
+                println!("{field},{value}");
+            }
+        }
+        Format::Json => {
+            let fields = stats
+                .iter()
+                .map(|(field, value)| format!("  {}: {value}", json_string(field)))
+                .collect::<Vec<_>>();
+            println!("{{\n{}\n}}", fields.join(",\n"));
+        }
+        Format::Text => {
+            println!("Version:     {}", map.version());
+            println!("Size:        {} bytes", map.size());
+            println!("Flags:       {:?}", map.flags());
+            println!("Count:       {}", map.count());
+            println!("Hash Factor: {:#x}", map.hash_factor());
+            println!();
+            println!(
+                "Namespace Entries:           {}",
+                entry_stats.namespace_entries()
+            );
+            println!(
+                "  Sealed:                    {}",
+                entry_stats.sealed_namespace_entries()
+            );
+            println!(
+                "  Extensions:                {}",
+                entry_stats.extension_namespace_entries()
+            );
+            println!(
+                "Value Entries:               {}",
+                entry_stats.value_entries()
+            );
+            println!(
+                "  Max per Namespace Entry:   {}",
+                entry_stats.max_value_entries()
+            );
             println!(
-                "  ○ Value Entry: \"{}\" -> \"{}\"",
-                value_entry.name()?,
-                value_entry.value()?
+                "Hash Entries:                {}",
+                entry_stats.hash_entries()
             );
+            println!("Errors:                      {}", entry_stats.errors());
         }
     }
 
     Ok(())
 }
+
+fn print_header(format: Format) {
+    match format {
+        Format::Csv => {
+            println!("namespace_entry,namespace_entry_flags,importing_module,host_module,value_entry_flags")
+        }
+        Format::Json => println!("["),
+        Format::Text => (),
+    }
+}
+
+fn print_namespace_entry(namespace_entry: &DumpedNamespaceEntry, format: Format, first: bool) {
+    match format {
+        Format::Csv => {
+            let name = csv_field(&namespace_entry.name);
+
+            if namespace_entry.value_entries.is_empty() {
+                println!("{name},{},,,", namespace_entry.flags);
+            }
+
+            for value_entry in &namespace_entry.value_entries {
+                println!(
+                    "{name},{},{},{},{}",
+                    namespace_entry.flags,
+                    csv_field(&value_entry.importing_module),
+                    csv_field(&value_entry.host_module),
+                    value_entry.flags
+                );
+            }
+        }
+        Format::Json => {
+            let value_entries = namespace_entry
+                .value_entries
+                .iter()
+                .map(|value_entry| {
+                    format!(
+                        "      {{\"offset\": {}, \"importing_module\": {}, \"host_module\": {}, \"flags\": {}}}",
+                        value_entry.offset,
+                        json_string(&value_entry.importing_module),
+                        json_string(&value_entry.host_module),
+                        value_entry.flags
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            if !first {
+                println!(",");
+            }
+
+            print!(
+                "  {{\n    \"offset\": {},\n    \"name\": {},\n    \"flags\": {},\n    \"value_entries\": [",
+                namespace_entry.offset,
+                json_string(&namespace_entry.name),
+                namespace_entry.flags
+            );
+
+            if value_entries.is_empty() {
+                print!("]\n  }}");
+            } else {
+                print!("\n{}\n    ]\n  }}", value_entries.join(",\n"));
+            }
+        }
+        Format::Text => {
+            println!("● Namespace Entry: \"{}\"", namespace_entry.name);
+
+            for value_entry in &namespace_entry.value_entries {
+                println!(
+                    "  ○ Value Entry: \"{}\" -> \"{}\"",
+                    value_entry.importing_module, value_entry.host_module
+                );
+            }
+        }
+    }
+}
+
+fn print_footer(format: Format, empty: bool) {
+    if format == Format::Json {
+        if empty {
+            println!("]");
+        } else {
+            println!("\n]");
+        }
+    }
+}
+
+fn run(options: Options) -> Result<i32> {
+    let dll = fs::read(&options.filename)
+        .with_context(|| format!("Failed to read \"{}\"", options.filename))?;
+    let pe_file = PeFile::from_bytes(&dll)?;
+    let map = ApiSetMap::try_from_pe64(pe_file)?;
+
+    if options.stats {
+        print_stats(&map, options.format)?;
+        return Ok(0);
+    }
+
+    if let Some(name) = &options.name {
+        let namespace_entry = match map.find_namespace_entry(name) {
+            Some(namespace_entry) => namespace_entry?,
+            None => {
+                eprintln!("API Set \"{name}\" not found");
+                return Ok(1);
+            }
+        };
+
+        let dumped = dump_namespace_entry(&namespace_entry)
+            .map_err(|e| anyhow!("Malformed entry at byte {:#x}: {}", e.offset, e.error))?;
+
+        print_header(options.format);
+        print_namespace_entry(&dumped, options.format, true);
+        print_footer(options.format, false);
+        return Ok(0);
+    }
+
+    let namespace_entries: Box<dyn Iterator<Item = nt_apiset::Result<ApiSetNamespaceEntry>>> =
+        match &options.filter {
+            Some(filter) => Box::new(map.find_namespace_entries_containing(filter)?),
+            None => Box::new(map.namespace_entries()?.map(Ok)),
+        };
+
+    let mut first = true;
+    let mut errors = 0;
+    print_header(options.format);
+
+    // Report malformed entries and continue with the next one instead of aborting the dump.
+    for namespace_entry in namespace_entries {
+        let dumped = namespace_entry
+            .map_err(DumpError::from)
+            .and_then(|namespace_entry| dump_namespace_entry(&namespace_entry));
+
+        match dumped {
+            Ok(dumped) => {
+                print_namespace_entry(&dumped, options.format, first);
+                first = false;
+            }
+            Err(e) => {
+                eprintln!("Malformed entry at byte {:#x}: {}", e.offset, e.error);
+                errors += 1;
+            }
+        }
+    }
+
+    print_footer(options.format, first);
+
+    if errors > 0 {
+        eprintln!("{errors} malformed entries have been skipped");
+        Ok(2)
+    } else {
+        Ok(0)
+    }
+}
+
+fn main() -> Result<()> {
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(e) => {
+            print_usage();
+            bail!(e);
+        }
+    };
+
+    let exit_code = run(options)?;
+    process::exit(exit_code);
+}
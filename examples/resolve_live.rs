@@ -0,0 +1,32 @@
+#[cfg(windows)]
+fn main() -> anyhow::Result<()> {
+    use anyhow::bail;
+    use nt_apiset::ApiSetMap;
+
+    let args = std::env::args().collect::<Vec<_>>();
+
+    if args.len() != 2 && args.len() != 3 {
+        println!("Usage: resolve_live <IMPORT NAME> [IMPORTING MODULE]");
+        println!("Example: resolve_live api-ms-win-core-processthreads-l1-1-2.dll kernel32.dll");
+        bail!("Aborted");
+    }
+
+    let import_name = &args[1];
+    let importing_module = args.get(2).map(String::as_str);
+
+    let map = ApiSetMap::try_from_current_process()?;
+
+    match map.resolve(import_name, importing_module) {
+        Some(host) => println!("{import_name} -> {}", host?),
+        None => println!("{import_name} is not an API Set of the running system"),
+    }
+
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn main() {
+    println!(
+        "This example reads the API Set Map of the running process and only works on Windows."
+    );
+}
@@ -0,0 +1,36 @@
+use std::fs;
+
+use anyhow::{bail, Result};
+use nt_apiset::ApiSetMap;
+use pelite::pe64::PeFile;
+
+fn main() -> Result<()> {
+    let args = std::env::args().collect::<Vec<_>>();
+
+    if args.len() != 3 {
+        println!("Usage: reverse_lookup <FILENAME> <HOST DLL>");
+        println!("Example: reverse_lookup C:\\Windows\\system32\\apisetschema.dll kernelbase.dll");
+        bail!("Aborted");
+    }
+
+    let filename = &args[1];
+    let host_dll = &args[2];
+
+    let dll = fs::read(filename)?;
+    let pe_file = PeFile::from_bytes(&dll)?;
+    let map = ApiSetMap::try_from_pe64(pe_file)?;
+
+    for result in map.find_by_host(host_dll)? {
+        let (namespace_entry, value_entry) = result?;
+        let name = namespace_entry.name()?;
+        let importing_module = value_entry.name()?;
+
+        if importing_module.is_empty() {
+            println!("● \"{name}\"");
+        } else {
+            println!("● \"{name}\" (when imported by \"{importing_module}\")");
+        }
+    }
+
+    Ok(())
+}
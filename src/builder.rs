@@ -0,0 +1,515 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::mem;
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use zerocopy::{AsBytes, U32};
+
+use crate::error::{NtApiSetError, Result};
+use crate::hash_entry::ApiSetHashEntryHeader;
+use crate::helpers::{hash_name, u16_to_ascii_lowercase};
+use crate::map::{ApiSetMapFlags, ApiSetMapHeader};
+use crate::namespace_entry::{ApiSetNamespaceEntryFlags, ApiSetNamespaceEntryHeader};
+use crate::value_entry::{ApiSetValueEntryFlags, ApiSetValueEntryHeader};
+
+/// Hash factor used by all known API Set Maps.
+const DEFAULT_HASH_FACTOR: u32 = 0x1f;
+
+/// Builds the bytes of a version 6 `.apiset` section from scratch.
+///
+/// The resulting section can be parsed via [`ApiSetMap::try_from_apiset_section_bytes`].
+/// Namespace Entries and Hash Entries are sorted as expected by the Windows loader, and all offsets, lengths, and hashes are computed.
+///
+/// ```
+/// # use nt_apiset::{ApiSetMap, ApiSetMapBuilder, ApiSetNamespaceEntryBuilder, ApiSetNamespaceEntryFlags};
+/// let section_bytes = ApiSetMapBuilder::new()
+///     .add_namespace_entry(
+///         ApiSetNamespaceEntryBuilder::new("api-ms-win-core-foo-l1-1-0", ApiSetNamespaceEntryFlags::SEALED)
+///             .add_value_entry("", "foo.dll")
+///             .add_value_entry("kernel32.dll", "foo_legacy.dll"),
+///     )
+///     .build()
+///     .unwrap();
+///
+/// let map = ApiSetMap::try_from_apiset_section_bytes(&section_bytes).unwrap();
+/// let host = map.resolve("api-ms-win-core-foo-l1-1-0.dll", Some("kernel32.dll")).unwrap().unwrap();
+/// assert_eq!(host, "foo_legacy.dll");
+/// ```
+///
+/// [`ApiSetMap::try_from_apiset_section_bytes`]: crate::map::ApiSetMap::try_from_apiset_section_bytes
+#[derive(Clone, Debug)]
+pub struct ApiSetMapBuilder {
+    flags: ApiSetMapFlags,
+    hash_factor: u32,
+    namespace_entries: Vec<ApiSetNamespaceEntryBuilder>,
+}
+
+impl ApiSetMapBuilder {
+    /// Creates a builder for an empty API Set Map without flags and with the hash factor of all known API Set Maps (`0x1f`).
+    pub const fn new() -> Self {
+        Self {
+            flags: ApiSetMapFlags::empty(),
+            hash_factor: DEFAULT_HASH_FACTOR,
+            namespace_entries: Vec::new(),
+        }
+    }
+
+    /// Adds a Namespace Entry.
+    ///
+    /// Namespace Entries can be added in any order, they are sorted when building.
+    pub fn add_namespace_entry(mut self, namespace_entry: ApiSetNamespaceEntryBuilder) -> Self {
+        self.namespace_entries.push(namespace_entry);
+        self
+    }
+
+    /// Builds the bytes of the `.apiset` section.
+    ///
+    /// Namespace Entries whose names only differ in case are rejected with [`NtApiSetError::DuplicateNamespaceEntryName`],
+    /// and Value Entries of the same Namespace Entry whose importing modules only differ in case are rejected with
+    /// [`NtApiSetError::DuplicateValueEntryName`].
+    /// Names the loader cannot hash are rejected with [`NtApiSetError::InvalidNamespaceEntryName`],
+    /// and module names with non-ASCII characters are rejected with [`NtApiSetError::InvalidValueEntryName`].
+    /// If the section would exceed the 4 GiB that can be addressed by its offsets, [`NtApiSetError::SectionTooLarge`] is returned.
+    pub fn build(&self) -> Result<Vec<u8>> {
+        let order = self.sorted_namespace_entries()?;
+        let count = order.len();
+        let value_entry_count = self
+            .namespace_entries
+            .iter()
+            .map(|namespace_entry| namespace_entry.value_entries.len())
+            .sum::<usize>();
+        let string_pool_size = self
+            .namespace_entries
+            .iter()
+            .map(ApiSetNamespaceEntryBuilder::string_size)
+            .sum::<usize>();
+
+        let namespace_entry_offset = mem::size_of::<ApiSetMapHeader>();
+        let value_entry_offset =
+            namespace_entry_offset + count * mem::size_of::<ApiSetNamespaceEntryHeader>();
+        let hash_entry_offset =
+            value_entry_offset + value_entry_count * mem::size_of::<ApiSetValueEntryHeader>();
+        let string_offset = hash_entry_offset + count * mem::size_of::<ApiSetHashEntryHeader>();
+        let size = string_offset + string_pool_size;
+
+        // All offsets and lengths are smaller than the section size, so they fit into a u32 if the size does.
+        let size_u32 = u32::try_from(size).map_err(|_| NtApiSetError::SectionTooLarge { size })?;
+
+        let mut namespace_entry_bytes = Vec::new();
+        let mut value_entry_bytes = Vec::new();
+        let mut string_pool = StringPool {
+            offset: string_offset,
+            bytes: Vec::with_capacity(string_pool_size),
+        };
+        let mut hash_entries = Vec::with_capacity(count);
+        let mut next_value_entry_offset = value_entry_offset;
+
+        for (index, (lowercase_name, insertion_index)) in order.iter().enumerate() {
+            let namespace_entry = &self.namespace_entries[*insertion_index];
+
+            // NTDLL hashes the lowercase name up to but not including the last hyphen.
+            let hashed_length = lowercase_name
+                .iter()
+                .rposition(|c| *c == u16::from(b'-'))
+                .unwrap_or(lowercase_name.len());
+            let hash = hash_name(
+                lowercase_name[..hashed_length]
+                    .iter()
+                    .map(|c| u32::from(*c)),
+                self.hash_factor,
+            );
+            hash_entries.push((hash, index as u32));
+
+            let (name_offset, name_length) = string_pool.push(&namespace_entry.name);
+            let value_entries = namespace_entry.sorted_value_entries();
+
+            let header = ApiSetNamespaceEntryHeader {
+                flags: U32::new(namespace_entry.flags.bits()),
+                name_offset: U32::new(name_offset),
+                name_length: U32::new(name_length),
+                hashed_length: U32::new((hashed_length * mem::size_of::<u16>()) as u32),
+                array_offset: U32::new(next_value_entry_offset as u32),
+                array_count: U32::new(value_entries.len() as u32),
+            };
+            namespace_entry_bytes.extend_from_slice(header.as_bytes());
+
+            for value_entry in &value_entries {
+                let (name_offset, name_length) = string_pool.push(&value_entry.importing_module);
+                let (value_offset, value_length) = string_pool.push(&value_entry.host_module);
+
+                let header = ApiSetValueEntryHeader {
+                    flags: U32::new(value_entry.flags.bits()),
+                    name_offset: U32::new(name_offset),
+                    name_length: U32::new(name_length),
+                    value_offset: U32::new(value_offset),
+                    value_length: U32::new(value_length),
+                };
+                value_entry_bytes.extend_from_slice(header.as_bytes());
+            }
+
+            next_value_entry_offset +=
+                value_entries.len() * mem::size_of::<ApiSetValueEntryHeader>();
+        }
+
+        hash_entries.sort_unstable();
+
+        let header = ApiSetMapHeader {
+            version: U32::new(6),
+            size: U32::new(size_u32),
+            flags: U32::new(self.flags.bits()),
+            count: U32::new(count as u32),
+            namespace_entry_offset: U32::new(namespace_entry_offset as u32),
+            hash_entry_offset: U32::new(hash_entry_offset as u32),
+            hash_factor: U32::new(self.hash_factor),
+        };
+
+        let mut section_bytes = Vec::with_capacity(size);
+        section_bytes.extend_from_slice(header.as_bytes());
+        section_bytes.extend_from_slice(&namespace_entry_bytes);
+        section_bytes.extend_from_slice(&value_entry_bytes);
+
+        for (hash, index) in hash_entries {
+            let hash_entry = ApiSetHashEntryHeader {
+                hash: U32::new(hash),
+                index: U32::new(index),
+            };
+            section_bytes.extend_from_slice(hash_entry.as_bytes());
+        }
+
+        section_bytes.extend_from_slice(&string_pool.bytes);
+
+        Ok(section_bytes)
+    }
+
+    /// Performs all checks of [`build`](Self::build) that don't depend on the size of the section.
+    #[cfg(feature = "serde")]
+    pub(crate) fn check(&self) -> Result<()> {
+        self.sorted_namespace_entries().map(|_| ())
+    }
+
+    /// Checks all Namespace Entries and returns their lowercase names along with their insertion indexes,
+    /// sorted case-insensitively by name.
+    fn sorted_namespace_entries(&self) -> Result<Vec<(Vec<u16>, usize)>> {
+        for (index, namespace_entry) in self.namespace_entries.iter().enumerate() {
+            namespace_entry.check_names(index)?;
+        }
+
+        // Keep the insertion index for error reporting.
+        let mut order = self
+            .namespace_entries
+            .iter()
+            .enumerate()
+            .map(|(index, namespace_entry)| (to_lowercase_utf16(&namespace_entry.name), index))
+            .collect::<Vec<_>>();
+        order.sort_unstable();
+
+        for pair in order.windows(2) {
+            if pair[0].0 == pair[1].0 {
+                return Err(NtApiSetError::DuplicateNamespaceEntryName {
+                    index: pair[1].1,
+                    previous_index: pair[0].1,
+                });
+            }
+        }
+
+        Ok(order)
+    }
+
+    /// Sets the flags of the API Set Map.
+    pub const fn flags(mut self, flags: ApiSetMapFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Sets the hash factor used to compute the hashes of all Namespace Entries.
+    pub const fn hash_factor(mut self, hash_factor: u32) -> Self {
+        self.hash_factor = hash_factor;
+        self
+    }
+}
+
+impl Default for ApiSetMapBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A Namespace Entry to be added to an [`ApiSetMapBuilder`].
+#[derive(Clone, Debug)]
+pub struct ApiSetNamespaceEntryBuilder {
+    name: String,
+    flags: ApiSetNamespaceEntryFlags,
+    value_entries: Vec<ValueEntry>,
+}
+
+impl ApiSetNamespaceEntryBuilder {
+    /// Creates a Namespace Entry without any Value Entries.
+    ///
+    /// `name` is the full name of the API Set without a file extension, e.g. `api-ms-win-core-sysinfo-l1-1-0`.
+    /// It must only consist of ASCII letters, digits, and hyphens, and contain a hyphen after the first character,
+    /// because the loader hashes the part up to but not including the last hyphen.
+    /// This is checked by [`ApiSetMapBuilder::build`].
+    pub fn new(name: &str, flags: ApiSetNamespaceEntryFlags) -> Self {
+        Self {
+            name: name.to_string(),
+            flags,
+            value_entries: Vec::new(),
+        }
+    }
+
+    /// Adds a mapping from `importing_module` to `host_module`.
+    ///
+    /// The first Value Entry is the default mapping, whose `importing_module` is usually an empty string.
+    /// All further Value Entries are host-specific and sorted case-insensitively by `importing_module` when building.
+    /// Both names must only consist of ASCII characters, which is checked by [`ApiSetMapBuilder::build`].
+    pub fn add_value_entry(self, importing_module: &str, host_module: &str) -> Self {
+        self.add_value_entry_with_flags(
+            importing_module,
+            host_module,
+            ApiSetValueEntryFlags::empty(),
+        )
+    }
+
+    /// Adds a mapping from `importing_module` to `host_module` with the given `flags`.
+    ///
+    /// See [`add_value_entry`](Self::add_value_entry) for details.
+    pub fn add_value_entry_with_flags(
+        mut self,
+        importing_module: &str,
+        host_module: &str,
+        flags: ApiSetValueEntryFlags,
+    ) -> Self {
+        self.value_entries.push(ValueEntry {
+            importing_module: importing_module.to_string(),
+            host_module: host_module.to_string(),
+            flags,
+        });
+        self
+    }
+
+    /// Checks that all names of this entry, which has been added to the builder at `index`, can be stored and hashed,
+    /// and that no importing module has more than one Value Entry.
+    fn check_names(&self, index: usize) -> Result<()> {
+        let is_valid_name_char = |c: char| c.is_ascii_alphanumeric() || c == '-';
+        let has_hashable_part = matches!(self.name.rfind('-'), Some(position) if position > 0);
+
+        if !self.name.chars().all(is_valid_name_char) || !has_hashable_part {
+            return Err(NtApiSetError::InvalidNamespaceEntryName { index });
+        }
+
+        for (value_index, value_entry) in self.value_entries.iter().enumerate() {
+            if !value_entry.importing_module.is_ascii() || !value_entry.host_module.is_ascii() {
+                return Err(NtApiSetError::InvalidValueEntryName {
+                    namespace_entry_index: index,
+                    index: value_index,
+                });
+            }
+        }
+
+        let mut importing_modules = self
+            .value_entries
+            .iter()
+            .enumerate()
+            .map(|(value_index, value_entry)| {
+                (
+                    to_lowercase_utf16(&value_entry.importing_module),
+                    value_index,
+                )
+            })
+            .collect::<Vec<_>>();
+        importing_modules.sort_unstable();
+
+        for pair in importing_modules.windows(2) {
+            if pair[0].0 == pair[1].0 {
+                return Err(NtApiSetError::DuplicateValueEntryName {
+                    namespace_entry_index: index,
+                    index: pair[1].1,
+                    previous_index: pair[0].1,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn sorted_value_entries(&self) -> Vec<&ValueEntry> {
+        let mut value_entries = self.value_entries.iter().collect::<Vec<_>>();
+
+        if let Some(host_specific_entries) = value_entries.get_mut(1..) {
+            host_specific_entries.sort_by_cached_key(|value_entry| {
+                to_lowercase_utf16(&value_entry.importing_module)
+            });
+        }
+
+        value_entries
+    }
+
+    /// Returns the number of bytes this entry occupies in the string pool.
+    fn string_size(&self) -> usize {
+        let strings = self
+            .value_entries
+            .iter()
+            .flat_map(|value_entry| [&value_entry.importing_module, &value_entry.host_module]);
+
+        core::iter::once(&self.name)
+            .chain(strings)
+            .map(|string| string.encode_utf16().count() * mem::size_of::<u16>())
+            .sum()
+    }
+}
+
+/// A Value Entry to be added to an [`ApiSetNamespaceEntryBuilder`].
+#[derive(Clone, Debug)]
+struct ValueEntry {
+    importing_module: String,
+    host_module: String,
+    flags: ApiSetValueEntryFlags,
+}
+
+/// Collects all strings of the section.
+struct StringPool {
+    offset: usize,
+    bytes: Vec<u8>,
+}
+
+impl StringPool {
+    /// Appends `string` as UTF-16LE and returns its offset and length in bytes.
+    ///
+    /// Empty strings are not stored and get an offset of zero, as in API Set Maps created by Microsoft.
+    fn push(&mut self, string: &str) -> (u32, u32) {
+        if string.is_empty() {
+            return (0, 0);
+        }
+
+        let offset = self.offset + self.bytes.len();
+        for c in string.encode_utf16() {
+            self.bytes.extend_from_slice(&c.to_le_bytes());
+        }
+        let length = self.offset + self.bytes.len() - offset;
+
+        (offset as u32, length as u32)
+    }
+}
+
+fn to_lowercase_utf16(string: &str) -> Vec<u16> {
+    string.encode_utf16().map(u16_to_ascii_lowercase).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::ApiSetMap;
+
+    fn namespace_entry(name: &str) -> ApiSetNamespaceEntryBuilder {
+        ApiSetNamespaceEntryBuilder::new(name, ApiSetNamespaceEntryFlags::empty())
+            .add_value_entry("", "foo.dll")
+    }
+
+    #[test]
+    fn test_value_entry_flags() {
+        let bytes = ApiSetMapBuilder::new()
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "api-ms-win-core-foo-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                )
+                .add_value_entry_with_flags("", "foo.dll", ApiSetValueEntryFlags::SEALED)
+                .add_value_entry("kernel32.dll", "foo_legacy.dll"),
+            )
+            .build()
+            .unwrap();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let namespace_entry = map.namespace_entries().unwrap().next().unwrap();
+        let mut value_entries = namespace_entry.value_entries().unwrap();
+
+        assert_eq!(
+            value_entries.next().unwrap().flags(),
+            ApiSetValueEntryFlags::SEALED
+        );
+        assert_eq!(
+            value_entries.next().unwrap().flags(),
+            ApiSetValueEntryFlags::empty()
+        );
+    }
+
+    #[test]
+    fn test_duplicate_namespace_entry_name() {
+        let result = ApiSetMapBuilder::new()
+            .add_namespace_entry(namespace_entry("api-ms-win-core-foo-l1-1-0"))
+            .add_namespace_entry(namespace_entry("api-ms-win-core-bar-l1-1-0"))
+            .add_namespace_entry(namespace_entry("API-MS-WIN-CORE-FOO-L1-1-0"))
+            .build();
+
+        assert_eq!(
+            result,
+            Err(NtApiSetError::DuplicateNamespaceEntryName {
+                index: 2,
+                previous_index: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn test_duplicate_value_entry_name() {
+        let result = ApiSetMapBuilder::new()
+            .add_namespace_entry(
+                namespace_entry("api-ms-win-core-foo-l1-1-0")
+                    .add_value_entry("kernel32.dll", "foo_legacy.dll")
+                    .add_value_entry("KERNEL32.DLL", "foo_other.dll"),
+            )
+            .build();
+
+        assert_eq!(
+            result,
+            Err(NtApiSetError::DuplicateValueEntryName {
+                namespace_entry_index: 0,
+                index: 2,
+                previous_index: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn test_invalid_namespace_entry_name() {
+        for name in [
+            "",
+            "apimswincorefoo",
+            "-foo",
+            "api-ms-win-core-fö-l1-1-0",
+            "api-ms-win-core-foo-l1-1-0.dll",
+        ] {
+            let result = ApiSetMapBuilder::new()
+                .add_namespace_entry(namespace_entry("api-ms-win-core-bar-l1-1-0"))
+                .add_namespace_entry(namespace_entry(name))
+                .build();
+
+            assert_eq!(
+                result,
+                Err(NtApiSetError::InvalidNamespaceEntryName { index: 1 }),
+                "{name:?} has been accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_value_entry_name() {
+        let result = ApiSetMapBuilder::new()
+            .add_namespace_entry(namespace_entry("api-ms-win-core-bar-l1-1-0"))
+            .add_namespace_entry(
+                namespace_entry("api-ms-win-core-foo-l1-1-0")
+                    .add_value_entry("kernel32.dll", "fö.dll"),
+            )
+            .build();
+
+        assert_eq!(
+            result,
+            Err(NtApiSetError::InvalidValueEntryName {
+                namespace_entry_index: 1,
+                index: 1,
+            })
+        );
+    }
+}
@@ -0,0 +1,307 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, PoisonError, RwLock, Weak};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::dual_arch::load_file;
+use crate::error::NtApiSetError;
+use crate::owned::OwnedApiSetMap;
+use crate::resolver::ApiSetResolver;
+
+/// A hot-reloadable API Set Map file for long-running services.
+///
+/// A service that resolves imports continuously can pick up an updated `apisetschema.dll` (e.g. after a Windows update)
+/// without restarting.
+/// The cell holds the current [`OwnedApiSetMap`] and replaces it as a whole when the file is reloaded.
+/// Every loaded file must pass [`ApiSetMap::validate`](crate::map::ApiSetMap::validate).
+/// If it doesn't, the previous API Set Map is kept.
+///
+/// Readers call [`resolver`](Self::resolver) or [`map`](Self::map) to get the current API Set Map behind an [`Arc`].
+/// Only this call briefly takes a read lock to clone the [`Arc`].
+/// The lookups themselves never lock, and a reload never waits for readers to finish with an older API Set Map.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use nt_apiset::ApiSetMapCell;
+/// let cell = ApiSetMapCell::open_watching("apisetschema.dll", Duration::from_secs(10)).unwrap();
+///
+/// // Every call picks up the latest successfully loaded API Set Map.
+/// let resolver = cell.resolver();
+/// if let Some(host) = resolver.resolve("api-ms-win-core-sysinfo-l1-1-0.dll", None) {
+///     println!("{}", host.unwrap());
+/// }
+/// ```
+#[cfg_attr(docsrs, doc(cfg(all(feature = "std", feature = "pelite"))))]
+#[derive(Debug)]
+pub struct ApiSetMapCell {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    path: PathBuf,
+    current: RwLock<Arc<OwnedApiSetMap>>,
+}
+
+/// Last modification time and size of a file, used by the watcher thread to detect changes.
+type FileStamp = Option<(SystemTime, u64)>;
+
+impl ApiSetMapCell {
+    /// Returns the current API Set Map.
+    pub fn map(&self) -> Arc<OwnedApiSetMap> {
+        self.shared.map()
+    }
+
+    /// Loads the 32-bit or 64-bit API Set Map file at `path` and validates it.
+    ///
+    /// If the file cannot be read, [`NtApiSetError::FileReadFailed`] is returned.
+    /// If it is no PE file, [`NtApiSetError::InvalidPeFile`] is returned.
+    /// If it fails validation, all findings are returned.
+    pub fn open<P>(path: P) -> Result<Self, Vec<NtApiSetError>>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        let map = load_validated(&path)?;
+
+        Ok(Self {
+            shared: Arc::new(Shared {
+                path,
+                current: RwLock::new(Arc::new(map)),
+            }),
+        })
+    }
+
+    /// Loads the API Set Map file at `path` like [`open`](Self::open) and starts a thread watching that file.
+    ///
+    /// Every `poll_interval`, the thread checks whether the modification time or the size of the file has changed,
+    /// and calls [`reload`](Self::reload) if so.
+    /// The thread exits during the first check after the [`ApiSetMapCell`] has been dropped.
+    ///
+    /// Replace the file atomically (e.g. by renaming a new file over it) to make sure that it is never read half-written.
+    /// A half-written file would fail validation and not be picked up before its next change.
+    pub fn open_watching<P>(path: P, poll_interval: Duration) -> Result<Self, Vec<NtApiSetError>>
+    where
+        P: AsRef<Path>,
+    {
+        let stamp = file_stamp(path.as_ref());
+        let cell = Self::open(path)?;
+
+        let shared = Arc::downgrade(&cell.shared);
+        thread::spawn(move || watch(shared, poll_interval, stamp));
+
+        Ok(cell)
+    }
+
+    /// Returns the path of the API Set Map file.
+    pub fn path(&self) -> &Path {
+        &self.shared.path
+    }
+
+    /// Loads the API Set Map file again and replaces the current API Set Map by it.
+    ///
+    /// If the file cannot be loaded or fails validation, the current API Set Map is kept, and the same errors as for
+    /// [`open`](Self::open) are returned.
+    pub fn reload(&self) -> Result<(), Vec<NtApiSetError>> {
+        self.shared.reload()
+    }
+
+    /// Returns the current API Set Map as an [`ApiSetResolver`].
+    ///
+    /// The returned resolver keeps using that API Set Map even if the cell is reloaded afterwards.
+    /// Call this method again to pick up a reloaded one.
+    pub fn resolver(&self) -> Arc<dyn ApiSetResolver> {
+        self.map()
+    }
+}
+
+impl Shared {
+    fn map(&self) -> Arc<OwnedApiSetMap> {
+        let current = self.current.read().unwrap_or_else(PoisonError::into_inner);
+        Arc::clone(&current)
+    }
+
+    fn reload(&self) -> Result<(), Vec<NtApiSetError>> {
+        let map = Arc::new(load_validated(&self.path)?);
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = map;
+        Ok(())
+    }
+}
+
+fn file_stamp(path: &Path) -> FileStamp {
+    let metadata = path.metadata().ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+fn load_validated(path: &Path) -> Result<OwnedApiSetMap, Vec<NtApiSetError>> {
+    let map = load_file(path).map_err(|e| vec![e])?;
+    map.as_map().validate()?;
+    Ok(map)
+}
+
+fn watch(shared: Weak<Shared>, poll_interval: Duration, mut stamp: FileStamp) {
+    loop {
+        thread::sleep(poll_interval);
+
+        let shared = match shared.upgrade() {
+            Some(shared) => shared,
+            None => return,
+        };
+
+        let new_stamp = file_stamp(&shared.path);
+        if new_stamp != stamp {
+            stamp = new_stamp;
+
+            // A file that fails to load is ignored, and the previous API Set Map is kept.
+            let _ = shared.reload();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::Instant;
+
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+    use crate::fixtures::{build_pe_file, PE_SECTION_FILE_OFFSET};
+    use crate::namespace_entry::ApiSetNamespaceEntryFlags;
+
+    const NAME: &str = "api-ms-win-core-foo-l1-1-0.dll";
+
+    fn build(host: &str) -> Vec<u8> {
+        let section_bytes = ApiSetMapBuilder::new()
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "api-ms-win-core-foo-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                )
+                .add_value_entry("", host),
+            )
+            .build()
+            .unwrap();
+        build_pe_file(true, &section_bytes)
+    }
+
+    /// Returns a path for the API Set Map file of a single test.
+    fn temp_path(test: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "nt-apiset-{}-{test}-apisetschema.dll",
+            std::process::id()
+        ))
+    }
+
+    /// Atomically replaces the file at `path`, so the watcher thread never reads it half-written.
+    fn replace(path: &Path, bytes: &[u8]) {
+        let temp = path.with_extension("tmp");
+        fs::write(&temp, bytes).unwrap();
+        fs::rename(&temp, path).unwrap();
+    }
+
+    fn resolve(resolver: &dyn ApiSetResolver) -> String {
+        resolver
+            .resolve(NAME, None)
+            .unwrap()
+            .unwrap()
+            .to_string()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_reload() {
+        let path = temp_path("reload");
+        replace(&path, &build("foo.dll"));
+
+        let cell = ApiSetMapCell::open(&path).unwrap();
+        let old_resolver = cell.resolver();
+        assert_eq!(resolve(&*old_resolver), "foo.dll");
+
+        replace(&path, &build("foo_new.dll"));
+        cell.reload().unwrap();
+
+        // New readers see the new mapping, while existing ones keep using the old API Set Map.
+        assert_eq!(resolve(&*cell.resolver()), "foo_new.dll");
+        assert_eq!(resolve(&*old_resolver), "foo.dll");
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_reload_keeps_old_map() {
+        let path = temp_path("reload-keeps-old-map");
+        replace(&path, &build("foo.dll"));
+        let cell = ApiSetMapCell::open(&path).unwrap();
+
+        // A file that fails validation.
+        let mut bytes = build("foo_new.dll");
+        let section = PE_SECTION_FILE_OFFSET as usize;
+        let hash_entry_offset = section + 20;
+        let hash = section
+            + u32::from_le_bytes(
+                bytes[hash_entry_offset..hash_entry_offset + 4]
+                    .try_into()
+                    .unwrap(),
+            ) as usize;
+        bytes[hash..hash + 4].copy_from_slice(&0xdead_beefu32.to_le_bytes());
+        replace(&path, &bytes);
+
+        let findings = cell.reload().unwrap_err();
+        assert!(matches!(findings[0], NtApiSetError::HashMismatch { .. }));
+        assert_eq!(resolve(&*cell.resolver()), "foo.dll");
+
+        // A file that is no PE file.
+        replace(&path, b"not a PE file");
+        assert_eq!(cell.reload().unwrap_err(), [NtApiSetError::InvalidPeFile]);
+        assert_eq!(resolve(&*cell.resolver()), "foo.dll");
+
+        // A missing file.
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            cell.reload().unwrap_err()[..],
+            [NtApiSetError::FileReadFailed { .. }]
+        ));
+        assert_eq!(resolve(&*cell.resolver()), "foo.dll");
+    }
+
+    #[test]
+    fn test_open_watching() {
+        let path = temp_path("open-watching");
+        replace(&path, &build("foo.dll"));
+        let cell = ApiSetMapCell::open_watching(&path, Duration::from_millis(10)).unwrap();
+
+        // Readers keep resolving while the file is swapped, until they see the new mapping.
+        let reader = {
+            let cell = Arc::new(cell);
+            let reader_cell = Arc::clone(&cell);
+            let reader = thread::spawn(move || {
+                let deadline = Instant::now() + Duration::from_secs(10);
+                let mut lookups = 0usize;
+
+                while Instant::now() < deadline {
+                    lookups += 1;
+
+                    match resolve(&*reader_cell.resolver()).as_str() {
+                        "foo.dll" => (),
+                        "foo_watched.dll" => return Some(lookups),
+                        host => panic!("unexpected host {host}"),
+                    }
+                }
+
+                None
+            });
+
+            // A different size is noticed even if the modification time has a coarse granularity.
+            let mut bytes = build("foo_watched.dll");
+            bytes.resize(bytes.len() + 0x200, 0);
+            replace(&path, &bytes);
+            reader
+        };
+
+        assert!(reader.join().unwrap().is_some());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,261 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::fmt;
+
+use crate::error::{NtApiSetError, Result};
+
+/// Progress reported by an operation that accepts an [`OpControl`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ProgressEvent {
+    completed: usize,
+    total: usize,
+}
+
+impl ProgressEvent {
+    /// Returns the number of entries that have been processed so far.
+    pub fn completed(&self) -> usize {
+        self.completed
+    }
+
+    /// Returns the number of entries the operation is going to process in total.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+}
+
+/// Cancellation and progress callbacks for long-running operations, e.g. in GUI applications.
+///
+/// This is accepted by [`ApiSetMap::validate_with`](crate::map::ApiSetMap::validate_with),
+/// [`ApiSetMap::build_index_with`](crate::map::ApiSetMap::build_index_with),
+/// and [`ApiSetMap::to_snapshot_with`](crate::map::ApiSetMap::to_snapshot_with).
+/// Both callbacks are invoked once per entry:
+/// The cancellation callback is asked before an entry is processed, and the progress callback is notified afterwards.
+/// If the cancellation callback returns `true`, the operation stops right away and reports [`NtApiSetError::Cancelled`].
+///
+/// ```
+/// # use std::sync::atomic::{AtomicBool, Ordering};
+/// # use nt_apiset::{ApiSetMap, NtApiSetError, OpControl};
+/// # let bytes = nt_apiset::ApiSetMapBuilder::new().build().unwrap();
+/// # let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+/// let cancel = AtomicBool::new(false);
+/// let should_cancel = || cancel.load(Ordering::Relaxed);
+/// let progress = |event: nt_apiset::ProgressEvent| {
+///     println!("{}/{}", event.completed(), event.total());
+/// };
+///
+/// let control = OpControl::new()
+///     .should_cancel(&should_cancel)
+///     .progress(&progress);
+/// map.validate_with(&control).unwrap();
+/// ```
+#[derive(Clone, Copy, Default)]
+pub struct OpControl<'c> {
+    should_cancel: Option<&'c (dyn Fn() -> bool + Sync)>,
+    progress: Option<&'c (dyn Fn(ProgressEvent) + Sync)>,
+}
+
+impl<'c> OpControl<'c> {
+    /// Creates an [`OpControl`] without any callbacks, which lets an operation run to completion.
+    pub const fn new() -> Self {
+        Self {
+            should_cancel: None,
+            progress: None,
+        }
+    }
+
+    /// Sets the callback that is asked before every entry whether the operation shall be cancelled.
+    pub fn should_cancel(mut self, should_cancel: &'c (dyn Fn() -> bool + Sync)) -> Self {
+        self.should_cancel = Some(should_cancel);
+        self
+    }
+
+    /// Sets the callback that is notified after every processed entry.
+    pub fn progress(mut self, progress: &'c (dyn Fn(ProgressEvent) + Sync)) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Starts tracking an operation that processes `total` entries.
+    pub(crate) fn start(&self, total: usize) -> Progress<'_, 'c> {
+        Progress {
+            control: self,
+            event: ProgressEvent {
+                completed: 0,
+                total,
+            },
+        }
+    }
+}
+
+impl<'c> fmt::Debug for OpControl<'c> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OpControl")
+            .field("should_cancel", &self.should_cancel.is_some())
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+/// Progress of a single operation, created by [`OpControl::start`].
+pub(crate) struct Progress<'o, 'c> {
+    control: &'o OpControl<'c>,
+    event: ProgressEvent,
+}
+
+impl<'o, 'c> Progress<'o, 'c> {
+    /// Returns [`NtApiSetError::Cancelled`] if the operation shall be cancelled before processing the next entry.
+    pub(crate) fn check(&self) -> Result<()> {
+        match self.control.should_cancel {
+            Some(should_cancel) if should_cancel() => Err(NtApiSetError::Cancelled {
+                completed: self.event.completed,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reports that another entry has been processed.
+    pub(crate) fn advance(&mut self) {
+        self.event.completed += 1;
+
+        if let Some(progress) = self.control.progress {
+            progress(self.event);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+    use crate::map::ApiSetMap;
+    use crate::namespace_entry::ApiSetNamespaceEntryFlags;
+
+    const NAMESPACE_ENTRIES: usize = 5;
+
+    fn build_map() -> alloc::vec::Vec<u8> {
+        (0..NAMESPACE_ENTRIES)
+            .fold(ApiSetMapBuilder::new(), |builder, i| {
+                builder.add_namespace_entry(
+                    ApiSetNamespaceEntryBuilder::new(
+                        &alloc::format!("api-ms-win-core-test{i}-l1-1-0"),
+                        ApiSetNamespaceEntryFlags::SEALED,
+                    )
+                    .add_value_entry("", &alloc::format!("test{i}.dll")),
+                )
+            })
+            .build()
+            .unwrap()
+    }
+
+    /// Runs `op` with an [`OpControl`] that cancels once `cancel_after` progress events have been reported.
+    /// Returns the result of `op` and all reported progress events.
+    fn run_cancelled<T>(
+        cancel_after: usize,
+        op: impl FnOnce(&OpControl) -> T,
+    ) -> (T, alloc::vec::Vec<ProgressEvent>) {
+        let events = std::sync::Mutex::new(alloc::vec::Vec::new());
+        let checks = AtomicUsize::new(0);
+        let should_cancel = || {
+            checks.fetch_add(1, Ordering::Relaxed);
+            events.lock().unwrap().len() >= cancel_after
+        };
+        let progress = |event| events.lock().unwrap().push(event);
+
+        let control = OpControl::new()
+            .should_cancel(&should_cancel)
+            .progress(&progress);
+        let result = op(&control);
+
+        // The cancellation must be noticed by the very next check.
+        assert_eq!(checks.load(Ordering::Relaxed), cancel_after + 1);
+
+        (result, events.into_inner().unwrap())
+    }
+
+    fn expected_events(completed: usize, total: usize) -> alloc::vec::Vec<ProgressEvent> {
+        (1..=completed)
+            .map(|completed| ProgressEvent { completed, total })
+            .collect()
+    }
+
+    #[test]
+    fn test_validate_cancelled() {
+        let mut bytes = build_map();
+
+        // Let the hashed length of the first Namespace Entry exceed its name.
+        let namespace_entry_offset = ApiSetMap::try_from_apiset_section_bytes(&bytes)
+            .unwrap()
+            .namespace_entry_offset();
+        let hashed_length = namespace_entry_offset as usize + 12;
+        bytes[hashed_length..hashed_length + 4].copy_from_slice(&0xffffu32.to_le_bytes());
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+        // Every Namespace Entry and every Hash Entry is one step.
+        let total = 2 * NAMESPACE_ENTRIES;
+        let (result, events) = run_cancelled(2, |control| map.validate_with(control));
+        let findings = result.unwrap_err();
+
+        // The findings so far are returned, followed by the cancellation.
+        assert_eq!(findings.len(), 2);
+        assert!(matches!(
+            findings[0],
+            NtApiSetError::HashedLengthOutOfBounds { .. }
+        ));
+        assert_eq!(findings[1], NtApiSetError::Cancelled { completed: 2 });
+        assert_eq!(events, expected_events(2, total));
+
+        // Without cancellation, only the corruption is reported.
+        assert_eq!(map.validate().unwrap_err(), findings[..1]);
+    }
+
+    #[test]
+    fn test_validate_progress() {
+        let bytes = build_map();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+        let events = std::sync::Mutex::new(alloc::vec::Vec::new());
+        let progress = |event| events.lock().unwrap().push(event);
+        map.validate_with(&OpControl::new().progress(&progress))
+            .unwrap();
+
+        let total = 2 * NAMESPACE_ENTRIES;
+        assert_eq!(events.into_inner().unwrap(), expected_events(total, total));
+    }
+
+    #[test]
+    fn test_build_index_cancelled() {
+        let bytes = build_map();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+        for cancel_after in 0..NAMESPACE_ENTRIES {
+            let (result, events) =
+                run_cancelled(cancel_after, |control| map.build_index_with(control));
+
+            assert_eq!(
+                result.unwrap_err(),
+                NtApiSetError::Cancelled {
+                    completed: cancel_after
+                }
+            );
+            assert_eq!(events, expected_events(cancel_after, NAMESPACE_ENTRIES));
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_to_snapshot_cancelled() {
+        let bytes = build_map();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+        let (result, events) = run_cancelled(3, |control| map.to_snapshot_with(control));
+
+        assert_eq!(
+            result.unwrap_err(),
+            NtApiSetError::Cancelled { completed: 3 }
+        );
+        assert_eq!(events, expected_events(3, NAMESPACE_ENTRIES));
+    }
+}
@@ -0,0 +1,479 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::ops::Range;
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use nt_string::u16strle::U16StrLe;
+
+use crate::error::Result;
+use crate::map::ApiSetMap;
+use crate::namespace_entry::{ApiSetNamespaceEntries, ApiSetNamespaceEntry};
+use crate::value_entry::{ApiSetValueEntries, ApiSetValueEntry};
+
+const BITS_PER_WORD: usize = u64::BITS as usize;
+
+/// Records which bytes of the `.apiset` section have been read by operations on an [`ApiSetMap`].
+///
+/// This is useful for corpus minimization and format research.
+/// Wrapping an [`ApiSetMap`] doesn't change it in any way, and lookups performed directly on the [`ApiSetMap`] are not recorded.
+///
+/// Upon creation via [`CoverageMap::wrap`], the API Set Map header is recorded, because it has been read to parse the [`ApiSetMap`].
+/// Lookups via [`CoverageMap::find_namespace_entry`], [`CoverageMap::resolve_import`], and [`CoverageMap::resolve`] are recorded
+/// automatically, and so are traversals via [`CoverageMap::namespace_entries`] and [`CoverageMap::value_entries`].
+/// Entries obtained in other ways can be recorded via [`CoverageMap::record_namespace_entry`] and
+/// [`CoverageMap::record_value_entry`].
+#[derive(Clone, Debug)]
+pub struct CoverageMap<'m, 'a> {
+    map: &'m ApiSetMap<'a>,
+    bitmap: Vec<u64>,
+}
+
+impl<'m, 'a> CoverageMap<'m, 'a> {
+    /// Returns the recorded byte ranges, sorted and with adjacent ranges merged.
+    ///
+    /// The byte ranges are relative to the start of the `.apiset` section.
+    pub fn coverage(&self) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        let mut current_start = None;
+
+        for offset in 0..self.map.section_bytes().len() {
+            match (self.is_covered(offset), current_start) {
+                (true, None) => current_start = Some(offset),
+                (false, Some(start)) => {
+                    ranges.push(start..offset);
+                    current_start = None;
+                }
+                _ => (),
+            }
+        }
+
+        if let Some(start) = current_start {
+            ranges.push(start..self.map.section_bytes().len());
+        }
+
+        ranges
+    }
+
+    /// Returns the number of recorded bytes.
+    pub fn covered_bytes(&self) -> usize {
+        self.bitmap
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// Performs a lookup via [`ApiSetMap::find_namespace_entry`] and records all bytes read by it.
+    ///
+    /// This covers the Hash Entries visited by the binary search, the header of the found Namespace Entry, and its name.
+    /// For API Set Maps without a hash table, it covers the headers and names of all Namespace Entries visited by the binary search.
+    pub fn find_namespace_entry(
+        &mut self,
+        namespace_entry_name: &str,
+    ) -> Option<Result<ApiSetNamespaceEntry<'a>>> {
+        let map = self.map;
+        map.find_namespace_entry_traced(namespace_entry_name, &mut |range| self.record(range))
+    }
+
+    /// Returns an iterator over the Namespace Entries like [`ApiSetMap::namespace_entries`],
+    /// which records the header and the name of every returned entry.
+    pub fn namespace_entries<'c>(&'c mut self) -> Result<CoveredNamespaceEntries<'c, 'm, 'a>> {
+        let namespace_entries = self.map.namespace_entries()?;
+
+        Ok(CoveredNamespaceEntries {
+            coverage_map: self,
+            namespace_entries,
+        })
+    }
+
+    /// Returns the wrapped [`ApiSetMap`].
+    pub const fn map(&self) -> &'m ApiSetMap<'a> {
+        self.map
+    }
+
+    /// Adds all byte ranges recorded by `other` to this [`CoverageMap`].
+    ///
+    /// This allows to accumulate the coverage of many operations, e.g. performed on different threads.
+    /// Both [`CoverageMap`]s should wrap the same [`ApiSetMap`].
+    /// Recorded bytes of `other` beyond the size of this [`ApiSetMap`] are ignored.
+    pub fn merge(&mut self, other: &CoverageMap) {
+        for (word, other_word) in self.bitmap.iter_mut().zip(&other.bitmap) {
+            *word |= other_word;
+        }
+
+        self.clear_slack();
+    }
+
+    /// Performs a lookup via [`ApiSetMap::resolve`] and records all bytes read by it.
+    ///
+    /// In addition to the bytes covered by [`resolve_import`](Self::resolve_import), this covers the Value Entries visited by the
+    /// binary search for `importing_module` along with their names, and the value of the resolved Value Entry.
+    pub fn resolve(
+        &mut self,
+        apiset_name: &str,
+        importing_module: Option<&str>,
+    ) -> Option<Result<U16StrLe<'a>>> {
+        let map = self.map;
+        map.resolve_traced(apiset_name, importing_module, &mut |range| {
+            self.record(range)
+        })
+    }
+
+    /// Performs a lookup via [`ApiSetMap::resolve_import`] and records all bytes read by it.
+    ///
+    /// This covers the same bytes as [`find_namespace_entry`](Self::find_namespace_entry), but for every candidate with a
+    /// matching hash.
+    pub fn resolve_import(
+        &mut self,
+        import_name: &str,
+    ) -> Option<Result<ApiSetNamespaceEntry<'a>>> {
+        let map = self.map;
+        map.resolve_import_traced(import_name, &mut |range| self.record(range))
+    }
+
+    /// Records an arbitrary byte range of the `.apiset` section.
+    ///
+    /// Bytes beyond the size of the `.apiset` section are ignored.
+    pub fn record(&mut self, range: Range<usize>) {
+        let end = range.end.min(self.map.section_bytes().len());
+
+        for offset in range.start..end {
+            self.bitmap[offset / BITS_PER_WORD] |= 1 << (offset % BITS_PER_WORD);
+        }
+    }
+
+    /// Records the header and the name of `namespace_entry`.
+    ///
+    /// The name is only recorded if it is within the bounds of the `.apiset` section.
+    pub fn record_namespace_entry(&mut self, namespace_entry: &ApiSetNamespaceEntry) {
+        let start = namespace_entry.offset();
+        self.record(start..start + self.map.schema().namespace_entry_size());
+
+        if namespace_entry.name().is_ok() {
+            self.record(namespace_entry.name_range());
+        }
+    }
+
+    /// Records the header, the name, and the value of `value_entry`.
+    ///
+    /// The name and value are only recorded if they are within the bounds of the `.apiset` section.
+    pub fn record_value_entry(&mut self, value_entry: &ApiSetValueEntry) {
+        let start = value_entry.offset();
+        self.record(start..start + self.map.schema().value_entry_size());
+
+        if value_entry.name().is_ok() {
+            self.record(value_entry.name_range());
+        }
+
+        if value_entry.value().is_ok() {
+            self.record(value_entry.value_range());
+        }
+    }
+
+    /// Returns an iterator over the Value Entries of `namespace_entry` like [`ApiSetNamespaceEntry::value_entries`],
+    /// which records the header, the name, and the value of every returned entry.
+    ///
+    /// Before version 6, the Value Entries are preceded by a header with their count, which is recorded as well.
+    pub fn value_entries<'c>(
+        &'c mut self,
+        namespace_entry: &ApiSetNamespaceEntry<'a>,
+    ) -> Result<CoveredValueEntries<'c, 'm, 'a>> {
+        let value_entries = namespace_entry.value_entries()?;
+
+        let value_array_header_size = self.map.schema().value_array_header_size();
+        if value_array_header_size > 0 {
+            let start = namespace_entry.value_array_offset();
+            self.record(start..start + value_array_header_size);
+        }
+
+        Ok(CoveredValueEntries {
+            coverage_map: self,
+            value_entries,
+        })
+    }
+
+    /// Wraps `map` into a new [`CoverageMap`] that initially only covers the API Set Map header.
+    pub fn wrap(map: &'m ApiSetMap<'a>) -> Self {
+        let word_count = (map.section_bytes().len() + BITS_PER_WORD - 1) / BITS_PER_WORD;
+
+        let mut coverage_map = Self {
+            map,
+            bitmap: vec![0; word_count],
+        };
+        coverage_map.record(0..map.schema().map_header_size());
+
+        coverage_map
+    }
+
+    fn clear_slack(&mut self) {
+        let length = self.map.section_bytes().len();
+        let used_bits = length % BITS_PER_WORD;
+
+        if used_bits != 0 {
+            if let Some(last_word) = self.bitmap.last_mut() {
+                *last_word &= (1 << used_bits) - 1;
+            }
+        }
+    }
+
+    fn is_covered(&self, offset: usize) -> bool {
+        self.bitmap[offset / BITS_PER_WORD] & (1 << (offset % BITS_PER_WORD)) != 0
+    }
+}
+
+/// Iterator over the Namespace Entries of a [`CoverageMap`], returned by [`CoverageMap::namespace_entries`].
+#[derive(Debug)]
+pub struct CoveredNamespaceEntries<'c, 'm, 'a> {
+    coverage_map: &'c mut CoverageMap<'m, 'a>,
+    namespace_entries: ApiSetNamespaceEntries<'a>,
+}
+
+impl<'c, 'm, 'a> Iterator for CoveredNamespaceEntries<'c, 'm, 'a> {
+    type Item = ApiSetNamespaceEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let namespace_entry = self.namespace_entries.next()?;
+        self.coverage_map.record_namespace_entry(&namespace_entry);
+        Some(namespace_entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.namespace_entries.size_hint()
+    }
+}
+
+/// Iterator over the Value Entries of a Namespace Entry of a [`CoverageMap`], returned by [`CoverageMap::value_entries`].
+#[derive(Debug)]
+pub struct CoveredValueEntries<'c, 'm, 'a> {
+    coverage_map: &'c mut CoverageMap<'m, 'a>,
+    value_entries: ApiSetValueEntries<'a>,
+}
+
+impl<'c, 'm, 'a> Iterator for CoveredValueEntries<'c, 'm, 'a> {
+    type Item = ApiSetValueEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let value_entry = self.value_entries.next()?;
+        self.coverage_map.record_value_entry(&value_entry);
+        Some(value_entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.value_entries.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+    use crate::namespace_entry::ApiSetNamespaceEntryFlags;
+
+    const NAMESPACE_ENTRIES: usize = 64;
+    const HASH_ENTRY_SIZE: usize = 8;
+
+    /// Builds a map whose Namespace Entries all have different hashes.
+    fn build_map() -> Vec<u8> {
+        (0..NAMESPACE_ENTRIES)
+            .fold(ApiSetMapBuilder::new(), |builder, i| {
+                builder.add_namespace_entry(
+                    ApiSetNamespaceEntryBuilder::new(
+                        &format!("api-ms-win-core-test{i:02}-l1-1-0"),
+                        ApiSetNamespaceEntryFlags::empty(),
+                    )
+                    .add_value_entry("", &format!("test{i:02}.dll"))
+                    .add_value_entry("advapi32.dll", "advapi.dll")
+                    .add_value_entry("kernel32.dll", "kernelbase.dll")
+                    .add_value_entry("user32.dll", "user.dll"),
+                )
+            })
+            .build()
+            .unwrap()
+    }
+
+    /// Returns the number of recorded bytes within `range`.
+    fn covered(coverage_map: &CoverageMap, range: Range<usize>) -> usize {
+        coverage_map
+            .coverage()
+            .into_iter()
+            .map(|covered| {
+                covered
+                    .end
+                    .min(range.end)
+                    .saturating_sub(covered.start.max(range.start))
+            })
+            .sum()
+    }
+
+    fn hash_table(map: &ApiSetMap) -> Range<usize> {
+        let start = map.hash_entry_offset() as usize;
+        start..start + NAMESPACE_ENTRIES * HASH_ENTRY_SIZE
+    }
+
+    fn namespace_entry_header(namespace_entry: &ApiSetNamespaceEntry) -> Range<usize> {
+        let start = namespace_entry.offset();
+        start..start + namespace_entry.schema().namespace_entry_size()
+    }
+
+    #[test]
+    fn test_find_namespace_entry() {
+        let bytes = build_map();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let header_size = map.schema().map_header_size();
+        let max_hash_entries = NAMESPACE_ENTRIES.trailing_zeros() as usize + 1;
+
+        for i in [0, 17, NAMESPACE_ENTRIES - 1] {
+            let name = format!("api-ms-win-core-test{i:02}-l1-1-0");
+            let mut coverage_map = CoverageMap::wrap(&map);
+            let namespace_entry = coverage_map.find_namespace_entry(&name).unwrap().unwrap();
+
+            // The header, about log2(n) Hash Entries, a single Namespace Entry header, and its name.
+            let hash_entries = covered(&coverage_map, hash_table(&map)) / HASH_ENTRY_SIZE;
+            assert!((1..=max_hash_entries).contains(&hash_entries));
+            assert_eq!(covered(&coverage_map, 0..header_size), header_size);
+
+            let header = namespace_entry_header(&namespace_entry);
+            let name_range = namespace_entry.name_range();
+            assert_eq!(covered(&coverage_map, header.clone()), header.len());
+            assert_eq!(covered(&coverage_map, name_range.clone()), name_range.len());
+
+            assert_eq!(
+                coverage_map.covered_bytes(),
+                header_size + hash_entries * HASH_ENTRY_SIZE + header.len() + name_range.len()
+            );
+
+            // `resolve_import` reads the same bytes for an exact name.
+            let mut import_coverage_map = CoverageMap::wrap(&map);
+            import_coverage_map
+                .resolve_import(&format!("{}.DLL", name.to_uppercase()))
+                .unwrap()
+                .unwrap();
+            assert_eq!(import_coverage_map.coverage(), coverage_map.coverage());
+        }
+
+        // A miss only touches the header and the Hash Entries.
+        let mut coverage_map = CoverageMap::wrap(&map);
+        assert!(coverage_map
+            .find_namespace_entry("api-ms-win-core-missing-l1-1-0")
+            .is_none());
+        let hash_entries = covered(&coverage_map, hash_table(&map)) / HASH_ENTRY_SIZE;
+        assert!(hash_entries <= max_hash_entries);
+        assert_eq!(
+            coverage_map.covered_bytes(),
+            header_size + hash_entries * HASH_ENTRY_SIZE
+        );
+    }
+
+    #[test]
+    fn test_resolve() {
+        let bytes = build_map();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let name = "api-ms-win-core-test05-l1-1-0.dll";
+
+        let mut import_coverage_map = CoverageMap::wrap(&map);
+        let namespace_entry = import_coverage_map.resolve_import(name).unwrap().unwrap();
+        let value_entries = namespace_entry.value_entries().unwrap().collect::<Vec<_>>();
+
+        // Resolving the default host reads the default Value Entry and its value in addition.
+        let mut coverage_map = CoverageMap::wrap(&map);
+        assert_eq!(
+            coverage_map.resolve(name, None).unwrap().unwrap(),
+            "test05.dll"
+        );
+        let default_value_entry = &value_entries[0];
+        let mut expected = import_coverage_map.clone();
+        let start = default_value_entry.offset();
+        expected.record(start..start + map.schema().value_entry_size());
+        expected.record(default_value_entry.value_range());
+        assert_eq!(coverage_map.coverage(), expected.coverage());
+
+        // Resolving a host-specific mapping reads the visited Value Entries and their names, but only the resolved value.
+        let mut coverage_map = CoverageMap::wrap(&map);
+        assert_eq!(
+            coverage_map
+                .resolve(name, Some("KERNEL32.dll"))
+                .unwrap()
+                .unwrap(),
+            "kernelbase.dll"
+        );
+        let kernel32_value_entry = &value_entries[2];
+        assert_eq!(
+            covered(&coverage_map, kernel32_value_entry.name_range()),
+            kernel32_value_entry.name_range().len()
+        );
+        assert_eq!(
+            covered(&coverage_map, kernel32_value_entry.value_range()),
+            kernel32_value_entry.value_range().len()
+        );
+        assert_eq!(covered(&coverage_map, default_value_entry.value_range()), 0);
+
+        for value_entry in &value_entries[1..] {
+            if value_entry.offset() != kernel32_value_entry.offset() {
+                assert_eq!(covered(&coverage_map, value_entry.value_range()), 0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_traversal() {
+        let bytes = build_map();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let mut coverage_map = CoverageMap::wrap(&map);
+
+        let namespace_entries = coverage_map
+            .namespace_entries()
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(namespace_entries.len(), NAMESPACE_ENTRIES);
+
+        // Traversing reads every Namespace Entry header and name, but no Hash Entry.
+        for namespace_entry in &namespace_entries {
+            let header = namespace_entry_header(namespace_entry);
+            assert_eq!(covered(&coverage_map, header.clone()), header.len());
+            let name_range = namespace_entry.name_range();
+            assert_eq!(covered(&coverage_map, name_range.clone()), name_range.len());
+        }
+        assert_eq!(covered(&coverage_map, hash_table(&map)), 0);
+
+        // Traversing the Value Entries of one Namespace Entry reads all of their strings.
+        let value_entries = coverage_map
+            .value_entries(&namespace_entries[3])
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(value_entries.len(), 4);
+
+        for value_entry in &value_entries {
+            let value_range = value_entry.value_range();
+            assert_eq!(
+                covered(&coverage_map, value_range.clone()),
+                value_range.len()
+            );
+        }
+
+        let other_value_entry = namespace_entries[4].default_value_entry().unwrap().unwrap();
+        assert_eq!(covered(&coverage_map, other_value_entry.value_range()), 0);
+    }
+
+    #[test]
+    fn test_merge() {
+        let bytes = build_map();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+        let mut first = CoverageMap::wrap(&map);
+        first.resolve("api-ms-win-core-test01-l1-1-0", None);
+        let mut second = CoverageMap::wrap(&map);
+        second.resolve("api-ms-win-core-test42-l1-1-0", Some("user32.dll"));
+
+        let mut both = CoverageMap::wrap(&map);
+        both.resolve("api-ms-win-core-test01-l1-1-0", None);
+        both.resolve("api-ms-win-core-test42-l1-1-0", Some("user32.dll"));
+
+        first.merge(&second);
+        assert_eq!(first.coverage(), both.coverage());
+        assert_eq!(first.covered_bytes(), both.covered_bytes());
+    }
+}
@@ -0,0 +1,369 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::error::{NtApiSetError, Result};
+use crate::map::ApiSetMap;
+use crate::mappings::ApiSetMappings;
+use crate::namespace_entry::ApiSetNamespaceEntries;
+
+/// Position of an [`ApiSetNamespaceEntries`] iterator that can be stored and resumed later.
+///
+/// A cursor is returned by [`ApiSetNamespaceEntries::cursor`] and consumed by [`ApiSetMap::resume`].
+/// It consists of the index range of the Namespace Entries that have not been returned yet and a fingerprint of
+/// the `.apiset` section, so that resuming on a different API Set Map is detected.
+/// As both ends are saved, this also works for iterators that have been consumed from the back.
+///
+/// Use [`to_bytes`](Self::to_bytes) and [`from_bytes`](Self::from_bytes) to persist a cursor, e.g. across process restarts.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ApiSetCursor {
+    index: u64,
+    end: u64,
+    fingerprint: u64,
+}
+
+impl ApiSetCursor {
+    pub(crate) const fn new(index: usize, end: usize, section_bytes: &[u8]) -> Self {
+        Self {
+            index: index as u64,
+            end: end as u64,
+            fingerprint: fingerprint(section_bytes),
+        }
+    }
+
+    /// Returns the index after the last Namespace Entry to be returned after resuming.
+    pub const fn end(&self) -> u64 {
+        self.end
+    }
+
+    /// Returns the fingerprint of the `.apiset` section this cursor was created for.
+    pub const fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    /// Reconstructs a cursor from the bytes returned by [`to_bytes`](Self::to_bytes).
+    pub fn from_bytes(bytes: [u8; 24]) -> Self {
+        let [index, end, fingerprint] = u64_fields(bytes);
+
+        Self {
+            index,
+            end,
+            fingerprint,
+        }
+    }
+
+    /// Returns the index of the next Namespace Entry to be returned after resuming.
+    pub const fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Returns a platform-independent representation of this cursor for storing it.
+    pub fn to_bytes(&self) -> [u8; 24] {
+        u64_fields_to_bytes([self.index, self.end, self.fingerprint])
+    }
+}
+
+/// Position of an [`ApiSetMappings`] iterator that can be stored and resumed later.
+///
+/// A cursor is returned by [`ApiSetMappings::cursor`] and consumed by [`ApiSetMap::resume_mappings`].
+/// It consists of the index of the current Namespace Entry, the index of the next Value Entry inside it,
+/// and a fingerprint of the `.apiset` section, so that resuming on a different API Set Map is detected.
+///
+/// Use [`to_bytes`](Self::to_bytes) and [`from_bytes`](Self::from_bytes) to persist a cursor, e.g. across process restarts.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ApiSetMappingCursor {
+    namespace_entry_index: u64,
+    value_entry_index: u64,
+    fingerprint: u64,
+}
+
+impl ApiSetMappingCursor {
+    pub(crate) const fn new(
+        namespace_entry_index: usize,
+        value_entry_index: usize,
+        section_bytes: &[u8],
+    ) -> Self {
+        Self {
+            namespace_entry_index: namespace_entry_index as u64,
+            value_entry_index: value_entry_index as u64,
+            fingerprint: fingerprint(section_bytes),
+        }
+    }
+
+    /// Returns the fingerprint of the `.apiset` section this cursor was created for.
+    pub const fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    /// Reconstructs a cursor from the bytes returned by [`to_bytes`](Self::to_bytes).
+    pub fn from_bytes(bytes: [u8; 24]) -> Self {
+        let [namespace_entry_index, value_entry_index, fingerprint] = u64_fields(bytes);
+
+        Self {
+            namespace_entry_index,
+            value_entry_index,
+            fingerprint,
+        }
+    }
+
+    /// Returns the index of the Namespace Entry to continue with after resuming.
+    pub const fn namespace_entry_index(&self) -> u64 {
+        self.namespace_entry_index
+    }
+
+    /// Returns a platform-independent representation of this cursor for storing it.
+    pub fn to_bytes(&self) -> [u8; 24] {
+        u64_fields_to_bytes([
+            self.namespace_entry_index,
+            self.value_entry_index,
+            self.fingerprint,
+        ])
+    }
+
+    /// Returns the index of the next Value Entry of that Namespace Entry to be returned after resuming.
+    pub const fn value_entry_index(&self) -> u64 {
+        self.value_entry_index
+    }
+}
+
+impl<'a> ApiSetMap<'a> {
+    /// Returns an iterator over the [`ApiSetNamespaceEntry`]s of this [`ApiSetMap`], starting at the position saved in `cursor`.
+    ///
+    /// The iterator covers the same Namespace Entries that the iterator `cursor` was created from had not returned yet,
+    /// from either end.
+    ///
+    /// If `cursor` was created for a different `.apiset` section, [`NtApiSetError::CursorMismatch`] is returned.
+    /// Computing the fingerprint requires reading the entire section.
+    ///
+    /// [`ApiSetNamespaceEntry`]: crate::namespace_entry::ApiSetNamespaceEntry
+    pub fn resume(&self, cursor: ApiSetCursor) -> Result<ApiSetNamespaceEntries<'a>> {
+        self.check_fingerprint(cursor.fingerprint)?;
+
+        let index = usize::try_from(cursor.index).unwrap_or(usize::MAX);
+        let end = usize::try_from(cursor.end).unwrap_or(usize::MAX);
+
+        self.namespace_entries()?.restrict(index..end)
+    }
+
+    /// Returns an iterator over the mappings of this [`ApiSetMap`], starting at the position saved in `cursor`.
+    ///
+    /// See [`mappings`](Self::mappings).
+    /// If `cursor` was created for a different `.apiset` section, [`NtApiSetError::CursorMismatch`] is returned.
+    /// Computing the fingerprint requires reading the entire section.
+    pub fn resume_mappings(&self, cursor: ApiSetMappingCursor) -> Result<ApiSetMappings<'a>> {
+        self.check_fingerprint(cursor.fingerprint)?;
+
+        let namespace_entry_index =
+            usize::try_from(cursor.namespace_entry_index).unwrap_or(usize::MAX);
+        let value_entry_index = usize::try_from(cursor.value_entry_index).unwrap_or(usize::MAX);
+
+        let namespace_entries = self.namespace_entries()?;
+        let count = namespace_entries.len();
+        let namespace_entries = namespace_entries.restrict(namespace_entry_index..count)?;
+
+        ApiSetMappings::new(namespace_entries).skip_value_entries(value_entry_index)
+    }
+
+    fn check_fingerprint(&self, expected: u64) -> Result<()> {
+        let actual = fingerprint(self.section_bytes());
+
+        if expected == actual {
+            Ok(())
+        } else {
+            Err(NtApiSetError::CursorMismatch { expected, actual })
+        }
+    }
+}
+
+/// Computes the 64-bit FNV-1a hash of `bytes`.
+///
+/// This is no cryptographic hash, but sufficient to detect that a cursor is used with a different API Set Map.
+const fn fingerprint(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        hash ^= bytes[i] as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+        i += 1;
+    }
+
+    hash
+}
+
+fn u64_fields(bytes: [u8; 24]) -> [u64; 3] {
+    let mut fields = [0u64; 3];
+
+    for (field, chunk) in fields.iter_mut().zip(bytes.chunks_exact(8)) {
+        let mut field_bytes = [0u8; 8];
+        field_bytes.copy_from_slice(chunk);
+        *field = u64::from_le_bytes(field_bytes);
+    }
+
+    fields
+}
+
+fn u64_fields_to_bytes(fields: [u64; 3]) -> [u8; 24] {
+    let mut bytes = [0u8; 24];
+
+    for (chunk, field) in bytes.chunks_exact_mut(8).zip(fields) {
+        chunk.copy_from_slice(&field.to_le_bytes());
+    }
+
+    bytes
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+    use crate::namespace_entry::{ApiSetNamespaceEntry, ApiSetNamespaceEntryFlags};
+    use crate::value_entry::ApiSetValueEntry;
+
+    fn build(host: &str) -> Vec<u8> {
+        let namespace_entry =
+            |name| ApiSetNamespaceEntryBuilder::new(name, ApiSetNamespaceEntryFlags::empty());
+
+        ApiSetMapBuilder::new()
+            .add_namespace_entry(
+                namespace_entry("api-ms-win-core-a-l1-1-0").add_value_entry("", host),
+            )
+            .add_namespace_entry(
+                namespace_entry("api-ms-win-core-b-l1-1-0")
+                    .add_value_entry("", "b.dll")
+                    .add_value_entry("kernel32.dll", "kernelbase.dll")
+                    .add_value_entry("user32.dll", "win32u.dll"),
+            )
+            .add_namespace_entry(namespace_entry("api-ms-win-core-c-l1-1-0"))
+            .add_namespace_entry(
+                namespace_entry("api-ms-win-core-d-l1-1-0")
+                    .add_value_entry("", "d.dll")
+                    .add_value_entry("kernel32.dll", "kernelbase.dll"),
+            )
+            .add_namespace_entry(
+                namespace_entry("api-ms-win-core-e-l1-1-0").add_value_entry("", "e.dll"),
+            )
+            .build()
+            .unwrap()
+    }
+
+    fn name(namespace_entry: ApiSetNamespaceEntry) -> String {
+        namespace_entry.name().unwrap().to_string().unwrap()
+    }
+
+    fn mapping(
+        result: Result<(ApiSetNamespaceEntry, ApiSetValueEntry)>,
+    ) -> (String, String, String) {
+        let (namespace_entry, value_entry) = result.unwrap();
+        (
+            name(namespace_entry),
+            value_entry.name().unwrap().to_string().unwrap(),
+            value_entry.value().unwrap().to_string().unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_resume() {
+        let bytes = build("a.dll");
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let full = map
+            .namespace_entries()
+            .unwrap()
+            .map(name)
+            .collect::<Vec<_>>();
+
+        for consumed in 0..=full.len() {
+            let mut namespace_entries = map.namespace_entries().unwrap();
+            let mut output = namespace_entries
+                .by_ref()
+                .take(consumed)
+                .map(name)
+                .collect::<Vec<_>>();
+            let stored = namespace_entries.cursor().to_bytes();
+
+            // Resume on a separately parsed copy of the same bytes.
+            let copy = bytes.clone();
+            let resumed_map = ApiSetMap::try_from_apiset_section_bytes(&copy).unwrap();
+            let cursor = ApiSetCursor::from_bytes(stored);
+            assert_eq!(cursor.index(), consumed as u64);
+
+            output.extend(resumed_map.resume(cursor).unwrap().map(name));
+            assert_eq!(output, full);
+        }
+    }
+
+    #[test]
+    fn test_resume_from_both_ends() {
+        let bytes = build("a.dll");
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+        let mut namespace_entries = map.namespace_entries().unwrap();
+        namespace_entries.next().unwrap();
+        namespace_entries.next_back().unwrap();
+        namespace_entries.next_back().unwrap();
+
+        let cursor = ApiSetCursor::from_bytes(namespace_entries.cursor().to_bytes());
+        assert_eq!((cursor.index(), cursor.end()), (1, 3));
+
+        let resumed = map.resume(cursor).unwrap().map(name).collect::<Vec<_>>();
+        let remaining = namespace_entries.map(name).collect::<Vec<_>>();
+        assert_eq!(
+            resumed,
+            ["api-ms-win-core-b-l1-1-0", "api-ms-win-core-c-l1-1-0"]
+        );
+        assert_eq!(resumed, remaining);
+    }
+
+    #[test]
+    fn test_resume_mappings() {
+        let bytes = build("a.dll");
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let full = map.mappings().unwrap().map(mapping).collect::<Vec<_>>();
+        assert_eq!(full.len(), 7);
+
+        for consumed in 0..=full.len() {
+            let mut mappings = map.mappings().unwrap();
+            let mut output = mappings
+                .by_ref()
+                .take(consumed)
+                .map(mapping)
+                .collect::<Vec<_>>();
+            let stored = mappings.cursor().to_bytes();
+
+            let copy = bytes.clone();
+            let resumed_map = ApiSetMap::try_from_apiset_section_bytes(&copy).unwrap();
+            let cursor = ApiSetMappingCursor::from_bytes(stored);
+
+            output.extend(resumed_map.resume_mappings(cursor).unwrap().map(mapping));
+            assert_eq!(output, full);
+        }
+    }
+
+    #[test]
+    fn test_resume_mismatch() {
+        let bytes = build("a.dll");
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let other_bytes = build("other.dll");
+        let other = ApiSetMap::try_from_apiset_section_bytes(&other_bytes).unwrap();
+
+        let mut namespace_entries = map.namespace_entries().unwrap();
+        namespace_entries.next().unwrap();
+        let cursor = namespace_entries.cursor();
+
+        let mut mappings = map.mappings().unwrap();
+        mappings.next().unwrap().unwrap();
+        let mapping_cursor = mappings.cursor();
+
+        let expected_error = NtApiSetError::CursorMismatch {
+            expected: cursor.fingerprint(),
+            actual: fingerprint(&other_bytes),
+        };
+        assert_eq!(other.resume(cursor).unwrap_err(), expected_error);
+        assert_eq!(
+            other.resume_mappings(mapping_cursor).unwrap_err(),
+            expected_error
+        );
+    }
+}
@@ -0,0 +1,318 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+#[cfg(feature = "pelite")]
+use std::path::Path;
+
+use nt_string::u16strle::U16StrLe;
+
+use crate::error::{NtApiSetError, Result};
+use crate::map::{ApiSetMap, ApiSetMapFlags};
+use crate::owned::OwnedApiSetMap;
+
+/// Architecture whose API Set Map is used by [`DualArchSchema::resolve`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Architecture {
+    /// The native architecture, whose `apisetschema.dll` is located in `System32`.
+    Native,
+    /// 32-bit processes running under WoW64, whose `apisetschema.dll` is located in `SysWOW64`.
+    Wow64,
+}
+
+/// Difference between the native and the WoW64 API Set Map of a [`DualArchSchema`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DualArchFinding {
+    /// The API Set Maps have different flags.
+    Flags {
+        /// Flags of the native API Set Map.
+        native: ApiSetMapFlags,
+        /// Flags of the WoW64 API Set Map.
+        wow64: ApiSetMapFlags,
+    },
+    /// The first Namespace Entry of the API Set Map of `architecture` that doesn't exist with the same mappings in the other one.
+    NamespaceEntry {
+        /// Architecture of the API Set Map containing the Namespace Entry.
+        architecture: Architecture,
+        /// Name of the Namespace Entry.
+        name: String,
+    },
+    /// The API Set Maps have different versions, so their Namespace Entries have not been compared further.
+    Version {
+        /// Version of the native API Set Map.
+        native: u32,
+        /// Version of the WoW64 API Set Map.
+        wow64: u32,
+    },
+}
+
+/// The native and the WoW64 API Set Map of a 64-bit Windows installation.
+///
+/// On 64-bit Windows, both `System32` and `SysWOW64` contain an `apisetschema.dll`.
+/// They are supposed to be semantically identical, which is checked upon creation.
+/// Any differences are reported by [`findings`](Self::findings).
+///
+/// ```
+/// # use nt_apiset::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder, ApiSetNamespaceEntryFlags, Architecture, DualArchFinding, DualArchSchema, OwnedApiSetMap};
+/// let build = |host| {
+///     let bytes = ApiSetMapBuilder::new()
+///         .add_namespace_entry(
+///             ApiSetNamespaceEntryBuilder::new("api-ms-win-core-foo-l1-1-0", ApiSetNamespaceEntryFlags::empty())
+///                 .add_value_entry("", host),
+///         )
+///         .build()
+///         .unwrap();
+///     OwnedApiSetMap::try_from_apiset_section_bytes(bytes).unwrap()
+/// };
+///
+/// let schema = DualArchSchema::new(build("foo.dll"), build("foo32.dll")).unwrap();
+/// assert!(!schema.is_consistent());
+/// assert_eq!(
+///     schema.resolve(Architecture::Wow64, "api-ms-win-core-foo-l1-1-0.dll", None).unwrap().unwrap(),
+///     "foo32.dll"
+/// );
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[derive(Clone, Debug)]
+pub struct DualArchSchema {
+    native: OwnedApiSetMap,
+    wow64: OwnedApiSetMap,
+    findings: Vec<DualArchFinding>,
+}
+
+impl DualArchSchema {
+    /// Returns all differences between the native and the WoW64 API Set Map.
+    ///
+    /// Per architecture, at most one [`DualArchFinding::NamespaceEntry`] is reported.
+    pub fn findings(&self) -> &[DualArchFinding] {
+        &self.findings
+    }
+
+    /// Returns `true` if the native and the WoW64 API Set Map are semantically identical.
+    pub fn is_consistent(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    /// Loads the `apisetschema.dll` files from the `System32` and `SysWOW64` directories of the running Windows installation.
+    ///
+    /// See [`load_from`](Self::load_from).
+    #[cfg(all(windows, feature = "pelite"))]
+    #[cfg_attr(docsrs, doc(cfg(all(windows, feature = "std", feature = "pelite"))))]
+    pub fn load() -> Result<Self> {
+        let system_root = std::env::var_os("SystemRoot").unwrap_or_else(|| "C:\\Windows".into());
+        let system_root = Path::new(&system_root);
+
+        Self::load_from(
+            system_root.join("System32").join("apisetschema.dll"),
+            system_root.join("SysWOW64").join("apisetschema.dll"),
+        )
+    }
+
+    /// Loads the native and the WoW64 `apisetschema.dll` from the given paths and compares them.
+    ///
+    /// Both 32-bit and 64-bit files are accepted.
+    /// If a file cannot be read, [`NtApiSetError::FileReadFailed`] is returned.
+    /// If it is no PE file, [`NtApiSetError::InvalidPeFile`] is returned.
+    #[cfg(feature = "pelite")]
+    #[cfg_attr(docsrs, doc(cfg(all(feature = "std", feature = "pelite"))))]
+    pub fn load_from<P, Q>(native_path: P, wow64_path: Q) -> Result<Self>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        Self::new(
+            load_file(native_path.as_ref())?,
+            load_file(wow64_path.as_ref())?,
+        )
+    }
+
+    /// Returns the native API Set Map.
+    pub fn native(&self) -> ApiSetMap<'_> {
+        self.native.as_map()
+    }
+
+    /// Compares the native and the WoW64 API Set Map and combines them.
+    ///
+    /// An error is only returned if an entry of either API Set Map cannot be read.
+    pub fn new(native: OwnedApiSetMap, wow64: OwnedApiSetMap) -> Result<Self> {
+        let findings = compare(&native.as_map(), &wow64.as_map())?;
+
+        Ok(Self {
+            native,
+            wow64,
+            findings,
+        })
+    }
+
+    /// Resolves an imported API Set to the name of its host DLL in the context of the given `architecture`.
+    ///
+    /// See [`ApiSetMap::resolve`].
+    pub fn resolve(
+        &self,
+        architecture: Architecture,
+        apiset_name: &str,
+        importing_module: Option<&str>,
+    ) -> Option<Result<U16StrLe<'_>>> {
+        let map = match architecture {
+            Architecture::Native => &self.native,
+            Architecture::Wow64 => &self.wow64,
+        };
+
+        map.resolve(apiset_name, importing_module)
+    }
+
+    /// Returns the WoW64 API Set Map.
+    pub fn wow64(&self) -> ApiSetMap<'_> {
+        self.wow64.as_map()
+    }
+}
+
+fn compare(native: &ApiSetMap, wow64: &ApiSetMap) -> Result<Vec<DualArchFinding>> {
+    let mut findings = Vec::new();
+
+    if native.version() != wow64.version() {
+        findings.push(DualArchFinding::Version {
+            native: native.version(),
+            wow64: wow64.version(),
+        });
+        return Ok(findings);
+    }
+
+    if native.flags() != wow64.flags() {
+        findings.push(DualArchFinding::Flags {
+            native: native.flags(),
+            wow64: wow64.flags(),
+        });
+    }
+
+    for (architecture, map, other) in [
+        (Architecture::Native, native, wow64),
+        (Architecture::Wow64, wow64, native),
+    ] {
+        if let Some(namespace_entry) = map.semantic_subset_counterexample(other)? {
+            let name = namespace_entry.name()?;
+            let name = name.to_string().map_err(|_| NtApiSetError::InvalidUtf16 {
+                range: namespace_entry.name_range(),
+            })?;
+
+            findings.push(DualArchFinding::NamespaceEntry { architecture, name });
+        }
+    }
+
+    Ok(findings)
+}
+
+/// Loads a 32-bit or 64-bit API Set Map file.
+#[cfg(feature = "pelite")]
+pub(crate) fn load_file(path: &Path) -> Result<OwnedApiSetMap> {
+    let dll = std::fs::read(path).map_err(|e| NtApiSetError::FileReadFailed {
+        path: path.to_path_buf(),
+        kind: e.kind(),
+    })?;
+    let pe_file = pelite::PeFile::from_bytes(&dll).map_err(|_| NtApiSetError::InvalidPeFile)?;
+
+    OwnedApiSetMap::try_from_pe_file(pe_file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+    use crate::namespace_entry::ApiSetNamespaceEntryFlags;
+
+    fn build(flags: ApiSetMapFlags, entries: &[(&str, &str)]) -> OwnedApiSetMap {
+        let bytes = entries
+            .iter()
+            .fold(
+                ApiSetMapBuilder::new().flags(flags),
+                |builder, (name, host)| {
+                    builder.add_namespace_entry(
+                        ApiSetNamespaceEntryBuilder::new(name, ApiSetNamespaceEntryFlags::empty())
+                            .add_value_entry("", host),
+                    )
+                },
+            )
+            .build()
+            .unwrap();
+
+        OwnedApiSetMap::try_from_apiset_section_bytes(bytes).unwrap()
+    }
+
+    #[test]
+    fn test_consistent() {
+        let entries = [
+            ("api-ms-win-core-bar-l1-1-0", "bar.dll"),
+            ("api-ms-win-core-foo-l1-1-0", "foo.dll"),
+        ];
+        let schema = DualArchSchema::new(
+            build(ApiSetMapFlags::SEALED, &entries),
+            build(ApiSetMapFlags::SEALED, &entries),
+        )
+        .unwrap();
+
+        assert!(schema.is_consistent());
+        assert!(schema.native().semantic_eq(&schema.wow64()).unwrap());
+    }
+
+    #[test]
+    fn test_divergence() {
+        let native = build(
+            ApiSetMapFlags::SEALED,
+            &[
+                ("api-ms-win-core-bar-l1-1-0", "bar.dll"),
+                ("api-ms-win-core-foo-l1-1-0", "foo.dll"),
+            ],
+        );
+        let wow64 = build(
+            ApiSetMapFlags::empty(),
+            &[
+                ("api-ms-win-core-foo-l1-1-0", "foo.dll"),
+                ("api-ms-win-core-qux-l1-1-0", "qux.dll"),
+            ],
+        );
+        let schema = DualArchSchema::new(native, wow64).unwrap();
+
+        assert_eq!(
+            schema.findings(),
+            [
+                DualArchFinding::Flags {
+                    native: ApiSetMapFlags::SEALED,
+                    wow64: ApiSetMapFlags::empty(),
+                },
+                DualArchFinding::NamespaceEntry {
+                    architecture: Architecture::Native,
+                    name: "api-ms-win-core-bar-l1-1-0".into(),
+                },
+                DualArchFinding::NamespaceEntry {
+                    architecture: Architecture::Wow64,
+                    name: "api-ms-win-core-qux-l1-1-0".into(),
+                },
+            ]
+        );
+
+        assert_eq!(
+            schema
+                .resolve(Architecture::Native, "api-ms-win-core-bar-l1-1-0.dll", None)
+                .unwrap()
+                .unwrap(),
+            "bar.dll"
+        );
+        assert!(schema
+            .resolve(Architecture::Wow64, "api-ms-win-core-bar-l1-1-0.dll", None)
+            .is_none());
+    }
+
+    #[cfg(feature = "pelite")]
+    #[test]
+    fn test_load_from_missing_file() {
+        let error =
+            DualArchSchema::load_from("does-not-exist.dll", "does-not-exist.dll").unwrap_err();
+
+        assert_eq!(
+            error,
+            NtApiSetError::FileReadFailed {
+                path: "does-not-exist.dll".into(),
+                kind: std::io::ErrorKind::NotFound,
+            }
+        );
+    }
+}
@@ -0,0 +1,83 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// `embedded_map.apiset.deflate` is the raw DEFLATE stream of `synthetic_map(EMBEDDED_MAP_SEED)`.
+// After changing the generator, regenerate it via:
+//
+//     cargo test --features embedded-map,test-utils -- --ignored regenerate_embedded_map
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use once_cell::race::OnceBox;
+
+use crate::map::ApiSetMap;
+
+/// Seed of the synthetic API Set Map returned by [`embedded_map`].
+#[cfg_attr(not(test), allow(dead_code))]
+const EMBEDDED_MAP_SEED: u64 = 1;
+
+static EMBEDDED_MAP_DEFLATED: &[u8] = include_bytes!("embedded_map.apiset.deflate");
+static EMBEDDED_MAP: OnceBox<Vec<u8>> = OnceBox::new();
+
+/// Returns the built-in API Set Map of the `embedded-map` feature.
+///
+/// This lets offline tools, examples, and doctests work without an `apisetschema.dll` file.
+///
+/// **The embedded API Set Map is synthetic.**
+/// It has been generated by [`ApiSetMapBuilder`](crate::builder::ApiSetMapBuilder) and mirrors the shape and family names
+/// of a real schema (e.g. `api-ms-win-core-synch-l1-2-0` or `ext-ms-win-shell-file-l1-1-0`), but its Namespace Entries and host
+/// modules are made up and don't match any Windows release.
+/// It doesn't contain any data from Microsoft binaries and can therefore be redistributed freely.
+///
+/// The map is stored compressed and decompressed into a static buffer upon the first call.
+///
+/// ```
+/// let map = nt_apiset::embedded_map();
+/// let host = map
+///     .resolve("api-ms-win-core-processthreads5-l1-1-1.dll", None)
+///     .unwrap()
+///     .unwrap();
+/// assert_eq!(host, "processthreads.dll");
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "embedded-map")))]
+pub fn embedded_map() -> ApiSetMap<'static> {
+    let section_bytes = EMBEDDED_MAP.get_or_init(|| {
+        let section_bytes = miniz_oxide::inflate::decompress_to_vec(EMBEDDED_MAP_DEFLATED)
+            .expect("the embedded API Set Map is a valid DEFLATE stream");
+        Box::new(section_bytes)
+    });
+
+    ApiSetMap::try_from_apiset_section_bytes(section_bytes)
+        .expect("the embedded API Set Map is valid")
+}
+
+#[cfg(all(test, feature = "test-utils"))]
+mod tests {
+    use super::*;
+    use crate::synthetic::synthetic_map;
+
+    #[test]
+    fn test_embedded_map() {
+        let map = embedded_map();
+        map.validate().unwrap();
+
+        assert_eq!(map.section_bytes(), synthetic_map(EMBEDDED_MAP_SEED));
+        assert_eq!(
+            map.section_bytes().as_ptr(),
+            embedded_map().section_bytes().as_ptr()
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    #[ignore]
+    fn regenerate_embedded_map() {
+        let deflated = miniz_oxide::deflate::compress_to_vec(&synthetic_map(EMBEDDED_MAP_SEED), 10);
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src/embedded_map.apiset.deflate"
+        );
+        std::fs::write(path, deflated).unwrap();
+    }
+}
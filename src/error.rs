@@ -11,10 +11,71 @@ pub type Result<T, E = NtApiSetError> = core::result::Result<T, E>;
 /// Central error type of nt-apiset.
 #[derive(Clone, Debug, Display, Eq, PartialEq)]
 pub enum NtApiSetError {
+    /// Failed to allocate memory for {size} bytes
+    AllocationFailed {
+        /// Number of bytes that were requested.
+        size: usize,
+    },
     /// Did not find the ".apiset" section in the PE file
     ApiSetSectionNotFound,
     /// The ".apiset" section in the PE file references data that is out of bounds
     ApiSetSectionOutOfBounds,
+    /// The operation was cancelled after {completed} entries
+    Cancelled {
+        /// Number of entries that had been processed before the operation was cancelled.
+        completed: usize,
+    },
+    /// The hash table references {referenced} distinct namespace entries, but there are {count} namespace entries
+    CountMismatch {
+        /// Number of distinct namespace entries referenced by the hash entries.
+        referenced: usize,
+        /// Actual number of namespace entries.
+        count: usize,
+    },
+    /// The cursor was created for an API Set Map with fingerprint {expected:#x}, but this API Set Map has fingerprint {actual:#x}
+    CursorMismatch {
+        /// Fingerprint saved in the cursor.
+        expected: u64,
+        /// Fingerprint of this API Set Map.
+        actual: u64,
+    },
+    /// The API Set Map header declares a size of {size} bytes, but the ".apiset" section only has a size of {actual} bytes
+    DeclaredSizeOutOfBounds {
+        /// Size in bytes declared by the API Set Map header.
+        size: usize,
+        /// Actual size of the ".apiset" section.
+        actual: usize,
+    },
+    /// Namespace Entry {index} has the same name as Namespace Entry {previous_index}, ignoring case
+    DuplicateNamespaceEntryName {
+        /// Index of the Namespace Entry, in the order it has been added to the builder.
+        index: usize,
+        /// Index of the previously added Namespace Entry with the same name.
+        previous_index: usize,
+    },
+    /// Redirect rule {index} has the same kind and pattern as redirect rule {previous_index}, ignoring case
+    DuplicateRedirectRule {
+        /// Index of the rule, in the order it has been added to the overlay.
+        index: usize,
+        /// Index of the previously added rule with the same kind and pattern.
+        previous_index: usize,
+    },
+    /// Value Entry {index} of Namespace Entry {namespace_entry_index} has the same importing module as Value Entry {previous_index}, ignoring case
+    DuplicateValueEntryName {
+        /// Index of the Namespace Entry, in the order it has been added to the builder.
+        namespace_entry_index: usize,
+        /// Index of the Value Entry, in the order it has been added to the Namespace Entry.
+        index: usize,
+        /// Index of the previously added Value Entry with the same importing module.
+        previous_index: usize,
+    },
+    /// Tried to access the entries at index range {range:?}, but there are only {count} entries
+    EntryIndexRangeOutOfBounds {
+        /// Range of entry indexes that was requested.
+        range: Range<usize>,
+        /// Actual number of entries.
+        count: usize,
+    },
     /// Tried to read the name at byte range {name_range:?} of the entry at byte {entry_offset}, but the ".apiset" section only has a size of {actual} bytes
     EntryNameOutOfBounds {
         /// Range of bytes where the entry name was expected.
@@ -24,6 +85,22 @@ pub enum NtApiSetError {
         /// Actual size of the ".apiset" section.
         actual: usize,
     },
+    /// Failed to read the file {path:?} ({kind:?})
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    FileReadFailed {
+        /// Path of the file.
+        path: std::path::PathBuf,
+        /// Kind of the I/O error.
+        kind: std::io::ErrorKind,
+    },
+    /// Failed to write the formatted output
+    FormatFailed,
+    /// Hash entry {index} has a smaller hash than its predecessor
+    HashEntriesNotSorted {
+        /// Index of the hash entry.
+        index: usize,
+    },
     /// Tried to read the apiset hash entries from byte range {range:?}, but the ".apiset" section only has a size of {actual} bytes
     HashEntriesOutOfBounds {
         /// Start..end range where the hash entries were expected, as byte offsets relative to the start of the ".apiset" section.
@@ -31,6 +108,26 @@ pub enum NtApiSetError {
         /// Actual size of the ".apiset" section.
         actual: usize,
     },
+    /// Hash entry {index} has the hash {actual:#x}, but the name of its namespace entry hashes to {expected:#x}
+    HashMismatch {
+        /// Index of the hash entry.
+        index: usize,
+        /// Hash recomputed from the hashed name prefix of the namespace entry.
+        expected: u32,
+        /// Hash stored in the hash entry.
+        actual: u32,
+    },
+    /// The hashed length ({hashed_length}) of the entry at byte {entry_offset} exceeds the length of its name ({name_length} bytes)
+    HashedLengthOutOfBounds {
+        /// Number of bytes of the name that are supposed to be hashed.
+        hashed_length: usize,
+        /// Actual length in bytes of the name.
+        name_length: usize,
+        /// Byte offset of the entry inside the ".apiset" section.
+        entry_offset: usize,
+    },
+    /// The import directory of the PE file is invalid
+    ImportDirectoryInvalid,
     /// Tried to read {expected} bytes for the API Set Map header, but only {actual} bytes are left in the slice
     InvalidMapHeaderSize {
         /// Size in bytes of the API Set Map header.
@@ -38,6 +135,46 @@ pub enum NtApiSetError {
         /// Actual size in bytes of the provided slice.
         actual: usize,
     },
+    /// Namespace Entry {index} has a name that is empty, contains characters other than ASCII letters, digits, and hyphens, or has no hyphen to hash up to
+    InvalidNamespaceEntryName {
+        /// Index of the Namespace Entry, in the order it has been added to the builder.
+        index: usize,
+    },
+    /// The file is not a valid PE file
+    InvalidPeFile,
+    /// Redirect rule {index} has a pattern that doesn't look like an API Set name, or a host module that is empty or not ASCII
+    InvalidRedirectRule {
+        /// Index of the rule, in the order it has been added to the overlay.
+        index: usize,
+    },
+    /// The string at byte range {range:?} is not valid UTF-16
+    InvalidUtf16 {
+        /// Range of bytes where the string is stored.
+        range: Range<usize>,
+    },
+    /// Value Entry {index} of Namespace Entry {namespace_entry_index} has a module name with non-ASCII characters
+    InvalidValueEntryName {
+        /// Index of the Namespace Entry, in the order it has been added to the builder.
+        namespace_entry_index: usize,
+        /// Index of the Value Entry, in the order it has been added to the Namespace Entry.
+        index: usize,
+    },
+    /// Failed to read {length} bytes of memory at address {address:#x}
+    MemoryReadFailed {
+        /// Address of the memory.
+        address: u64,
+        /// Number of bytes that were requested.
+        length: usize,
+    },
+    /// Only {read} of {length} bytes of memory at address {address:#x} could be read
+    MemoryReadPartial {
+        /// Address of the memory.
+        address: u64,
+        /// Number of bytes that were requested.
+        length: usize,
+        /// Number of bytes that could be read, e.g. up to the first page that is not mapped.
+        read: usize,
+    },
     /// Tried to read the apiset namespace entries from byte range {range:?}, but the ".apiset" section only has a size of {actual} bytes
     NamespaceEntriesOutOfBounds {
         /// Start..end range where the namespace entries were expected, as byte offsets relative to the start of the ".apiset" section.
@@ -45,6 +182,42 @@ pub enum NtApiSetError {
         /// Actual size of the ".apiset" section.
         actual: usize,
     },
+    /// Tried to access apiset namespace entry {index}, but there are only {count} namespace entries
+    NamespaceEntryIndexOutOfBounds {
+        /// Index of the namespace entry that was requested.
+        index: usize,
+        /// Actual number of namespace entries.
+        count: usize,
+    },
+    /// The API Set Map passed as extension is not flagged as a schema extension (flags {flags:#x})
+    NotAnExtension {
+        /// Raw bits of the [`ApiSetMapFlags`](crate::map::ApiSetMapFlags) of that API Set Map.
+        flags: u32,
+    },
+    /// The string at byte range {range:?} has an odd length, which is impossible for UTF-16
+    OddStringLength {
+        /// Range of bytes where the string is stored.
+        range: Range<usize>,
+    },
+    /// The ".apiset" section would have a size of {size} bytes, which exceeds the 4 GiB addressable by its offsets
+    SectionTooLarge {
+        /// Size in bytes of the section.
+        size: usize,
+    },
+    /// The string at byte range {range:?} does not fit into a fixed-capacity buffer of {capacity} bytes
+    StringTooLong {
+        /// Range of bytes where the string is stored.
+        range: Range<usize>,
+        /// Capacity in bytes of the fixed-capacity buffer.
+        capacity: usize,
+    },
+    /// Entry {index} at byte offset {offset} is cut off by the end of the ".apiset" section
+    TruncatedEntry {
+        /// Index of the first entry of its array that does not fit into the ".apiset" section.
+        index: usize,
+        /// Byte offset where that entry was expected, relative to the start of the ".apiset" section.
+        offset: usize,
+    },
     /// The apiset map version ({version}) is unsupported
     UnsupportedVersion {
         /// Version number reported by the API Set Map.
@@ -57,6 +230,13 @@ pub enum NtApiSetError {
         /// Actual size of the ".apiset" section.
         actual: usize,
     },
+    /// The apiset value entries at byte range {range:?} overlap the string at byte range {string_range:?}
+    ValueEntriesOverlapString {
+        /// Start..end range of the value entries, as byte offsets relative to the start of the ".apiset" section.
+        range: Range<usize>,
+        /// Range of bytes where the overlapping string is stored.
+        string_range: Range<usize>,
+    },
 }
 
 #[cfg(feature = "std")]
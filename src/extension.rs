@@ -0,0 +1,914 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::slice;
+#[cfg(feature = "alloc")]
+use core::{fmt, ops::Range};
+
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use nt_string::u16strle::U16StrLe;
+
+#[cfg(feature = "alloc")]
+use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+use crate::error::{NtApiSetError, Result};
+#[cfg(feature = "alloc")]
+use crate::helpers::cmp_ignore_ascii_case;
+use crate::map::{ApiSetMap, ApiSetMapFlags};
+use crate::namespace_entry::{ApiSetNamespaceEntry, ApiSetNamespaceEntryFlags};
+#[cfg(feature = "alloc")]
+use crate::value_entry::ApiSetValueEntryFlags;
+
+/// Combined lookup view of a base API Set Map and schema extensions, as applied by the Windows loader.
+///
+/// This view is returned by [`ApiSetMapView::with_extension`] and [`ApiSetMapView::with_extensions`].
+/// Namespace Entries of an extension add to the base API Set Map or override its Namespace Entries of the same name.
+/// However, if the base API Set Map or the overridden Namespace Entry is [`SEALED`](ApiSetNamespaceEntryFlags::SEALED),
+/// the base wins and the extension is ignored.
+/// Several extensions are applied in order, so a later extension may also override an unsealed Namespace Entry of an earlier one.
+///
+/// Lookups behave exactly like the ones of a plain [`ApiSetMap`].
+///
+/// ```
+/// # use nt_apiset::{ApiSetMap, ApiSetMapBuilder, ApiSetMapFlags, ApiSetMapView, ApiSetNamespaceEntryBuilder, ApiSetNamespaceEntryFlags};
+/// let base_bytes = ApiSetMapBuilder::new()
+///     .add_namespace_entry(
+///         ApiSetNamespaceEntryBuilder::new("api-ms-win-core-foo-l1-1-0", ApiSetNamespaceEntryFlags::empty())
+///             .add_value_entry("", "foo.dll"),
+///     )
+///     .add_namespace_entry(
+///         ApiSetNamespaceEntryBuilder::new("api-ms-win-core-bar-l1-1-0", ApiSetNamespaceEntryFlags::SEALED)
+///             .add_value_entry("", "bar.dll"),
+///     )
+///     .build()
+///     .unwrap();
+/// let extension_bytes = ApiSetMapBuilder::new()
+///     .flags(ApiSetMapFlags::IS_EXTENSION)
+///     .add_namespace_entry(
+///         ApiSetNamespaceEntryBuilder::new("api-ms-win-core-foo-l1-1-0", ApiSetNamespaceEntryFlags::empty())
+///             .add_value_entry("", "foo_ext.dll"),
+///     )
+///     .add_namespace_entry(
+///         ApiSetNamespaceEntryBuilder::new("api-ms-win-core-bar-l1-1-0", ApiSetNamespaceEntryFlags::empty())
+///             .add_value_entry("", "bar_ext.dll"),
+///     )
+///     .add_namespace_entry(
+///         ApiSetNamespaceEntryBuilder::new("ext-ms-win-baz-l1-1-0", ApiSetNamespaceEntryFlags::IS_EXTENSION)
+///             .add_value_entry("", "baz.dll"),
+///     )
+///     .build()
+///     .unwrap();
+///
+/// let base = ApiSetMap::try_from_apiset_section_bytes(&base_bytes).unwrap();
+/// let extension = ApiSetMap::try_from_apiset_section_bytes(&extension_bytes).unwrap();
+/// let view = ApiSetMapView::with_extension(&base, &extension).unwrap();
+///
+/// // The extension overrides an unsealed entry.
+/// assert_eq!(view.resolve("api-ms-win-core-foo-l1-1-0.dll", None).unwrap().unwrap(), "foo_ext.dll");
+/// // The extension is ignored for a sealed entry.
+/// assert_eq!(view.resolve("api-ms-win-core-bar-l1-1-0.dll", None).unwrap().unwrap(), "bar.dll");
+/// // The extension adds a brand-new entry.
+/// assert_eq!(view.resolve("ext-ms-win-baz-l1-1-0.dll", None).unwrap().unwrap(), "baz.dll");
+///
+/// // An API Set Map that is not flagged as an extension is rejected.
+/// assert!(ApiSetMapView::with_extension(&base, &base).is_err());
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct ApiSetMapView<'m, 'a> {
+    base: &'m ApiSetMap<'a>,
+    extensions: &'m [ApiSetMap<'a>],
+    base_label: Option<&'m str>,
+    extension_labels: &'m [&'m str],
+}
+
+impl<'m, 'a> ApiSetMapView<'m, 'a> {
+    /// Returns the base API Set Map of this view.
+    pub const fn base(&self) -> &'m ApiSetMap<'a> {
+        self.base
+    }
+
+    /// Returns the schema extensions of this view, in the order they are applied.
+    pub const fn extensions(&self) -> &'m [ApiSetMap<'a>] {
+        self.extensions
+    }
+
+    /// Finds a namespace entry in the combined view, preferring the extensions as described for [`ApiSetMapView`].
+    ///
+    /// `namespace_entry_name` is subject to the same requirements as for [`ApiSetMap::find_namespace_entry`].
+    pub fn find_namespace_entry(
+        &self,
+        namespace_entry_name: &str,
+    ) -> Option<Result<ApiSetNamespaceEntry<'a>>> {
+        let sourced_entry = iter_try!(self.find_namespace_entry_with_source(namespace_entry_name)?);
+        Some(Ok(sourced_entry.namespace_entry))
+    }
+
+    /// Like [`find_namespace_entry`](Self::find_namespace_entry), but also reports which API Set Map supplied the entry.
+    pub fn find_namespace_entry_with_source(
+        &self,
+        namespace_entry_name: &str,
+    ) -> Option<Result<ApiSetSourcedEntry<'m, 'a>>> {
+        self.find_with(|map| map.find_namespace_entry(namespace_entry_name))
+    }
+
+    /// Returns the label of the given source, as set via [`with_labels`](Self::with_labels).
+    pub fn label(&self, source: ApiSetSource) -> Option<&'m str> {
+        match source {
+            ApiSetSource::Base => self.base_label,
+            ApiSetSource::Extension(index) => self.extension_labels.get(index).copied(),
+        }
+    }
+
+    /// Returns all Namespace Entries of the combined view, each along with the API Set Map that supplied it.
+    ///
+    /// Every name is returned once, with the Namespace Entry that [`find_namespace_entry`](Self::find_namespace_entry) would return.
+    /// The entries are sorted case-insensitively by name, like the Namespace Entries of a version 6 API Set Map.
+    ///
+    /// In contrast to the lookups, this reads every Namespace Entry and returns an error for the first unreadable one.
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn namespace_entries(&self) -> Result<Vec<ApiSetSourcedEntry<'m, 'a>>> {
+        Ok(self
+            .merged_entries()?
+            .into_iter()
+            .map(|merged_entry| merged_entry.sourced_entry)
+            .collect())
+    }
+
+    /// Physically merges the base API Set Map and all schema extensions into a single version 6 API Set Map.
+    ///
+    /// The merged API Set Map has the flags and the hash factor of the base API Set Map and contains the
+    /// Namespace Entries returned by [`namespace_entries`](Self::namespace_entries).
+    /// The returned [`ApiSetMerge`] also reports every Namespace Entry that an extension has added or overridden.
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn merge(&self) -> Result<ApiSetMerge<'m>> {
+        let merged_entries = self.merged_entries()?;
+        let mut builder = ApiSetMapBuilder::new()
+            .flags(self.base.flags())
+            .hash_factor(self.base.hash_factor());
+        let mut changes = Vec::new();
+
+        for merged_entry in merged_entries {
+            let sourced_entry = merged_entry.sourced_entry;
+            let namespace_entry = &sourced_entry.namespace_entry;
+            let name = to_string(&namespace_entry.name()?, namespace_entry.name_range())?;
+
+            let mut namespace_entry_builder = ApiSetNamespaceEntryBuilder::new(
+                &name,
+                ApiSetNamespaceEntryFlags::from_bits_retain(namespace_entry.raw_flags()),
+            );
+
+            for value_entry in namespace_entry.value_entries()? {
+                namespace_entry_builder = namespace_entry_builder.add_value_entry_with_flags(
+                    &to_string(&value_entry.name()?, value_entry.name_range())?,
+                    &to_string(&value_entry.value()?, value_entry.value_range())?,
+                    ApiSetValueEntryFlags::from_bits_retain(value_entry.raw_flags()),
+                );
+            }
+
+            builder = builder.add_namespace_entry(namespace_entry_builder);
+
+            if sourced_entry.source != ApiSetSource::Base {
+                changes.push(ApiSetMergeChange {
+                    name,
+                    kind: if merged_entry.in_base {
+                        ApiSetMergeChangeKind::Overridden
+                    } else {
+                        ApiSetMergeChangeKind::Added
+                    },
+                    source: sourced_entry.source,
+                    label: sourced_entry.label,
+                });
+            }
+        }
+
+        Ok(ApiSetMerge {
+            section_bytes: builder.build()?,
+            changes,
+        })
+    }
+
+    /// Resolves an imported API Set to the name of its host DLL in the combined view, the same way the Windows loader does.
+    ///
+    /// See [`ApiSetMap::resolve`] for details.
+    pub fn resolve(
+        &self,
+        apiset_name: &str,
+        importing_module: Option<&str>,
+    ) -> Option<Result<U16StrLe<'a>>> {
+        let namespace_entry = iter_try!(self.resolve_import(apiset_name)?);
+        let value_entry = iter_try!(namespace_entry.resolve_value_entry(importing_module)?);
+        Some(value_entry.value())
+    }
+
+    /// Resolves the name of an imported DLL to its namespace entry in the combined view, the same way the Windows loader does.
+    ///
+    /// See [`ApiSetMap::resolve_import`] for details.
+    pub fn resolve_import(&self, import_name: &str) -> Option<Result<ApiSetNamespaceEntry<'a>>> {
+        let sourced_entry = iter_try!(self.resolve_import_with_source(import_name)?);
+        Some(Ok(sourced_entry.namespace_entry))
+    }
+
+    /// Like [`resolve_import`](Self::resolve_import), but also reports which API Set Map supplied the entry.
+    pub fn resolve_import_with_source(
+        &self,
+        import_name: &str,
+    ) -> Option<Result<ApiSetSourcedEntry<'m, 'a>>> {
+        self.find_with(|map| map.resolve_import(import_name))
+    }
+
+    /// Creates a combined lookup view of the `base` API Set Map and the schema extension `extension`.
+    ///
+    /// This is a shortcut for calling [`with_extensions`](Self::with_extensions) with a single extension.
+    pub fn with_extension(base: &'m ApiSetMap<'a>, extension: &'m ApiSetMap<'a>) -> Result<Self> {
+        Self::with_extensions(base, slice::from_ref(extension))
+    }
+
+    /// Creates a combined lookup view of the `base` API Set Map and the schema `extensions`, which are applied in the given order.
+    ///
+    /// If any of the `extensions` is not flagged as [`IS_EXTENSION`](ApiSetMapFlags::IS_EXTENSION),
+    /// [`NtApiSetError::NotAnExtension`] is returned.
+    pub fn with_extensions(
+        base: &'m ApiSetMap<'a>,
+        extensions: &'m [ApiSetMap<'a>],
+    ) -> Result<Self> {
+        for extension in extensions {
+            if !extension.flags().contains(ApiSetMapFlags::IS_EXTENSION) {
+                return Err(NtApiSetError::NotAnExtension {
+                    flags: extension.flags().bits(),
+                });
+            }
+        }
+
+        Ok(Self {
+            base,
+            extensions,
+            base_label: None,
+            extension_labels: &[],
+        })
+    }
+
+    /// Attaches labels to the base API Set Map and the schema extensions (e.g. their file names).
+    ///
+    /// `extension_labels` are assigned to the extensions in the order they have been passed to [`with_extensions`](Self::with_extensions).
+    /// Extensions without a corresponding label remain unlabeled.
+    /// The labels are returned by [`ApiSetSourcedEntry::label`] and [`ApiSetMergeChange::label`] to identify the source of an entry.
+    pub const fn with_labels(
+        mut self,
+        base_label: &'m str,
+        extension_labels: &'m [&'m str],
+    ) -> Self {
+        self.base_label = Some(base_label);
+        self.extension_labels = extension_labels;
+        self
+    }
+
+    /// Returns `true` if the extensions may override `sourced_entry`, which has been supplied by the base or an earlier extension.
+    fn is_overridable(&self, sourced_entry: &ApiSetSourcedEntry<'m, 'a>) -> bool {
+        !self.base.flags().contains(ApiSetMapFlags::SEALED)
+            && !sourced_entry
+                .namespace_entry
+                .flags()
+                .contains(ApiSetNamespaceEntryFlags::SEALED)
+    }
+
+    /// Performs the lookup `find` on the base API Set Map and on every extension, unless the entry found so far is sealed.
+    fn find_with<F>(&self, find: F) -> Option<Result<ApiSetSourcedEntry<'m, 'a>>>
+    where
+        F: Fn(&ApiSetMap<'a>) -> Option<Result<ApiSetNamespaceEntry<'a>>>,
+    {
+        let sourced = |result: Option<Result<ApiSetNamespaceEntry<'a>>>, source| {
+            result.map(|result| {
+                result.map(|namespace_entry| ApiSetSourcedEntry {
+                    namespace_entry,
+                    source,
+                    label: self.label(source),
+                })
+            })
+        };
+
+        let mut result = sourced(find(self.base), ApiSetSource::Base);
+
+        if self.base.flags().contains(ApiSetMapFlags::SEALED) || matches!(result, Some(Err(_))) {
+            return result;
+        }
+
+        for (index, extension) in self.extensions.iter().enumerate() {
+            if let Some(Ok(sourced_entry)) = &result {
+                if !self.is_overridable(sourced_entry) {
+                    break;
+                }
+            }
+
+            // Only a readable entry of an extension overrides the entry found so far.
+            // An unreadable one must not hide a valid mapping, but is reported if there is none.
+            match sourced(find(extension), ApiSetSource::Extension(index)) {
+                Some(Ok(sourced_entry)) => result = Some(Ok(sourced_entry)),
+                Some(Err(e)) if !matches!(result, Some(Ok(_))) => result = Some(Err(e)),
+                _ => (),
+            }
+        }
+
+        result
+    }
+
+    /// Combines the Namespace Entries of the base API Set Map and all extensions, sorted case-insensitively by name.
+    #[cfg(feature = "alloc")]
+    fn merged_entries(&self) -> Result<Vec<MergedEntry<'m, 'a>>> {
+        let mut merged_entries = Vec::<MergedEntry>::new();
+        let maps = core::iter::once((ApiSetSource::Base, self.base)).chain(
+            self.extensions
+                .iter()
+                .enumerate()
+                .map(|(index, extension)| (ApiSetSource::Extension(index), extension)),
+        );
+
+        for (source, map) in maps {
+            if source != ApiSetSource::Base && self.base.flags().contains(ApiSetMapFlags::SEALED) {
+                break;
+            }
+
+            for namespace_entry in map.namespace_entries()? {
+                let name = namespace_entry.name()?;
+                let sourced_entry = ApiSetSourcedEntry {
+                    namespace_entry,
+                    source,
+                    label: self.label(source),
+                };
+
+                let position = merged_entries.binary_search_by(|merged_entry| {
+                    cmp_ignore_ascii_case(&merged_entry.name, &name)
+                });
+
+                match position {
+                    Ok(index) => {
+                        let merged_entry = &mut merged_entries[index];
+
+                        if self.is_overridable(&merged_entry.sourced_entry) {
+                            merged_entry.sourced_entry = sourced_entry;
+                        }
+                    }
+                    Err(index) => merged_entries.insert(
+                        index,
+                        MergedEntry {
+                            name,
+                            sourced_entry,
+                            in_base: source == ApiSetSource::Base,
+                        },
+                    ),
+                }
+            }
+        }
+
+        Ok(merged_entries)
+    }
+}
+
+/// Namespace Entry of the combined view, as determined by [`ApiSetMapView::merged_entries`].
+#[cfg(feature = "alloc")]
+struct MergedEntry<'m, 'a> {
+    name: U16StrLe<'a>,
+    sourced_entry: ApiSetSourcedEntry<'m, 'a>,
+    /// Whether the base API Set Map has a Namespace Entry of this name.
+    in_base: bool,
+}
+
+/// API Set Map of an [`ApiSetMapView`] that supplied a Namespace Entry.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ApiSetSource {
+    /// The base API Set Map.
+    Base,
+    /// The schema extension at the given index of [`ApiSetMapView::extensions`].
+    Extension(usize),
+}
+
+/// Namespace Entry found via an [`ApiSetMapView`], along with the API Set Map that supplied it.
+///
+/// This is returned by [`ApiSetMapView::find_namespace_entry_with_source`], [`ApiSetMapView::resolve_import_with_source`],
+/// and [`ApiSetMapView::namespace_entries`].
+#[derive(Clone, Debug)]
+pub struct ApiSetSourcedEntry<'m, 'a> {
+    namespace_entry: ApiSetNamespaceEntry<'a>,
+    source: ApiSetSource,
+    label: Option<&'m str>,
+}
+
+impl<'m, 'a> ApiSetSourcedEntry<'m, 'a> {
+    /// Returns the label of the API Set Map that supplied the Namespace Entry, as set via [`ApiSetMapView::with_labels`].
+    pub const fn label(&self) -> Option<&'m str> {
+        self.label
+    }
+
+    /// Returns the Namespace Entry.
+    pub const fn namespace_entry(&self) -> &ApiSetNamespaceEntry<'a> {
+        &self.namespace_entry
+    }
+
+    /// Returns the API Set Map that supplied the Namespace Entry.
+    ///
+    /// This is an [`ApiSetSource::Extension`] if that extension added the Namespace Entry or overrode an unsealed one
+    /// of the base API Set Map or of an earlier extension.
+    pub const fn source(&self) -> ApiSetSource {
+        self.source
+    }
+}
+
+/// Result of [`ApiSetMapView::merge`].
+///
+/// Its [`Display`](fmt::Display) implementation outputs a diff-style report of all [`changes`](Self::changes), one per line.
+/// The alternate form (`{:#}`) also outputs the source of each change.
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+#[derive(Clone, Debug)]
+pub struct ApiSetMerge<'m> {
+    section_bytes: Vec<u8>,
+    changes: Vec<ApiSetMergeChange<'m>>,
+}
+
+#[cfg(feature = "alloc")]
+impl<'m> ApiSetMerge<'m> {
+    /// Returns all Namespace Entries that an extension has added to or overridden in the base API Set Map, sorted by name.
+    pub fn changes(&self) -> &[ApiSetMergeChange<'m>] {
+        &self.changes
+    }
+
+    /// Returns the owned bytes of the merged `.apiset` section.
+    pub fn into_section_bytes(self) -> Vec<u8> {
+        self.section_bytes
+    }
+
+    /// Returns the bytes of the merged `.apiset` section.
+    pub fn section_bytes(&self) -> &[u8] {
+        &self.section_bytes
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'m> fmt::Display for ApiSetMerge<'m> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            if f.alternate() {
+                writeln!(f, "{change:#}")?;
+            } else {
+                writeln!(f, "{change}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Namespace Entry that an extension has added to or overridden in the base API Set Map, as reported by [`ApiSetMerge::changes`].
+///
+/// Its [`Display`](fmt::Display) implementation outputs a line like `+ ext-ms-win-foo-l1-1-0` or `~ api-ms-win-core-bar-l1-1-0`.
+/// The alternate form (`{:#}`) appends the label of the extension, or its index if it is unlabeled.
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ApiSetMergeChange<'m> {
+    name: String,
+    kind: ApiSetMergeChangeKind,
+    source: ApiSetSource,
+    label: Option<&'m str>,
+}
+
+#[cfg(feature = "alloc")]
+impl<'m> ApiSetMergeChange<'m> {
+    /// Returns whether the Namespace Entry has been added or overridden.
+    pub const fn kind(&self) -> ApiSetMergeChangeKind {
+        self.kind
+    }
+
+    /// Returns the label of the extension that supplied the Namespace Entry, as set via [`ApiSetMapView::with_labels`].
+    pub const fn label(&self) -> Option<&'m str> {
+        self.label
+    }
+
+    /// Returns the name of the Namespace Entry.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the extension that supplied the Namespace Entry.
+    pub const fn source(&self) -> ApiSetSource {
+        self.source
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'m> fmt::Display for ApiSetMergeChange<'m> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let marker = match self.kind {
+            ApiSetMergeChangeKind::Added => '+',
+            ApiSetMergeChangeKind::Overridden => '~',
+        };
+        write!(f, "{marker} {}", self.name)?;
+
+        if f.alternate() {
+            match (self.label, self.source) {
+                (Some(label), _) => write!(f, " ({label})")?,
+                (None, ApiSetSource::Extension(index)) => write!(f, " (extension {index})")?,
+                (None, ApiSetSource::Base) => write!(f, " (base)")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Kind of an [`ApiSetMergeChange`].
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ApiSetMergeChangeKind {
+    /// The base API Set Map has no Namespace Entry of this name.
+    Added,
+    /// An extension has overridden an unsealed Namespace Entry of the base API Set Map.
+    Overridden,
+}
+
+#[cfg(feature = "alloc")]
+fn to_string(string: &U16StrLe, range: Range<usize>) -> Result<String> {
+    string
+        .to_string()
+        .map_err(|_| NtApiSetError::InvalidUtf16 { range })
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+
+    fn build(
+        flags: ApiSetMapFlags,
+        entries: &[(&str, ApiSetNamespaceEntryFlags, &str)],
+    ) -> Vec<u8> {
+        entries
+            .iter()
+            .fold(
+                ApiSetMapBuilder::new().flags(flags),
+                |builder, (name, flags, host)| {
+                    builder.add_namespace_entry(
+                        ApiSetNamespaceEntryBuilder::new(name, *flags).add_value_entry("", host),
+                    )
+                },
+            )
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_source() {
+        let base_bytes = build(
+            ApiSetMapFlags::empty(),
+            &[
+                (
+                    "api-ms-win-core-foo-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                    "foo.dll",
+                ),
+                (
+                    "api-ms-win-core-bar-l1-1-0",
+                    ApiSetNamespaceEntryFlags::SEALED,
+                    "bar.dll",
+                ),
+                (
+                    "api-ms-win-core-qux-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                    "qux.dll",
+                ),
+            ],
+        );
+        let extension_bytes = build(
+            ApiSetMapFlags::IS_EXTENSION,
+            &[
+                (
+                    "api-ms-win-core-foo-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                    "foo_ext.dll",
+                ),
+                (
+                    "api-ms-win-core-bar-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                    "bar_ext.dll",
+                ),
+                (
+                    "ext-ms-win-baz-l1-1-0",
+                    ApiSetNamespaceEntryFlags::IS_EXTENSION,
+                    "baz.dll",
+                ),
+            ],
+        );
+
+        let base = ApiSetMap::try_from_apiset_section_bytes(&base_bytes).unwrap();
+        let extension = ApiSetMap::try_from_apiset_section_bytes(&extension_bytes).unwrap();
+        let view = ApiSetMapView::with_extension(&base, &extension)
+            .unwrap()
+            .with_labels("apisetschema.dll", &["ext.dll"]);
+
+        let source = |import_name, host| {
+            let sourced_entry = view
+                .resolve_import_with_source(import_name)
+                .unwrap()
+                .unwrap();
+            let value_entry = sourced_entry
+                .namespace_entry()
+                .default_value_entry()
+                .unwrap()
+                .unwrap();
+            assert_eq!(value_entry.value().unwrap(), host);
+            (sourced_entry.source(), sourced_entry.label())
+        };
+
+        // Overridden unsealed entry.
+        assert_eq!(
+            source("api-ms-win-core-foo-l1-1-0.dll", "foo_ext.dll"),
+            (ApiSetSource::Extension(0), Some("ext.dll"))
+        );
+        // Sealed entry that the extension tried to override.
+        assert_eq!(
+            source("api-ms-win-core-bar-l1-1-0.dll", "bar.dll"),
+            (ApiSetSource::Base, Some("apisetschema.dll"))
+        );
+        // Entry only present in the base.
+        assert_eq!(
+            source("api-ms-win-core-qux-l1-1-0.dll", "qux.dll"),
+            (ApiSetSource::Base, Some("apisetschema.dll"))
+        );
+        // Entry added by the extension.
+        assert_eq!(
+            source("ext-ms-win-baz-l1-1-0.dll", "baz.dll"),
+            (ApiSetSource::Extension(0), Some("ext.dll"))
+        );
+
+        let sourced_entry = view
+            .find_namespace_entry_with_source("api-ms-win-core-foo-l1-1-0")
+            .unwrap()
+            .unwrap();
+        assert_eq!(sourced_entry.source(), ApiSetSource::Extension(0));
+
+        // Without labels, only the source is known.
+        let view = ApiSetMapView::with_extension(&base, &extension).unwrap();
+        let sourced_entry = view
+            .resolve_import_with_source("api-ms-win-core-foo-l1-1-0.dll")
+            .unwrap()
+            .unwrap();
+        assert_eq!(sourced_entry.source(), ApiSetSource::Extension(0));
+        assert_eq!(sourced_entry.label(), None);
+    }
+
+    #[test]
+    fn test_unreadable_extension_entry() {
+        let base_bytes = build(
+            ApiSetMapFlags::empty(),
+            &[(
+                "api-ms-win-core-foo-l1-1-0",
+                ApiSetNamespaceEntryFlags::empty(),
+                "foo.dll",
+            )],
+        );
+        let mut extension_bytes = build(
+            ApiSetMapFlags::IS_EXTENSION,
+            &[
+                (
+                    "api-ms-win-core-foo-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                    "foo_ext.dll",
+                ),
+                (
+                    "ext-ms-win-baz-l1-1-0",
+                    ApiSetNamespaceEntryFlags::IS_EXTENSION,
+                    "baz.dll",
+                ),
+            ],
+        );
+
+        // Let the names of all Namespace Entries of the extension point out of bounds.
+        let namespace_entry_offsets = ApiSetMap::try_from_apiset_section_bytes(&extension_bytes)
+            .unwrap()
+            .namespace_entries()
+            .unwrap()
+            .map(|namespace_entry| namespace_entry.offset())
+            .collect::<Vec<_>>();
+        for offset in namespace_entry_offsets {
+            let name_offset = offset + 4;
+            extension_bytes[name_offset..name_offset + 4]
+                .copy_from_slice(&0xffff_0000u32.to_le_bytes());
+        }
+
+        let base = ApiSetMap::try_from_apiset_section_bytes(&base_bytes).unwrap();
+        let extension = ApiSetMap::try_from_apiset_section_bytes(&extension_bytes).unwrap();
+        let view = ApiSetMapView::with_extension(&base, &extension).unwrap();
+
+        // The valid mapping of the base is not hidden by the unreadable entry of the extension.
+        let sourced_entry = view
+            .resolve_import_with_source("api-ms-win-core-foo-l1-1-0.dll")
+            .unwrap()
+            .unwrap();
+        assert_eq!(sourced_entry.source(), ApiSetSource::Base);
+        assert_eq!(
+            view.resolve("api-ms-win-core-foo-l1-1-0.dll", None)
+                .unwrap()
+                .unwrap(),
+            "foo.dll"
+        );
+
+        // Without a mapping in the base, the unreadable entry of the extension is reported.
+        assert!(matches!(
+            view.resolve_import_with_source("ext-ms-win-baz-l1-1-0.dll"),
+            Some(Err(NtApiSetError::EntryNameOutOfBounds { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_two_extensions() {
+        let base_bytes = build(
+            ApiSetMapFlags::empty(),
+            &[
+                (
+                    "api-ms-win-core-bar-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                    "bar.dll",
+                ),
+                (
+                    "api-ms-win-core-foo-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                    "foo.dll",
+                ),
+                (
+                    "api-ms-win-core-qux-l1-1-0",
+                    ApiSetNamespaceEntryFlags::SEALED,
+                    "qux.dll",
+                ),
+            ],
+        );
+        let first_bytes = build(
+            ApiSetMapFlags::IS_EXTENSION,
+            &[
+                (
+                    "api-ms-win-core-foo-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                    "foo_first.dll",
+                ),
+                (
+                    "ext-ms-win-baz-l1-1-0",
+                    ApiSetNamespaceEntryFlags::SEALED,
+                    "baz_first.dll",
+                ),
+            ],
+        );
+        let second_bytes = build(
+            ApiSetMapFlags::IS_EXTENSION,
+            &[
+                (
+                    "API-MS-WIN-CORE-BAR-L1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                    "bar_second.dll",
+                ),
+                (
+                    "api-ms-win-core-qux-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                    "qux_second.dll",
+                ),
+                (
+                    "ext-ms-win-baz-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                    "baz_second.dll",
+                ),
+                (
+                    "ext-ms-win-new-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                    "new.dll",
+                ),
+            ],
+        );
+
+        let base = ApiSetMap::try_from_apiset_section_bytes(&base_bytes).unwrap();
+        let extensions = [
+            ApiSetMap::try_from_apiset_section_bytes(&first_bytes).unwrap(),
+            ApiSetMap::try_from_apiset_section_bytes(&second_bytes).unwrap(),
+        ];
+        let view = ApiSetMapView::with_extensions(&base, &extensions)
+            .unwrap()
+            .with_labels("base.dll", &["first.dll", "second.dll"]);
+
+        let expected = [
+            // Overridden by the second extension.
+            (
+                "API-MS-WIN-CORE-BAR-L1-1-0",
+                "bar_second.dll",
+                ApiSetSource::Extension(1),
+                "second.dll",
+            ),
+            // Overridden by the first extension.
+            (
+                "api-ms-win-core-foo-l1-1-0",
+                "foo_first.dll",
+                ApiSetSource::Extension(0),
+                "first.dll",
+            ),
+            // Sealed in the base, so the second extension is ignored.
+            (
+                "api-ms-win-core-qux-l1-1-0",
+                "qux.dll",
+                ApiSetSource::Base,
+                "base.dll",
+            ),
+            // Added and sealed by the first extension, so the second extension is ignored.
+            (
+                "ext-ms-win-baz-l1-1-0",
+                "baz_first.dll",
+                ApiSetSource::Extension(0),
+                "first.dll",
+            ),
+            // Added by the second extension.
+            (
+                "ext-ms-win-new-l1-1-0",
+                "new.dll",
+                ApiSetSource::Extension(1),
+                "second.dll",
+            ),
+        ];
+
+        let default_host = |sourced_entry: &ApiSetSourcedEntry| {
+            sourced_entry
+                .namespace_entry()
+                .default_value_entry()
+                .unwrap()
+                .unwrap()
+                .value()
+                .unwrap()
+                .to_string()
+                .unwrap()
+        };
+
+        // Lookups.
+        for (name, host, source, label) in expected {
+            let sourced_entry = view
+                .resolve_import_with_source(&name.to_ascii_lowercase())
+                .unwrap()
+                .unwrap();
+            assert_eq!(default_host(&sourced_entry), host, "{name}");
+            assert_eq!(sourced_entry.source(), source, "{name}");
+            assert_eq!(sourced_entry.label(), Some(label), "{name}");
+        }
+
+        // Merged iteration, sorted by name.
+        let namespace_entries = view.namespace_entries().unwrap();
+        assert_eq!(namespace_entries.len(), expected.len());
+
+        for (sourced_entry, (name, host, source, label)) in namespace_entries.iter().zip(expected) {
+            assert_eq!(sourced_entry.namespace_entry().name().unwrap(), name);
+            assert_eq!(default_host(sourced_entry), host, "{name}");
+            assert_eq!(sourced_entry.source(), source, "{name}");
+            assert_eq!(sourced_entry.label(), Some(label), "{name}");
+        }
+
+        // Physical merge.
+        let merge = view.merge().unwrap();
+        let merged = ApiSetMap::try_from_apiset_section_bytes(merge.section_bytes()).unwrap();
+        merged.validate().unwrap();
+
+        for (name, host, _, _) in expected {
+            let import_name = alloc::format!("{}.dll", name.to_ascii_lowercase());
+            assert_eq!(
+                merged.resolve(&import_name, None).unwrap().unwrap(),
+                host,
+                "{name}"
+            );
+        }
+
+        assert_eq!(
+            alloc::format!("{merge}"),
+            "~ API-MS-WIN-CORE-BAR-L1-1-0\n\
+             ~ api-ms-win-core-foo-l1-1-0\n\
+             + ext-ms-win-baz-l1-1-0\n\
+             + ext-ms-win-new-l1-1-0\n"
+        );
+        assert_eq!(
+            alloc::format!("{merge:#}"),
+            "~ API-MS-WIN-CORE-BAR-L1-1-0 (second.dll)\n\
+             ~ api-ms-win-core-foo-l1-1-0 (first.dll)\n\
+             + ext-ms-win-baz-l1-1-0 (first.dll)\n\
+             + ext-ms-win-new-l1-1-0 (second.dll)\n"
+        );
+        assert_eq!(merge.changes()[0].kind(), ApiSetMergeChangeKind::Overridden);
+        assert_eq!(merge.changes()[3].source(), ApiSetSource::Extension(1));
+
+        // Any API Set Map that is not flagged as an extension is rejected.
+        let maps = [
+            ApiSetMap::try_from_apiset_section_bytes(&first_bytes).unwrap(),
+            ApiSetMap::try_from_apiset_section_bytes(&base_bytes).unwrap(),
+        ];
+        assert_eq!(
+            ApiSetMapView::with_extensions(&base, &maps).unwrap_err(),
+            NtApiSetError::NotAnExtension { flags: 0 }
+        );
+    }
+}
@@ -0,0 +1,223 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Fixture factories shared by the tests of several modules.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::schema::Schema;
+
+/// File offset of the `.apiset` section in the PE file created by [`build_pe_file`].
+pub(crate) const PE_SECTION_FILE_OFFSET: u32 = 0x200;
+
+/// RVA of the `.apiset` section in the PE file created by [`build_pe_file`].
+pub(crate) const PE_SECTION_RVA: u32 = 0x1000;
+
+/// Wraps the bytes of an `.apiset` section into a minimal PE32 (`is_64bit == false`) or PE32+ (`is_64bit == true`) file.
+///
+/// The file has no other sections and no data directories, just like an `apisetschema.dll`.
+/// The section is padded to the file alignment, hence it is followed by zero bytes.
+pub(crate) fn build_pe_file(is_64bit: bool, section_bytes: &[u8]) -> Vec<u8> {
+    build_pe_file_with_imports(is_64bit, section_bytes, &[])
+}
+
+/// Does the same as [`build_pe_file`], but adds an `.idata` section importing the DLLs `dll_names` (in this order).
+///
+/// The import directory only consists of the DLL names, no function is imported.
+/// Without any `dll_names`, this is the same as [`build_pe_file`].
+pub(crate) fn build_pe_file_with_imports(
+    is_64bit: bool,
+    section_bytes: &[u8],
+    dll_names: &[&str],
+) -> Vec<u8> {
+    const FILE_ALIGNMENT: u32 = 0x200;
+    const SECTION_ALIGNMENT: u32 = 0x1000;
+    const NT_HEADERS_OFFSET: usize = 0x40;
+    const IMPORT_DESCRIPTOR_SIZE: usize = 20;
+
+    let align = |value: u32, alignment: u32| (value + alignment - 1) / alignment * alignment;
+    let virtual_size = align(section_bytes.len() as u32, SECTION_ALIGNMENT);
+
+    // IMAGE_IMPORT_DESCRIPTORs including the terminating one, followed by an empty thunk array shared by all of them,
+    // followed by the DLL names.
+    let import_rva = PE_SECTION_RVA + virtual_size;
+    let descriptors_size = (dll_names.len() + 1) * IMPORT_DESCRIPTOR_SIZE;
+    let thunks_rva = import_rva + descriptors_size as u32;
+    let mut import_bytes = vec![0u8; descriptors_size + 8];
+
+    for (i, dll_name) in dll_names.iter().enumerate() {
+        let name_rva = import_rva + import_bytes.len() as u32;
+        import_bytes.extend_from_slice(dll_name.as_bytes());
+        import_bytes.push(0);
+
+        let descriptor = i * IMPORT_DESCRIPTOR_SIZE;
+        import_bytes[descriptor..descriptor + 4].copy_from_slice(&thunks_rva.to_le_bytes());
+        import_bytes[descriptor + 12..descriptor + 16].copy_from_slice(&name_rva.to_le_bytes());
+        import_bytes[descriptor + 16..descriptor + 20].copy_from_slice(&thunks_rva.to_le_bytes());
+    }
+
+    let mut sections = vec![(*b".apiset\0", PE_SECTION_RVA, section_bytes)];
+    if !dll_names.is_empty() {
+        sections.push((*b".idata\0\0", import_rva, &import_bytes[..]));
+    }
+
+    let mut file_size = PE_SECTION_FILE_OFFSET;
+    let mut image_size = PE_SECTION_RVA;
+    for (_, _, bytes) in &sections {
+        file_size += align(bytes.len() as u32, FILE_ALIGNMENT);
+        image_size += align(bytes.len() as u32, SECTION_ALIGNMENT);
+    }
+
+    let mut file = vec![0u8; file_size as usize];
+    let mut write = |offset: usize, bytes: &[u8]| {
+        file[offset..offset + bytes.len()].copy_from_slice(bytes);
+    };
+
+    // IMAGE_DOS_HEADER
+    write(0, b"MZ");
+    write(0x3c, &(NT_HEADERS_OFFSET as u32).to_le_bytes());
+
+    // IMAGE_NT_HEADERS with IMAGE_FILE_HEADER
+    let (machine, magic, optional_header_size, rva_count_offset): (u16, u16, u16, usize) =
+        if is_64bit {
+            (0x8664, 0x20b, 240, 108)
+        } else {
+            (0x14c, 0x10b, 224, 92)
+        };
+    write(NT_HEADERS_OFFSET, b"PE\0\0");
+    write(NT_HEADERS_OFFSET + 4, &machine.to_le_bytes());
+    write(
+        NT_HEADERS_OFFSET + 6,
+        &(sections.len() as u16).to_le_bytes(),
+    );
+    write(NT_HEADERS_OFFSET + 20, &optional_header_size.to_le_bytes());
+    write(NT_HEADERS_OFFSET + 22, &0x2102u16.to_le_bytes());
+
+    // IMAGE_OPTIONAL_HEADER32 / IMAGE_OPTIONAL_HEADER64
+    let optional_header = NT_HEADERS_OFFSET + 24;
+    write(optional_header, &magic.to_le_bytes());
+    write(optional_header + 32, &SECTION_ALIGNMENT.to_le_bytes());
+    write(optional_header + 36, &FILE_ALIGNMENT.to_le_bytes());
+    write(optional_header + 56, &image_size.to_le_bytes());
+    write(optional_header + 60, &PE_SECTION_FILE_OFFSET.to_le_bytes());
+    write(optional_header + rva_count_offset, &16u32.to_le_bytes());
+
+    // IMAGE_DIRECTORY_ENTRY_IMPORT
+    if !dll_names.is_empty() {
+        let import_directory = optional_header + rva_count_offset + 4 + 8;
+        write(import_directory, &import_rva.to_le_bytes());
+        write(
+            import_directory + 4,
+            &(descriptors_size as u32).to_le_bytes(),
+        );
+    }
+
+    // IMAGE_SECTION_HEADERs
+    let mut section_header = optional_header + optional_header_size as usize;
+    let mut section_file_offset = PE_SECTION_FILE_OFFSET;
+
+    for (name, rva, bytes) in &sections {
+        let section_raw_size = align(bytes.len() as u32, FILE_ALIGNMENT);
+
+        write(section_header, name);
+        write(section_header + 8, &(bytes.len() as u32).to_le_bytes());
+        write(section_header + 12, &rva.to_le_bytes());
+        write(section_header + 16, &section_raw_size.to_le_bytes());
+        write(section_header + 20, &section_file_offset.to_le_bytes());
+        write(section_header + 36, &0x4000_0040u32.to_le_bytes());
+        write(section_file_offset as usize, bytes);
+
+        section_header += 40;
+        section_file_offset += section_raw_size;
+    }
+
+    file
+}
+
+/// Builds the bytes of a version 2 or version 4 `.apiset` section for tests, as [`ApiSetMapBuilder`] only writes version 6.
+///
+/// Each Namespace Entry is given by its name and its pairs of importing module and host module.
+/// Names must be given without their "api-" or "ext-" prefix and sorted case-insensitively, as Windows stores them.
+/// All flags are zero.
+///
+/// [`ApiSetMapBuilder`]: crate::builder::ApiSetMapBuilder
+pub(crate) fn build_legacy_map(
+    version: u32,
+    namespace_entries: &[(&str, &[(&str, &str)])],
+) -> Vec<u8> {
+    let schema = Schema::from_version(version).expect("unsupported version");
+    assert!(
+        !schema.has_hash_table(),
+        "use ApiSetMapBuilder for version 6"
+    );
+
+    let value_entry_count = namespace_entries
+        .iter()
+        .map(|(_, value_entries)| value_entries.len())
+        .sum::<usize>();
+    let values_start =
+        schema.map_header_size() + namespace_entries.len() * schema.namespace_entry_size();
+    let strings_start = values_start
+        + namespace_entries.len() * schema.value_array_header_size()
+        + value_entry_count * schema.value_entry_size();
+
+    let mut header = Vec::new();
+    let mut namespace_bytes = Vec::new();
+    let mut value_bytes = Vec::new();
+    let mut string_bytes = Vec::new();
+
+    let push_u32 = |bytes: &mut Vec<u8>, value: usize| {
+        bytes.extend_from_slice(&(value as u32).to_le_bytes());
+    };
+    let push_string = |string_bytes: &mut Vec<u8>, string: &str| {
+        let offset = strings_start + string_bytes.len();
+        string_bytes.extend(string.encode_utf16().flat_map(u16::to_le_bytes));
+        (offset, strings_start + string_bytes.len() - offset)
+    };
+
+    for (name, value_entries) in namespace_entries {
+        let (name_offset, name_length) = push_string(&mut string_bytes, name);
+        let data_offset = values_start + value_bytes.len();
+
+        if version == 4 {
+            push_u32(&mut namespace_bytes, 0);
+        }
+        push_u32(&mut namespace_bytes, name_offset);
+        push_u32(&mut namespace_bytes, name_length);
+        if version == 4 {
+            // The alias is the name without the version suffix, which isn't used by this crate.
+            push_u32(&mut namespace_bytes, name_offset);
+            push_u32(&mut namespace_bytes, name_length);
+        }
+        push_u32(&mut namespace_bytes, data_offset);
+
+        if version == 4 {
+            push_u32(&mut value_bytes, 0);
+        }
+        push_u32(&mut value_bytes, value_entries.len());
+
+        for (importing_module, host_module) in value_entries.iter() {
+            let (name_offset, name_length) = push_string(&mut string_bytes, importing_module);
+            let (value_offset, value_length) = push_string(&mut string_bytes, host_module);
+
+            if version == 4 {
+                push_u32(&mut value_bytes, 0);
+            }
+            push_u32(&mut value_bytes, name_offset);
+            push_u32(&mut value_bytes, name_length);
+            push_u32(&mut value_bytes, value_offset);
+            push_u32(&mut value_bytes, value_length);
+        }
+    }
+
+    let size = strings_start + string_bytes.len();
+    push_u32(&mut header, version as usize);
+    if version == 4 {
+        push_u32(&mut header, size);
+        push_u32(&mut header, 0);
+    }
+    push_u32(&mut header, namespace_entries.len());
+
+    [header, namespace_bytes, value_bytes, string_bytes].concat()
+}
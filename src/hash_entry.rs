@@ -5,14 +5,17 @@ use core::iter::FusedIterator;
 use core::mem;
 use core::ops::Range;
 
-use zerocopy::{FromBytes, LayoutVerified, LittleEndian, Unaligned, U32};
+use zerocopy::{AsBytes, FromBytes, LayoutVerified, LittleEndian, Unaligned, U32};
+
+use crate::error::{NtApiSetError, Result};
+use crate::namespace_entry::{ApiSetNamespaceEntries, ApiSetNamespaceEntry};
 
 #[allow(dead_code)]
-#[derive(Debug, FromBytes, Unaligned)]
+#[derive(AsBytes, Debug, FromBytes, Unaligned)]
 #[repr(packed)]
 pub(crate) struct ApiSetHashEntryHeader {
-    hash: U32<LittleEndian>,
-    index: U32<LittleEndian>,
+    pub(crate) hash: U32<LittleEndian>,
+    pub(crate) index: U32<LittleEndian>,
 }
 
 /// Iterator over the [`ApiSetHashEntry`]s of an [`ApiSetMap`].
@@ -38,16 +41,21 @@ impl<'a> ApiSetHashEntries<'a> {
             range,
         }
     }
+
+    fn entry_at(&self, position: usize) -> Option<ApiSetHashEntry<'a>> {
+        let (header, _) = LayoutVerified::<_, ApiSetHashEntryHeader>::new_unaligned_from_prefix(
+            self.section_bytes.get(position..self.range.end)?,
+        )?;
+
+        Some(ApiSetHashEntry { position, header })
+    }
 }
 
 impl<'a> Iterator for ApiSetHashEntries<'a> {
     type Item = ApiSetHashEntry<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let (header, _) = LayoutVerified::<_, ApiSetHashEntryHeader>::new_unaligned_from_prefix(
-            self.section_bytes.get(self.range.clone())?,
-        )?;
-        let entry = ApiSetHashEntry { header };
+        let entry = self.entry_at(self.range.start)?;
         self.range.start += mem::size_of::<ApiSetHashEntryHeader>();
 
         Some(entry)
@@ -66,6 +74,21 @@ impl<'a> Iterator for ApiSetHashEntries<'a> {
     }
 }
 
+impl<'a> DoubleEndedIterator for ApiSetHashEntries<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let entry_size = mem::size_of::<ApiSetHashEntryHeader>();
+        if self.range.len() < entry_size {
+            return None;
+        }
+
+        let position = self.range.end - entry_size;
+        let entry = self.entry_at(position)?;
+        self.range.end = position;
+
+        Some(entry)
+    }
+}
+
 impl<'a> ExactSizeIterator for ApiSetHashEntries<'a> {}
 impl<'a> FusedIterator for ApiSetHashEntries<'a> {}
 
@@ -79,6 +102,7 @@ impl<'a> FusedIterator for ApiSetHashEntries<'a> {}
 /// [`ApiSetNamespaceEntry`]: crate::namespace_entry::ApiSetNamespaceEntry
 #[derive(Debug)]
 pub struct ApiSetHashEntry<'a> {
+    position: usize,
     header: LayoutVerified<&'a [u8], ApiSetHashEntryHeader>,
 }
 
@@ -97,4 +121,172 @@ impl<'a> ApiSetHashEntry<'a> {
     pub fn index(&self) -> u32 {
         self.header.index.get()
     }
+
+    /// Returns the byte offset of this [`ApiSetHashEntry`] within the `.apiset` section.
+    pub const fn offset(&self) -> usize {
+        self.position
+    }
+}
+
+/// Iterator over the [`ApiSetHashEntry`]s of an [`ApiSetMap`] along with the [`ApiSetNamespaceEntry`] each of them points to.
+///
+/// This iterator is returned by [`ApiSetMap::hash_joined`].
+/// It walks the hash table in order and resolves the index of each hash entry.
+/// An out-of-range index is returned as an error item.
+///
+/// [`ApiSetMap`]: crate::map::ApiSetMap
+/// [`ApiSetMap::hash_joined`]: crate::map::ApiSetMap::hash_joined
+/// [`ApiSetNamespaceEntry`]: crate::namespace_entry::ApiSetNamespaceEntry
+#[derive(Clone, Debug)]
+pub struct ApiSetHashJoinedEntries<'a> {
+    hash_entries: ApiSetHashEntries<'a>,
+    namespace_entries: ApiSetNamespaceEntries<'a>,
+}
+
+impl<'a> ApiSetHashJoinedEntries<'a> {
+    pub(crate) const fn new(
+        hash_entries: ApiSetHashEntries<'a>,
+        namespace_entries: ApiSetNamespaceEntries<'a>,
+    ) -> Self {
+        Self {
+            hash_entries,
+            namespace_entries,
+        }
+    }
+
+    fn join(&self, hash_entry: ApiSetHashEntry<'a>) -> <Self as Iterator>::Item {
+        let index = hash_entry.index() as usize;
+        let namespace_entry = self.namespace_entries.clone().nth(index).ok_or(
+            NtApiSetError::NamespaceEntryIndexOutOfBounds {
+                index,
+                count: self.namespace_entries.len(),
+            },
+        )?;
+
+        Ok((hash_entry, namespace_entry))
+    }
+}
+
+impl<'a> Iterator for ApiSetHashJoinedEntries<'a> {
+    type Item = Result<(ApiSetHashEntry<'a>, ApiSetNamespaceEntry<'a>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let hash_entry = self.hash_entries.next()?;
+        Some(self.join(hash_entry))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.hash_entries.size_hint()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let hash_entry = self.hash_entries.nth(n)?;
+        Some(self.join(hash_entry))
+    }
+}
+
+impl<'a> DoubleEndedIterator for ApiSetHashJoinedEntries<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let hash_entry = self.hash_entries.next_back()?;
+        Some(self.join(hash_entry))
+    }
+}
+
+impl<'a> ExactSizeIterator for ApiSetHashJoinedEntries<'a> {}
+impl<'a> FusedIterator for ApiSetHashJoinedEntries<'a> {}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+    use crate::map::ApiSetMap;
+    use crate::namespace_entry::ApiSetNamespaceEntryFlags;
+
+    const NAMES: [&str; 3] = [
+        "api-ms-win-core-bar-l1-1-0",
+        "api-ms-win-core-foo-l1-1-0",
+        "ext-ms-win-qux-l1-1-0",
+    ];
+
+    fn build() -> Vec<u8> {
+        NAMES
+            .iter()
+            .fold(ApiSetMapBuilder::new(), |builder, name| {
+                builder.add_namespace_entry(ApiSetNamespaceEntryBuilder::new(
+                    name,
+                    ApiSetNamespaceEntryFlags::empty(),
+                ))
+            })
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_hash_joined() {
+        let bytes = build();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+        let joined = map.hash_joined().unwrap();
+        assert_eq!(joined.len(), NAMES.len());
+
+        let mut names = Vec::new();
+
+        for item in joined {
+            let (hash_entry, namespace_entry) = item.unwrap();
+            let name = namespace_entry.name().unwrap().to_string().unwrap();
+            let (hashed_name, _) = name.rsplit_once('-').unwrap();
+
+            assert_eq!(hash_entry.hash(), map.hash_name(hashed_name));
+            assert_eq!(
+                map.namespace_entries()
+                    .unwrap()
+                    .nth(hash_entry.index() as usize)
+                    .unwrap()
+                    .offset(),
+                namespace_entry.offset()
+            );
+            names.push(name);
+        }
+
+        // Every Namespace Entry is paired exactly once.
+        names.sort_unstable();
+        assert_eq!(names, NAMES);
+    }
+
+    #[test]
+    fn test_hash_joined_index_out_of_bounds() {
+        let mut bytes = build();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let index_offset = map.hash_entries().unwrap().nth(1).unwrap().offset() + 4;
+        bytes[index_offset..index_offset + 4].copy_from_slice(&7u32.to_le_bytes());
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+        let joined = map.hash_joined().unwrap().collect::<Vec<_>>();
+        assert_eq!(joined.len(), NAMES.len());
+        assert!(joined[0].is_ok());
+        assert_eq!(
+            joined[1].as_ref().unwrap_err(),
+            &NtApiSetError::NamespaceEntryIndexOutOfBounds { index: 7, count: 3 }
+        );
+
+        // The iterator continues after the error item.
+        let (hash_entry, namespace_entry) = joined[2].as_ref().unwrap();
+        assert_eq!(
+            map.namespace_entries()
+                .unwrap()
+                .nth(hash_entry.index() as usize)
+                .unwrap()
+                .offset(),
+            namespace_entry.offset()
+        );
+
+        // The same applies from the back.
+        let mut joined = map.hash_joined().unwrap();
+        assert!(joined.next_back().unwrap().is_ok());
+        assert!(joined.next_back().unwrap().is_err());
+        assert!(joined.next_back().unwrap().is_ok());
+        assert!(joined.next_back().is_none());
+    }
 }
@@ -1,6 +1,13 @@
 // Copyright 2023 Colin Finck <colin@reactos.org>
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+use core::cmp::Ordering;
+use core::ops::Range;
+
+use nt_string::u16strle::U16StrLe;
+
+use crate::error::{NtApiSetError, Result};
+
 macro_rules! iter_try {
     ($e:expr) => {
         match $e {
@@ -9,3 +16,451 @@ macro_rules! iter_try {
         }
     };
 }
+
+/// Compares two UTF-16 strings case-insensitively, the same way the Namespace Entries and Value Entries are sorted.
+///
+/// Only ASCII characters are folded, which is sufficient for all names found in API Set Maps.
+pub(crate) fn cmp_ignore_ascii_case(a: &U16StrLe, b: &U16StrLe) -> Ordering {
+    a.u16_iter()
+        .map(u16_to_ascii_lowercase)
+        .cmp(b.u16_iter().map(u16_to_ascii_lowercase))
+}
+
+/// Compares a UTF-16 string of the API Set Map case-insensitively with a string provided by the caller.
+pub(crate) fn cmp_ignore_ascii_case_str(a: &U16StrLe, b: &str) -> Ordering {
+    a.u16_iter()
+        .map(u16_to_ascii_lowercase)
+        .cmp(b.encode_utf16().map(u16_to_ascii_lowercase))
+}
+
+/// Checks a UTF-16 string of the API Set Map case-insensitively for equality with a string provided by the caller.
+pub(crate) fn eq_ignore_ascii_case_str(a: &U16StrLe, b: &str) -> bool {
+    cmp_ignore_ascii_case_str(a, b) == Ordering::Equal
+}
+
+/// Checks case-insensitively whether a UTF-16 string of the API Set Map begins with a string provided by the caller.
+pub(crate) fn starts_with_ignore_ascii_case_str(a: &U16StrLe, prefix: &str) -> bool {
+    let mut chars = a.u16_iter().map(u16_to_ascii_lowercase);
+
+    prefix
+        .encode_utf16()
+        .map(u16_to_ascii_lowercase)
+        .all(|c| chars.next() == Some(c))
+}
+
+/// Checks case-insensitively whether a UTF-16 string of the API Set Map contains a string provided by the caller.
+pub(crate) fn contains_ignore_ascii_case_str(a: &U16StrLe, needle: &str) -> bool {
+    let length = a.0.len() / 2;
+    let needle_length = needle.encode_utf16().count();
+
+    (0..(length + 1).saturating_sub(needle_length)).any(|start| {
+        a.u16_iter()
+            .skip(start)
+            .map(u16_to_ascii_lowercase)
+            .zip(needle.encode_utf16().map(u16_to_ascii_lowercase))
+            .all(|(c, n)| c == n)
+    })
+}
+
+pub(crate) const fn u16_to_ascii_lowercase(c: u16) -> u16 {
+    if c >= b'A' as u16 && c <= b'Z' as u16 {
+        c + (b'a' - b'A') as u16
+    } else {
+        c
+    }
+}
+
+/// Returns the end offset of an array of `count` entries of `entry_size` bytes each, starting at `start`.
+///
+/// A header-declared `count` may be arbitrarily large in a corrupted API Set Map.
+/// If the end offset cannot be represented, `usize::MAX` is returned, which then fails the bounds check.
+pub(crate) fn entry_array_end(start: usize, entry_size: usize, count: usize) -> usize {
+    entry_size
+        .checked_mul(count)
+        .and_then(|size| start.checked_add(size))
+        .unwrap_or(usize::MAX)
+}
+
+/// Returns the byte range of the leading entries of an array of `count` entries of `entry_size` bytes each, starting at `start`,
+/// that fit entirely into a section of `section_length` bytes.
+///
+/// This is used by lenient API Set Maps to enumerate the intact entries of a truncated section.
+pub(crate) fn clamp_entry_array(
+    start: usize,
+    entry_size: usize,
+    count: usize,
+    section_length: usize,
+) -> Range<usize> {
+    let available = section_length.saturating_sub(start) / entry_size;
+    start..start + count.min(available) * entry_size
+}
+
+/// Computes the hash of an API Set name the same way NTDLL does.
+///
+/// `chars` must only contain the part of the name up to but not including the last hyphen, already lowercased.
+pub(crate) fn hash_name<I>(chars: I, hash_factor: u32) -> u32
+where
+    I: Iterator<Item = u32>,
+{
+    chars.fold(0u32, |acc, x| acc.wrapping_mul(hash_factor).wrapping_add(x))
+}
+
+/// Returns `s` without `prefix` if it begins with `prefix`, compared case-insensitively.
+pub(crate) fn strip_prefix_ignore_ascii_case<'s>(s: &'s str, prefix: &str) -> Option<&'s str> {
+    let (start, rest) = (s.get(..prefix.len())?, s.get(prefix.len()..)?);
+    start.eq_ignore_ascii_case(prefix).then(|| rest)
+}
+
+/// Returns `s` without `suffix` if it ends with `suffix`, compared case-insensitively.
+pub(crate) fn strip_suffix_ignore_ascii_case<'s>(s: &'s str, suffix: &str) -> Option<&'s str> {
+    let split = s.len().checked_sub(suffix.len())?;
+    let (rest, end) = (s.get(..split)?, s.get(split..)?);
+    end.eq_ignore_ascii_case(suffix).then(|| rest)
+}
+
+/// Narrows the byte `range` of an entry array of `entry_size` bytes per entry to the entries at `index_range`.
+///
+/// `range` must already be bounds-checked against the `.apiset` section.
+/// An `index_range` that is inverted or exceeds the number of entries is rejected instead of being clamped.
+pub(crate) fn entry_subrange(
+    range: &Range<usize>,
+    entry_size: usize,
+    index_range: Range<usize>,
+) -> Result<Range<usize>> {
+    let count = range.len() / entry_size;
+
+    if index_range.start > index_range.end || index_range.end > count {
+        return Err(NtApiSetError::EntryIndexRangeOutOfBounds {
+            range: index_range,
+            count,
+        });
+    }
+
+    let start = range.start + index_range.start * entry_size;
+    let end = range.start + index_range.end * entry_size;
+    Ok(start..end)
+}
+
+/// Creates an empty [`Vec`] with space for at least `capacity` elements, without aborting if the allocation fails.
+#[cfg(feature = "alloc")]
+pub(crate) fn try_vec_with_capacity<T>(capacity: usize) -> Result<alloc::vec::Vec<T>> {
+    let mut vec = alloc::vec::Vec::new();
+    try_reserve(&mut vec, capacity)?;
+    Ok(vec)
+}
+
+/// Appends `value` to `vec`, without aborting if growing `vec` fails.
+#[cfg(feature = "alloc")]
+pub(crate) fn try_push<T>(vec: &mut alloc::vec::Vec<T>, value: T) -> Result<()> {
+    try_reserve(vec, 1)?;
+    vec.push(value);
+    Ok(())
+}
+
+/// Reserves space for at least `additional` more elements in `vec`, returning [`NtApiSetError::AllocationFailed`]
+/// instead of aborting if the allocation fails.
+#[cfg(feature = "alloc")]
+pub(crate) fn try_reserve<T>(vec: &mut alloc::vec::Vec<T>, additional: usize) -> Result<()> {
+    vec.try_reserve(additional)
+        .map_err(|_| NtApiSetError::AllocationFailed {
+            size: additional.saturating_mul(core::mem::size_of::<T>()),
+        })
+}
+
+/// Aborts via [`handle_alloc_error`](alloc::alloc::handle_alloc_error) if `result` is [`NtApiSetError::AllocationFailed`].
+///
+/// This lets the infallible variant of an API share the implementation of its `try_` variant,
+/// like [`Vec::reserve`](alloc::vec::Vec::reserve) does for [`Vec::try_reserve`](alloc::vec::Vec::try_reserve).
+#[cfg(feature = "alloc")]
+pub(crate) fn abort_on_allocation_failure<T>(result: Result<T>) -> Result<T> {
+    match result {
+        Err(NtApiSetError::AllocationFailed { size }) => {
+            let layout = alloc::alloc::Layout::from_size_align(size, 1)
+                .unwrap_or_else(|_| alloc::alloc::Layout::new::<u8>());
+            alloc::alloc::handle_alloc_error(layout)
+        }
+        result => result,
+    }
+}
+
+/// Copies a UTF-16 string from the `.apiset` section at `range` into a fixed-capacity [`heapless::String`].
+#[cfg(feature = "heapless")]
+pub(crate) fn to_fixed_string<const N: usize>(
+    string: &U16StrLe,
+    range: Range<usize>,
+) -> Result<heapless::String<N>> {
+    let mut fixed_string = heapless::String::new();
+
+    for c in core::char::decode_utf16(string.u16_iter()) {
+        let c = c.map_err(|_| NtApiSetError::InvalidUtf16 {
+            range: range.clone(),
+        })?;
+
+        fixed_string
+            .push(c)
+            .map_err(|_| NtApiSetError::StringTooLong {
+                range: range.clone(),
+                capacity: N,
+            })?;
+    }
+
+    Ok(fixed_string)
+}
+
+/// Defines a bitflags-like type without exposing an external crate in the public API.
+///
+/// Unknown bits are retained by [`from_bits_retain`] and shown by the [`Debug`] implementation,
+/// so that flags introduced by newer Windows versions don't get lost.
+macro_rules! flags {
+    (
+        $(#[$outer:meta])*
+        pub struct $name:ident: $ty:ty {
+            $(
+                $(#[$inner:meta])*
+                const $flag:ident = $value:expr;
+            )*
+        }
+    ) => {
+        $(#[$outer])*
+        #[derive(Clone, Copy, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+        pub struct $name($ty);
+
+        impl $name {
+            $(
+                $(#[$inner])*
+                pub const $flag: Self = Self($value);
+            )*
+
+            const KNOWN_FLAGS: &'static [(&'static str, $ty)] = &[$((stringify!($flag), $value)),*];
+
+            /// Returns a value with no flags set.
+            pub const fn empty() -> Self {
+                Self(0)
+            }
+
+            /// Returns a value with all known flags set.
+            pub const fn all() -> Self {
+                Self(0 $(| $value)*)
+            }
+
+            /// Returns the raw value of the flags, including any unknown bits.
+            pub const fn bits(&self) -> $ty {
+                self.0
+            }
+
+            /// Converts from a raw value, returning `None` if any unknown bits are set.
+            pub const fn from_bits(bits: $ty) -> Option<Self> {
+                if bits & !Self::all().0 == 0 {
+                    Some(Self(bits))
+                } else {
+                    None
+                }
+            }
+
+            /// Converts from a raw value, removing any unknown bits.
+            pub const fn from_bits_truncate(bits: $ty) -> Self {
+                Self(bits & Self::all().0)
+            }
+
+            /// Converts from a raw value, retaining any unknown bits.
+            pub const fn from_bits_retain(bits: $ty) -> Self {
+                Self(bits)
+            }
+
+            /// Returns `true` if no flags are set.
+            pub const fn is_empty(&self) -> bool {
+                self.0 == 0
+            }
+
+            /// Returns `true` if all known flags are set.
+            pub const fn is_all(&self) -> bool {
+                self.0 & Self::all().0 == Self::all().0
+            }
+
+            /// Returns `true` if all flags of `other` are also set in `self`.
+            pub const fn contains(&self, other: Self) -> bool {
+                self.0 & other.0 == other.0
+            }
+
+            /// Returns `true` if any flag of `other` is also set in `self`.
+            pub const fn intersects(&self, other: Self) -> bool {
+                self.0 & other.0 != 0
+            }
+
+            /// Sets all flags of `other` in `self`.
+            pub fn insert(&mut self, other: Self) {
+                self.0 |= other.0;
+            }
+
+            /// Clears all flags of `other` in `self`.
+            pub fn remove(&mut self, other: Self) {
+                self.0 &= !other.0;
+            }
+
+            /// Toggles all flags of `other` in `self`.
+            pub fn toggle(&mut self, other: Self) {
+                self.0 ^= other.0;
+            }
+
+            /// Sets or clears all flags of `other` in `self`, depending on `value`.
+            pub fn set(&mut self, other: Self, value: bool) {
+                if value {
+                    self.insert(other);
+                } else {
+                    self.remove(other);
+                }
+            }
+        }
+
+        impl core::fmt::Debug for $name {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                write!(f, concat!(stringify!($name), "("))?;
+
+                let mut remaining = self.0;
+                let mut first = true;
+
+                for (flag_name, flag_value) in Self::KNOWN_FLAGS {
+                    if remaining & flag_value == *flag_value && *flag_value != 0 {
+                        if !first {
+                            write!(f, " | ")?;
+                        }
+
+                        write!(f, "{flag_name}")?;
+                        remaining &= !flag_value;
+                        first = false;
+                    }
+                }
+
+                if remaining != 0 || first {
+                    if !first {
+                        write!(f, " | ")?;
+                    }
+
+                    write!(f, "{remaining:#x}")?;
+                }
+
+                write!(f, ")")
+            }
+        }
+
+        impl core::ops::BitAnd for $name {
+            type Output = Self;
+
+            fn bitand(self, rhs: Self) -> Self {
+                Self(self.0 & rhs.0)
+            }
+        }
+
+        impl core::ops::BitAndAssign for $name {
+            fn bitand_assign(&mut self, rhs: Self) {
+                self.0 &= rhs.0;
+            }
+        }
+
+        impl core::ops::BitOr for $name {
+            type Output = Self;
+
+            fn bitor(self, rhs: Self) -> Self {
+                Self(self.0 | rhs.0)
+            }
+        }
+
+        impl core::ops::BitOrAssign for $name {
+            fn bitor_assign(&mut self, rhs: Self) {
+                self.0 |= rhs.0;
+            }
+        }
+
+        impl core::ops::BitXor for $name {
+            type Output = Self;
+
+            fn bitxor(self, rhs: Self) -> Self {
+                Self(self.0 ^ rhs.0)
+            }
+        }
+
+        impl core::ops::BitXorAssign for $name {
+            fn bitxor_assign(&mut self, rhs: Self) {
+                self.0 ^= rhs.0;
+            }
+        }
+
+        impl core::ops::Not for $name {
+            type Output = Self;
+
+            fn not(self) -> Self {
+                Self::from_bits_truncate(!self.0)
+            }
+        }
+
+        impl core::ops::Sub for $name {
+            type Output = Self;
+
+            fn sub(self, rhs: Self) -> Self {
+                Self(self.0 & !rhs.0)
+            }
+        }
+
+        impl core::ops::SubAssign for $name {
+            fn sub_assign(&mut self, rhs: Self) {
+                self.0 &= !rhs.0;
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::map::ApiSetMapFlags;
+    use crate::namespace_entry::ApiSetNamespaceEntryFlags;
+
+    #[test]
+    fn test_flags_unknown_bits() {
+        let flags = ApiSetMapFlags::from_bits_retain(0x8000_0002);
+        assert_eq!(flags.bits(), 0x8000_0002);
+        assert!(flags.contains(ApiSetMapFlags::IS_EXTENSION));
+        assert!(!flags.contains(ApiSetMapFlags::SEALED));
+        assert_ne!(flags, ApiSetMapFlags::IS_EXTENSION);
+
+        assert_eq!(ApiSetMapFlags::from_bits(0x8000_0002), None);
+        assert_eq!(
+            ApiSetMapFlags::from_bits_truncate(0x8000_0002),
+            ApiSetMapFlags::IS_EXTENSION
+        );
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_flags_debug() {
+        use alloc::format;
+
+        assert_eq!(
+            format!("{:?}", ApiSetNamespaceEntryFlags::empty()),
+            "ApiSetNamespaceEntryFlags(0x0)"
+        );
+        assert_eq!(
+            format!("{:?}", ApiSetNamespaceEntryFlags::all()),
+            "ApiSetNamespaceEntryFlags(SEALED | IS_EXTENSION)"
+        );
+        assert_eq!(
+            format!("{:?}", ApiSetMapFlags::from_bits_retain(0x8000_0001)),
+            "ApiSetMapFlags(SEALED | 0x80000000)"
+        );
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_map_flags_retain_unknown_bits() {
+        use crate::builder::ApiSetMapBuilder;
+        use crate::map::ApiSetMap;
+
+        let bytes = ApiSetMapBuilder::new()
+            .flags(ApiSetMapFlags::from_bits_retain(0x8000_0001))
+            .build()
+            .unwrap();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+        assert_eq!(map.flags().bits(), 0x8000_0001);
+        assert!(map.flags().contains(ApiSetMapFlags::SEALED));
+    }
+}
@@ -0,0 +1,446 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::fmt;
+use core::mem;
+use core::ops::Range;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use nt_string::u16strle::U16StrLe;
+
+use crate::error::{NtApiSetError, Result};
+use crate::hash_entry::ApiSetHashEntryHeader;
+use crate::map::ApiSetMap;
+
+/// Character gutter printed by [`ApiSetMap::annotate_hexdump`] between the hexadecimal bytes and the annotations.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HexdumpGutter {
+    /// One character per byte, showing printable ASCII characters.
+    Ascii,
+    /// One character per UTF-16LE code unit, showing code units that are printable ASCII characters.
+    ///
+    /// As all strings of an API Set Map are UTF-16LE, this makes them readable.
+    /// Code units start at even offsets of the section.
+    Utf16,
+}
+
+/// Options for [`ApiSetMap::annotate_hexdump`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HexdumpOptions {
+    bytes_per_line: usize,
+    gutter: HexdumpGutter,
+    range: Option<Range<usize>>,
+    namespace_entry: Option<usize>,
+}
+
+impl HexdumpOptions {
+    /// Creates options for dumping the entire section with 16 bytes per line and an ASCII gutter.
+    pub const fn new() -> Self {
+        Self {
+            bytes_per_line: 16,
+            gutter: HexdumpGutter::Ascii,
+            range: None,
+            namespace_entry: None,
+        }
+    }
+
+    /// Sets the number of bytes printed per line.
+    ///
+    /// Values of zero are treated as one.
+    pub const fn bytes_per_line(mut self, bytes_per_line: usize) -> Self {
+        self.bytes_per_line = bytes_per_line;
+        self
+    }
+
+    /// Sets the character gutter, see [`HexdumpGutter`].
+    pub const fn gutter(mut self, gutter: HexdumpGutter) -> Self {
+        self.gutter = gutter;
+        self
+    }
+
+    /// Restricts the dump to the given byte range of the section.
+    pub fn range(mut self, range: Range<usize>) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    /// Restricts the dump to the bytes belonging to the Namespace Entry at the given index.
+    ///
+    /// This covers its header, its Value Entry headers, and all strings referenced by them.
+    pub const fn namespace_entry(mut self, index: usize) -> Self {
+        self.namespace_entry = Some(index);
+        self
+    }
+}
+
+impl Default for HexdumpOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A byte range of the section along with a description of the structure it belongs to.
+struct Region {
+    range: Range<usize>,
+    namespace_entry: Option<usize>,
+    label: String,
+}
+
+impl<'a> ApiSetMap<'a> {
+    /// Writes a hexdump of the `.apiset` section to `w`, annotating every line with the structures its bytes belong to.
+    ///
+    /// Each line consists of the byte offset, the bytes in hexadecimal, an ASCII or UTF-16 gutter, and the annotations
+    /// (e.g. `namespace[371].header` or `string: api-ms-win-core-sysinfo-l1-1-0`).
+    /// Bytes that don't belong to any structure are annotated as `slack`.
+    ///
+    /// Strings that lie outside the section are silently left out, so this also works for partly corrupted maps.
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn annotate_hexdump<W>(&self, w: &mut W, options: &HexdumpOptions) -> Result<()>
+    where
+        W: fmt::Write,
+    {
+        let section_bytes = self.section_bytes();
+        let bytes_per_line = options.bytes_per_line.max(1);
+
+        let mut regions = self.regions()?;
+        regions.sort_by_key(|region| (region.range.start, region.range.end));
+
+        // Determine the byte ranges to dump.
+        let mut dump_ranges = Vec::new();
+
+        if let Some(index) = options.namespace_entry {
+            dump_ranges.extend(
+                regions
+                    .iter()
+                    .filter(|region| region.namespace_entry == Some(index))
+                    .map(|region| region.range.clone()),
+            );
+        } else {
+            dump_ranges.push(0..section_bytes.len());
+        }
+
+        if let Some(range) = &options.range {
+            for dump_range in &mut dump_ranges {
+                dump_range.start = dump_range.start.max(range.start);
+                dump_range.end = dump_range.end.min(range.end);
+            }
+        }
+
+        // Align all ranges to full lines, so that overlapping lines are only printed once.
+        let mut lines = Vec::new();
+
+        for dump_range in dump_ranges {
+            if dump_range.start >= dump_range.end {
+                continue;
+            }
+
+            let first_line = dump_range.start / bytes_per_line;
+            let last_line = (dump_range.end - 1) / bytes_per_line;
+            lines.push(first_line..last_line + 1);
+        }
+
+        lines.sort_by_key(|line_range| line_range.start);
+
+        let mut next_line = 0;
+        let mut next_region = 0;
+        let mut active_regions = Vec::<&Region>::new();
+
+        for line_range in lines {
+            for line in line_range.start.max(next_line)..line_range.end {
+                let start = line * bytes_per_line;
+                let end = (start + bytes_per_line).min(section_bytes.len());
+                let mut range = start..end;
+
+                if let Some(restriction) = &options.range {
+                    range.start = range.start.max(restriction.start);
+                    range.end = range.end.min(restriction.end);
+                }
+
+                if range.start >= range.end {
+                    continue;
+                }
+
+                // Collect all regions overlapping this line.
+                while next_region < regions.len() && regions[next_region].range.start < range.end {
+                    active_regions.push(&regions[next_region]);
+                    next_region += 1;
+                }
+
+                active_regions.retain(|region| region.range.end > range.start);
+
+                self.write_hexdump_line(w, bytes_per_line, options.gutter, range, &active_regions)
+                    .map_err(|_| NtApiSetError::FormatFailed)?;
+            }
+
+            next_line = next_line.max(line_range.end);
+        }
+
+        Ok(())
+    }
+
+    fn regions(&self) -> Result<Vec<Region>> {
+        let section_bytes = self.section_bytes();
+        let schema = self.schema();
+        let mut regions = Vec::new();
+
+        regions.push(Region {
+            range: 0..schema.map_header_size(),
+            namespace_entry: None,
+            label: String::from("header"),
+        });
+
+        for (i, hash_entry) in self.hash_entries()?.enumerate() {
+            let start = hash_entry.offset();
+
+            regions.push(Region {
+                range: start..start + mem::size_of::<ApiSetHashEntryHeader>(),
+                namespace_entry: Some(hash_entry.index() as usize),
+                label: format!("hash[{i}]"),
+            });
+        }
+
+        let string_region = |range: Range<usize>, namespace_entry: usize| {
+            let bytes = section_bytes.get(range.clone())?;
+
+            Some(Region {
+                range,
+                namespace_entry: Some(namespace_entry),
+                label: format!("string: {}", U16StrLe(bytes)),
+            })
+        };
+
+        for (i, namespace_entry) in self.namespace_entries()?.enumerate() {
+            let start = namespace_entry.offset();
+
+            regions.push(Region {
+                range: start..start + schema.namespace_entry_size(),
+                namespace_entry: Some(i),
+                label: format!("namespace[{i}].header"),
+            });
+            regions.extend(string_region(namespace_entry.name_range(), i));
+
+            let value_array_header_size = schema.value_array_header_size();
+            if value_array_header_size > 0 {
+                let start = namespace_entry.value_array_offset();
+
+                regions.push(Region {
+                    range: start..start.saturating_add(value_array_header_size),
+                    namespace_entry: Some(i),
+                    label: format!("namespace[{i}].values.header"),
+                });
+            }
+
+            let value_entries = match namespace_entry.value_entries() {
+                Ok(value_entries) => value_entries,
+                Err(_) => continue,
+            };
+
+            for (j, value_entry) in value_entries.enumerate() {
+                let start = value_entry.offset();
+
+                regions.push(Region {
+                    range: start..start + schema.value_entry_size(),
+                    namespace_entry: Some(i),
+                    label: format!("namespace[{i}].value[{j}].header"),
+                });
+                regions.extend(string_region(value_entry.name_range(), i));
+                regions.extend(string_region(value_entry.value_range(), i));
+            }
+        }
+
+        // Empty strings occupy no bytes.
+        regions.retain(|region| !region.range.is_empty());
+
+        Ok(regions)
+    }
+
+    fn write_hexdump_line<W>(
+        &self,
+        w: &mut W,
+        bytes_per_line: usize,
+        gutter: HexdumpGutter,
+        range: Range<usize>,
+        active_regions: &[&Region],
+    ) -> fmt::Result
+    where
+        W: fmt::Write,
+    {
+        let line_start = range.start - range.start % bytes_per_line;
+        let bytes = &self.section_bytes()[range.clone()];
+
+        write!(w, "{:08x} ", line_start)?;
+
+        for offset in line_start..line_start + bytes_per_line {
+            if range.contains(&offset) {
+                write!(w, " {:02x}", bytes[offset - range.start])?;
+            } else {
+                write!(w, "   ")?;
+            }
+        }
+
+        write!(w, "  |")?;
+
+        match gutter {
+            HexdumpGutter::Ascii => {
+                for offset in line_start..line_start + bytes_per_line {
+                    if range.contains(&offset) {
+                        write!(w, "{}", printable(bytes[offset - range.start].into()))?;
+                    } else {
+                        write!(w, " ")?;
+                    }
+                }
+            }
+            HexdumpGutter::Utf16 => {
+                let first_unit = line_start + line_start % 2;
+
+                for offset in (first_unit..line_start + bytes_per_line).step_by(2) {
+                    if range.contains(&offset) && range.contains(&(offset + 1)) {
+                        let unit_bytes = &bytes[offset - range.start..offset - range.start + 2];
+                        let unit = u16::from_le_bytes([unit_bytes[0], unit_bytes[1]]);
+                        write!(w, "{}", printable(unit))?;
+                    } else {
+                        write!(w, " ")?;
+                    }
+                }
+            }
+        }
+
+        write!(w, "| ")?;
+
+        let mut first = true;
+
+        for region in active_regions {
+            if region.range.start >= range.end || region.range.end <= range.start {
+                continue;
+            }
+
+            if !first {
+                write!(w, ", ")?;
+            }
+
+            write!(w, "{}", region.label)?;
+            first = false;
+        }
+
+        if covered_bytes(active_regions, &range) < range.len() {
+            if !first {
+                write!(w, ", ")?;
+            }
+
+            write!(w, "slack")?;
+        }
+
+        writeln!(w)
+    }
+}
+
+/// Returns the number of bytes of `range` that are covered by at least one of the `regions`, which must be sorted by their start.
+///
+/// Regions may overlap, e.g. when strings are shared between entries, so every byte is only counted once.
+fn covered_bytes(regions: &[&Region], range: &Range<usize>) -> usize {
+    let mut covered = 0;
+    let mut covered_until = range.start;
+
+    for region in regions {
+        let overlap_start = region.range.start.max(covered_until);
+        let overlap_end = region.range.end.min(range.end);
+
+        if overlap_start < overlap_end {
+            covered += overlap_end - overlap_start;
+            covered_until = overlap_end;
+        }
+    }
+
+    covered
+}
+
+/// Returns the character for `c` if it is printable ASCII, and a dot otherwise.
+fn printable(c: u16) -> char {
+    match u8::try_from(c) {
+        Ok(byte) if byte.is_ascii_graphic() || byte == b' ' => byte as char,
+        _ => '.',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+    use crate::namespace_entry::ApiSetNamespaceEntryFlags;
+
+    fn fixture() -> Vec<u8> {
+        ApiSetMapBuilder::new()
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "api-ms-foo-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                )
+                .add_value_entry("", "foo.dll"),
+            )
+            .build()
+            .unwrap()
+    }
+
+    fn hexdump(options: &HexdumpOptions) -> String {
+        let bytes = fixture();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let mut output = String::new();
+        map.annotate_hexdump(&mut output, options).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_snapshot() {
+        assert_eq!(
+            hexdump(&HexdumpOptions::new()),
+            "\
+00000000  06 00 00 00 80 00 00 00 00 00 00 00 01 00 00 00  |................| header
+00000010  1c 00 00 00 48 00 00 00 1f 00 00 00 00 00 00 00  |....H...........| header, namespace[0].header
+00000020  50 00 00 00 22 00 00 00 1e 00 00 00 34 00 00 00  |P...\".......4...| namespace[0].header
+00000030  01 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00  |................| namespace[0].header, namespace[0].value[0].header
+00000040  72 00 00 00 0e 00 00 00 64 1b 2a 51 00 00 00 00  |r.......d.*Q....| namespace[0].value[0].header, hash[0]
+00000050  61 00 70 00 69 00 2d 00 6d 00 73 00 2d 00 66 00  |a.p.i.-.m.s.-.f.| string: api-ms-foo-l1-1-0
+00000060  6f 00 6f 00 2d 00 6c 00 31 00 2d 00 31 00 2d 00  |o.o.-.l.1.-.1.-.| string: api-ms-foo-l1-1-0
+00000070  30 00 66 00 6f 00 6f 00 2e 00 64 00 6c 00 6c 00  |0.f.o.o...d.l.l.| string: api-ms-foo-l1-1-0, string: foo.dll
+"
+        );
+    }
+
+    #[test]
+    fn test_utf16_gutter() {
+        let options = HexdumpOptions::new()
+            .gutter(HexdumpGutter::Utf16)
+            .range(0x64..0x80);
+
+        assert_eq!(
+            hexdump(&options),
+            "\
+00000060              2d 00 6c 00 31 00 2d 00 31 00 2d 00  |  -l1-1-| string: api-ms-foo-l1-1-0
+00000070  30 00 66 00 6f 00 6f 00 2e 00 64 00 6c 00 6c 00  |0foo.dll| string: api-ms-foo-l1-1-0, string: foo.dll
+"
+        );
+    }
+
+    #[test]
+    fn test_covered_bytes_overlapping() {
+        let region = |range: Range<usize>| Region {
+            range,
+            namespace_entry: None,
+            label: String::new(),
+        };
+
+        // Two entries sharing the same string must not hide the 4 bytes of slack in between.
+        let shared = region(0..8);
+        let shared_again = region(0..8);
+        let nested = region(2..6);
+        let last = region(12..20);
+        let regions = [&shared, &shared_again, &nested, &last];
+
+        assert_eq!(covered_bytes(&regions, &(0..16)), 12);
+        assert_eq!(covered_bytes(&regions, &(4..16)), 8);
+        assert_eq!(covered_bytes(&regions, &(8..12)), 0);
+    }
+}
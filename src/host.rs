@@ -0,0 +1,215 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::iter::FusedIterator;
+
+use nt_string::u16strle::U16StrLe;
+
+use crate::error::Result;
+use crate::helpers::{strip_suffix_ignore_ascii_case, u16_to_ascii_lowercase};
+use crate::map::ApiSetMap;
+use crate::namespace_entry::{ApiSetNamespaceEntries, ApiSetNamespaceEntry};
+use crate::value_entry::{ApiSetValueEntries, ApiSetValueEntry};
+
+/// Iterator over all [`ApiSetValueEntry`]s of an [`ApiSetMap`] that map to a given host DLL,
+/// along with the [`ApiSetNamespaceEntry`] each of them belongs to.
+///
+/// This iterator is returned by [`ApiSetMap::find_by_host`].
+/// It walks all Namespace Entries in order and yields every matching Value Entry, including host-specific ones.
+/// A Namespace Entry whose Value Entries cannot be read, or a Value Entry whose value cannot be read, is returned as an error item.
+/// Iteration then continues with the next entry.
+#[derive(Clone, Debug)]
+pub struct ApiSetHostMatches<'a, 'h> {
+    host_stem: &'h str,
+    namespace_entries: ApiSetNamespaceEntries<'a>,
+    current: Option<(ApiSetNamespaceEntry<'a>, ApiSetValueEntries<'a>)>,
+}
+
+impl<'a, 'h> Iterator for ApiSetHostMatches<'a, 'h> {
+    type Item = Result<(ApiSetNamespaceEntry<'a>, ApiSetValueEntry<'a>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((namespace_entry, value_entries)) = &mut self.current {
+                for value_entry in value_entries {
+                    let value = iter_try!(value_entry.value());
+
+                    if is_host_match(&value, self.host_stem) {
+                        let namespace_entry = namespace_entry.clone();
+                        return Some(Ok((namespace_entry, value_entry)));
+                    }
+                }
+            }
+
+            let namespace_entry = self.namespace_entries.next()?;
+            self.current = None;
+
+            let value_entries = iter_try!(namespace_entry.value_entries());
+            self.current = Some((namespace_entry, value_entries));
+        }
+    }
+}
+
+impl<'a, 'h> FusedIterator for ApiSetHostMatches<'a, 'h> {}
+
+impl<'a> ApiSetMap<'a> {
+    /// Returns an iterator over all Value Entries mapping to the host DLL `host_dll`, along with their Namespace Entries.
+    ///
+    /// This answers questions like "which API Sets resolve to `kernelbase.dll`?".
+    /// `host_dll` is compared case-insensitively and may be given with or without its ".dll" extension.
+    /// Both the default Value Entries and host-specific ones are considered.
+    ///
+    /// This walks the entire API Set Map and doesn't allocate.
+    /// See [`ApiSetHostMatches`] for how errors are reported.
+    pub fn find_by_host<'h>(&self, host_dll: &'h str) -> Result<ApiSetHostMatches<'a, 'h>> {
+        let host_stem = strip_suffix_ignore_ascii_case(host_dll, ".dll").unwrap_or(host_dll);
+
+        Ok(ApiSetHostMatches {
+            host_stem,
+            namespace_entries: self.namespace_entries()?,
+            current: None,
+        })
+    }
+}
+
+/// Checks whether the host module name `value` equals `host_stem`, ignoring ASCII case and an optional ".dll" extension of `value`.
+fn is_host_match(value: &U16StrLe, host_stem: &str) -> bool {
+    let lowercase_value = || value.u16_iter().map(u16_to_ascii_lowercase);
+    let host_stem = host_stem.encode_utf16();
+
+    lowercase_value().eq(host_stem.clone().map(u16_to_ascii_lowercase))
+        || lowercase_value().eq(host_stem
+            .chain(".dll".encode_utf16())
+            .map(u16_to_ascii_lowercase))
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+    use crate::namespace_entry::ApiSetNamespaceEntryFlags;
+
+    fn build() -> Vec<u8> {
+        let entries: &[(&str, &[(&str, &str)])] = &[
+            ("api-ms-win-core-a-l1-1-0", &[("", "kernelbase.dll")]),
+            (
+                "api-ms-win-core-b-l1-1-0",
+                &[("", "foo.dll"), ("kernel32.dll", "KernelBase.DLL")],
+            ),
+            ("api-ms-win-core-c-l1-1-0", &[("", "kernelbase")]),
+            ("api-ms-win-core-d-l1-1-0", &[("", "kernelbase2.dll")]),
+        ];
+
+        entries
+            .iter()
+            .fold(ApiSetMapBuilder::new(), |builder, (name, value_entries)| {
+                let namespace_entry = value_entries.iter().fold(
+                    ApiSetNamespaceEntryBuilder::new(name, ApiSetNamespaceEntryFlags::empty()),
+                    |namespace_entry, (importing_module, host_module)| {
+                        namespace_entry.add_value_entry(importing_module, host_module)
+                    },
+                );
+                builder.add_namespace_entry(namespace_entry)
+            })
+            .build()
+            .unwrap()
+    }
+
+    /// Returns the Namespace Entry name and the importing module of every match, or `None` for an error item.
+    fn find(map: &ApiSetMap, host_dll: &str) -> Vec<Option<(String, String)>> {
+        map.find_by_host(host_dll)
+            .unwrap()
+            .map(|result| {
+                let (namespace_entry, value_entry) = result.ok()?;
+                Some((
+                    namespace_entry.name().unwrap().to_string().unwrap(),
+                    value_entry.name().unwrap().to_string().unwrap(),
+                ))
+            })
+            .collect()
+    }
+
+    fn expected(matches: &[(&str, &str)]) -> Vec<Option<(String, String)>> {
+        matches
+            .iter()
+            .map(|&(name, importing_module)| Some((name.into(), importing_module.into())))
+            .collect()
+    }
+
+    #[test]
+    fn test_find_by_host() {
+        let bytes = build();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let kernelbase_matches = expected(&[
+            ("api-ms-win-core-a-l1-1-0", ""),
+            ("api-ms-win-core-b-l1-1-0", "kernel32.dll"),
+            ("api-ms-win-core-c-l1-1-0", ""),
+        ]);
+
+        // With and without ".dll", in any case, including host-specific Value Entries.
+        for host_dll in [
+            "kernelbase.dll",
+            "kernelbase",
+            "KERNELBASE.Dll",
+            "KernelBase",
+        ] {
+            assert_eq!(find(&map, host_dll), kernelbase_matches);
+        }
+
+        assert_eq!(
+            find(&map, "FOO"),
+            expected(&[("api-ms-win-core-b-l1-1-0", "")])
+        );
+        assert_eq!(
+            find(&map, "kernelbase2.dll"),
+            expected(&[("api-ms-win-core-d-l1-1-0", "")])
+        );
+
+        // Neither prefixes nor extensions other than ".dll" match.
+        assert!(find(&map, "kernel").is_empty());
+        assert!(find(&map, "kernelbase.exe").is_empty());
+    }
+
+    #[test]
+    fn test_find_by_host_errors() {
+        let mut bytes = build();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let namespace_entry_b = map.namespace_entry(1).unwrap().unwrap();
+        let value_entry_offset = namespace_entry_b
+            .default_value_entry()
+            .unwrap()
+            .unwrap()
+            .offset();
+        let namespace_entry_c_offset = map.namespace_entry(2).unwrap().unwrap().offset();
+
+        // Let the value of the default Value Entry of "api-ms-win-core-b-l1-1-0" point outside the section.
+        let value_offset = value_entry_offset + 12;
+        bytes[value_offset..value_offset + 4].copy_from_slice(&0xffff_0000u32.to_le_bytes());
+
+        // Let the Value Entries of "api-ms-win-core-c-l1-1-0" start outside the section.
+        let array_offset = namespace_entry_c_offset + 16;
+        bytes[array_offset..array_offset + 4].copy_from_slice(&0xffff_0000u32.to_le_bytes());
+
+        // Both errors are returned as items, and iteration continues after each of them.
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let mut matches = expected(&[
+            ("api-ms-win-core-a-l1-1-0", ""),
+            ("api-ms-win-core-b-l1-1-0", "kernel32.dll"),
+        ]);
+        matches.insert(1, None);
+        matches.push(None);
+        assert_eq!(find(&map, "kernelbase"), matches);
+
+        assert_eq!(
+            find(&map, "kernelbase2"),
+            [
+                None,
+                None,
+                Some(("api-ms-win-core-d-l1-1-0".into(), "".into()))
+            ]
+        );
+    }
+}
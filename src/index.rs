@@ -0,0 +1,416 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::mem;
+
+use alloc::vec::Vec;
+
+use nt_string::u16strle::U16StrLe;
+
+use crate::control::OpControl;
+use crate::error::{NtApiSetError, Result};
+use crate::helpers::{
+    abort_on_allocation_failure, hash_name, strip_prefix_ignore_ascii_case,
+    strip_suffix_ignore_ascii_case, try_push, try_reserve, try_vec_with_capacity,
+    u16_to_ascii_lowercase,
+};
+use crate::map::ApiSetMap;
+use crate::namespace_entry::ApiSetNamespaceEntry;
+use crate::schema::Schema;
+
+/// Hash factor used for the keys of an [`ApiSetIndex`], independent of the one stored in the API Set Map.
+const INDEX_HASH_FACTOR: u32 = 0x1f;
+
+/// A lookup index over all Namespace Entries of an [`ApiSetMap`], built via [`ApiSetMap::build_index`].
+///
+/// All Namespace Entries and Value Entries are parsed once when building the index.
+/// Afterwards, every lookup hashes the requested name, performs a binary search over presorted hashes,
+/// and compares a single precomputed lowercase name.
+/// It doesn't touch the section bytes or re-parse any entry.
+/// This pays off when resolving many imports against the same [`ApiSetMap`].
+///
+/// The index borrows the names and values from the section bytes of the [`ApiSetMap`].
+/// Lookups via the index are not reported to the `metrics` recorder.
+#[derive(Clone, Debug)]
+pub struct ApiSetIndex<'a> {
+    schema: Schema,
+    entries: Vec<IndexedEntry<'a>>,
+    /// Hashes of the full names (sorted) and the corresponding index into `entries`.
+    names: Vec<(u32, usize)>,
+    /// Hashes of the hashed name prefixes (sorted) and the corresponding index into `entries`.
+    /// Empty for API Set Maps without a hash table.
+    hashed_names: Vec<(u32, usize)>,
+}
+
+#[derive(Clone, Debug)]
+struct IndexedEntry<'a> {
+    /// Lowercase name.
+    name: Vec<u16>,
+    /// Length of the hashed name prefix in UTF-16 code units.
+    hashed_length: usize,
+    namespace_entry: ApiSetNamespaceEntry<'a>,
+    default_value: Option<&'a [u8]>,
+    /// Lowercase importing module names (sorted) and the corresponding host module names.
+    host_values: Vec<(Vec<u16>, &'a [u8])>,
+}
+
+impl<'a> ApiSetIndex<'a> {
+    /// Returns the namespace entry with the given name.
+    ///
+    /// This has the same semantics as [`ApiSetMap::find_namespace_entry`]:
+    /// The full name is compared case-insensitively, and for API Set Maps before version 6,
+    /// names without their "api-" or "ext-" prefix are found as well.
+    pub fn get(&self, namespace_entry_name: &str) -> Option<&ApiSetNamespaceEntry<'a>> {
+        let entry = self.get_entry(namespace_entry_name)?;
+        Some(&entry.namespace_entry)
+    }
+
+    /// Returns `true` if the index contains no Namespace Entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the number of Namespace Entries in the index.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Resolves an imported API Set to the name of its host DLL.
+    ///
+    /// This has the same semantics as [`ApiSetMap::resolve`].
+    /// As all entries have been parsed when building the index, no error can occur here.
+    pub fn resolve(
+        &self,
+        apiset_name: &str,
+        importing_module: Option<&str>,
+    ) -> Option<U16StrLe<'a>> {
+        let entry = self.resolve_import_entry(apiset_name)?;
+
+        if let Some(importing_module) = importing_module {
+            let key = || importing_module.encode_utf16().map(u16_to_ascii_lowercase);
+
+            if let Ok(index) = entry
+                .host_values
+                .binary_search_by(|(name, _)| name.iter().copied().cmp(key()))
+            {
+                return Some(U16StrLe(entry.host_values[index].1));
+            }
+        }
+
+        entry.default_value.map(U16StrLe)
+    }
+
+    /// Resolves the name of an imported DLL to its namespace entry.
+    ///
+    /// This has the same semantics as [`ApiSetMap::resolve_import`].
+    pub fn resolve_import(&self, import_name: &str) -> Option<&ApiSetNamespaceEntry<'a>> {
+        let entry = self.resolve_import_entry(import_name)?;
+        Some(&entry.namespace_entry)
+    }
+
+    fn get_entry(&self, namespace_entry_name: &str) -> Option<&IndexedEntry<'a>> {
+        self.search(&self.names, namespace_entry_name, |entry| entry.name.len())
+            .or_else(|| {
+                if self.schema.has_hash_table() {
+                    return None;
+                }
+
+                // API Set Maps before version 6 store names without the "api-" or "ext-" prefix.
+                let name_without_prefix =
+                    strip_prefix_ignore_ascii_case(namespace_entry_name, "api-")
+                        .or_else(|| strip_prefix_ignore_ascii_case(namespace_entry_name, "ext-"))?;
+                self.search(&self.names, name_without_prefix, |entry| entry.name.len())
+            })
+    }
+
+    fn resolve_import_entry(&self, import_name: &str) -> Option<&IndexedEntry<'a>> {
+        let name = strip_suffix_ignore_ascii_case(import_name, ".dll").unwrap_or(import_name);
+
+        if !self.schema.has_hash_table() {
+            return self.get_entry(name);
+        }
+
+        let (hashed_name, _) = name.rsplit_once('-')?;
+        self.search(&self.hashed_names, hashed_name, |entry| entry.hashed_length)
+    }
+
+    /// Looks up `name` in one of the sorted hash tables.
+    ///
+    /// The name of every candidate is compared up to the length returned by `key_length`.
+    fn search<F>(
+        &self,
+        table: &[(u32, usize)],
+        name: &str,
+        key_length: F,
+    ) -> Option<&IndexedEntry<'a>>
+    where
+        F: Fn(&IndexedEntry<'a>) -> usize,
+    {
+        let chars = || name.encode_utf16().map(u16_to_ascii_lowercase);
+        let hash = hash_name(chars().map(u32::from), INDEX_HASH_FACTOR);
+
+        let first = table.partition_point(|(x, _)| *x < hash);
+
+        table[first..]
+            .iter()
+            .take_while(|(x, _)| *x == hash)
+            .map(|(_, index)| &self.entries[*index])
+            .find(|entry| entry.name[..key_length(entry)].iter().copied().eq(chars()))
+    }
+}
+
+impl<'a> ApiSetMap<'a> {
+    /// Parses all Namespace Entries and Value Entries of this API Set Map into an [`ApiSetIndex`] for repeated lookups.
+    ///
+    /// An error is returned if any entry, name, or value cannot be read, instead of silently omitting that entry from the index.
+    /// If an allocation fails, this aborts like any other allocating function.
+    /// Use [`try_build_index`](Self::try_build_index) to handle that case.
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn build_index(&self) -> Result<ApiSetIndex<'a>> {
+        abort_on_allocation_failure(self.try_build_index())
+    }
+
+    /// Performs the same as [`build_index`](Self::build_index), but returns [`NtApiSetError::AllocationFailed`]
+    /// instead of aborting if an allocation fails.
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn try_build_index(&self) -> Result<ApiSetIndex<'a>> {
+        self.try_build_index_with(&OpControl::new())
+    }
+
+    /// Performs the same as [`build_index`](Self::build_index), but reports progress and checks for cancellation via `control`.
+    ///
+    /// Every Namespace Entry (along with its Value Entries) counts as one step.
+    /// If the operation is cancelled, [`NtApiSetError::Cancelled`] is returned.
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn build_index_with(&self, control: &OpControl) -> Result<ApiSetIndex<'a>> {
+        abort_on_allocation_failure(self.try_build_index_with(control))
+    }
+
+    fn try_build_index_with(&self, control: &OpControl) -> Result<ApiSetIndex<'a>> {
+        let namespace_entries = self.namespace_entries()?;
+        let count = namespace_entries.len();
+        let mut progress = control.start(count);
+
+        let mut entries = try_vec_with_capacity(count)?;
+        let mut names = try_vec_with_capacity(count)?;
+        let mut hashed_names = Vec::new();
+
+        if self.schema().has_hash_table() {
+            try_reserve(&mut hashed_names, count)?;
+        }
+
+        for (index, namespace_entry) in namespace_entries.enumerate() {
+            progress.check()?;
+            let name = to_key(namespace_entry.name()?.u16_iter())?;
+            try_push(&mut names, (hash_key(&name), index))?;
+
+            let hashed_length = if self.schema().has_hash_table() {
+                let hashed_length = namespace_entry.hashed_length();
+                let name_length = namespace_entry.name_range().len();
+
+                if hashed_length > name_length {
+                    return Err(NtApiSetError::HashedLengthOutOfBounds {
+                        hashed_length,
+                        name_length,
+                        entry_offset: namespace_entry.offset(),
+                    });
+                }
+
+                let hashed_length = hashed_length / mem::size_of::<u16>();
+                try_push(&mut hashed_names, (hash_key(&name[..hashed_length]), index))?;
+                hashed_length
+            } else {
+                name.len()
+            };
+
+            let mut value_entries = namespace_entry.value_entries()?;
+            let default_value = match value_entries.next() {
+                Some(value_entry) => Some(value_entry.value()?.0),
+                None => None,
+            };
+
+            let mut host_values = try_vec_with_capacity(value_entries.len())?;
+            for value_entry in value_entries {
+                let importing_module = to_key(value_entry.name()?.u16_iter())?;
+                try_push(&mut host_values, (importing_module, value_entry.value()?.0))?;
+            }
+
+            // An unstable sort doesn't allocate.
+            host_values.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+
+            try_push(
+                &mut entries,
+                IndexedEntry {
+                    name,
+                    hashed_length,
+                    namespace_entry,
+                    default_value,
+                    host_values,
+                },
+            )?;
+            progress.advance();
+        }
+
+        names.sort_unstable();
+        hashed_names.sort_unstable();
+
+        Ok(ApiSetIndex {
+            schema: self.schema(),
+            entries,
+            names,
+            hashed_names,
+        })
+    }
+}
+
+fn hash_key(key: &[u16]) -> u32 {
+    hash_name(key.iter().map(|c| u32::from(*c)), INDEX_HASH_FACTOR)
+}
+
+fn to_key<I>(chars: I) -> Result<Vec<u16>>
+where
+    I: Iterator<Item = u16>,
+{
+    let mut key = try_vec_with_capacity(chars.size_hint().0)?;
+
+    for c in chars {
+        try_push(&mut key, u16_to_ascii_lowercase(c))?;
+    }
+
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+    use alloc::string::String;
+
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+    use crate::fixtures::build_legacy_map;
+    use crate::namespace_entry::ApiSetNamespaceEntryFlags;
+
+    /// Checks that `index` returns the same results as `map` for all `import_names` and `importing_modules`.
+    fn check_agreement(map: &ApiSetMap, import_names: &[String], importing_modules: &[&str]) {
+        let index = map.build_index().unwrap();
+        assert_eq!(index.len(), map.namespace_entries().unwrap().len());
+
+        for import_name in import_names {
+            // `find_namespace_entry` only accepts names without any file extension.
+            if !import_name.contains('.') {
+                assert_eq!(
+                    index.get(import_name).map(|entry| entry.offset()),
+                    map.find_namespace_entry(import_name)
+                        .map(|entry| entry.unwrap().offset()),
+                    "get({import_name:?})"
+                );
+            }
+
+            assert_eq!(
+                index
+                    .resolve_import(import_name)
+                    .map(|entry| entry.offset()),
+                map.resolve_import(import_name)
+                    .map(|entry| entry.unwrap().offset()),
+                "resolve_import({import_name:?})"
+            );
+
+            for importing_module in importing_modules.iter().map(|m| Some(*m)).chain([None]) {
+                assert_eq!(
+                    index.resolve(import_name, importing_module),
+                    map.resolve(import_name, importing_module)
+                        .map(|host| host.unwrap()),
+                    "resolve({import_name:?}, {importing_module:?})"
+                );
+            }
+        }
+    }
+
+    /// Returns every Namespace Entry name of `map` with some variations as imported DLL names, plus misses.
+    fn import_names(map: &ApiSetMap, prefix: &str) -> Vec<String> {
+        let mut import_names = Vec::new();
+
+        for namespace_entry in map.namespace_entries().unwrap() {
+            let name = format!("{prefix}{}", namespace_entry.name().unwrap());
+            import_names.push(format!("{name}.dll"));
+            import_names.push(format!("{}.DLL", name.to_ascii_uppercase()));
+            import_names.push(name.clone());
+
+            // A different version, which only resolves via the hashed name prefix.
+            let (base, _) = name.rsplit_once('-').unwrap();
+            import_names.push(format!("{base}-9.dll"));
+            import_names.push(format!("{name}-missing.dll"));
+        }
+
+        import_names.push("api-ms-win-core-missing-l1-1-0.dll".into());
+        import_names
+    }
+
+    #[test]
+    fn test_agrees_with_map() {
+        let bytes = ApiSetMapBuilder::new()
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "api-ms-win-core-foo-l1-1-0",
+                    ApiSetNamespaceEntryFlags::SEALED,
+                )
+                .add_value_entry("", "foo.dll")
+                .add_value_entry("kernel32.dll", "kernelbase.dll"),
+            )
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "api-ms-win-core-foo-l1-2-0",
+                    ApiSetNamespaceEntryFlags::SEALED,
+                )
+                .add_value_entry("", "foo2.dll"),
+            )
+            .add_namespace_entry(ApiSetNamespaceEntryBuilder::new(
+                "ext-ms-win-bar-l1-1-0",
+                ApiSetNamespaceEntryFlags::empty(),
+            ))
+            .build()
+            .unwrap();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+        check_agreement(
+            &map,
+            &import_names(&map, ""),
+            &["kernel32.dll", "KERNEL32.DLL", "foo.dll"],
+        );
+    }
+
+    #[test]
+    fn test_agrees_with_legacy_map() {
+        for version in [2, 4] {
+            let bytes = build_legacy_map(
+                version,
+                &[
+                    ("ms-win-bar-l1-1-0", &[]),
+                    (
+                        "ms-win-core-foo-l1-1-0",
+                        &[("", "foo.dll"), ("kernel32.dll", "kernelbase.dll")],
+                    ),
+                ],
+            );
+            let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+            let mut import_names = import_names(&map, "");
+            import_names.extend(self::import_names(&map, "api-"));
+            import_names.extend(self::import_names(&map, "ext-"));
+            check_agreement(&map, &import_names, &["kernel32.dll", "KERNEL32.DLL"]);
+        }
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[test]
+    fn test_agrees_with_synthetic_map() {
+        let bytes = crate::synthetic::synthetic_map(1);
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+        check_agreement(
+            &map,
+            &import_names(&map, ""),
+            &["kernel32.dll", "kernelbase.dll"],
+        );
+    }
+}
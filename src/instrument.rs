@@ -0,0 +1,144 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Counters reported via the `metrics` crate.
+// Keep their names in sync with the "Metrics" section of the crate documentation.
+
+use crate::error::Result;
+
+pub(crate) fn record_hash_collision() {
+    ::metrics::counter!("nt_apiset_hash_collisions_total").increment(1);
+}
+
+pub(crate) fn record_lookup<T>(result: &Option<Result<T>>) {
+    ::metrics::counter!("nt_apiset_lookups_total").increment(1);
+
+    let outcome = match result {
+        Some(Ok(_)) => "nt_apiset_lookup_hits_total",
+        Some(Err(_)) => "nt_apiset_lookup_errors_total",
+        None => "nt_apiset_lookup_misses_total",
+    };
+    ::metrics::counter!(outcome).increment(1);
+}
+
+pub(crate) fn record_parse<T>(result: &Result<T>) {
+    if result.is_err() {
+        ::metrics::counter!("nt_apiset_parse_failures_total").increment(1);
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use alloc::vec::Vec;
+
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+    use crate::coverage::CoverageMap;
+    use crate::map::ApiSetMap;
+    use crate::mem_read::{MemRead, MemReadError};
+    use crate::namespace_entry::ApiSetNamespaceEntryFlags;
+
+    fn counter(snapshotter: &Snapshotter, name: &str) -> u64 {
+        snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .find_map(|(key, _, _, value)| match value {
+                DebugValue::Counter(value) if key.key().name() == name => Some(value),
+                _ => None,
+            })
+            .unwrap_or(0)
+    }
+
+    /// Memory that only contains `bytes` at address 0.
+    struct Memory(Vec<u8>);
+
+    impl MemRead for Memory {
+        fn read(&self, va: u64, buf: &mut [u8]) -> core::result::Result<(), MemReadError> {
+            let bytes = self
+                .0
+                .get(va as usize..va as usize + buf.len())
+                .ok_or(MemReadError::Unreadable)?;
+            buf.copy_from_slice(bytes);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_lookups() {
+        let bytes = ApiSetMapBuilder::new()
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "api-ms-win-core-foo-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                )
+                .add_value_entry("", "foo.dll"),
+            )
+            .build()
+            .unwrap();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+
+        metrics::with_local_recorder(&recorder, || {
+            assert!(map
+                .find_namespace_entry("api-ms-win-core-foo-l1-1-0")
+                .is_some());
+            assert!(map
+                .find_namespace_entry("api-ms-win-core-bar-l1-1-0")
+                .is_none());
+            assert!(map
+                .resolve_import("api-ms-win-core-foo-l1-1-0.dll")
+                .is_some());
+
+            let mut coverage = CoverageMap::wrap(&map);
+            assert!(coverage
+                .find_namespace_entry("api-ms-win-core-foo-l1-1-0")
+                .is_some());
+            assert!(coverage
+                .find_namespace_entry("api-ms-win-core-qux-l1-1-0")
+                .is_none());
+        });
+
+        assert_eq!(counter(&snapshotter, "nt_apiset_lookups_total"), 5);
+        assert_eq!(counter(&snapshotter, "nt_apiset_lookup_hits_total"), 3);
+        assert_eq!(counter(&snapshotter, "nt_apiset_lookup_misses_total"), 2);
+        assert_eq!(counter(&snapshotter, "nt_apiset_lookup_errors_total"), 0);
+        assert_eq!(counter(&snapshotter, "nt_apiset_parse_failures_total"), 0);
+    }
+
+    #[test]
+    fn test_parse_failures() {
+        let bytes = ApiSetMapBuilder::new().build().unwrap();
+
+        // A header declaring a size smaller than itself passes the header parsing, but fails afterwards.
+        let mut undersized = bytes.clone();
+        undersized[4..8].copy_from_slice(&4u32.to_le_bytes());
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+
+        metrics::with_local_recorder(&recorder, || {
+            ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+            ApiSetMap::try_from_apiset_section_bytes(&[]).unwrap_err();
+            assert_eq!(counter(&snapshotter, "nt_apiset_parse_failures_total"), 1);
+
+            ApiSetMap::read_from_memory(&Memory(bytes.clone()), 0).unwrap();
+            ApiSetMap::read_from_memory(&Memory(undersized.clone()), 0).unwrap_err();
+            assert_eq!(counter(&snapshotter, "nt_apiset_parse_failures_total"), 2);
+
+            #[cfg(feature = "raw-pointer")]
+            #[allow(unsafe_code)]
+            {
+                // SAFETY: Both pointers are valid for reads of the declared size.
+                unsafe {
+                    ApiSetMap::try_from_ptr(bytes.as_ptr()).unwrap();
+                    ApiSetMap::try_from_ptr(undersized.as_ptr()).unwrap_err();
+                }
+                assert_eq!(counter(&snapshotter, "nt_apiset_parse_failures_total"), 3);
+            }
+        });
+    }
+}
@@ -1,7 +1,10 @@
 // Copyright 2023 Colin Finck <colin@reactos.org>
 // SPDX-License-Identifier: MIT OR Apache-2.0
 //
-//! A parser for API Set Map files of Windows 10 and later.
+//! A parser for API Set Map files of Windows 7 and later.
+//!
+//! The API Set Map versions 2 (Windows 7), 4 (Windows 8.1), and 6 (Windows 10 and later) are supported.
+//! Version 3 of Windows 8 is not supported.
 //!
 //! API Sets are dependencies of PE executables whose names start with "api-" or "ext-", e.g. `api-ms-win-core-sysinfo-l1-1-0`.
 //! They don't exist as real DLL files.
@@ -25,29 +28,293 @@
 //!     .find_namespace_entry("api-ms-win-core-sysinfo-l1-1-0")
 //!     .unwrap()
 //!     .unwrap();
-//! let value_entry = namespace_entry.value_entries().unwrap().next().unwrap();
 //!
-//! let name = namespace_entry.name().unwrap();
-//! let default_value = value_entry.value().unwrap();
-//! println!("{name} -> {default_value}");
+//! // The first value entry holds the default mapping.
+//! // API Sets without any mapping have no value entries at all.
+//! if let Some(value_entry) = namespace_entry.value_entries().unwrap().next() {
+//!     let name = namespace_entry.name().unwrap();
+//!     let default_value = value_entry.value().unwrap();
+//!     println!("{name} -> {default_value}");
+//! }
 //! ```
+//!
+//! To resolve an import the same way the Windows loader does, including host-specific mappings, use [`ApiSetMap::resolve`].
+//! This example uses the synthetic API Set Map of the `embedded-map` feature, but works the same for a real one:
+//!
+//! ```
+//! # #[cfg(feature = "embedded-map")]
+//! # {
+//! let map = nt_apiset::embedded_map();
+//!
+//! // The default host of this API Set.
+//! let host = map
+//!     .resolve("api-ms-win-core-sysinfo10-l1-1-0.dll", None)
+//!     .unwrap()
+//!     .unwrap();
+//! assert_eq!(host, "sysinfo.dll");
+//!
+//! // The host module itself imports the API Set from a different host.
+//! let host = map
+//!     .resolve("api-ms-win-core-sysinfo10-l1-1-0.dll", Some("sysinfo.dll"))
+//!     .unwrap()
+//!     .unwrap();
+//! assert_eq!(host, "kernel32.dll");
+//! # }
+//! ```
+//!
+//! # Metrics
+//!
+//! With the `metrics` feature enabled, lookups and parsing are instrumented via the [`metrics`](https://docs.rs/metrics) crate.
+//! The following counters are reported to the installed recorder:
+//!
+//! | Counter | Incremented on |
+//! |---------|----------------|
+//! | `nt_apiset_lookups_total` | Every call to [`ApiSetMap::find_namespace_entry`], [`ApiSetMap::resolve_import`], [`ApiSetMap::resolve`], or their [`CoverageMap`] counterparts |
+//! | `nt_apiset_lookup_hits_total` | A lookup that found the namespace entry |
+//! | `nt_apiset_lookup_misses_total` | A lookup that found no namespace entry |
+//! | `nt_apiset_lookup_errors_total` | A lookup that hit a malformed entry |
+//! | `nt_apiset_hash_collisions_total` | A lookup whose hash matched an entry with a different name |
+//! | `nt_apiset_parse_failures_total` | A failed call to [`ApiSetMap::try_from_apiset_section_bytes`], [`ApiSetMap::try_from_ptr`], or [`ApiSetMap::read_from_memory`], counted once per call |
+//!
+//! Without the feature, no instrumentation code is compiled in.
+//!
+//! # Fallible Allocation
+//!
+//! Every API that is available without the `alloc` feature is free of allocations entirely.
+//! This covers parsing via [`ApiSetMap::try_from_apiset_section_bytes`], lookups via [`ApiSetMap::find_namespace_entry`]
+//! and [`ApiSetMap::resolve`], and iterating over Namespace Entries, Value Entries, and Hash Entries.
+//!
+//! The following APIs allocate, but return [`NtApiSetError::AllocationFailed`] instead of aborting if an allocation fails:
+//!
+//! * [`ApiSetMap::try_to_owned_map`]
+//! * [`ApiSetMap::try_build_index`]
+//! * [`ApiSetMap::read_from_memory`]
+//!
+//! All other APIs of the `alloc` feature, including [`ApiSetMap::to_owned_map`] and [`ApiSetMap::build_index`],
+//! abort on allocation failure like the collections of the standard library.
+//!
+//! # Raw Pointers
+//!
+//! This crate doesn't contain any unsafe code by default.
+//! The `raw-pointer` feature adds [`ApiSetMap::try_from_ptr`] to parse an API Set Map in memory,
+//! and on Windows also [`ApiSetMap::try_from_current_process`] to parse the API Set Map the loader has mapped into the current process.
+//!
+//! # Synthetic API Set Maps
+//!
+//! The `test-utils` feature adds [`synthetic_map`] and [`synthetic_map_builder`] to generate realistic API Set Maps
+//! from a seed.
+//! They are used by the benchmarks of this crate and can be used by your own tests, without requiring any Microsoft binaries.
+//!
+//! # Embedded API Set Map
+//!
+//! The `embedded-map` feature adds [`embedded_map`], which returns a built-in API Set Map for offline tools and examples.
+//! It is a compressed synthetic map generated the same way as by [`synthetic_map`], so it doesn't match any Windows release.
+//!
+//! # Hot Reloading
+//!
+//! Long-running services can keep an [`ApiSetMapCell`] (`std` and `pelite` features) to pick up an updated API Set Map file
+//! without restarting.
+//! It hands out the current API Set Map as an [`ApiSetResolver`] and can watch the file for changes.
+//!
+//! # Thread Safety
+//!
+//! All types of this crate are `Send` and `Sync`.
+//! An [`ApiSetMap`] only borrows the section bytes and never mutates any state during lookups,
+//! so it can be shared between any number of threads without locking.
+//! Any caching layer added to this crate must uphold these guarantees and must not introduce a global lock
+//! on the lookup path.
+//! This is checked at compile time.
 
 #![cfg_attr(not(feature = "std"), no_std)]
 #![cfg_attr(docsrs, feature(doc_cfg))]
-#![forbid(unsafe_code)]
+#![cfg_attr(not(feature = "raw-pointer"), forbid(unsafe_code))]
+#![cfg_attr(feature = "raw-pointer", deny(unsafe_code))]
 #![warn(missing_docs)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
 #[macro_use]
 mod helpers;
 
+#[cfg(feature = "alloc")]
+mod builder;
+#[cfg(all(feature = "std", feature = "pelite"))]
+mod cell;
+#[cfg(feature = "alloc")]
+mod control;
+#[cfg(feature = "alloc")]
+mod coverage;
+mod cursor;
+#[cfg(feature = "std")]
+mod dual_arch;
+#[cfg(feature = "embedded-map")]
+mod embedded;
 mod error;
+mod extension;
+#[cfg(all(test, feature = "alloc"))]
+mod fixtures;
 mod hash_entry;
+#[cfg(feature = "alloc")]
+mod hexdump;
+mod host;
+#[cfg(feature = "alloc")]
+mod index;
+#[cfg(feature = "metrics")]
+mod instrument;
 mod map;
+mod mappings;
+#[cfg(feature = "alloc")]
+mod mem_read;
+#[cfg(feature = "alloc")]
+mod min_version;
 mod namespace_entry;
+#[cfg(feature = "alloc")]
+mod overlay;
+#[cfg(feature = "alloc")]
+mod owned;
+#[cfg(all(feature = "alloc", feature = "pelite"))]
+mod pe_ext;
+#[cfg(feature = "raw-pointer")]
+mod raw_pointer;
+#[cfg(feature = "alloc")]
+mod repair;
+mod resolver;
+mod schema;
+mod search;
+#[cfg(feature = "self-test")]
+mod self_test;
+#[cfg(all(feature = "alloc", feature = "serde"))]
+mod snapshot;
+#[cfg(feature = "test-utils")]
+mod synthetic;
+mod truncation;
+#[cfg(feature = "alloc")]
+mod validate;
 mod value_entry;
+mod visit;
 
+#[cfg(feature = "alloc")]
+pub use builder::*;
+#[cfg(all(feature = "std", feature = "pelite"))]
+pub use cell::*;
+#[cfg(feature = "alloc")]
+pub use control::*;
+#[cfg(feature = "alloc")]
+pub use coverage::*;
+pub use cursor::*;
+#[cfg(feature = "std")]
+pub use dual_arch::*;
+#[cfg(feature = "embedded-map")]
+pub use embedded::*;
 pub use error::*;
+pub use extension::*;
 pub use hash_entry::*;
+#[cfg(feature = "alloc")]
+pub use hexdump::*;
+pub use host::*;
+#[cfg(feature = "alloc")]
+pub use index::*;
 pub use map::*;
+pub use mappings::*;
+#[cfg(feature = "alloc")]
+pub use mem_read::*;
+#[cfg(feature = "alloc")]
+pub use min_version::*;
 pub use namespace_entry::*;
+#[cfg(feature = "alloc")]
+pub use overlay::*;
+#[cfg(feature = "alloc")]
+pub use owned::*;
+#[cfg(all(feature = "alloc", feature = "pelite"))]
+pub use pe_ext::*;
+pub use resolver::*;
+pub use search::*;
+#[cfg(feature = "self-test")]
+pub use self_test::*;
+#[cfg(all(feature = "alloc", feature = "serde"))]
+pub use snapshot::*;
+#[cfg(feature = "test-utils")]
+pub use synthetic::*;
+pub use truncation::*;
+#[cfg(feature = "alloc")]
+pub use validate::*;
 pub use value_entry::*;
+pub use visit::*;
+
+/// Compile-time check that all public types remain `Send` and `Sync`.
+#[allow(dead_code)]
+fn assert_send_sync() {
+    fn assert<T: Send + Sync>() {}
+
+    assert::<ApiSetCursor>();
+    assert::<ApiSetHashEntries>();
+    assert::<ApiSetHashEntry>();
+    assert::<ApiSetHashJoinedEntries>();
+    assert::<ApiSetHostMatches>();
+    assert::<ApiSetMap>();
+    assert::<ApiSetMapFlags>();
+    assert::<ApiSetMappingCursor>();
+    assert::<ApiSetMappings>();
+    assert::<ApiSetMapView>();
+    assert::<ApiSetNamespaceEntries>();
+    assert::<ApiSetNamespaceEntry>();
+    assert::<ApiSetNamespaceEntryFlags>();
+    assert::<ApiSetPrefixMatches>();
+    assert::<ApiSetSource>();
+    assert::<ApiSetSourcedEntry>();
+    assert::<ApiSetStats>();
+    assert::<ApiSetSubstringMatches>();
+    assert::<ApiSetTryEntries<ApiSetNamespaceEntries>>();
+    assert::<ApiSetValueEntries>();
+    assert::<ApiSetValueEntry>();
+    assert::<ApiSetValueEntryFlags>();
+    assert::<NtApiSetError>();
+
+    #[cfg(feature = "alloc")]
+    {
+        assert::<ApiSetIndex>();
+        assert::<ApiSetMapBuilder>();
+        assert::<ApiSetMerge>();
+        assert::<ApiSetMergeChange>();
+        assert::<ApiSetMergeChangeKind>();
+        assert::<ApiSetNamespaceEntryBuilder>();
+        assert::<BuildReport<alloc::string::String>>();
+        assert::<CoverageMap>();
+        assert::<CoveredNamespaceEntries>();
+        assert::<CoveredValueEntries>();
+        assert::<HexdumpGutter>();
+        assert::<HexdumpOptions>();
+        assert::<MemReadError>();
+        assert::<MinVersionReport<alloc::string::String>>();
+        assert::<OpControl>();
+        assert::<OverlaidResolver>();
+        assert::<OverlayPrecedence>();
+        assert::<OwnedApiSetMap>();
+        assert::<ProgressEvent>();
+        assert::<RedirectOverlay>();
+        assert::<RedirectRule>();
+        assert::<RedirectRuleKind>();
+        assert::<ValidationInfo>();
+    }
+
+    #[cfg(feature = "self-test")]
+    assert::<SelfTestReport>();
+
+    #[cfg(feature = "std")]
+    {
+        assert::<Architecture>();
+        assert::<DualArchFinding>();
+        assert::<DualArchSchema>();
+    }
+
+    #[cfg(all(feature = "std", feature = "pelite"))]
+    assert::<ApiSetMapCell>();
+
+    #[cfg(all(feature = "alloc", feature = "serde"))]
+    {
+        assert::<ApiSetMapSnapshot>();
+        assert::<ApiSetNamespaceEntrySnapshot>();
+        assert::<ApiSetValueEntrySnapshot>();
+    }
+}
@@ -3,33 +3,123 @@
 
 use core::cmp::Ordering;
 use core::mem;
+use core::ops::Range;
 
-use bitflags::bitflags;
-use zerocopy::{FromBytes, LayoutVerified, LittleEndian, Unaligned, U32};
+use nt_string::u16strle::U16StrLe;
+use zerocopy::{AsBytes, FromBytes, LayoutVerified, LittleEndian, Unaligned, U32};
 
 use crate::error::{NtApiSetError, Result};
-use crate::hash_entry::{ApiSetHashEntries, ApiSetHashEntryHeader};
+use crate::hash_entry::{ApiSetHashEntries, ApiSetHashEntryHeader, ApiSetHashJoinedEntries};
+use crate::helpers::{
+    clamp_entry_array, cmp_ignore_ascii_case, cmp_ignore_ascii_case_str, entry_array_end,
+    eq_ignore_ascii_case_str, hash_name, starts_with_ignore_ascii_case_str,
+    strip_prefix_ignore_ascii_case, strip_suffix_ignore_ascii_case,
+};
 use crate::namespace_entry::{
     ApiSetNamespaceEntries, ApiSetNamespaceEntry, ApiSetNamespaceEntryHeader,
 };
+use crate::schema::Schema;
+
+#[allow(dead_code)]
+#[derive(AsBytes, Debug, FromBytes, Unaligned)]
+#[repr(packed)]
+pub(crate) struct ApiSetMapHeader {
+    pub(crate) version: U32<LittleEndian>,
+    pub(crate) size: U32<LittleEndian>,
+    /// See [`ApiSetMapFlags`]
+    pub(crate) flags: U32<LittleEndian>,
+    pub(crate) count: U32<LittleEndian>,
+    pub(crate) namespace_entry_offset: U32<LittleEndian>,
+    pub(crate) hash_entry_offset: U32<LittleEndian>,
+    pub(crate) hash_factor: U32<LittleEndian>,
+}
+
+#[derive(Debug, FromBytes, Unaligned)]
+#[repr(packed)]
+pub(crate) struct ApiSetMapHeaderV2 {
+    version: U32<LittleEndian>,
+    count: U32<LittleEndian>,
+}
 
 #[allow(dead_code)]
 #[derive(Debug, FromBytes, Unaligned)]
 #[repr(packed)]
-struct ApiSetMapHeader {
+pub(crate) struct ApiSetMapHeaderV4 {
     version: U32<LittleEndian>,
     size: U32<LittleEndian>,
     /// See [`ApiSetMapFlags`]
     flags: U32<LittleEndian>,
     count: U32<LittleEndian>,
-    namespace_entry_offset: U32<LittleEndian>,
-    hash_entry_offset: U32<LittleEndian>,
-    hash_factor: U32<LittleEndian>,
 }
 
-const APISET_VERSION_WINDOWS_10: u32 = 6;
+/// Fields of an API Set Map header, independent of the [`Schema`] it has been read from.
+#[derive(Clone, Copy, Debug)]
+struct MapFields {
+    version: u32,
+    /// Always 0 in version 2.
+    size: u32,
+    flags: u32,
+    count: u32,
+    namespace_entry_offset: u32,
+    /// Always 0 before version 6.
+    hash_entry_offset: u32,
+    /// Always 0 before version 6.
+    hash_factor: u32,
+}
+
+impl MapFields {
+    fn read(schema: Schema, bytes: &[u8]) -> Option<Self> {
+        // Before version 6, the Namespace Entries directly follow the header.
+        let fields = match schema {
+            Schema::V2 => {
+                let (header, _) =
+                    LayoutVerified::<_, ApiSetMapHeaderV2>::new_unaligned_from_prefix(bytes)?;
+
+                Self {
+                    version: header.version.get(),
+                    size: 0,
+                    flags: 0,
+                    count: header.count.get(),
+                    namespace_entry_offset: schema.map_header_size() as u32,
+                    hash_entry_offset: 0,
+                    hash_factor: 0,
+                }
+            }
+            Schema::V4 => {
+                let (header, _) =
+                    LayoutVerified::<_, ApiSetMapHeaderV4>::new_unaligned_from_prefix(bytes)?;
+
+                Self {
+                    version: header.version.get(),
+                    size: header.size.get(),
+                    flags: header.flags.get(),
+                    count: header.count.get(),
+                    namespace_entry_offset: schema.map_header_size() as u32,
+                    hash_entry_offset: 0,
+                    hash_factor: 0,
+                }
+            }
+            Schema::V6 => {
+                let (header, _) =
+                    LayoutVerified::<_, ApiSetMapHeader>::new_unaligned_from_prefix(bytes)?;
+
+                Self {
+                    version: header.version.get(),
+                    size: header.size.get(),
+                    flags: header.flags.get(),
+                    count: header.count.get(),
+                    namespace_entry_offset: header.namespace_entry_offset.get(),
+                    hash_entry_offset: header.hash_entry_offset.get(),
+                    hash_factor: header.hash_factor.get(),
+                }
+            }
+        };
+
+        Some(fields)
+    }
+}
 
-bitflags! {
+flags! {
     /// Flags returned by [`ApiSetMap::flags`].
     pub struct ApiSetMapFlags: u32 {
         /// This API Set Map is sealed, meaning the loader shall not look for schema extensions.
@@ -39,85 +129,356 @@ bitflags! {
     }
 }
 
+/// Location of the `.apiset` section within the PE file it has been read from.
+///
+/// This is captured by [`ApiSetMap::try_from_pe64`] and returned by [`ApiSetMap::section_location`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SectionLocation {
+    virtual_address: u32,
+    pointer_to_raw_data: u32,
+    size_of_raw_data: u32,
+}
+
+impl SectionLocation {
+    /// Creates a [`SectionLocation`] from the respective fields of the section header.
+    pub const fn new(
+        virtual_address: u32,
+        pointer_to_raw_data: u32,
+        size_of_raw_data: u32,
+    ) -> Self {
+        Self {
+            virtual_address,
+            pointer_to_raw_data,
+            size_of_raw_data,
+        }
+    }
+
+    /// Returns the file offset of the section data (`PointerToRawData`).
+    pub const fn pointer_to_raw_data(&self) -> u32 {
+        self.pointer_to_raw_data
+    }
+
+    /// Returns the size of the section data in the file (`SizeOfRawData`).
+    pub const fn size_of_raw_data(&self) -> u32 {
+        self.size_of_raw_data
+    }
+
+    /// Returns the RVA of the section (`VirtualAddress`).
+    pub const fn virtual_address(&self) -> u32 {
+        self.virtual_address
+    }
+}
+
 /// Root structure describing an API Set Map.
+///
+/// API Set Maps of versions 2 (Windows 7), 4 (Windows 8.1), and 6 (Windows 10 and later) are supported.
+/// Version 3 (Windows 8) is rejected with [`NtApiSetError::UnsupportedVersion`].
+/// See [`version`](Self::version) for the differences.
 #[derive(Debug)]
 pub struct ApiSetMap<'a> {
     section_bytes: &'a [u8],
-    header: LayoutVerified<&'a [u8], ApiSetMapHeader>,
+    schema: Schema,
+    header: MapFields,
+    section_location: Option<SectionLocation>,
+    /// Clamp the entry arrays to the section instead of returning an error, see [`ApiSetMap::try_from_apiset_section_bytes_lenient`].
+    lenient: bool,
 }
 
 impl<'a> ApiSetMap<'a> {
+    /// Returns the number of Namespace Entries (and Hash Entries) as declared by the header of this API Set Map.
+    ///
+    /// This is the raw header field.
+    /// [`namespace_entries`](Self::namespace_entries) checks it against the size of the `.apiset` section.
+    pub const fn count(&self) -> u32 {
+        self.header.count
+    }
+
     /// Returns flags set for this [`ApiSetMap`] as specified by [`ApiSetMapFlags`].
+    ///
+    /// Bits unknown to this crate are retained and can be inspected via [`ApiSetMapFlags::bits`].
     pub fn flags(&self) -> ApiSetMapFlags {
-        ApiSetMapFlags::from_bits_truncate(self.header.flags.get())
+        ApiSetMapFlags::from_bits_retain(self.header.flags)
     }
 
     /// Finds a namespace entry efficiently in the hash table of the API Set Map.
     ///
-    /// `namespace_entry_name` must be non-empty and only consist of lowercase characters, digits, and hyphens.
+    /// `namespace_entry_name` must be non-empty and only consist of ASCII letters, digits, and hyphens.
     /// This is asserted in debug builds.
+    /// Letters are compared case-insensitively.
     /// If you fail to adhere to these requirements in release builds, the lookup will be performed anyway and return `None`.
+    ///
+    /// API Set Maps before version 6 have no hash table.
+    /// For them, a binary search over the names of the sorted Namespace Entries is performed instead,
+    /// and names without their "api-" or "ext-" prefix are found as well.
     pub fn find_namespace_entry(
         &self,
         namespace_entry_name: &str,
     ) -> Option<Result<ApiSetNamespaceEntry<'a>>> {
-        debug_assert!(!namespace_entry_name.is_empty());
-        debug_assert!(namespace_entry_name
-            .chars()
-            .all(|x| x.is_ascii_lowercase() || x.is_ascii_digit() || x == '-'));
+        self.find_namespace_entry_traced(namespace_entry_name, &mut |_| {})
+    }
+
+    /// Performs the lookup of [`find_namespace_entry`](Self::find_namespace_entry) and reports every byte range it reads to `trace`.
+    ///
+    /// The public lookup functions pass a no-op closure, which is optimized away.
+    /// Every lookup going through here is counted in the metrics.
+    pub(crate) fn find_namespace_entry_traced<F>(
+        &self,
+        namespace_entry_name: &str,
+        trace: &mut F,
+    ) -> Option<Result<ApiSetNamespaceEntry<'a>>>
+    where
+        F: FnMut(Range<usize>),
+    {
+        let result = self.search_namespace_entry_traced(namespace_entry_name, trace);
+
+        #[cfg(feature = "metrics")]
+        crate::instrument::record_lookup(&result);
+
+        result
+    }
+
+    fn search_namespace_entry_traced<F>(
+        &self,
+        namespace_entry_name: &str,
+        trace: &mut F,
+    ) -> Option<Result<ApiSetNamespaceEntry<'a>>>
+    where
+        F: FnMut(Range<usize>),
+    {
+        debug_assert_namespace_entry_name(namespace_entry_name);
+
+        if !self.schema.has_hash_table() {
+            return self.find_namespace_entry_by_name_traced(namespace_entry_name, trace);
+        }
 
         // "NTDLL first hashes the supposed name up to but not including the last hyphen"
         let (name_to_hash, _) = namespace_entry_name.rsplit_once('-')?;
+        let hash = self.hash_name(name_to_hash);
+
+        // The hash table only gets us candidates.
+        // Check the name to make absolutely sure.
+        self.find_namespace_entry_by_hash_traced(hash, trace, |namespace_entry, trace| {
+            let name = namespace_entry.name()?;
+            trace(namespace_entry.name_range());
+
+            Ok(eq_ignore_ascii_case_str(&name, namespace_entry_name))
+        })
+    }
+
+    /// Finds a namespace entry in the hash table of the API Set Map, but skips verifying its name.
+    ///
+    /// This is a faster variant of [`find_namespace_entry`](Self::find_namespace_entry) for batch workloads.
+    /// It returns the namespace entry indicated by the hash table without reading and comparing its name.
+    ///
+    /// The result is only trustworthy if this API Set Map has been validated before, `namespace_entry_name` is known to exist in it,
+    /// and no other namespace entry shares its hash (e.g. another minor version of the same API Set).
+    /// For any other name (including one that merely shares its hash with an existing entry), an unrelated namespace entry may be returned.
+    /// Use [`find_namespace_entry`](Self::find_namespace_entry) if you cannot rule that out.
+    ///
+    /// `namespace_entry_name` is subject to the same requirements as for [`find_namespace_entry`](Self::find_namespace_entry).
+    ///
+    /// API Set Maps before version 6 have no hash table, so this is the same as [`find_namespace_entry`](Self::find_namespace_entry) for them.
+    pub fn find_namespace_entry_unverified(
+        &self,
+        namespace_entry_name: &str,
+    ) -> Option<Result<ApiSetNamespaceEntry<'a>>> {
+        self.find_namespace_entry_unverified_traced(namespace_entry_name, &mut |_| {})
+    }
+
+    fn find_namespace_entry_unverified_traced<F>(
+        &self,
+        namespace_entry_name: &str,
+        trace: &mut F,
+    ) -> Option<Result<ApiSetNamespaceEntry<'a>>>
+    where
+        F: FnMut(Range<usize>),
+    {
+        debug_assert_namespace_entry_name(namespace_entry_name);
+
+        if !self.schema.has_hash_table() {
+            return self.find_namespace_entry_by_name_traced(namespace_entry_name, trace);
+        }
+
+        let (name_to_hash, _) = namespace_entry_name.rsplit_once('-')?;
+        let hash = self.hash_name(name_to_hash);
 
-        let hash_factor = self.header.hash_factor.get();
-        let hash = name_to_hash.chars().fold(0u32, |acc, x| {
-            acc.wrapping_mul(hash_factor).wrapping_add(x as u32)
-        });
+        self.find_namespace_entry_by_hash_traced(hash, trace, |_, _| Ok(true))
+    }
 
+    /// Returns the first namespace entry accepted by `is_match` among those pointed to by Hash Entries with the given `hash`.
+    ///
+    /// Different names may share the same hash, so all adjacent Hash Entries with that hash are examined, the same way NTDLL does.
+    /// A Hash Entry that points past the Namespace Entries yields [`NtApiSetError::NamespaceEntryIndexOutOfBounds`].
+    fn find_namespace_entry_by_hash_traced<F, M>(
+        &self,
+        hash: u32,
+        trace: &mut F,
+        mut is_match: M,
+    ) -> Option<Result<ApiSetNamespaceEntry<'a>>>
+    where
+        F: FnMut(Range<usize>),
+        M: FnMut(&ApiSetNamespaceEntry<'a>, &mut F) -> Result<bool>,
+    {
         let hash_entries = iter_try!(self.hash_entries());
-        let mut namespace_entries = iter_try!(self.namespace_entries());
+        let namespace_entries = iter_try!(self.namespace_entries());
+        let hash_entry_size = mem::size_of::<ApiSetHashEntryHeader>();
 
-        // Perform binary search in the sorted array of hash entries.
-        let mut left = 0i64;
-        let mut right = hash_entries.len() as i64 - 1;
-
-        while left <= right {
-            let mid = (left + right) / 2;
-            let hash_entry = hash_entries.clone().nth(mid as usize).unwrap();
-
-            match hash_entry.hash().cmp(&hash) {
-                Ordering::Equal => {
-                    // This must be the entry we are looking for.
-                    // Check the name to make absolutely sure.
-                    let index = hash_entry.index();
-                    let namespace_entry = namespace_entries.nth(index as usize)?;
-                    let name = iter_try!(namespace_entry.name());
-
-                    if name == namespace_entry_name {
-                        return Some(Ok(namespace_entry));
-                    } else {
-                        return None;
-                    }
+        // Perform binary search in the sorted array of hash entries to find the first one with a hash not less than `hash`.
+        // The half-open search range also covers an empty hash table without any special treatment.
+        let mut left = 0;
+        let mut right = hash_entries.len();
+
+        while left < right {
+            let mid = left + (right - left) / 2;
+            let hash_entry = hash_entries.clone().nth(mid)?;
+            let hash_entry_start = hash_entry.offset();
+            trace(hash_entry_start..hash_entry_start + hash_entry_size);
+
+            if hash_entry.hash() < hash {
+                left = mid + 1;
+            } else {
+                right = mid;
+            }
+        }
+
+        for hash_entry in hash_entries.skip(left) {
+            let hash_entry_start = hash_entry.offset();
+            trace(hash_entry_start..hash_entry_start + hash_entry_size);
+
+            if hash_entry.hash() != hash {
+                break;
+            }
+
+            let index = hash_entry.index() as usize;
+            let namespace_entry = match namespace_entries.clone().nth(index) {
+                Some(namespace_entry) => namespace_entry,
+                None => {
+                    return Some(Err(NtApiSetError::NamespaceEntryIndexOutOfBounds {
+                        index,
+                        count: namespace_entries.len(),
+                    }))
                 }
+            };
+            let namespace_entry_start = namespace_entry.offset();
+            trace(
+                namespace_entry_start
+                    ..namespace_entry_start + mem::size_of::<ApiSetNamespaceEntryHeader>(),
+            );
+
+            if iter_try!(is_match(&namespace_entry, trace)) {
+                return Some(Ok(namespace_entry));
+            }
+
+            #[cfg(feature = "metrics")]
+            crate::instrument::record_hash_collision();
+        }
+
+        None
+    }
+
+    /// Finds a namespace entry by performing a binary search over the names of the sorted Namespace Entries.
+    ///
+    /// This is used for API Set Maps without a hash table.
+    /// Names are compared case-insensitively.
+    fn find_namespace_entry_by_name_traced<F>(
+        &self,
+        namespace_entry_name: &str,
+        trace: &mut F,
+    ) -> Option<Result<ApiSetNamespaceEntry<'a>>>
+    where
+        F: FnMut(Range<usize>),
+    {
+        // API Set Maps before version 6 store names without the "api-" or "ext-" prefix.
+        self.binary_search_namespace_entries_traced(namespace_entry_name, trace)
+            .or_else(|| {
+                let name_without_prefix =
+                    strip_prefix_ignore_ascii_case(namespace_entry_name, "api-")
+                        .or_else(|| strip_prefix_ignore_ascii_case(namespace_entry_name, "ext-"))?;
+                self.binary_search_namespace_entries_traced(name_without_prefix, trace)
+            })
+    }
+
+    fn binary_search_namespace_entries_traced<F>(
+        &self,
+        namespace_entry_name: &str,
+        trace: &mut F,
+    ) -> Option<Result<ApiSetNamespaceEntry<'a>>>
+    where
+        F: FnMut(Range<usize>),
+    {
+        let namespace_entries = iter_try!(self.namespace_entries());
+
+        let mut left = 0;
+        let mut right = namespace_entries.len();
+
+        while left < right {
+            let mid = left + (right - left) / 2;
+            let namespace_entry = namespace_entries.clone().nth(mid)?;
+            let namespace_entry_start = namespace_entry.offset();
+            trace(
+                namespace_entry_start..namespace_entry_start + self.schema.namespace_entry_size(),
+            );
+
+            let name = iter_try!(namespace_entry.name());
+            trace(namespace_entry.name_range());
+
+            match cmp_ignore_ascii_case_str(&name, namespace_entry_name) {
+                Ordering::Equal => return Some(Ok(namespace_entry)),
                 Ordering::Less => left = mid + 1,
-                Ordering::Greater => right = mid - 1,
+                Ordering::Greater => right = mid,
             }
         }
 
         None
     }
 
+    /// Returns the byte offset of the Hash Entries within the `.apiset` section, as declared by the header of this API Set Map.
+    ///
+    /// API Set Maps before version 6 have no hash table, so 0 is returned for them.
+    pub const fn hash_entry_offset(&self) -> u32 {
+        self.header.hash_entry_offset
+    }
+
+    /// Returns the factor used to compute the hashes of the Hash Entries of this API Set Map.
+    ///
+    /// All known API Set Maps use `0x1f`.
+    /// API Set Maps before version 6 have no hash table, so 0 is returned for them.
+    pub const fn hash_factor(&self) -> u32 {
+        self.header.hash_factor
+    }
+
+    /// Hashes `name` the same way NTDLL does, using the [`hash_factor`](Self::hash_factor) of this API Set Map.
+    ///
+    /// ASCII letters are converted to lowercase before hashing.
+    /// The entire `name` is hashed.
+    /// Note that lookups only hash the part of an API Set name up to but not including the last hyphen,
+    /// which is the [`ApiSetNamespaceEntry::hashed_name`] of a Namespace Entry.
+    pub fn hash_name(&self, name: &str) -> u32 {
+        hash_name(
+            name.chars().map(|x| u32::from(x.to_ascii_lowercase())),
+            self.header.hash_factor,
+        )
+    }
+
     /// Returns an iterator over the [`ApiSetHashEntry`]s of this [`ApiSetMap`].
     ///
     /// You usually don't need to iterate through the hash entries manually.
     /// Use [`find_namespace_entry`](Self::find_namespace_entry) instead.
     ///
+    /// API Set Maps before version 6 have no hash table, so the returned iterator is empty for them.
+    ///
     /// [`ApiSetHashEntry`]: crate::hash_entry::ApiSetHashEntry
     /// [`ApiSetMap`]: crate::map::ApiSetMap
     pub fn hash_entries(&self) -> Result<ApiSetHashEntries<'a>> {
-        let start = self.header.hash_entry_offset.get() as usize;
-        let count = self.header.count.get() as usize;
-        let end = start + mem::size_of::<ApiSetHashEntryHeader>() * count;
+        let (start, count) = self.hash_entry_array();
+        let entry_size = mem::size_of::<ApiSetHashEntryHeader>();
+
+        if self.lenient {
+            let range = clamp_entry_array(start, entry_size, count, self.section_bytes.len());
+            return Ok(ApiSetHashEntries::new(self.section_bytes, range));
+        }
+
+        let end = entry_array_end(start, entry_size, count);
         let range = start..end;
 
         self.section_bytes
@@ -130,13 +491,65 @@ impl<'a> ApiSetMap<'a> {
         Ok(ApiSetHashEntries::new(self.section_bytes, range))
     }
 
+    /// Returns the start offset and the declared number of entries of the Hash Entry array.
+    ///
+    /// API Set Maps before version 6 have no hash table, so an empty array is returned for them.
+    pub(crate) const fn hash_entry_array(&self) -> (usize, usize) {
+        if self.schema.has_hash_table() {
+            (
+                self.header.hash_entry_offset as usize,
+                self.header.count as usize,
+            )
+        } else {
+            (0, 0)
+        }
+    }
+
+    /// Returns an iterator over the [`ApiSetHashEntry`]s of this [`ApiSetMap`] along with the [`ApiSetNamespaceEntry`] each of them points to.
+    ///
+    /// This is useful for auditing the hash table.
+    /// In contrast to iterating [`hash_entries`](Self::hash_entries) and [`namespace_entries`](Self::namespace_entries) separately,
+    /// you don't need to resolve the [`ApiSetHashEntry::index`] yourself.
+    /// A hash entry with an out-of-range index is returned as an error item.
+    ///
+    /// [`ApiSetHashEntry`]: crate::hash_entry::ApiSetHashEntry
+    /// [`ApiSetHashEntry::index`]: crate::hash_entry::ApiSetHashEntry::index
+    pub fn hash_joined(&self) -> Result<ApiSetHashJoinedEntries<'a>> {
+        let hash_entries = self.hash_entries()?;
+        let namespace_entries = self.namespace_entries()?;
+
+        Ok(ApiSetHashJoinedEntries::new(
+            hash_entries,
+            namespace_entries,
+        ))
+    }
+
+    /// Returns the byte offset of the Namespace Entries within the `.apiset` section.
+    ///
+    /// For version 6, this is the raw header field.
+    /// Before version 6, the Namespace Entries directly follow the header, so the header size is returned.
+    pub const fn namespace_entry_offset(&self) -> u32 {
+        self.header.namespace_entry_offset
+    }
+
     /// Returns an iterator over the [`ApiSetNamespaceEntry`] elements of this [`ApiSetMap`].
     ///
     /// Alternatively, you can lookup a specific namespace entry via the [`find_namespace_entry`](Self::find_namespace_entry) method.
     pub fn namespace_entries(&self) -> Result<ApiSetNamespaceEntries<'a>> {
-        let start = self.header.namespace_entry_offset.get() as usize;
-        let count = self.header.count.get() as usize;
-        let end = start + mem::size_of::<ApiSetNamespaceEntryHeader>() * count;
+        let (start, count) = self.namespace_entry_array();
+        let entry_size = self.schema.namespace_entry_size();
+
+        if self.lenient {
+            let range = clamp_entry_array(start, entry_size, count, self.section_bytes.len());
+            return Ok(ApiSetNamespaceEntries::new(
+                self.section_bytes,
+                self.schema,
+                range,
+                self.lenient,
+            ));
+        }
+
+        let end = entry_array_end(start, entry_size, count);
         let range = start..end;
 
         self.section_bytes.get(range.clone()).ok_or(
@@ -146,7 +559,303 @@ impl<'a> ApiSetMap<'a> {
             },
         )?;
 
-        Ok(ApiSetNamespaceEntries::new(self.section_bytes, range))
+        Ok(ApiSetNamespaceEntries::new(
+            self.section_bytes,
+            self.schema,
+            range,
+            self.lenient,
+        ))
+    }
+
+    /// Returns the start offset and the declared number of entries of the Namespace Entry array.
+    pub(crate) const fn namespace_entry_array(&self) -> (usize, usize) {
+        (
+            self.header.namespace_entry_offset as usize,
+            self.header.count as usize,
+        )
+    }
+
+    /// Returns the [`ApiSetNamespaceEntry`] at `index`, or `None` if there is no such entry.
+    ///
+    /// This is the same as `namespace_entries()?.nth(index)`.
+    /// It is useful for dereferencing the [`ApiSetHashEntry::index`] of a Hash Entry.
+    ///
+    /// [`ApiSetHashEntry::index`]: crate::hash_entry::ApiSetHashEntry::index
+    pub fn namespace_entry(&self, index: usize) -> Option<Result<ApiSetNamespaceEntry<'a>>> {
+        let mut namespace_entries = iter_try!(self.namespace_entries());
+        namespace_entries.nth(index).map(Ok)
+    }
+
+    /// Returns an iterator over the [`ApiSetNamespaceEntry`] elements of this [`ApiSetMap`] at the given `index_range`.
+    ///
+    /// This is useful for paginating over the Namespace Entries.
+    /// The total number of entries is returned by `namespace_entries()?.len()`.
+    ///
+    /// An `index_range` that is inverted or exceeds the number of entries is not clamped.
+    /// Instead, [`NtApiSetError::EntryIndexRangeOutOfBounds`] is returned.
+    pub fn namespace_entries_range(
+        &self,
+        index_range: Range<usize>,
+    ) -> Result<ApiSetNamespaceEntries<'a>> {
+        self.namespace_entries()?.restrict(index_range)
+    }
+
+    /// Returns whether this API Set Map has been created by [`try_from_apiset_section_bytes_lenient`](Self::try_from_apiset_section_bytes_lenient).
+    pub const fn is_lenient(&self) -> bool {
+        self.lenient
+    }
+
+    /// Checks whether every Namespace Entry of this API Set Map also exists with the same mappings in `other`.
+    ///
+    /// See [`semantic_subset_counterexample`](Self::semantic_subset_counterexample) for details.
+    pub fn is_semantic_subset_of(&self, other: &ApiSetMap) -> Result<bool> {
+        self.semantic_subset_counterexample(other)
+            .map(|counterexample| counterexample.is_none())
+    }
+
+    /// Returns the location of the `.apiset` section within its PE file.
+    ///
+    /// This is only known if the [`ApiSetMap`] has been created via [`try_from_pe64`](Self::try_from_pe64)
+    /// or the location has been set via [`set_section_location`](Self::set_section_location).
+    pub const fn section_location(&self) -> Option<SectionLocation> {
+        self.section_location
+    }
+
+    /// Returns the raw bytes of the `.apiset` section this [`ApiSetMap`] has been created from.
+    pub const fn section_bytes(&self) -> &'a [u8] {
+        self.section_bytes
+    }
+
+    /// Resolves an imported API Set to the name of its host DLL, the same way the Windows loader does.
+    ///
+    /// `apiset_name` is looked up via [`resolve_import`](Self::resolve_import), so it may be taken directly from an import table.
+    /// The Value Entry is then picked via [`ApiSetNamespaceEntry::resolve_value_entry`] for the given `importing_module`,
+    /// falling back to the default mapping.
+    ///
+    /// `None` is returned if there is no such API Set or it has no mapping at all.
+    /// Note that the default mapping may still be an empty string for API Sets that are not implemented.
+    pub fn resolve(
+        &self,
+        apiset_name: &str,
+        importing_module: Option<&str>,
+    ) -> Option<Result<U16StrLe<'a>>> {
+        self.resolve_traced(apiset_name, importing_module, &mut |_| {})
+    }
+
+    /// Performs the lookup of [`resolve`](Self::resolve) and reports every byte range it reads to `trace`.
+    pub(crate) fn resolve_traced<F>(
+        &self,
+        apiset_name: &str,
+        importing_module: Option<&str>,
+        trace: &mut F,
+    ) -> Option<Result<U16StrLe<'a>>>
+    where
+        F: FnMut(Range<usize>),
+    {
+        let namespace_entry = iter_try!(self.resolve_import_traced(apiset_name, trace)?);
+        let value_entry =
+            iter_try!(namespace_entry.resolve_value_entry_traced(importing_module, trace)?);
+        let value = iter_try!(value_entry.value());
+        trace(value_entry.value_range());
+
+        Some(Ok(value))
+    }
+
+    /// Resolves the name of an imported DLL to its namespace entry, the same way the Windows loader does.
+    ///
+    /// In contrast to [`find_namespace_entry`](Self::find_namespace_entry), `import_name` can be taken directly from an import table:
+    /// It is compared case-insensitively and may end with a ".dll" extension.
+    /// Like NTDLL's `ApiSetResolveToHost`, only the part up to but not including the last hyphen is compared.
+    /// Hence, `api-ms-win-core-sysinfo-l1-1-0.dll`, `API-MS-WIN-CORE-SYSINFO-L1-1-1.DLL`, and `api-ms-win-core-sysinfo-l1-1-0`
+    /// all resolve to the namespace entry `api-ms-win-core-sysinfo-l1-1-0`.
+    ///
+    /// API Set Maps before version 6 have no hash table, so the full name (without the extension) must match for them.
+    pub fn resolve_import(&self, import_name: &str) -> Option<Result<ApiSetNamespaceEntry<'a>>> {
+        self.resolve_import_traced(import_name, &mut |_| {})
+    }
+
+    /// Performs the lookup of [`resolve_import`](Self::resolve_import) and reports every byte range it reads to `trace`.
+    ///
+    /// Like [`find_namespace_entry_traced`](Self::find_namespace_entry_traced), every lookup going through here is counted in the metrics.
+    pub(crate) fn resolve_import_traced<F>(
+        &self,
+        import_name: &str,
+        trace: &mut F,
+    ) -> Option<Result<ApiSetNamespaceEntry<'a>>>
+    where
+        F: FnMut(Range<usize>),
+    {
+        let result = self.search_import_traced(import_name, trace);
+
+        #[cfg(feature = "metrics")]
+        crate::instrument::record_lookup(&result);
+
+        result
+    }
+
+    fn search_import_traced<F>(
+        &self,
+        import_name: &str,
+        trace: &mut F,
+    ) -> Option<Result<ApiSetNamespaceEntry<'a>>>
+    where
+        F: FnMut(Range<usize>),
+    {
+        let name = strip_suffix_ignore_ascii_case(import_name, ".dll").unwrap_or(import_name);
+
+        if !self.schema.has_hash_table() {
+            return self.find_namespace_entry_by_name_traced(name, trace);
+        }
+
+        let (name_to_hash, _) = name.rsplit_once('-')?;
+        let hash = self.hash_name(name_to_hash);
+
+        // Compare the hashed part of the name of each candidate, which is the part up to but not including the last hyphen.
+        self.find_namespace_entry_by_hash_traced(hash, trace, |namespace_entry, trace| {
+            let name = namespace_entry.name()?;
+            trace(namespace_entry.name_range());
+            let hashed_name = name.0.get(..namespace_entry.hashed_length()).map(U16StrLe);
+
+            Ok(hashed_name.map_or(false, |hashed_name| {
+                eq_ignore_ascii_case_str(&hashed_name, name_to_hash)
+            }))
+        })
+    }
+
+    /// Checks that this API Set Map and `other` have the same flags and the same Namespace Entries with the same mappings,
+    /// regardless of their byte layout.
+    ///
+    /// Entries are compared in the same way as in [`semantic_subset_counterexample`](Self::semantic_subset_counterexample).
+    /// API Set Maps of different versions can be compared as well:
+    /// Names are then compared without the "api-" or "ext-" prefix that is only stored from version 6 on,
+    /// and flags are not compared if one of the API Set Maps is of version 2, which has no flags.
+    ///
+    /// An error is returned if an entry of either API Set Map cannot be read.
+    pub fn semantic_eq(&self, other: &ApiSetMap) -> Result<bool> {
+        if self.schema.has_flags() && other.schema.has_flags() && self.flags() != other.flags() {
+            return Ok(false);
+        }
+
+        let namespace_entries = self.namespace_entries()?;
+        let other_namespace_entries = other.namespace_entries()?;
+        if namespace_entries.len() != other_namespace_entries.len() {
+            return Ok(false);
+        }
+
+        if self.schema.has_name_prefix() == other.schema.has_name_prefix() {
+            for (namespace_entry, other_namespace_entry) in
+                namespace_entries.zip(other_namespace_entries)
+            {
+                if !namespace_entry.semantic_eq(&other_namespace_entry)? {
+                    return Ok(false);
+                }
+            }
+
+            return Ok(true);
+        }
+
+        if self.schema.has_name_prefix() {
+            semantic_eq_unprefixed(namespace_entries, other_namespace_entries)
+        } else {
+            semantic_eq_unprefixed(other_namespace_entries, namespace_entries)
+        }
+    }
+
+    /// Returns the first Namespace Entry of this API Set Map that doesn't exist with the same mappings in `other`,
+    /// or `None` if this API Set Map is a semantic subset of `other`.
+    ///
+    /// Entries are compared by their content, not by their byte layout:
+    /// Names and values are compared case-insensitively, flags of Namespace Entries and Value Entries are compared exactly.
+    /// The flags of the API Set Maps themselves are not compared.
+    ///
+    /// Both API Set Maps must have their Namespace Entries sorted case-insensitively by name, as Windows does.
+    pub fn semantic_subset_counterexample(
+        &self,
+        other: &ApiSetMap,
+    ) -> Result<Option<ApiSetNamespaceEntry<'a>>> {
+        let mut other_namespace_entries = other.namespace_entries()?.peekable();
+
+        'outer: for namespace_entry in self.namespace_entries()? {
+            let name = namespace_entry.name()?;
+
+            while let Some(other_namespace_entry) = other_namespace_entries.peek() {
+                match cmp_ignore_ascii_case(&other_namespace_entry.name()?, &name) {
+                    Ordering::Less => {
+                        other_namespace_entries.next();
+                    }
+                    Ordering::Equal => {
+                        if namespace_entry.semantic_eq(other_namespace_entry)? {
+                            other_namespace_entries.next();
+                            continue 'outer;
+                        } else {
+                            break;
+                        }
+                    }
+                    Ordering::Greater => break,
+                }
+            }
+
+            return Ok(Some(namespace_entry));
+        }
+
+        Ok(None)
+    }
+
+    /// Sets the location of the `.apiset` section within its PE file.
+    ///
+    /// This is useful if you have created the [`ApiSetMap`] via [`try_from_apiset_section_bytes`](Self::try_from_apiset_section_bytes)
+    /// and want to use [`to_file_offset`](Self::to_file_offset) and [`to_rva`](Self::to_rva).
+    pub fn set_section_location(&mut self, section_location: SectionLocation) {
+        self.section_location = Some(section_location);
+    }
+
+    /// Translates a byte offset within the `.apiset` section (like [`ApiSetNamespaceEntry::offset`]) into an offset within the PE file.
+    ///
+    /// Returns `None` if the section location is unknown, or the offset is outside the section data stored in the file.
+    pub fn to_file_offset(&self, section_offset: usize) -> Option<u64> {
+        let section_location = self.section_location?;
+        let section_offset = u32::try_from(section_offset).ok()?;
+
+        if section_offset >= section_location.size_of_raw_data
+            || section_offset as usize >= self.section_bytes.len()
+        {
+            return None;
+        }
+
+        Some(u64::from(section_location.pointer_to_raw_data) + u64::from(section_offset))
+    }
+
+    /// Translates a byte offset within the `.apiset` section (like [`ApiSetNamespaceEntry::offset`]) into an RVA.
+    ///
+    /// Returns `None` if the section location is unknown, or the offset is outside the section.
+    pub fn to_rva(&self, section_offset: usize) -> Option<u32> {
+        let section_location = self.section_location?;
+
+        if section_offset >= self.section_bytes.len() {
+            return None;
+        }
+
+        let section_offset = u32::try_from(section_offset).ok()?;
+        section_location.virtual_address.checked_add(section_offset)
+    }
+
+    /// Creates an [`ApiSetMap`] from a 32-bit API Set Map file opened via the `pelite` crate.
+    ///
+    /// This is the 32-bit counterpart of [`try_from_pe64`](Self::try_from_pe64), e.g. for the `apisetschema.dll` of an x86 Windows installation.
+    /// If you don't know the bitness of the file in advance, consider using [`try_from_pe_file`](Self::try_from_pe_file).
+    #[cfg(feature = "pelite")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "pelite")))]
+    pub fn try_from_pe32<T>(pe32: T) -> Result<Self>
+    where
+        T: pelite::pe32::Pe<'a>,
+    {
+        let apiset_section_header = pe32
+            .section_headers()
+            .by_name(".apiset")
+            .ok_or(NtApiSetError::ApiSetSectionNotFound)?;
+        let section_bytes = pe32.get_section_bytes(apiset_section_header);
+
+        Self::try_from_pe_section(apiset_section_header, section_bytes)
     }
 
     /// Creates an [`ApiSetMap`] from an API Set Map file opened via the `pelite` crate.
@@ -162,35 +871,1304 @@ impl<'a> ApiSetMap<'a> {
             .section_headers()
             .by_name(".apiset")
             .ok_or(NtApiSetError::ApiSetSectionNotFound)?;
-        let section_bytes = pe64
-            .get_section_bytes(apiset_section_header)
-            .map_err(|_| NtApiSetError::ApiSetSectionOutOfBounds)?;
-        Self::try_from_apiset_section_bytes(section_bytes)
+        let section_bytes = pe64.get_section_bytes(apiset_section_header);
+
+        Self::try_from_pe_section(apiset_section_header, section_bytes)
+    }
+
+    /// Creates an [`ApiSetMap`] from a 32-bit or 64-bit API Set Map file opened via [`pelite::PeFile`].
+    ///
+    /// This dispatches to [`try_from_pe32`](Self::try_from_pe32) or [`try_from_pe64`](Self::try_from_pe64), depending on the bitness of the file.
+    #[cfg(feature = "pelite")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "pelite")))]
+    pub fn try_from_pe_file(pe_file: pelite::PeFile<'a>) -> Result<Self> {
+        match pe_file {
+            pelite::Wrap::T32(pe32) => Self::try_from_pe32(pe32),
+            pelite::Wrap::T64(pe64) => Self::try_from_pe64(pe64),
+        }
+    }
+
+    #[cfg(feature = "pelite")]
+    fn try_from_pe_section(
+        apiset_section_header: &pelite::image::IMAGE_SECTION_HEADER,
+        section_bytes: pelite::Result<&'a [u8]>,
+    ) -> Result<Self> {
+        let section_bytes = section_bytes.map_err(|_| NtApiSetError::ApiSetSectionOutOfBounds)?;
+
+        let mut map = Self::try_from_apiset_section_bytes(section_bytes)?;
+        map.section_location = Some(SectionLocation::new(
+            apiset_section_header.VirtualAddress,
+            apiset_section_header.PointerToRawData,
+            apiset_section_header.SizeOfRawData,
+        ));
+
+        Ok(map)
     }
 
     /// Creates an [`ApiSetMap`] from the raw bytes of the `.apiset` section of an API Set Map file.
     ///
-    /// If you only have the DLL file and not the `.apiset` section bytes, consider using [`try_from_pe64`](Self::try_from_pe64).
+    /// If you only have the DLL file and not the `.apiset` section bytes, consider using [`try_from_pe_file`](Self::try_from_pe_file).
     pub fn try_from_apiset_section_bytes(section_bytes: &'a [u8]) -> Result<Self> {
-        let length = section_bytes.len();
-        let (header, _) = LayoutVerified::<_, ApiSetMapHeader>::new_unaligned_from_prefix(
+        let result = Self::parse_apiset_section_bytes(section_bytes);
+
+        #[cfg(feature = "metrics")]
+        crate::instrument::record_parse(&result);
+
+        result
+    }
+
+    /// Creates an [`ApiSetMap`] from the raw bytes of a possibly truncated `.apiset` section, e.g. one carved out of a memory dump.
+    ///
+    /// Only the header must be intact.
+    /// In contrast to [`try_from_apiset_section_bytes`](Self::try_from_apiset_section_bytes),
+    /// [`namespace_entries`](Self::namespace_entries), [`hash_entries`](Self::hash_entries), and [`ApiSetNamespaceEntry::value_entries`]
+    /// don't return an error if their entry array exceeds the section.
+    /// Instead, they are clamped to the entries that fit entirely into the section, so all intact entries can still be enumerated.
+    /// Strings past the end of the section still yield [`NtApiSetError::EntryNameOutOfBounds`] for the entry referencing them.
+    ///
+    /// Use [`try_namespace_entries`](Self::try_namespace_entries) and friends to find out which entries have been cut off.
+    ///
+    /// ```
+    /// # use nt_apiset::{ApiSetMap, ApiSetMapBuilder, ApiSetNamespaceEntryBuilder, ApiSetNamespaceEntryFlags, NtApiSetError};
+    /// let bytes = ApiSetMapBuilder::new()
+    ///     .add_namespace_entry(
+    ///         ApiSetNamespaceEntryBuilder::new("api-ms-win-core-bar-l1-1-0", ApiSetNamespaceEntryFlags::empty())
+    ///             .add_value_entry("", "bar.dll"),
+    ///     )
+    ///     .add_namespace_entry(
+    ///         ApiSetNamespaceEntryBuilder::new("api-ms-win-core-foo-l1-1-0", ApiSetNamespaceEntryFlags::empty())
+    ///             .add_value_entry("", "foo.dll"),
+    ///     )
+    ///     .build()
+    ///     .unwrap();
+    /// let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+    /// let second_offset = map.namespace_entries().unwrap().nth(1).unwrap().offset();
+    ///
+    /// // Cut off the section in the middle of the second Namespace Entry.
+    /// let truncated = &bytes[..second_offset + 4];
+    /// let strict = ApiSetMap::try_from_apiset_section_bytes(truncated).unwrap();
+    /// assert!(strict.namespace_entries().is_err());
+    ///
+    /// let lenient = ApiSetMap::try_from_apiset_section_bytes_lenient(truncated).unwrap();
+    /// assert_eq!(lenient.namespace_entries().unwrap().len(), 1);
+    ///
+    /// let mut namespace_entries = lenient.try_namespace_entries();
+    /// assert!(namespace_entries.next().unwrap().is_ok());
+    /// assert_eq!(
+    ///     namespace_entries.next().unwrap().unwrap_err(),
+    ///     NtApiSetError::TruncatedEntry { index: 1, offset: second_offset }
+    /// );
+    /// assert!(namespace_entries.next().is_none());
+    ///
+    /// // Cut off the section in the middle of the string pool, which comes last.
+    /// let truncated = &bytes[..bytes.len() - 2];
+    /// let lenient = ApiSetMap::try_from_apiset_section_bytes_lenient(truncated).unwrap();
+    /// let mut namespace_entries = lenient.namespace_entries().unwrap();
+    ///
+    /// let bar = namespace_entries.next().unwrap();
+    /// assert_eq!(bar.name().unwrap(), "api-ms-win-core-bar-l1-1-0");
+    /// assert_eq!(bar.value_entries().unwrap().next().unwrap().value().unwrap(), "bar.dll");
+    ///
+    /// let foo = namespace_entries.next().unwrap();
+    /// assert_eq!(foo.name().unwrap(), "api-ms-win-core-foo-l1-1-0");
+    /// assert!(matches!(
+    ///     foo.value_entries().unwrap().next().unwrap().value(),
+    ///     Err(NtApiSetError::EntryNameOutOfBounds { .. })
+    /// ));
+    /// ```
+    pub fn try_from_apiset_section_bytes_lenient(section_bytes: &'a [u8]) -> Result<Self> {
+        let mut map = Self::try_from_apiset_section_bytes(section_bytes)?;
+        map.lenient = true;
+        Ok(map)
+    }
+
+    /// Returns the size in bytes of this API Set Map as declared by its header.
+    ///
+    /// This is the raw header field and may differ from the length of [`section_bytes`](Self::section_bytes),
+    /// e.g. because the section is padded.
+    /// API Set Maps of version 2 (Windows 7) don't declare a size, so 0 is returned for them.
+    pub const fn size(&self) -> u32 {
+        self.header.size
+    }
+
+    /// Returns the version of this API Set Map.
+    ///
+    /// * Version 2 is used by Windows 7.
+    ///   These API Set Maps have no flags and no hash table.
+    /// * Version 3 is used by Windows 8.
+    ///   These API Set Maps are not supported and rejected with [`NtApiSetError::UnsupportedVersion`].
+    /// * Version 4 is used by Windows 8.1.
+    ///   These API Set Maps have no hash table.
+    /// * Version 6 is used by Windows 10 and later.
+    ///
+    /// Before version 6, names of Namespace Entries are stored without their "api-" or "ext-" prefix.
+    pub const fn version(&self) -> u32 {
+        self.header.version
+    }
+
+    /// Returns a copy of this [`ApiSetMap`] that refers to `section_bytes` instead.
+    ///
+    /// `section_bytes` must be a copy of the bytes this [`ApiSetMap`] has been parsed from, so that the parsed header remains valid.
+    #[cfg(feature = "alloc")]
+    pub(crate) const fn rebind<'b>(&self, section_bytes: &'b [u8]) -> ApiSetMap<'b> {
+        ApiSetMap {
             section_bytes,
-        )
-        .ok_or(NtApiSetError::InvalidMapHeaderSize {
-            expected: mem::size_of::<ApiSetMapHeader>(),
-            actual: length,
-        })?;
+            schema: self.schema,
+            header: self.header,
+            section_location: self.section_location,
+            lenient: self.lenient,
+        }
+    }
 
-        // The internal structures are slightly different for older Windows versions.
-        // See https://www.geoffchappell.com/studies/windows/win32/apisetschema/index.htm
-        let version = header.version.get();
-        if version != APISET_VERSION_WINDOWS_10 {
-            return Err(NtApiSetError::UnsupportedVersion { version });
+    /// Returns a copy of this [`ApiSetMap`] that doesn't clamp its entry arrays, even if this one is lenient.
+    #[cfg(feature = "alloc")]
+    pub(crate) const fn strict(&self) -> ApiSetMap<'a> {
+        ApiSetMap {
+            lenient: false,
+            ..self.rebind(self.section_bytes)
         }
+    }
+
+    #[cfg_attr(not(feature = "alloc"), allow(dead_code))]
+    pub(crate) const fn schema(&self) -> Schema {
+        self.schema
+    }
+
+    /// Performs the parsing of [`try_from_apiset_section_bytes`](Self::try_from_apiset_section_bytes) without counting failures in the metrics.
+    ///
+    /// This is used by constructors that parse in multiple steps and only want to count a failure once.
+    pub(crate) fn parse_apiset_section_bytes(section_bytes: &'a [u8]) -> Result<Self> {
+        let length = section_bytes.len();
+        let invalid_map_header_size = |expected| NtApiSetError::InvalidMapHeaderSize {
+            expected,
+            actual: length,
+        };
+
+        // The version is the first field in every header.
+        let (version, _) =
+            LayoutVerified::<_, U32<LittleEndian>>::new_unaligned_from_prefix(section_bytes)
+                .ok_or_else(|| invalid_map_header_size(mem::size_of::<ApiSetMapHeader>()))?;
+        let version = version.get();
+        let schema =
+            Schema::from_version(version).ok_or(NtApiSetError::UnsupportedVersion { version })?;
+
+        let header = MapFields::read(schema, section_bytes)
+            .ok_or_else(|| invalid_map_header_size(schema.map_header_size()))?;
 
         Ok(Self {
             section_bytes,
+            schema,
             header,
+            section_location: None,
+            lenient: false,
         })
     }
 }
+
+/// Compares the Namespace Entries of a map storing names with their "api-" or "ext-" prefix with those of a map
+/// storing names without it, as done by [`ApiSetMap::semantic_eq`].
+fn semantic_eq_unprefixed(
+    prefixed_namespace_entries: ApiSetNamespaceEntries,
+    unprefixed_namespace_entries: ApiSetNamespaceEntries,
+) -> Result<bool> {
+    // The prefixed Namespace Entries are sorted by their full names.
+    // Without the prefix, the "api-" entries and the "ext-" entries are each still sorted, but interleaved.
+    // Merge both groups (plus any names without such a prefix) to get the order of the unprefixed Namespace Entries.
+    let mut groups = [
+        prefixed_namespace_entries.clone(),
+        prefixed_namespace_entries.clone(),
+        prefixed_namespace_entries,
+    ];
+    let mut heads = [None, None, None];
+    for (group, head) in heads.iter_mut().enumerate() {
+        *head = next_in_name_prefix_group(&mut groups[group], group)?;
+    }
+
+    for unprefixed_namespace_entry in unprefixed_namespace_entries {
+        let mut next = None;
+
+        for (group, head) in heads.iter().enumerate() {
+            if let Some(namespace_entry) = head {
+                let name = namespace_entry.name_without_prefix()?;
+
+                if next.as_ref().map_or(true, |(_, next_name)| {
+                    cmp_ignore_ascii_case(&name, next_name) == Ordering::Less
+                }) {
+                    next = Some((group, name));
+                }
+            }
+        }
+
+        let next = match next {
+            Some((next, _)) => next,
+            None => return Ok(false),
+        };
+        let namespace_entry = heads[next].take().unwrap();
+
+        if !namespace_entry.semantic_eq(&unprefixed_namespace_entry)? {
+            return Ok(false);
+        }
+
+        heads[next] = next_in_name_prefix_group(&mut groups[next], next)?;
+    }
+
+    Ok(true)
+}
+
+/// Returns the next Namespace Entry whose name begins with "api-" (`group` 0), "ext-" (`group` 1), or neither (`group` 2).
+fn next_in_name_prefix_group<'a>(
+    namespace_entries: &mut ApiSetNamespaceEntries<'a>,
+    group: usize,
+) -> Result<Option<ApiSetNamespaceEntry<'a>>> {
+    for namespace_entry in namespace_entries {
+        let name = namespace_entry.name()?;
+        let namespace_entry_group = if starts_with_ignore_ascii_case_str(&name, "api-") {
+            0
+        } else if starts_with_ignore_ascii_case_str(&name, "ext-") {
+            1
+        } else {
+            2
+        };
+
+        if namespace_entry_group == group {
+            return Ok(Some(namespace_entry));
+        }
+    }
+
+    Ok(None)
+}
+
+fn debug_assert_namespace_entry_name(namespace_entry_name: &str) {
+    debug_assert!(!namespace_entry_name.is_empty());
+    debug_assert!(namespace_entry_name
+        .chars()
+        .all(|x| x.is_ascii_alphanumeric() || x == '-'));
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+    use crate::fixtures::build_legacy_map;
+    use crate::namespace_entry::ApiSetNamespaceEntryFlags;
+
+    fn build(entries: &[(&str, &[(&str, &str)])]) -> Vec<u8> {
+        entries
+            .iter()
+            .fold(ApiSetMapBuilder::new(), |builder, (name, value_entries)| {
+                let namespace_entry = value_entries.iter().fold(
+                    ApiSetNamespaceEntryBuilder::new(name, ApiSetNamespaceEntryFlags::empty()),
+                    |namespace_entry, (importing_module, host_module)| {
+                        namespace_entry.add_value_entry(importing_module, host_module)
+                    },
+                );
+                builder.add_namespace_entry(namespace_entry)
+            })
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_semantic_eq() {
+        let bytes = build(&[
+            ("api-ms-win-core-foo-l1-1-0", &[("", "foo.dll")]),
+            (
+                "ext-ms-win-bar-l1-1-0",
+                &[("", "bar.dll"), ("kernel32.dll", "bar_legacy.dll")],
+            ),
+        ]);
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+        // Same content with different case, added in a different order, hence a different byte layout.
+        let other_bytes = build(&[
+            (
+                "EXT-MS-WIN-BAR-L1-1-0",
+                &[("", "BAR.dll"), ("kernel32.dll", "bar_legacy.dll")],
+            ),
+            ("api-ms-win-core-foo-l1-1-0", &[("", "foo.dll")]),
+        ]);
+        assert_ne!(bytes, other_bytes);
+        let other = ApiSetMap::try_from_apiset_section_bytes(&other_bytes).unwrap();
+        assert!(map.semantic_eq(&other).unwrap());
+        assert!(other.semantic_eq(&map).unwrap());
+
+        // Different host for the alias of a single importing module.
+        let other_bytes = build(&[
+            ("api-ms-win-core-foo-l1-1-0", &[("", "foo.dll")]),
+            (
+                "ext-ms-win-bar-l1-1-0",
+                &[("", "bar.dll"), ("kernel32.dll", "bar.dll")],
+            ),
+        ]);
+        let other = ApiSetMap::try_from_apiset_section_bytes(&other_bytes).unwrap();
+        assert!(!map.semantic_eq(&other).unwrap());
+
+        // One entry less.
+        let other_bytes = build(&[("api-ms-win-core-foo-l1-1-0", &[("", "foo.dll")])]);
+        let other = ApiSetMap::try_from_apiset_section_bytes(&other_bytes).unwrap();
+        assert!(!map.semantic_eq(&other).unwrap());
+
+        // Different flags of the API Set Map.
+        let other_bytes = ApiSetMapBuilder::new()
+            .flags(ApiSetMapFlags::SEALED)
+            .build()
+            .unwrap();
+        let empty_bytes = ApiSetMapBuilder::new().build().unwrap();
+        let other = ApiSetMap::try_from_apiset_section_bytes(&other_bytes).unwrap();
+        let empty = ApiSetMap::try_from_apiset_section_bytes(&empty_bytes).unwrap();
+        assert!(!empty.semantic_eq(&other).unwrap());
+    }
+
+    #[test]
+    fn test_semantic_eq_owned() {
+        let bytes = build(&[("api-ms-win-core-foo-l1-1-0", &[("", "foo.dll")])]);
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let owned_map = map.to_owned_map();
+        assert!(owned_map.semantic_eq(&map).unwrap());
+
+        let other_bytes = build(&[("api-ms-win-core-foo-l1-1-0", &[("", "bar.dll")])]);
+        let other = ApiSetMap::try_from_apiset_section_bytes(&other_bytes).unwrap();
+        assert!(!owned_map.semantic_eq(&other).unwrap());
+    }
+
+    #[test]
+    fn test_semantic_eq_across_versions() {
+        // Sorted by their full names, "api-ms-win-core-foo" comes before "ext-ms-win-bar".
+        // Without the prefix, "ms-win-bar" comes before "ms-win-core-foo".
+        let bytes = build(&[
+            ("api-ms-win-core-foo-l1-1-0", &[("", "foo.dll")]),
+            ("api-ms-win-core-qux-l1-1-0", &[("", "qux.dll")]),
+            ("ext-ms-win-bar-l1-1-0", &[("", "bar.dll")]),
+        ]);
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+        for version in [2, 4] {
+            let legacy_bytes = build_legacy_map(
+                version,
+                &[
+                    ("ms-win-bar-l1-1-0", &[("", "bar.dll")]),
+                    ("ms-win-core-foo-l1-1-0", &[("", "foo.dll")]),
+                    ("ms-win-core-qux-l1-1-0", &[("", "qux.dll")]),
+                ],
+            );
+            let legacy_map = ApiSetMap::try_from_apiset_section_bytes(&legacy_bytes).unwrap();
+            assert!(map.semantic_eq(&legacy_map).unwrap());
+            assert!(legacy_map.semantic_eq(&map).unwrap());
+
+            let legacy_bytes = build_legacy_map(
+                version,
+                &[
+                    ("ms-win-bar-l1-1-0", &[("", "bar.dll")]),
+                    ("ms-win-core-foo-l1-1-0", &[("", "foo.dll")]),
+                    ("ms-win-core-qux-l1-1-0", &[("", "other.dll")]),
+                ],
+            );
+            let legacy_map = ApiSetMap::try_from_apiset_section_bytes(&legacy_bytes).unwrap();
+            assert!(!map.semantic_eq(&legacy_map).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_hash_collisions() {
+        // With a hash factor of 0, the hash of a name is its last character.
+        // All hashed name prefixes end with "1", so all Hash Entries have the same hash.
+        let names = [
+            "api-ms-win-core-bar-l1-1-0",
+            "api-ms-win-core-foo-l1-1-0",
+            "api-ms-win-core-qux-l1-1-0",
+        ];
+        let mut bytes = names
+            .iter()
+            .fold(ApiSetMapBuilder::new().hash_factor(0), |builder, name| {
+                builder.add_namespace_entry(
+                    ApiSetNamespaceEntryBuilder::new(name, ApiSetNamespaceEntryFlags::empty())
+                        .add_value_entry("", &alloc::format!("{}.dll", &name[16..19])),
+                )
+            })
+            .build()
+            .unwrap();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+        let hashes = map
+            .hash_entries()
+            .unwrap()
+            .map(|hash_entry| hash_entry.hash())
+            .collect::<Vec<_>>();
+        assert_eq!(hashes, [u32::from(b'1'); 3]);
+
+        // Every colliding name must be found, not just the one of the first Hash Entry with that hash.
+        for name in names {
+            let namespace_entry = map.find_namespace_entry(name).unwrap().unwrap();
+            assert_eq!(namespace_entry.name().unwrap(), name);
+
+            let host = map
+                .resolve(&alloc::format!("{name}.dll"), None)
+                .unwrap()
+                .unwrap();
+            assert_eq!(host, alloc::format!("{}.dll", &name[16..19]).as_str());
+        }
+
+        // A name that collides with all of them, but doesn't exist.
+        assert!(map
+            .find_namespace_entry("api-ms-win-core-baz-l1-1-0")
+            .is_none());
+        assert!(map
+            .resolve_import("api-ms-win-core-baz-l1-1-0.dll")
+            .is_none());
+
+        // A Hash Entry pointing past the Namespace Entries is reported instead of being skipped.
+        let index_offset = map.hash_entries().unwrap().nth(1).unwrap().offset() + 4;
+        bytes[index_offset..index_offset + 4].copy_from_slice(&7u32.to_le_bytes());
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let first = map.hash_entries().unwrap().next().unwrap().index() as usize;
+        let other_name = names[(first + 1) % names.len()];
+
+        assert_eq!(
+            map.find_namespace_entry(other_name).unwrap().unwrap_err(),
+            NtApiSetError::NamespaceEntryIndexOutOfBounds { index: 7, count: 3 }
+        );
+    }
+
+    fn check_unverified_agrees(map: &ApiSetMap) {
+        map.validate().unwrap();
+
+        for namespace_entry in map.namespace_entries().unwrap() {
+            let name = namespace_entry.name().unwrap().to_string().unwrap();
+            let name = if map.schema.has_hash_table() {
+                name
+            } else {
+                alloc::format!("api-{name}")
+            };
+
+            let verified = map.find_namespace_entry(&name).unwrap().unwrap();
+            let unverified = map.find_namespace_entry_unverified(&name).unwrap().unwrap();
+            assert_eq!(verified.offset(), namespace_entry.offset(), "{name}");
+
+            // Names sharing their hash can only be told apart by comparing them.
+            let (hashed_name, _) = name.rsplit_once('-').unwrap();
+            let hash = map.hash_name(hashed_name);
+            let collisions = map
+                .hash_entries()
+                .unwrap()
+                .filter(|hash_entry| hash_entry.hash() == hash)
+                .count();
+
+            if collisions <= 1 {
+                assert_eq!(verified.offset(), unverified.offset(), "{name}");
+            } else {
+                assert_eq!(
+                    map.hash_name(&unverified.hashed_name().unwrap().to_string().unwrap()),
+                    hash,
+                    "{name}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_namespace_entry_unverified() {
+        let bytes = build(&[
+            ("api-ms-win-core-bar-l1-1-0", &[("", "bar.dll")]),
+            (
+                "api-ms-win-core-foo-l1-2-3",
+                &[("", "foo.dll"), ("kernel32.dll", "kernelbase.dll")],
+            ),
+            ("api-ms-win-core-foo-l1-2-4", &[("", "foo.dll")]),
+            ("ext-ms-win-qux-l1-1-0", &[]),
+        ]);
+        check_unverified_agrees(&ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap());
+
+        // Without a hash table, this falls back to comparing names.
+        for version in [2, 4] {
+            let bytes = build_legacy_map(
+                version,
+                &[
+                    ("ms-win-core-bar-l1-1-0", &[("", "bar.dll")]),
+                    ("ms-win-core-foo-l1-2-3", &[("", "foo.dll")]),
+                ],
+            );
+            check_unverified_agrees(&ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap());
+        }
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[test]
+    fn test_find_namespace_entry_unverified_synthetic() {
+        let bytes = crate::synthetic::synthetic_map(0);
+        check_unverified_agrees(&ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap());
+    }
+
+    #[cfg(feature = "pelite")]
+    #[test]
+    fn test_section_offset_translation() {
+        use pelite::pe64::{Pe, PeFile};
+
+        use crate::fixtures::{build_pe_file, PE_SECTION_FILE_OFFSET, PE_SECTION_RVA};
+
+        let section_bytes = build(&[("api-ms-win-core-foo-l1-1-0", &[("", "foo.dll")])]);
+        let file = build_pe_file(true, &section_bytes);
+        let pe_file = PeFile::from_bytes(&file).unwrap();
+        let map = ApiSetMap::try_from_pe64(pe_file).unwrap();
+
+        // The section data is padded to the file alignment.
+        let section_size = map.section_bytes().len();
+        assert!(section_size > section_bytes.len());
+
+        for section_offset in 0..section_size {
+            let file_offset = map.to_file_offset(section_offset).unwrap();
+            let rva = map.to_rva(section_offset).unwrap();
+            assert_eq!(
+                file_offset,
+                u64::from(PE_SECTION_FILE_OFFSET) + section_offset as u64
+            );
+            assert_eq!(rva, PE_SECTION_RVA + section_offset as u32);
+
+            // Both translations agree with the ones of the PE parser within the virtual size of the section.
+            if section_offset < section_bytes.len() {
+                assert_eq!(pe_file.rva_to_file_offset(rva).unwrap() as u64, file_offset);
+                assert_eq!(
+                    pe_file.file_offset_to_rva(file_offset as usize).unwrap(),
+                    rva
+                );
+            }
+
+            assert_eq!(
+                file[file_offset as usize],
+                map.section_bytes()[section_offset]
+            );
+        }
+
+        // Offsets outside the section.
+        for section_offset in [section_size, section_size + 1, usize::MAX] {
+            assert_eq!(map.to_file_offset(section_offset), None);
+            assert_eq!(map.to_rva(section_offset), None);
+        }
+
+        // Without a known section location, nothing can be translated.
+        let mut map = ApiSetMap::try_from_apiset_section_bytes(&section_bytes).unwrap();
+        assert_eq!(map.section_location(), None);
+        assert_eq!(map.to_file_offset(0), None);
+        assert_eq!(map.to_rva(0), None);
+
+        // Until it is set.
+        map.set_section_location(SectionLocation::new(
+            PE_SECTION_RVA,
+            PE_SECTION_FILE_OFFSET,
+            0x200,
+        ));
+        assert_eq!(
+            map.to_file_offset(0x10),
+            Some(u64::from(PE_SECTION_FILE_OFFSET) + 0x10)
+        );
+        assert_eq!(map.to_rva(0x10), Some(PE_SECTION_RVA + 0x10));
+        assert_eq!(map.to_rva(section_bytes.len()), None);
+    }
+
+    #[cfg(feature = "pelite")]
+    #[test]
+    fn test_try_from_pe32() {
+        use pelite::pe32::PeFile;
+
+        use crate::fixtures::{build_pe_file, PE_SECTION_FILE_OFFSET, PE_SECTION_RVA};
+
+        let section_bytes = build(&[
+            ("api-ms-win-core-foo-l1-1-0", &[("", "foo.dll")]),
+            ("api-ms-win-core-bar-l1-1-0", &[("", "bar.dll")]),
+        ]);
+        let expected = ApiSetMap::try_from_apiset_section_bytes(&section_bytes).unwrap();
+        let file = build_pe_file(false, &section_bytes);
+
+        let map = ApiSetMap::try_from_pe32(PeFile::from_bytes(&file).unwrap()).unwrap();
+        assert!(map.semantic_eq(&expected).unwrap());
+        assert_eq!(
+            map.resolve("api-ms-win-core-foo-l1-1-0.dll", None)
+                .unwrap()
+                .unwrap(),
+            "foo.dll"
+        );
+        assert_eq!(map.to_rva(0).unwrap(), PE_SECTION_RVA);
+        assert_eq!(
+            map.to_file_offset(0).unwrap(),
+            u64::from(PE_SECTION_FILE_OFFSET)
+        );
+    }
+
+    #[cfg(feature = "pelite")]
+    #[test]
+    fn test_try_from_pe_file() {
+        use crate::fixtures::build_pe_file;
+
+        let section_bytes = build(&[("api-ms-win-core-foo-l1-1-0", &[("", "foo.dll")])]);
+        let expected = ApiSetMap::try_from_apiset_section_bytes(&section_bytes).unwrap();
+
+        for is_64bit in [false, true] {
+            let mut file = build_pe_file(is_64bit, &section_bytes);
+            let pe_file = pelite::PeFile::from_bytes(&file).unwrap();
+            assert_eq!(matches!(pe_file, pelite::Wrap::T64(_)), is_64bit);
+
+            let map = ApiSetMap::try_from_pe_file(pe_file).unwrap();
+            assert!(map.semantic_eq(&expected).unwrap());
+            assert!(map.section_location().is_some());
+
+            // Rename the section, so that it's no longer found.
+            let section_name = file
+                .windows(8)
+                .position(|window| window == b".apiset\0")
+                .unwrap();
+            file[section_name + 7] = b'x';
+            let pe_file = pelite::PeFile::from_bytes(&file).unwrap();
+            assert_eq!(
+                ApiSetMap::try_from_pe_file(pe_file).unwrap_err(),
+                NtApiSetError::ApiSetSectionNotFound
+            );
+        }
+    }
+
+    #[test]
+    fn test_empty_map() {
+        let bytes = ApiSetMapBuilder::new().build().unwrap();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let name = "api-ms-win-core-foo-l1-1-0";
+
+        assert_eq!(map.count(), 0);
+        assert!(map.find_namespace_entry(name).is_none());
+        assert!(map.find_namespace_entry_unverified(name).is_none());
+        assert!(map
+            .resolve_import("api-ms-win-core-foo-l1-1-0.dll")
+            .is_none());
+        assert!(map
+            .resolve("api-ms-win-core-foo-l1-1-0.dll", Some("kernel32.dll"))
+            .is_none());
+
+        let mut namespace_entries = map.namespace_entries().unwrap();
+        assert_eq!(namespace_entries.len(), 0);
+        assert!(namespace_entries.next_back().is_none());
+        assert!(namespace_entries.nth(1).is_none());
+        assert!(namespace_entries.next().is_none());
+        assert!(map.namespace_entry(0).is_none());
+        assert_eq!(map.namespace_entries_range(0..0).unwrap().len(), 0);
+
+        assert_eq!(map.hash_entries().unwrap().len(), 0);
+        assert_eq!(map.hash_joined().unwrap().count(), 0);
+        assert_eq!(map.mappings().unwrap().count(), 0);
+        assert_eq!(
+            map.find_namespace_entries_by_prefix("api-")
+                .unwrap()
+                .count(),
+            0
+        );
+        assert_eq!(
+            map.find_namespace_entries_containing("").unwrap().count(),
+            0
+        );
+        assert_eq!(map.stats().unwrap().namespace_entries(), 0);
+
+        let index = map.build_index().unwrap();
+        assert!(index.is_empty());
+        assert!(index.get(name).is_none());
+        assert!(index
+            .resolve("api-ms-win-core-foo-l1-1-0.dll", None)
+            .is_none());
+
+        let other_bytes = ApiSetMapBuilder::new().build().unwrap();
+        let other = ApiSetMap::try_from_apiset_section_bytes(&other_bytes).unwrap();
+        assert!(map.semantic_eq(&other).unwrap());
+
+        let other_bytes = build(&[(name, &[("", "foo.dll")])]);
+        let other = ApiSetMap::try_from_apiset_section_bytes(&other_bytes).unwrap();
+        assert!(!map.semantic_eq(&other).unwrap());
+        assert!(!other.semantic_eq(&map).unwrap());
+
+        assert_eq!(
+            map.validate().unwrap(),
+            [crate::validate::ValidationInfo::NoNamespaceEntries]
+        );
+    }
+
+    #[test]
+    fn test_single_entry_map() {
+        let name = "api-ms-win-core-foo-l1-1-0";
+        let bytes = build(&[(name, &[("", "foo.dll")])]);
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+        let namespace_entry = map.find_namespace_entry(name).unwrap().unwrap();
+        assert_eq!(namespace_entry.name().unwrap(), name);
+        assert_eq!(
+            map.resolve("API-MS-WIN-CORE-FOO-L1-1-1.DLL", None)
+                .unwrap()
+                .unwrap(),
+            "foo.dll"
+        );
+
+        // Names sorting before and after the only entry are clean misses.
+        for other_name in ["api-ms-win-core-aaa-l1-1-0", "api-ms-win-core-zzz-l1-1-0"] {
+            assert!(map.find_namespace_entry(other_name).is_none());
+            assert!(map
+                .resolve(&alloc::format!("{other_name}.dll"), None)
+                .is_none());
+        }
+
+        let mut namespace_entries = map.namespace_entries().unwrap();
+        assert_eq!(namespace_entries.len(), 1);
+        assert_eq!(
+            namespace_entries.next_back().unwrap().offset(),
+            namespace_entry.offset()
+        );
+        assert!(namespace_entries.next().is_none());
+        assert!(map.namespace_entry(1).is_none());
+        assert_eq!(map.hash_entries().unwrap().len(), 1);
+        assert_eq!(map.hash_joined().unwrap().count(), 1);
+        assert_eq!(map.mappings().unwrap().count(), 1);
+
+        let index = map.build_index().unwrap();
+        assert_eq!(index.len(), 1);
+        assert_eq!(index.get(name).unwrap().offset(), namespace_entry.offset());
+        assert!(index.get("api-ms-win-core-zzz-l1-1-0").is_none());
+
+        let other_bytes = build(&[(name, &[("", "foo.dll")])]);
+        let other = ApiSetMap::try_from_apiset_section_bytes(&other_bytes).unwrap();
+        assert!(map.semantic_eq(&other).unwrap());
+
+        assert_eq!(map.validate().unwrap(), []);
+    }
+
+    #[test]
+    fn test_no_value_entries() {
+        let name = "api-ms-win-core-foo-l1-1-0";
+        let bytes = build(&[
+            ("api-ms-win-core-bar-l1-1-0", &[("", "bar.dll")]),
+            (name, &[]),
+        ]);
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+        let namespace_entry = map.find_namespace_entry(name).unwrap().unwrap();
+        assert_eq!(namespace_entry.value_count().unwrap(), 0);
+
+        let mut value_entries = namespace_entry.value_entries().unwrap();
+        assert_eq!(value_entries.len(), 0);
+        assert!(value_entries.next_back().is_none());
+        assert!(value_entries.next().is_none());
+        assert!(namespace_entry.default_value_entry().is_none());
+        assert!(namespace_entry.value_entry(0).is_none());
+        assert!(namespace_entry.resolve_value_entry(None).is_none());
+        assert!(namespace_entry
+            .resolve_value_entry(Some("kernel32.dll"))
+            .is_none());
+        assert_eq!(namespace_entry.value_entries_range(0..0).unwrap().len(), 0);
+
+        // The API Set exists, but doesn't resolve to any host.
+        assert!(map
+            .resolve_import("api-ms-win-core-foo-l1-1-0.dll")
+            .is_some());
+        assert!(map
+            .resolve("api-ms-win-core-foo-l1-1-0.dll", None)
+            .is_none());
+        assert_eq!(map.mappings().unwrap().count(), 1);
+
+        let index = map.build_index().unwrap();
+        assert!(index.get(name).is_some());
+        assert!(index
+            .resolve("api-ms-win-core-foo-l1-1-0.dll", Some("kernel32.dll"))
+            .is_none());
+
+        let other_bytes = build(&[
+            ("api-ms-win-core-bar-l1-1-0", &[("", "bar.dll")]),
+            (name, &[("", "foo.dll")]),
+        ]);
+        let other = ApiSetMap::try_from_apiset_section_bytes(&other_bytes).unwrap();
+        assert!(!map.semantic_eq(&other).unwrap());
+
+        assert_eq!(
+            map.validate().unwrap(),
+            [crate::validate::ValidationInfo::NoValueEntries { index: 1 }]
+        );
+    }
+
+    #[test]
+    fn test_resolve_import() {
+        let bytes = build(&[
+            ("api-ms-win-core-file-l1-1-0", &[("", "kernelbase.dll")]),
+            ("api-ms-win-core-sysinfo-l1-2-3", &[("", "kernelbase.dll")]),
+            ("ext-ms-win-gdi-draw-l1-1-0", &[("", "gdi32full.dll")]),
+        ]);
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let resolve_import = |import_name: &str| {
+            map.resolve_import(import_name).map(|namespace_entry| {
+                namespace_entry
+                    .unwrap()
+                    .name()
+                    .unwrap()
+                    .to_string()
+                    .unwrap()
+            })
+        };
+
+        for import_name in [
+            // Exact name with and without ".dll"
+            "api-ms-win-core-sysinfo-l1-2-3.dll",
+            "api-ms-win-core-sysinfo-l1-2-3",
+            // Mixed case
+            "API-MS-WIN-CORE-SYSINFO-L1-2-3.DLL",
+            "Api-Ms-Win-Core-SysInfo-L1-2-3.Dll",
+            // Differing minor versions, which share the hashed name up to the last hyphen
+            "api-ms-win-core-sysinfo-l1-2-0.dll",
+            "api-ms-win-core-sysinfo-l1-2-9",
+            "API-MS-WIN-CORE-SYSINFO-L1-2-42.DLL",
+        ] {
+            assert_eq!(
+                resolve_import(import_name).unwrap(),
+                "api-ms-win-core-sysinfo-l1-2-3",
+                "{import_name}"
+            );
+        }
+
+        // An exact lookup doesn't follow the loader semantics.
+        assert!(map
+            .find_namespace_entry("api-ms-win-core-sysinfo-l1-2-0")
+            .is_none());
+
+        assert_eq!(
+            resolve_import("EXT-MS-WIN-GDI-DRAW-L1-1-1").unwrap(),
+            "ext-ms-win-gdi-draw-l1-1-0"
+        );
+
+        // A differing major version or level is a different API Set.
+        for import_name in [
+            "api-ms-win-core-sysinfo-l1-1-0.dll",
+            "api-ms-win-core-sysinfo-l2-2-3.dll",
+            "api-ms-win-core-file-l2-1-0.dll",
+            "kernel32.dll",
+            "kernel32",
+            "",
+        ] {
+            assert!(resolve_import(import_name).is_none(), "{import_name}");
+        }
+    }
+
+    #[test]
+    fn test_resolve_import_legacy() {
+        let bytes = build_legacy_map(
+            2,
+            &[("ms-win-core-sysinfo-l1-2-3", &[("", "kernelbase.dll")])],
+        );
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+        // The name is compared case-insensitively, with or without ".dll" and the "api-" prefix.
+        for import_name in [
+            "api-ms-win-core-sysinfo-l1-2-3.dll",
+            "API-MS-WIN-CORE-SYSINFO-L1-2-3",
+            "ms-win-core-sysinfo-l1-2-3.DLL",
+        ] {
+            assert_eq!(
+                map.resolve_import(import_name)
+                    .unwrap()
+                    .unwrap()
+                    .name()
+                    .unwrap(),
+                "ms-win-core-sysinfo-l1-2-3"
+            );
+        }
+
+        // Without a hash table, the full name must match.
+        assert!(map
+            .resolve_import("api-ms-win-core-sysinfo-l1-2-0.dll")
+            .is_none());
+    }
+
+    #[test]
+    fn test_namespace_entries_range() {
+        let names = (0..7)
+            .map(|i| alloc::format!("api-ms-win-core-test{i}-l1-1-0"))
+            .collect::<Vec<_>>();
+        let bytes = names
+            .iter()
+            .fold(ApiSetMapBuilder::new(), |builder, name| {
+                builder.add_namespace_entry(
+                    ApiSetNamespaceEntryBuilder::new(name, ApiSetNamespaceEntryFlags::empty())
+                        .add_value_entry("", "foo.dll"),
+                )
+            })
+            .build()
+            .unwrap();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let page = |range: Range<usize>| {
+            map.namespace_entries_range(range)
+                .unwrap()
+                .map(|namespace_entry| namespace_entry.name().unwrap().to_string().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        // First page
+        assert_eq!(page(0..3), names[0..3]);
+
+        // Last partial page
+        assert_eq!(page(6..7), names[6..7]);
+        let mut last_page = map.namespace_entries_range(6..7).unwrap();
+        assert_eq!(last_page.len(), 1);
+        assert_eq!(
+            last_page.next_back().unwrap().name().unwrap(),
+            names[6].as_str()
+        );
+        assert!(last_page.next().is_none());
+
+        // A page in the middle can be iterated from both ends.
+        let mut middle_page = map.namespace_entries_range(3..6).unwrap();
+        assert_eq!(
+            middle_page.next_back().unwrap().name().unwrap(),
+            names[5].as_str()
+        );
+        assert_eq!(
+            middle_page.nth(1).unwrap().name().unwrap(),
+            names[4].as_str()
+        );
+        assert!(middle_page.next().is_none());
+
+        // Empty ranges, including one right at the end
+        for index in [0, 3, 7] {
+            assert!(page(index..index).is_empty());
+        }
+
+        // Start past the end, end past the end, and an inverted range
+        for range in [8..9, 5..8, Range { start: 4, end: 2 }] {
+            assert_eq!(
+                map.namespace_entries_range(range.clone()).unwrap_err(),
+                NtApiSetError::EntryIndexRangeOutOfBounds { range, count: 7 }
+            );
+        }
+    }
+
+    #[test]
+    fn test_value_entries_range() {
+        let hosts = [
+            ("", "foo.dll"),
+            ("advapi32.dll", "foo_advapi.dll"),
+            ("kernel32.dll", "foo_kernel.dll"),
+            ("ole32.dll", "foo_ole.dll"),
+            ("user32.dll", "foo_user.dll"),
+        ];
+        let bytes = build(&[("api-ms-win-core-foo-l1-1-0", &hosts)]);
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let namespace_entry = map.namespace_entry(0).unwrap().unwrap();
+        let page = |range: Range<usize>| {
+            namespace_entry
+                .value_entries_range(range)
+                .unwrap()
+                .map(|value_entry| {
+                    (
+                        value_entry.name().unwrap().to_string().unwrap(),
+                        value_entry.value().unwrap().to_string().unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let expected = |range: Range<usize>| {
+            hosts[range]
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        // First page
+        assert_eq!(page(0..2), expected(0..2));
+
+        // Last partial page
+        assert_eq!(page(4..5), expected(4..5));
+
+        // Entries of a page keep their position in the whole array.
+        let indexes = namespace_entry
+            .value_entries_range(2..4)
+            .unwrap()
+            .map(|value_entry| value_entry.array_index())
+            .collect::<Vec<_>>();
+        assert_eq!(indexes, [2, 3]);
+
+        // Empty ranges, including one right at the end
+        for index in [0, 2, 5] {
+            assert!(page(index..index).is_empty());
+        }
+
+        // Start past the end, end past the end, and an inverted range
+        for range in [6..6, 3..6, Range { start: 3, end: 1 }] {
+            assert_eq!(
+                namespace_entry
+                    .value_entries_range(range.clone())
+                    .unwrap_err(),
+                NtApiSetError::EntryIndexRangeOutOfBounds { range, count: 5 }
+            );
+        }
+    }
+
+    #[test]
+    fn test_hash_name_and_header() {
+        let entries: &[(&str, &[(&str, &str)])] = &[
+            ("api-ms-win-core-foo-l1-1-0", &[("", "foo.dll")]),
+            ("api-ms-win-core-sysinfo-l1-2-3", &[("", "kernelbase.dll")]),
+        ];
+        let bytes = build(entries);
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+        assert_eq!(map.version(), 6);
+        assert_eq!(map.size() as usize, bytes.len());
+        assert_eq!(map.count(), 2);
+        assert_eq!(map.hash_factor(), 0x1f);
+        assert_eq!(
+            map.namespace_entry_offset() as usize,
+            map.namespace_entry(0).unwrap().unwrap().offset()
+        );
+        assert_eq!(
+            map.hash_entry_offset() as usize,
+            map.hash_entries().unwrap().next().unwrap().offset()
+        );
+
+        // Known values, computed independently.
+        assert_eq!(map.hash_name("api-ms-win-core-sysinfo-l1-2"), 0x46ca_fe65);
+        assert_eq!(map.hash_name("API-MS-Win-Core-SysInfo-L1-2"), 0x46ca_fe65);
+        assert_eq!(map.hash_name(""), 0);
+
+        // Every Hash Entry holds the hash of the hashed name of its Namespace Entry.
+        for result in map.hash_joined().unwrap() {
+            let (hash_entry, namespace_entry) = result.unwrap();
+            let hashed_name = namespace_entry.hashed_name().unwrap().to_string().unwrap();
+            assert_eq!(hash_entry.hash(), map.hash_name(&hashed_name));
+        }
+
+        let bytes = ApiSetMapBuilder::new().hash_factor(0x25).build().unwrap();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        assert_eq!(map.hash_factor(), 0x25);
+        assert_eq!(map.hash_name("api-ms-win-core-sysinfo-l1-2"), 0x1d5e_6aa1);
+
+        // API Set Maps before version 6 have no hash table.
+        let bytes = build_legacy_map(4, &[("ms-win-core-foo-l1-1-0", &[("", "foo.dll")])]);
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        assert_eq!(map.version(), 4);
+        assert_eq!(map.size() as usize, bytes.len());
+        assert_eq!(map.count(), 1);
+        assert_eq!(map.hash_entry_offset(), 0);
+        assert_eq!(map.hash_factor(), 0);
+    }
+
+    /// Checks that `nth` and `next_back` of `iter` agree with its forward iteration, for every index and both ends.
+    fn check_iterator_agrees<I, K>(iter: I, key: impl Fn(I::Item) -> K)
+    where
+        I: Clone + DoubleEndedIterator + ExactSizeIterator,
+        K: Clone + core::fmt::Debug + PartialEq,
+    {
+        let all = iter.clone().map(&key).collect::<Vec<_>>();
+        assert_eq!(iter.len(), all.len());
+
+        for n in 0..all.len() + 2 {
+            let mut nth_iter = iter.clone();
+            assert_eq!(nth_iter.nth(n).map(&key), all.get(n).cloned());
+            assert_eq!(
+                nth_iter.map(&key).collect::<Vec<_>>(),
+                all.get(n + 1..).unwrap_or_default()
+            );
+        }
+        assert!(iter.clone().nth(usize::MAX).is_none());
+
+        let mut back_iter = iter.clone();
+        for expected in all.iter().rev() {
+            assert_eq!(back_iter.next_back().map(&key).as_ref(), Some(expected));
+        }
+        assert!(back_iter.next_back().is_none());
+        assert!(back_iter.next().is_none());
+
+        // Alternating between both ends yields every entry exactly once.
+        let mut both_iter = iter;
+        let (mut front, mut back) = (Vec::new(), Vec::new());
+        loop {
+            match both_iter.next() {
+                Some(item) => front.push(key(item)),
+                None => break,
+            }
+            match both_iter.next_back() {
+                Some(item) => back.push(key(item)),
+                None => break,
+            }
+        }
+        front.extend(back.into_iter().rev());
+        assert_eq!(front, all);
+    }
+
+    fn check_accessors_agree(map: &ApiSetMap) {
+        let namespace_entries = map.namespace_entries().unwrap().collect::<Vec<_>>();
+        check_iterator_agrees(map.namespace_entries().unwrap(), |entry| entry.offset());
+
+        for (index, namespace_entry) in namespace_entries.iter().enumerate() {
+            assert_eq!(
+                map.namespace_entry(index).unwrap().unwrap().offset(),
+                namespace_entry.offset()
+            );
+
+            let value_entries = namespace_entry.value_entries().unwrap().collect::<Vec<_>>();
+            check_iterator_agrees(namespace_entry.value_entries().unwrap(), |entry| {
+                entry.offset()
+            });
+
+            for n in 0..value_entries.len() + 2 {
+                assert_eq!(
+                    namespace_entry
+                        .value_entry(n)
+                        .map(|entry| entry.unwrap().offset()),
+                    value_entries.get(n).map(|entry| entry.offset())
+                );
+            }
+            assert_eq!(
+                namespace_entry
+                    .default_value_entry()
+                    .map(|entry| entry.unwrap().offset()),
+                value_entries.first().map(|entry| entry.offset())
+            );
+        }
+
+        for index in [
+            namespace_entries.len(),
+            namespace_entries.len() + 1,
+            usize::MAX,
+        ] {
+            assert!(map.namespace_entry(index).is_none());
+        }
+
+        if map.version() == 6 {
+            check_iterator_agrees(map.hash_entries().unwrap(), |entry| entry.offset());
+            check_iterator_agrees(map.hash_joined().unwrap(), |result| {
+                let (hash_entry, namespace_entry) = result.unwrap();
+                (hash_entry.offset(), namespace_entry.offset())
+            });
+        }
+    }
+
+    #[test]
+    fn test_accessors_agree_with_iteration() {
+        let entries: &[(&str, &[(&str, &str)])] = &[
+            ("api-ms-win-core-bar-l1-1-0", &[]),
+            ("api-ms-win-core-foo-l1-1-0", &[("", "foo.dll")]),
+            (
+                "api-ms-win-core-qux-l1-1-0",
+                &[
+                    ("", "qux.dll"),
+                    ("advapi32.dll", "advapi32_qux.dll"),
+                    ("kernel32.dll", "kernelbase.dll"),
+                ],
+            ),
+            ("ext-ms-win-baz-l1-1-0", &[("", "baz.dll")]),
+        ];
+
+        let bytes = build(entries);
+        check_accessors_agree(&ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap());
+
+        let empty_bytes = build(&[]);
+        check_accessors_agree(&ApiSetMap::try_from_apiset_section_bytes(&empty_bytes).unwrap());
+
+        let mut legacy_entries = entries
+            .iter()
+            .map(|(name, value_entries)| (name.split_once('-').unwrap().1, *value_entries))
+            .collect::<Vec<_>>();
+        legacy_entries.sort_unstable_by_key(|(name, _)| *name);
+        for version in [2, 4] {
+            let legacy_bytes = build_legacy_map(version, &legacy_entries);
+            check_accessors_agree(
+                &ApiSetMap::try_from_apiset_section_bytes(&legacy_bytes).unwrap(),
+            );
+        }
+    }
+
+    fn check_legacy_map(version: u32) {
+        let bytes = build_legacy_map(
+            version,
+            &[
+                ("ms-win-core-bar-l1-1-0", &[("", "bar.dll")]),
+                (
+                    "ms-win-core-foo-l1-1-0",
+                    &[("", "foo.dll"), ("kernel32.dll", "kernelbase.dll")],
+                ),
+                ("ms-win-core-qux-l1-1-0", &[]),
+            ],
+        );
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        assert_eq!(map.version(), version);
+        assert_eq!(map.flags(), ApiSetMapFlags::empty());
+        assert_eq!(map.hash_entries().unwrap().count(), 0);
+        map.validate().unwrap();
+
+        let names = map
+            .namespace_entries()
+            .unwrap()
+            .map(|namespace_entry| namespace_entry.name().unwrap().to_string().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "ms-win-core-bar-l1-1-0",
+                "ms-win-core-foo-l1-1-0",
+                "ms-win-core-qux-l1-1-0"
+            ]
+        );
+
+        let namespace_entry = map
+            .find_namespace_entry("api-ms-win-core-foo-l1-1-0")
+            .unwrap()
+            .unwrap();
+        let value_entries = namespace_entry
+            .value_entries()
+            .unwrap()
+            .map(|value_entry| {
+                (
+                    value_entry.name().unwrap().to_string().unwrap(),
+                    value_entry.value().unwrap().to_string().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            value_entries,
+            [
+                ("".into(), "foo.dll".into()),
+                ("kernel32.dll".into(), "kernelbase.dll".into())
+            ]
+        );
+
+        // Names are stored without their prefix, but looked up with it.
+        assert_eq!(
+            map.resolve("api-ms-win-core-bar-l1-1-0.dll", None)
+                .unwrap()
+                .unwrap(),
+            "bar.dll"
+        );
+        assert_eq!(
+            map.resolve("API-MS-WIN-CORE-FOO-L1-1-0.dll", Some("kernel32.dll"))
+                .unwrap()
+                .unwrap(),
+            "kernelbase.dll"
+        );
+        assert!(map
+            .find_namespace_entry("api-ms-win-core-baz-l1-1-0")
+            .is_none());
+        assert!(map
+            .resolve("api-ms-win-core-qux-l1-1-0.dll", None)
+            .is_none());
+    }
+
+    #[test]
+    fn test_version_2() {
+        check_legacy_map(2);
+    }
+
+    #[test]
+    fn test_version_3_unsupported() {
+        // Version 3 of Windows 8 is rejected instead of being misparsed with the layout of another version.
+        let mut bytes = build_legacy_map(4, &[("ms-win-core-foo-l1-1-0", &[("", "foo.dll")])]);
+        bytes[..4].copy_from_slice(&3u32.to_le_bytes());
+
+        assert_eq!(
+            ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap_err(),
+            NtApiSetError::UnsupportedVersion { version: 3 }
+        );
+    }
+
+    #[test]
+    fn test_version_4() {
+        check_legacy_map(4);
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::iter::FusedIterator;
+
+use crate::cursor::ApiSetMappingCursor;
+use crate::error::Result;
+use crate::map::ApiSetMap;
+use crate::namespace_entry::{ApiSetNamespaceEntries, ApiSetNamespaceEntry};
+use crate::value_entry::{ApiSetValueEntries, ApiSetValueEntry};
+
+/// Iterator over all [`ApiSetValueEntry`]s of an [`ApiSetMap`], along with the [`ApiSetNamespaceEntry`] each of them belongs to.
+///
+/// This iterator is returned by [`ApiSetMap::mappings`] and [`ApiSetMap::resume_mappings`].
+/// It flattens the Value Entries of all Namespace Entries in order, so every item is a single mapping from an API Set
+/// (and optionally an importing module) to a host module.
+/// Namespace Entries without Value Entries don't yield any item.
+/// A Namespace Entry whose Value Entries cannot be read is returned as an error item, and iteration then continues with the next one.
+#[derive(Clone, Debug)]
+pub struct ApiSetMappings<'a> {
+    namespace_entries: ApiSetNamespaceEntries<'a>,
+    current: Option<(ApiSetNamespaceEntry<'a>, ApiSetValueEntries<'a>)>,
+    namespace_entry_index: usize,
+    value_entry_index: usize,
+}
+
+impl<'a> ApiSetMappings<'a> {
+    pub(crate) fn new(namespace_entries: ApiSetNamespaceEntries<'a>) -> Self {
+        let namespace_entry_index = namespace_entries.position();
+
+        Self {
+            namespace_entries,
+            current: None,
+            namespace_entry_index,
+            value_entry_index: 0,
+        }
+    }
+
+    /// Returns an [`ApiSetMappingCursor`] that saves the current position of this iterator.
+    ///
+    /// Pass it to [`ApiSetMap::resume_mappings`] to continue iterating from this position later.
+    /// Computing the fingerprint of the cursor requires reading the entire `.apiset` section.
+    pub fn cursor(&self) -> ApiSetMappingCursor {
+        let section_bytes = self.namespace_entries.section_bytes();
+
+        match self.current {
+            Some(_) => ApiSetMappingCursor::new(
+                self.namespace_entry_index,
+                self.value_entry_index,
+                section_bytes,
+            ),
+            None => ApiSetMappingCursor::new(self.namespace_entries.position(), 0, section_bytes),
+        }
+    }
+
+    /// Continues at Value Entry `value_entry_index` of the next Namespace Entry, as saved in an [`ApiSetMappingCursor`].
+    pub(crate) fn skip_value_entries(mut self, value_entry_index: usize) -> Result<Self> {
+        if value_entry_index == 0 {
+            return Ok(self);
+        }
+
+        if let Some(namespace_entry) = self.namespace_entries.next() {
+            let value_count = namespace_entry.value_count()?;
+            let value_entries =
+                namespace_entry.value_entries_range(value_entry_index..value_count)?;
+
+            self.current = Some((namespace_entry, value_entries));
+            self.value_entry_index = value_entry_index;
+        }
+
+        Ok(self)
+    }
+}
+
+impl<'a> Iterator for ApiSetMappings<'a> {
+    type Item = Result<(ApiSetNamespaceEntry<'a>, ApiSetValueEntry<'a>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((namespace_entry, value_entries)) = &mut self.current {
+                if let Some(value_entry) = value_entries.next() {
+                    self.value_entry_index += 1;
+                    return Some(Ok((namespace_entry.clone(), value_entry)));
+                }
+            }
+
+            self.namespace_entry_index = self.namespace_entries.position();
+            self.value_entry_index = 0;
+            let namespace_entry = self.namespace_entries.next()?;
+            self.current = None;
+
+            let value_entries = iter_try!(namespace_entry.value_entries());
+            self.current = Some((namespace_entry, value_entries));
+        }
+    }
+}
+
+impl<'a> FusedIterator for ApiSetMappings<'a> {}
+
+impl<'a> ApiSetMap<'a> {
+    /// Returns an iterator over all mappings of this [`ApiSetMap`], i.e. all Value Entries along with their Namespace Entries.
+    ///
+    /// See [`ApiSetMappings`] for how errors are reported.
+    pub fn mappings(&self) -> Result<ApiSetMappings<'a>> {
+        Ok(ApiSetMappings::new(self.namespace_entries()?))
+    }
+}
@@ -0,0 +1,261 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::vec::Vec;
+
+use crate::error::{NtApiSetError, Result};
+use crate::helpers::try_reserve;
+use crate::map::ApiSetMap;
+use crate::owned::OwnedApiSetMap;
+use crate::schema::Schema;
+
+/// Size of the chunks in which [`ApiSetMap::read_from_memory`] reads an API Set Map, which is the page size of Windows.
+const CHUNK_SIZE: u64 = 0x1000;
+
+/// Reads memory at virtual addresses, e.g. of a process being debugged.
+///
+/// This is used by [`ApiSetMap::read_from_memory`].
+pub trait MemRead {
+    /// Fills `buf` with the memory at the virtual address `va`.
+    fn read(&self, va: u64, buf: &mut [u8]) -> core::result::Result<(), MemReadError>;
+}
+
+/// Error returned by a [`MemRead`] implementation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MemReadError {
+    /// None of the requested bytes could be read.
+    Unreadable,
+    /// Only the first `read` bytes could be read, e.g. because the following page is not mapped.
+    Partial {
+        /// Number of bytes that could be read.
+        read: usize,
+    },
+}
+
+impl<'a> ApiSetMap<'a> {
+    /// Reads an API Set Map from the memory at the virtual address `map_va` and parses it into an [`OwnedApiSetMap`].
+    ///
+    /// This is intended for debugger plugins, which only have a callback to read the memory of the debugged process.
+    /// The header is read first, and the size declared by it determines how many bytes are read afterwards.
+    /// The remaining bytes are read in chunks that don't cross page boundaries.
+    ///
+    /// Memory that cannot be read is reported as [`NtApiSetError::MemoryReadFailed`],
+    /// and memory that can only be read partially as [`NtApiSetError::MemoryReadPartial`].
+    /// API Set Maps of version 2 (Windows 7) don't declare their size and are rejected with [`NtApiSetError::UnsupportedVersion`].
+    /// A declared size that is smaller than the header is rejected with [`NtApiSetError::InvalidMapHeaderSize`].
+    /// As the declared size comes from untrusted memory, failing to allocate a buffer for it is reported as
+    /// [`NtApiSetError::AllocationFailed`] instead of aborting.
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn read_from_memory<R>(reader: &R, map_va: u64) -> Result<OwnedApiSetMap>
+    where
+        R: MemRead + ?Sized,
+    {
+        let result = read_map(reader, map_va);
+
+        #[cfg(feature = "metrics")]
+        crate::instrument::record_parse(&result);
+
+        result
+    }
+}
+
+fn read_map<R>(reader: &R, map_va: u64) -> Result<OwnedApiSetMap>
+where
+    R: MemRead + ?Sized,
+{
+    // The version is the first field in every header.
+    let mut version_bytes = [0u8; 4];
+    read(reader, map_va, &mut version_bytes)?;
+    let version = u32::from_le_bytes(version_bytes);
+    let schema = match Schema::from_version(version) {
+        Some(Schema::V2) | None => return Err(NtApiSetError::UnsupportedVersion { version }),
+        Some(schema) => schema,
+    };
+
+    let header_size = schema.map_header_size();
+    let mut header_bytes = Vec::new();
+    try_reserve(&mut header_bytes, header_size)?;
+    header_bytes.resize(header_size, 0);
+    read(reader, map_va, &mut header_bytes)?;
+    let size = ApiSetMap::parse_apiset_section_bytes(&header_bytes)?.size() as usize;
+
+    if size < header_size {
+        return Err(NtApiSetError::InvalidMapHeaderSize {
+            expected: header_size,
+            actual: size,
+        });
+    }
+
+    let mut section_bytes = header_bytes;
+    try_reserve(&mut section_bytes, size - header_size)?;
+    section_bytes.resize(size, 0);
+
+    let mut offset = header_size;
+    while offset < size {
+        let va = map_va.wrapping_add(offset as u64);
+        let until_page_end = (CHUNK_SIZE - va % CHUNK_SIZE) as usize;
+        let end = size.min(offset + until_page_end);
+
+        read(reader, va, &mut section_bytes[offset..end])?;
+        offset = end;
+    }
+
+    OwnedApiSetMap::parse_apiset_section_bytes(section_bytes)
+}
+
+fn read<R>(reader: &R, va: u64, buf: &mut [u8]) -> Result<()>
+where
+    R: MemRead + ?Sized,
+{
+    reader.read(va, buf).map_err(|e| match e {
+        MemReadError::Unreadable => NtApiSetError::MemoryReadFailed {
+            address: va,
+            length: buf.len(),
+        },
+        MemReadError::Partial { read } => NtApiSetError::MemoryReadPartial {
+            address: va,
+            length: buf.len(),
+            read,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use core::cell::Cell;
+
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+    use crate::fixtures::build_legacy_map;
+    use crate::namespace_entry::ApiSetNamespaceEntryFlags;
+
+    /// Fake address space that only maps `bytes` at `base`, up to `readable_end`.
+    struct FakeMemory {
+        base: u64,
+        bytes: Vec<u8>,
+        readable_end: u64,
+        reads: Cell<usize>,
+    }
+
+    impl FakeMemory {
+        fn new(base: u64, bytes: Vec<u8>) -> Self {
+            let readable_end = base + bytes.len() as u64;
+
+            Self {
+                base,
+                bytes,
+                readable_end,
+                reads: Cell::new(0),
+            }
+        }
+    }
+
+    impl MemRead for FakeMemory {
+        fn read(&self, va: u64, buf: &mut [u8]) -> core::result::Result<(), MemReadError> {
+            self.reads.set(self.reads.get() + 1);
+
+            if va < self.base || va >= self.readable_end {
+                return Err(MemReadError::Unreadable);
+            }
+
+            let start = (va - self.base) as usize;
+            let readable = ((self.readable_end - va) as usize).min(buf.len());
+            buf[..readable].copy_from_slice(&self.bytes[start..start + readable]);
+
+            if readable < buf.len() {
+                Err(MemReadError::Partial { read: readable })
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn fixture() -> Vec<u8> {
+        (0..200)
+            .fold(ApiSetMapBuilder::new(), |builder, i| {
+                builder.add_namespace_entry(
+                    ApiSetNamespaceEntryBuilder::new(
+                        &alloc::format!("api-ms-win-core-foo{i}-l1-1-0"),
+                        ApiSetNamespaceEntryFlags::empty(),
+                    )
+                    .add_value_entry("", "foo.dll"),
+                )
+            })
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_read_from_memory() {
+        let bytes = fixture();
+        assert!(bytes.len() > 3 * CHUNK_SIZE as usize);
+
+        let memory = FakeMemory::new(0x7ffe_0000_0800, bytes.clone());
+        let owned_map = ApiSetMap::read_from_memory(&memory, memory.base).unwrap();
+
+        assert_eq!(owned_map.section_bytes(), bytes);
+        assert_eq!(
+            owned_map
+                .resolve("api-ms-win-core-foo42-l1-1-0.dll", None)
+                .unwrap()
+                .unwrap(),
+            "foo.dll"
+        );
+
+        // Version, header, and one read per touched page.
+        let pages = (0x800 + bytes.len() as u64 + CHUNK_SIZE - 1) / CHUNK_SIZE;
+        assert_eq!(memory.reads.get(), 2 + pages as usize);
+    }
+
+    #[test]
+    fn test_read_from_memory_failing_mid_map() {
+        let bytes = fixture();
+        let mut memory = FakeMemory::new(0x10000, bytes);
+        memory.readable_end = 0x12100;
+
+        assert_eq!(
+            ApiSetMap::read_from_memory(&memory, memory.base).unwrap_err(),
+            NtApiSetError::MemoryReadPartial {
+                address: 0x12000,
+                length: 0x1000,
+                read: 0x100,
+            }
+        );
+
+        memory.readable_end = 0x12000;
+        assert_eq!(
+            ApiSetMap::read_from_memory(&memory, memory.base).unwrap_err(),
+            NtApiSetError::MemoryReadFailed {
+                address: 0x12000,
+                length: 0x1000,
+            }
+        );
+    }
+
+    #[test]
+    fn test_read_from_memory_unreadable() {
+        let memory = FakeMemory::new(0x10000, fixture());
+
+        assert_eq!(
+            ApiSetMap::read_from_memory(&memory, 0x20000).unwrap_err(),
+            NtApiSetError::MemoryReadFailed {
+                address: 0x20000,
+                length: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn test_read_from_memory_version_2() {
+        let memory = FakeMemory::new(
+            0x10000,
+            build_legacy_map(2, &[("ms-win-core-foo-l1-1-0", &[("", "foo.dll")])]),
+        );
+
+        assert_eq!(
+            ApiSetMap::read_from_memory(&memory, memory.base).unwrap_err(),
+            NtApiSetError::UnsupportedVersion { version: 2 }
+        );
+    }
+}
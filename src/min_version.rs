@@ -0,0 +1,207 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::error::Result;
+use crate::map::ApiSetMap;
+
+/// Result of [`min_supported_build`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MinVersionReport<L> {
+    first_passing: Option<usize>,
+    builds: Vec<BuildReport<L>>,
+}
+
+impl<L> MinVersionReport<L> {
+    /// Returns the evaluation results of all builds, in the order they were passed to [`min_supported_build`].
+    pub fn builds(&self) -> &[BuildReport<L>] {
+        &self.builds
+    }
+
+    /// Returns the first build where every API Set import resolves, or `None` if there is no such build.
+    pub fn first_passing(&self) -> Option<&BuildReport<L>> {
+        self.first_passing.map(|index| &self.builds[index])
+    }
+}
+
+/// Evaluation result of a single build within a [`MinVersionReport`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BuildReport<L> {
+    label: L,
+    blocking_contracts: Vec<String>,
+}
+
+impl<L> BuildReport<L> {
+    /// Returns the API Set imports that don't resolve in this build.
+    ///
+    /// They are returned in lowercase and without a file extension.
+    pub fn blocking_contracts(&self) -> &[String] {
+        &self.blocking_contracts
+    }
+
+    /// Returns the label of this build.
+    pub fn label(&self) -> &L {
+        &self.label
+    }
+
+    /// Returns `true` if every API Set import resolves in this build.
+    pub fn passed(&self) -> bool {
+        self.blocking_contracts.is_empty()
+    }
+}
+
+/// Determines the oldest build where every API Set import of a PE file resolves.
+///
+/// `pe_imports` are the names of all modules imported by the PE file (e.g. `api-ms-win-core-sysinfo-l1-1-0.dll`).
+/// Imports that don't start with "api-" or "ext-" are no API Sets and are ignored.
+/// A trailing ".dll" and the case of the imports don't matter.
+///
+/// `maps` are the API Set Maps of all builds to consider, each with a label of your choice, ordered from oldest to newest.
+/// Every build is evaluated, and the first one where all API Set imports resolve to a non-empty host module
+/// is reported as [`MinVersionReport::first_passing`].
+/// Imports are resolved via [`ApiSetMap::resolve_import`], so a newer minor version of a contract (e.g. `-l1-1-1`)
+/// is served by an entry of an older one (e.g. `-l1-1-0`), just like the loader does.
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub fn min_supported_build<L>(
+    pe_imports: &[String],
+    maps: &[(L, &ApiSetMap)],
+) -> Result<MinVersionReport<L>>
+where
+    L: Clone,
+{
+    let contracts = pe_imports
+        .iter()
+        .filter_map(|import| {
+            let import = import.to_ascii_lowercase();
+            let contract = import.strip_suffix(".dll").unwrap_or(&import);
+
+            if contract.starts_with("api-") || contract.starts_with("ext-") {
+                Some(String::from(contract))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut first_passing = None;
+    let mut builds = Vec::with_capacity(maps.len());
+
+    for (label, map) in maps {
+        let mut blocking_contracts = Vec::new();
+
+        for contract in &contracts {
+            if !resolves_to_host(map, contract)? {
+                blocking_contracts.push(contract.clone());
+            }
+        }
+
+        if first_passing.is_none() && blocking_contracts.is_empty() {
+            first_passing = Some(builds.len());
+        }
+
+        builds.push(BuildReport {
+            label: label.clone(),
+            blocking_contracts,
+        });
+    }
+
+    Ok(MinVersionReport {
+        first_passing,
+        builds,
+    })
+}
+
+fn resolves_to_host(map: &ApiSetMap, contract: &str) -> Result<bool> {
+    // Resolve the contract like the loader does, so that e.g. an import of `-l1-1-1` is served by an entry named `-l1-1-0`.
+    let namespace_entry = match map.resolve_import(contract) {
+        Some(namespace_entry) => namespace_entry?,
+        None => return Ok(false),
+    };
+
+    let default_value_entry = match namespace_entry.value_entries()?.next() {
+        Some(value_entry) => value_entry,
+        None => return Ok(false),
+    };
+
+    Ok(!default_value_entry.value()?.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+    use crate::namespace_entry::ApiSetNamespaceEntryFlags;
+
+    fn build(entries: &[(&str, &str)]) -> Vec<u8> {
+        entries
+            .iter()
+            .fold(ApiSetMapBuilder::new(), |builder, (name, host)| {
+                builder.add_namespace_entry(
+                    ApiSetNamespaceEntryBuilder::new(name, ApiSetNamespaceEntryFlags::empty())
+                        .add_value_entry("", host),
+                )
+            })
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_min_supported_build() {
+        // The contract exists, but is not implemented yet.
+        let old_bytes = build(&[
+            ("api-ms-win-core-foo-l1-1-0", ""),
+            ("ext-ms-win-bar-l1-1-0", "bar.dll"),
+        ]);
+        // Only an older minor version of the contract exists, which the loader uses all the same.
+        let middle_bytes = build(&[
+            ("api-ms-win-core-foo-l1-1-0", "foo.dll"),
+            ("ext-ms-win-bar-l1-1-0", "bar.dll"),
+        ]);
+        let new_bytes = build(&[
+            ("api-ms-win-core-foo-l1-1-1", "foo.dll"),
+            ("ext-ms-win-bar-l1-1-0", "bar.dll"),
+        ]);
+
+        let old = ApiSetMap::try_from_apiset_section_bytes(&old_bytes).unwrap();
+        let middle = ApiSetMap::try_from_apiset_section_bytes(&middle_bytes).unwrap();
+        let new = ApiSetMap::try_from_apiset_section_bytes(&new_bytes).unwrap();
+
+        let pe_imports = [
+            String::from("API-MS-WIN-CORE-FOO-L1-1-1.DLL"),
+            String::from("ext-ms-win-bar-l1-1-0.dll"),
+            String::from("kernel32.dll"),
+        ];
+        let report = min_supported_build(
+            &pe_imports,
+            &[("old", &old), ("middle", &middle), ("new", &new)],
+        )
+        .unwrap();
+
+        assert_eq!(report.first_passing().unwrap().label(), &"middle");
+
+        let builds = report.builds();
+        assert_eq!(
+            builds[0].blocking_contracts(),
+            ["api-ms-win-core-foo-l1-1-1"]
+        );
+        assert!(builds[1].passed());
+        assert!(builds[2].passed());
+    }
+
+    #[test]
+    fn test_min_supported_build_none_passing() {
+        let bytes = build(&[("api-ms-win-core-foo-l1-1-0", "foo.dll")]);
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+        let pe_imports = [String::from("api-ms-win-core-bar-l1-1-0.dll")];
+        let report = min_supported_build(&pe_imports, &[(1, &map)]).unwrap();
+
+        assert!(report.first_passing().is_none());
+        assert_eq!(
+            report.builds()[0].blocking_contracts(),
+            ["api-ms-win-core-bar-l1-1-0"]
+        );
+    }
+}
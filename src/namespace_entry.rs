@@ -1,31 +1,128 @@
 // Copyright 2023 Colin Finck <colin@reactos.org>
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+use core::cmp::Ordering;
 use core::iter::FusedIterator;
-use core::mem;
 use core::ops::Range;
 
-use bitflags::bitflags;
 use nt_string::u16strle::U16StrLe;
-use zerocopy::{FromBytes, LayoutVerified, LittleEndian, Unaligned, U32};
+use zerocopy::{AsBytes, FromBytes, LayoutVerified, LittleEndian, Unaligned, U32};
 
+use crate::cursor::ApiSetCursor;
 use crate::error::{NtApiSetError, Result};
-use crate::value_entry::{ApiSetValueEntries, ApiSetValueEntryHeader};
+#[cfg(feature = "heapless")]
+use crate::helpers::to_fixed_string;
+use crate::helpers::{
+    clamp_entry_array, cmp_ignore_ascii_case, cmp_ignore_ascii_case_str, entry_array_end,
+    entry_subrange, eq_ignore_ascii_case_str, starts_with_ignore_ascii_case_str,
+};
+use crate::schema::Schema;
+use crate::value_entry::{read_value_array_header, ApiSetValueEntries, ApiSetValueEntry};
+
+#[derive(AsBytes, Debug, FromBytes, Unaligned)]
+#[repr(packed)]
+pub(crate) struct ApiSetNamespaceEntryHeader {
+    /// See [`ApiSetNamespaceEntryFlags`]
+    pub(crate) flags: U32<LittleEndian>,
+    pub(crate) name_offset: U32<LittleEndian>,
+    pub(crate) name_length: U32<LittleEndian>,
+    pub(crate) hashed_length: U32<LittleEndian>,
+    pub(crate) array_offset: U32<LittleEndian>,
+    pub(crate) array_count: U32<LittleEndian>,
+}
+
+#[derive(Debug, FromBytes, Unaligned)]
+#[repr(packed)]
+pub(crate) struct ApiSetNamespaceEntryHeaderV2 {
+    name_offset: U32<LittleEndian>,
+    name_length: U32<LittleEndian>,
+    /// Points to an `ApiSetValueArrayHeaderV2`
+    data_offset: U32<LittleEndian>,
+}
 
 #[allow(dead_code)]
 #[derive(Debug, FromBytes, Unaligned)]
 #[repr(packed)]
-pub(crate) struct ApiSetNamespaceEntryHeader {
+pub(crate) struct ApiSetNamespaceEntryHeaderV4 {
     /// See [`ApiSetNamespaceEntryFlags`]
     flags: U32<LittleEndian>,
     name_offset: U32<LittleEndian>,
     name_length: U32<LittleEndian>,
-    hashed_length: U32<LittleEndian>,
-    array_offset: U32<LittleEndian>,
-    array_count: U32<LittleEndian>,
+    alias_offset: U32<LittleEndian>,
+    alias_length: U32<LittleEndian>,
+    /// Points to an `ApiSetValueArrayHeaderV4`
+    data_offset: U32<LittleEndian>,
 }
 
-bitflags! {
+/// Fields of a Namespace Entry, independent of the [`Schema`] it has been read from.
+#[derive(Clone, Copy, Debug)]
+struct NamespaceEntryFields {
+    flags: u32,
+    name_offset: u32,
+    name_length: u32,
+    /// Always 0 before version 6.
+    hashed_length: u32,
+    /// Before version 6, this points to the structure preceding the Value Entries, which holds their count.
+    array_offset: u32,
+    /// Always 0 before version 6.
+    array_count: u32,
+}
+
+impl NamespaceEntryFields {
+    fn read(schema: Schema, bytes: &[u8]) -> Option<Self> {
+        let fields = match schema {
+            Schema::V2 => {
+                let (header, _) =
+                    LayoutVerified::<_, ApiSetNamespaceEntryHeaderV2>::new_unaligned_from_prefix(
+                        bytes,
+                    )?;
+
+                Self {
+                    flags: 0,
+                    name_offset: header.name_offset.get(),
+                    name_length: header.name_length.get(),
+                    hashed_length: 0,
+                    array_offset: header.data_offset.get(),
+                    array_count: 0,
+                }
+            }
+            Schema::V4 => {
+                let (header, _) =
+                    LayoutVerified::<_, ApiSetNamespaceEntryHeaderV4>::new_unaligned_from_prefix(
+                        bytes,
+                    )?;
+
+                Self {
+                    flags: header.flags.get(),
+                    name_offset: header.name_offset.get(),
+                    name_length: header.name_length.get(),
+                    hashed_length: 0,
+                    array_offset: header.data_offset.get(),
+                    array_count: 0,
+                }
+            }
+            Schema::V6 => {
+                let (header, _) =
+                    LayoutVerified::<_, ApiSetNamespaceEntryHeader>::new_unaligned_from_prefix(
+                        bytes,
+                    )?;
+
+                Self {
+                    flags: header.flags.get(),
+                    name_offset: header.name_offset.get(),
+                    name_length: header.name_length.get(),
+                    hashed_length: header.hashed_length.get(),
+                    array_offset: header.array_offset.get(),
+                    array_count: header.array_count.get(),
+                }
+            }
+        };
+
+        Some(fields)
+    }
+}
+
+flags! {
     /// Flags returned by [`ApiSetNamespaceEntry::flags`].
     pub struct ApiSetNamespaceEntryFlags: u32 {
         /// This API Set Namespace Entry is sealed, meaning the loader shall not look for a schema extension.
@@ -46,49 +143,106 @@ bitflags! {
 #[derive(Clone, Debug)]
 pub struct ApiSetNamespaceEntries<'a> {
     section_bytes: &'a [u8],
+    schema: Schema,
+    array_start: usize,
     range: Range<usize>,
+    lenient: bool,
 }
 
 impl<'a> ApiSetNamespaceEntries<'a> {
-    pub(crate) const fn new(section_bytes: &'a [u8], range: Range<usize>) -> Self {
+    pub(crate) const fn new(
+        section_bytes: &'a [u8],
+        schema: Schema,
+        range: Range<usize>,
+        lenient: bool,
+    ) -> Self {
         Self {
             section_bytes,
+            schema,
+            array_start: range.start,
             range,
+            lenient,
         }
     }
+
+    /// Returns an [`ApiSetCursor`] that saves the current position of this iterator.
+    ///
+    /// Pass it to [`ApiSetMap::resume`] to continue iterating from this position later.
+    /// Computing the fingerprint of the cursor requires reading the entire `.apiset` section.
+    ///
+    /// [`ApiSetMap::resume`]: crate::map::ApiSetMap::resume
+    pub fn cursor(&self) -> ApiSetCursor {
+        let end = (self.range.end - self.array_start) / self.schema.namespace_entry_size();
+        ApiSetCursor::new(self.position(), end, self.section_bytes)
+    }
+
+    /// Returns the index of the next Namespace Entry returned by [`next`](Iterator::next).
+    pub(crate) fn position(&self) -> usize {
+        let start = self.range.start.min(self.range.end);
+        (start - self.array_start) / self.schema.namespace_entry_size()
+    }
+
+    pub(crate) fn restrict(mut self, index_range: Range<usize>) -> Result<Self> {
+        self.range = entry_subrange(&self.range, self.schema.namespace_entry_size(), index_range)?;
+        Ok(self)
+    }
+
+    pub(crate) const fn section_bytes(&self) -> &'a [u8] {
+        self.section_bytes
+    }
+
+    fn entry_at(&self, position: usize) -> Option<ApiSetNamespaceEntry<'a>> {
+        let bytes = self.section_bytes.get(position..self.range.end)?;
+        let header = NamespaceEntryFields::read(self.schema, bytes)?;
+
+        Some(ApiSetNamespaceEntry {
+            section_bytes: self.section_bytes,
+            schema: self.schema,
+            position,
+            header,
+            lenient: self.lenient,
+        })
+    }
 }
 
 impl<'a> Iterator for ApiSetNamespaceEntries<'a> {
     type Item = ApiSetNamespaceEntry<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let (header, _) =
-            LayoutVerified::<_, ApiSetNamespaceEntryHeader>::new_unaligned_from_prefix(
-                self.section_bytes.get(self.range.clone())?,
-            )?;
-        let entry = ApiSetNamespaceEntry {
-            section_bytes: self.section_bytes,
-            position: self.range.start,
-            header,
-        };
-        self.range.start += mem::size_of::<ApiSetNamespaceEntryHeader>();
+        let entry = self.entry_at(self.range.start)?;
+        self.range.start += self.schema.namespace_entry_size();
 
         Some(entry)
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let size = self.range.len() / mem::size_of::<ApiSetNamespaceEntryHeader>();
+        let size = self.range.len() / self.schema.namespace_entry_size();
         (size, Some(size))
     }
 
     fn nth(&mut self, n: usize) -> Option<Self::Item> {
         // `n` is arbitrary and usize, so we may hit boundaries here. Check that!
-        let bytes_to_skip = n.checked_mul(mem::size_of::<ApiSetNamespaceEntryHeader>())?;
+        let bytes_to_skip = n.checked_mul(self.schema.namespace_entry_size())?;
         self.range.start = self.range.start.checked_add(bytes_to_skip)?;
         self.next()
     }
 }
 
+impl<'a> DoubleEndedIterator for ApiSetNamespaceEntries<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let entry_size = self.schema.namespace_entry_size();
+        if self.range.len() < entry_size {
+            return None;
+        }
+
+        let position = self.range.end - entry_size;
+        let entry = self.entry_at(position)?;
+        self.range.end = position;
+
+        Some(entry)
+    }
+}
+
 impl<'a> ExactSizeIterator for ApiSetNamespaceEntries<'a> {}
 impl<'a> FusedIterator for ApiSetNamespaceEntries<'a> {}
 
@@ -98,28 +252,44 @@ impl<'a> FusedIterator for ApiSetNamespaceEntries<'a> {}
 ///
 /// [`ApiSetMap`]: crate::map::ApiSetMap
 /// [`ApiSetMap::find_namespace_entry`]: crate::map::ApiSetMap::find_namespace_entry
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct ApiSetNamespaceEntry<'a> {
     section_bytes: &'a [u8],
+    schema: Schema,
     position: usize,
-    header: LayoutVerified<&'a [u8], ApiSetNamespaceEntryHeader>,
+    header: NamespaceEntryFields,
+    lenient: bool,
 }
 
 impl<'a> ApiSetNamespaceEntry<'a> {
     /// Returns flags set for this [`ApiSetNamespaceEntry`] as specified by [`ApiSetNamespaceEntryFlags`].
+    ///
+    /// Bits unknown to this crate are retained and can be inspected via [`ApiSetNamespaceEntryFlags::bits`].
+    /// API Set Maps of version 2 (Windows 7) have no flags, so they are always empty for them.
     pub fn flags(&self) -> ApiSetNamespaceEntryFlags {
-        ApiSetNamespaceEntryFlags::from_bits_truncate(self.header.flags.get())
+        ApiSetNamespaceEntryFlags::from_bits_retain(self.header.flags)
+    }
+
+    /// Returns the flags field of this [`ApiSetNamespaceEntry`] exactly as stored, including any unknown bits.
+    ///
+    /// API Set Maps of version 2 (Windows 7) have no flags, so 0 is returned for them.
+    pub const fn raw_flags(&self) -> u32 {
+        self.header.flags
+    }
+
+    /// Returns the byte offset of this [`ApiSetNamespaceEntry`] within the `.apiset` section.
+    pub const fn offset(&self) -> usize {
+        self.position
     }
 
     /// Returns the name of this API Set Namespace Entry.
     ///
     /// This name should begin with either "api-" or "ext-".
     /// It does not end with a file extension.
+    ///
+    /// API Set Maps before version 6 (Windows 10) store the name without the "api-" or "ext-" prefix.
     pub fn name(&self) -> Result<U16StrLe<'a>> {
-        let start = self.header.name_offset.get() as usize;
-        let length = self.header.name_length.get() as usize;
-        let end = start + length;
-        let range = start..end;
+        let range = self.name_range();
 
         let name_bytes =
             self.section_bytes
@@ -133,15 +303,84 @@ impl<'a> ApiSetNamespaceEntry<'a> {
         Ok(U16StrLe(name_bytes))
     }
 
+    /// Checks whether the [`name`](Self::name) of this API Set Namespace Entry equals `other`, ignoring ASCII case.
+    ///
+    /// This compares the UTF-16 code units directly and doesn't allocate.
+    pub fn name_eq_ignore_case(&self, other: &str) -> Result<bool> {
+        Ok(eq_ignore_ascii_case_str(&self.name()?, other))
+    }
+
+    /// Checks whether the [`name`](Self::name) of this API Set Namespace Entry begins with `prefix`, ignoring ASCII case.
+    ///
+    /// This is useful for filtering Namespace Entries (e.g. by `api-ms-win-core-`) without allocating.
+    /// Note that API Set Maps before version 6 store the name without the "api-" or "ext-" prefix.
+    pub fn name_starts_with_ignore_case(&self, prefix: &str) -> Result<bool> {
+        Ok(starts_with_ignore_ascii_case_str(&self.name()?, prefix))
+    }
+
+    /// Returns a copy of the [`name`](Self::name) of this API Set Namespace Entry in a fixed-capacity string.
+    ///
+    /// This allows to keep the name beyond the lifetime of the section bytes without allocating.
+    /// `N` is the capacity in UTF-8 bytes.
+    /// If the name doesn't fit, [`NtApiSetError::StringTooLong`] is returned.
+    #[cfg(feature = "heapless")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "heapless")))]
+    pub fn name_to_fixed<const N: usize>(&self) -> Result<heapless::String<N>> {
+        to_fixed_string(&self.name()?, self.name_range())
+    }
+
+    /// Returns the part of the [`name`](Self::name) of this API Set Namespace Entry that has been hashed for the hash table.
+    ///
+    /// This is the name up to but not including the last hyphen, truncated to [`hashed_length`](Self::hashed_length) bytes.
+    /// If the hashed length exceeds the name, [`NtApiSetError::HashedLengthOutOfBounds`] is returned.
+    /// API Set Maps before version 6 have no hash table, so an empty string is returned for them.
+    pub fn hashed_name(&self) -> Result<U16StrLe<'a>> {
+        let name = self.name()?;
+        let hashed_length = self.hashed_length();
+        let hashed_name =
+            name.0
+                .get(..hashed_length)
+                .ok_or(NtApiSetError::HashedLengthOutOfBounds {
+                    hashed_length,
+                    name_length: name.0.len(),
+                    entry_offset: self.position,
+                })?;
+
+        Ok(U16StrLe(hashed_name))
+    }
+
+    /// Returns the length in bytes of the part of the [`name`](Self::name) that has been hashed for the hash table.
+    ///
+    /// See [`hashed_name`](Self::hashed_name) for details.
+    /// API Set Maps before version 6 have no hash table, so 0 is returned for them.
+    pub const fn hashed_length(&self) -> usize {
+        self.header.hashed_length as usize
+    }
+
+    pub(crate) fn name_range(&self) -> Range<usize> {
+        let start = self.header.name_offset as usize;
+        let length = self.header.name_length as usize;
+        start..start.saturating_add(length)
+    }
+
     /// Returns an iterator over the [`ApiSetValueEntry`]s of this [`ApiSetNamespaceEntry`].
     ///
     /// These entries describe the mapping destination of an API Set Namespace Entry.
     ///
+    /// If the API Set Map has been created by [`ApiSetMap::try_from_apiset_section_bytes_lenient`],
+    /// the Value Entries are clamped to the ones that fit entirely into the `.apiset` section.
+    ///
     /// [`ApiSetValueEntry`]: crate::value_entry::ApiSetValueEntry
+    /// [`ApiSetMap::try_from_apiset_section_bytes_lenient`]: crate::map::ApiSetMap::try_from_apiset_section_bytes_lenient
     pub fn value_entries(&self) -> Result<ApiSetValueEntries<'a>> {
-        let start = self.header.array_offset.get() as usize;
-        let count = self.header.array_count.get() as usize;
-        let end = start + mem::size_of::<ApiSetValueEntryHeader>() * count;
+        let (start, count) = self.value_entry_array()?;
+        let entry_size = self.schema.value_entry_size();
+
+        if self.lenient {
+            return Ok(self.clamped_value_entries(start, count));
+        }
+
+        let end = entry_array_end(start, entry_size, count);
         let range = start..end;
 
         self.section_bytes
@@ -151,6 +390,472 @@ impl<'a> ApiSetNamespaceEntry<'a> {
                 actual: self.section_bytes.len(),
             })?;
 
-        Ok(ApiSetValueEntries::new(self.section_bytes, range))
+        Ok(self.value_entries_at(range))
+    }
+
+    /// Returns the Value Entries of the array declared by `start` and `count` that fit entirely into the `.apiset` section.
+    pub(crate) fn clamped_value_entries(
+        &self,
+        start: usize,
+        count: usize,
+    ) -> ApiSetValueEntries<'a> {
+        let range = clamp_entry_array(
+            start,
+            self.schema.value_entry_size(),
+            count,
+            self.section_bytes.len(),
+        );
+        self.value_entries_at(range)
+    }
+
+    fn value_entries_at(&self, range: Range<usize>) -> ApiSetValueEntries<'a> {
+        ApiSetValueEntries::new(
+            self.section_bytes,
+            self.schema,
+            range,
+            self.position,
+            self.name_range(),
+        )
+    }
+
+    pub(crate) const fn schema(&self) -> Schema {
+        self.schema
+    }
+
+    /// Returns the start offset and the declared number of entries of the Value Entry array.
+    ///
+    /// Before version 6, this requires reading the Value Entry array header, which may fail.
+    pub(crate) fn value_entry_array(&self) -> Result<(usize, usize)> {
+        if self.schema.value_array_header_size() > 0 {
+            read_value_array_header(self.section_bytes, self.schema, self.value_array_offset())
+        } else {
+            Ok((
+                self.header.array_offset as usize,
+                self.header.array_count as usize,
+            ))
+        }
+    }
+
+    /// Returns the [`ApiSetValueEntry`] that the Windows loader picks when `importing_module` imports this API Set.
+    ///
+    /// The host-specific Value Entries are searched case-insensitively for `importing_module` (e.g. `kernel32.dll`).
+    /// If there is no match or `importing_module` is `None`, the first (default) Value Entry is returned.
+    /// API Sets without any mapping have no Value Entries at all, in which case `None` is returned.
+    ///
+    /// [`ApiSetValueEntry`]: crate::value_entry::ApiSetValueEntry
+    pub fn resolve_value_entry(
+        &self,
+        importing_module: Option<&str>,
+    ) -> Option<Result<ApiSetValueEntry<'a>>> {
+        self.resolve_value_entry_traced(importing_module, &mut |_| {})
+    }
+
+    /// Performs the lookup of [`resolve_value_entry`](Self::resolve_value_entry) and reports every byte range it reads to `trace`.
+    pub(crate) fn resolve_value_entry_traced<F>(
+        &self,
+        importing_module: Option<&str>,
+        trace: &mut F,
+    ) -> Option<Result<ApiSetValueEntry<'a>>>
+    where
+        F: FnMut(Range<usize>),
+    {
+        let value_entries = iter_try!(self.value_entries());
+        let value_array_header_size = self.schema.value_array_header_size();
+        if value_array_header_size > 0 {
+            let value_array_offset = self.value_array_offset();
+            trace(value_array_offset..value_array_offset + value_array_header_size);
+        }
+
+        let value_entry_size = self.schema.value_entry_size();
+        let default_value_entry = value_entries.clone().next()?;
+        let default_value_entry_start = default_value_entry.offset();
+        trace(default_value_entry_start..default_value_entry_start + value_entry_size);
+
+        if let Some(importing_module) = importing_module {
+            // The default entry is followed by the host-specific entries, sorted by the name of the importing module.
+            let mut left = 1;
+            let mut right = value_entries.len();
+
+            while left < right {
+                let mid = left + (right - left) / 2;
+                let value_entry = value_entries.clone().nth(mid)?;
+                let value_entry_start = value_entry.offset();
+                trace(value_entry_start..value_entry_start + value_entry_size);
+
+                let name = iter_try!(value_entry.name());
+                trace(value_entry.name_range());
+
+                match cmp_ignore_ascii_case_str(&name, importing_module) {
+                    Ordering::Equal => return Some(Ok(value_entry)),
+                    Ordering::Less => left = mid + 1,
+                    Ordering::Greater => right = mid,
+                }
+            }
+        }
+
+        Some(Ok(default_value_entry))
+    }
+
+    /// Returns the [`ApiSetValueEntry`] at `index` of this [`ApiSetNamespaceEntry`], or `None` if there is no such entry.
+    ///
+    /// This is the same as `value_entries()?.nth(index)`, but saves you the iterator gymnastics.
+    ///
+    /// [`ApiSetValueEntry`]: crate::value_entry::ApiSetValueEntry
+    pub fn value_entry(&self, index: usize) -> Option<Result<ApiSetValueEntry<'a>>> {
+        let mut value_entries = iter_try!(self.value_entries());
+        value_entries.nth(index).map(Ok)
+    }
+
+    /// Returns the default [`ApiSetValueEntry`] of this [`ApiSetNamespaceEntry`], which is the one at index 0.
+    ///
+    /// Its importing module name is empty, and it is used whenever no host-specific Value Entry matches.
+    /// API Sets without any mapping have no Value Entries at all, in which case `None` is returned.
+    ///
+    /// [`ApiSetValueEntry`]: crate::value_entry::ApiSetValueEntry
+    pub fn default_value_entry(&self) -> Option<Result<ApiSetValueEntry<'a>>> {
+        self.value_entry(0)
+    }
+
+    /// Returns the byte offset of the Value Entries, or of the structure preceding them before version 6.
+    pub(crate) const fn value_array_offset(&self) -> usize {
+        self.header.array_offset as usize
+    }
+
+    /// Returns the number of [`ApiSetValueEntry`]s of this [`ApiSetNamespaceEntry`] without checking their bounds.
+    ///
+    /// An API Set with more than one Value Entry has host-specific mappings, and one without any Value Entries has no mapping at all.
+    /// For version 6, this is the raw count field of the Namespace Entry.
+    /// Before version 6, the count precedes the Value Entries, so reading it may fail.
+    ///
+    /// [`ApiSetValueEntry`]: crate::value_entry::ApiSetValueEntry
+    pub fn value_count(&self) -> Result<usize> {
+        if self.schema.value_array_header_size() > 0 {
+            let (_, count) = read_value_array_header(
+                self.section_bytes,
+                self.schema,
+                self.value_array_offset(),
+            )?;
+            Ok(count)
+        } else {
+            Ok(self.header.array_count as usize)
+        }
+    }
+
+    /// Returns an iterator over the [`ApiSetValueEntry`]s of this [`ApiSetNamespaceEntry`] at the given `index_range`.
+    ///
+    /// This is useful for paginating over the Value Entries.
+    /// The total number of entries is returned by `value_entries()?.len()`.
+    ///
+    /// An `index_range` that is inverted or exceeds the number of entries is not clamped.
+    /// Instead, [`NtApiSetError::EntryIndexRangeOutOfBounds`] is returned.
+    ///
+    /// [`ApiSetValueEntry`]: crate::value_entry::ApiSetValueEntry
+    pub fn value_entries_range(&self, index_range: Range<usize>) -> Result<ApiSetValueEntries<'a>> {
+        self.value_entries()?.restrict(index_range)
+    }
+
+    /// Returns the [`name`](Self::name) without its "api-" or "ext-" prefix.
+    ///
+    /// This is the name as stored by API Set Maps before version 6.
+    pub(crate) fn name_without_prefix(&self) -> Result<U16StrLe<'a>> {
+        let name = self.name()?;
+
+        if self.schema.has_name_prefix()
+            && (starts_with_ignore_ascii_case_str(&name, "api-")
+                || starts_with_ignore_ascii_case_str(&name, "ext-"))
+        {
+            Ok(U16StrLe(&name.0["api-".len() * 2..]))
+        } else {
+            Ok(name)
+        }
+    }
+
+    /// Checks that this entry and `other` describe the same API Set with the same mappings, regardless of their byte layout.
+    ///
+    /// Names and values are compared case-insensitively, flags are compared exactly.
+    /// If only one of the entries stores its name with the "api-" or "ext-" prefix, names are compared without the prefix.
+    /// If only one of the entries has flags (version 2 has none), flags are not compared.
+    pub(crate) fn semantic_eq(&self, other: &ApiSetNamespaceEntry) -> Result<bool> {
+        let compare_flags = self.schema.has_flags() && other.schema.has_flags();
+        let names_eq = if self.schema.has_name_prefix() == other.schema.has_name_prefix() {
+            cmp_ignore_ascii_case(&self.name()?, &other.name()?) == Ordering::Equal
+        } else {
+            cmp_ignore_ascii_case(&self.name_without_prefix()?, &other.name_without_prefix()?)
+                == Ordering::Equal
+        };
+
+        if !names_eq || (compare_flags && self.flags() != other.flags()) {
+            return Ok(false);
+        }
+
+        let value_entries = self.value_entries()?;
+        let other_value_entries = other.value_entries()?;
+        if value_entries.len() != other_value_entries.len() {
+            return Ok(false);
+        }
+
+        for (value_entry, other_value_entry) in value_entries.zip(other_value_entries) {
+            if (compare_flags && value_entry.flags() != other_value_entry.flags())
+                || cmp_ignore_ascii_case(&value_entry.name()?, &other_value_entry.name()?)
+                    != Ordering::Equal
+                || cmp_ignore_ascii_case(&value_entry.value()?, &other_value_entry.value()?)
+                    != Ordering::Equal
+            {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+    use crate::fixtures::build_legacy_map;
+    use crate::map::ApiSetMap;
+
+    const NAME: &str = "api-ms-win-core-sysinfo-l1-2-3";
+
+    fn build_map() -> alloc::vec::Vec<u8> {
+        ApiSetMapBuilder::new()
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(NAME, ApiSetNamespaceEntryFlags::empty())
+                    .add_value_entry("", "kernelbase.dll"),
+            )
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_hashed_name() {
+        let names = [
+            "api-ms-win-core-foo-l1-1-0",
+            NAME,
+            "ext-ms-win-foo-bar-l1-1-0",
+            "api-ms-win-unversioned",
+        ];
+        let bytes = names
+            .iter()
+            .fold(ApiSetMapBuilder::new(), |builder, name| {
+                builder.add_namespace_entry(
+                    ApiSetNamespaceEntryBuilder::new(name, ApiSetNamespaceEntryFlags::empty())
+                        .add_value_entry("", "foo.dll"),
+                )
+            })
+            .build()
+            .unwrap();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+        for namespace_entry in map.namespace_entries().unwrap() {
+            let name = namespace_entry.name().unwrap().to_string().unwrap();
+            let expected = &name[..name.rfind('-').unwrap()];
+
+            assert_eq!(namespace_entry.hashed_name().unwrap(), expected);
+            assert_eq!(namespace_entry.hashed_length(), 2 * expected.len());
+        }
+
+        // API Set Maps before version 6 have no hash table.
+        let bytes = build_legacy_map(4, &[("ms-win-core-foo-l1-1-0", &[("", "foo.dll")])]);
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let namespace_entry = map.namespace_entry(0).unwrap().unwrap();
+        assert_eq!(namespace_entry.hashed_name().unwrap(), "");
+        assert_eq!(namespace_entry.hashed_length(), 0);
+    }
+
+    #[test]
+    fn test_raw_flags_and_value_count() {
+        let unknown_flags = ApiSetNamespaceEntryFlags::from_bits_retain(0x8000_0004);
+        let bytes = ApiSetMapBuilder::new()
+            .add_namespace_entry(ApiSetNamespaceEntryBuilder::new(
+                "api-ms-win-core-bar-l1-1-0",
+                ApiSetNamespaceEntryFlags::empty(),
+            ))
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "api-ms-win-core-foo-l1-1-0",
+                    ApiSetNamespaceEntryFlags::SEALED,
+                )
+                .add_value_entry("", "foo.dll"),
+            )
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "ext-ms-win-qux-l1-1-0",
+                    ApiSetNamespaceEntryFlags::IS_EXTENSION | unknown_flags,
+                )
+                .add_value_entry("", "qux.dll")
+                .add_value_entry("advapi32.dll", "advapi32_qux.dll")
+                .add_value_entry("kernel32.dll", "kernelbase.dll"),
+            )
+            .build()
+            .unwrap();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let namespace_entries = map
+            .namespace_entries()
+            .unwrap()
+            .collect::<alloc::vec::Vec<_>>();
+
+        let raw_flags = namespace_entries
+            .iter()
+            .map(ApiSetNamespaceEntry::raw_flags)
+            .collect::<alloc::vec::Vec<_>>();
+        assert_eq!(raw_flags, [0, 0x1, 0x8000_0006]);
+
+        // Unknown bits are kept, both in the raw field and in the parsed flags.
+        assert_eq!(namespace_entries[2].flags().bits(), 0x8000_0006);
+        assert!(namespace_entries[2]
+            .flags()
+            .contains(ApiSetNamespaceEntryFlags::IS_EXTENSION));
+
+        for (namespace_entry, expected) in namespace_entries.iter().zip([0, 1, 3]) {
+            assert_eq!(namespace_entry.value_count().unwrap(), expected);
+            assert_eq!(namespace_entry.value_entries().unwrap().len(), expected);
+        }
+
+        // Before version 6, the count precedes the Value Entries.
+        let entries: &[(&str, &[(&str, &str)])] = &[
+            ("ms-win-core-bar-l1-1-0", &[]),
+            (
+                "ms-win-core-foo-l1-1-0",
+                &[("", "foo.dll"), ("kernel32.dll", "kernelbase.dll")],
+            ),
+        ];
+        for version in [2, 4] {
+            let mut bytes = build_legacy_map(version, entries);
+            let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+            let namespace_entries = map
+                .namespace_entries()
+                .unwrap()
+                .collect::<alloc::vec::Vec<_>>();
+
+            for (namespace_entry, expected) in namespace_entries.iter().zip([0, 2]) {
+                assert_eq!(namespace_entry.raw_flags(), 0);
+                assert_eq!(namespace_entry.value_count().unwrap(), expected);
+            }
+
+            // Let the count of the second Namespace Entry lie outside the section.
+            let entry_size = Schema::from_version(version)
+                .unwrap()
+                .namespace_entry_size();
+            let array_offset = namespace_entries[1].offset() + entry_size - 4;
+            bytes[array_offset..array_offset + 4].copy_from_slice(&0xffff_0000u32.to_le_bytes());
+            let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+            assert!(map
+                .namespace_entry(1)
+                .unwrap()
+                .unwrap()
+                .value_count()
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn test_name_eq_ignore_case() {
+        let bytes = build_map();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let namespace_entry = map.namespace_entry(0).unwrap().unwrap();
+
+        for other in [
+            NAME,
+            "API-MS-WIN-CORE-SYSINFO-L1-2-3",
+            "Api-Ms-Win-Core-SysInfo-L1-2-3",
+        ] {
+            assert!(
+                namespace_entry.name_eq_ignore_case(other).unwrap(),
+                "{other}"
+            );
+        }
+
+        for other in [
+            "",
+            "api-ms-win-core-sysinfo-l1-2",
+            "api-ms-win-core-sysinfo-l1-2-30",
+            "api-ms-win-core-sysinfo-l1-2-3.dll",
+            "api-ms-win-core-sysinfo-l1-2-0",
+            // Only ASCII letters are compared case-insensitively.
+            "api-ms-win-core-sys\u{131}nfo-l1-2-3",
+        ] {
+            assert!(
+                !namespace_entry.name_eq_ignore_case(other).unwrap(),
+                "{other}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_name_starts_with_ignore_case() {
+        let bytes = build_map();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let namespace_entry = map.namespace_entry(0).unwrap().unwrap();
+
+        for prefix in [
+            "",
+            "api-",
+            "API-MS-WIN-CORE-",
+            "api-ms-win-core-SYSINFO",
+            NAME,
+        ] {
+            assert!(
+                namespace_entry
+                    .name_starts_with_ignore_case(prefix)
+                    .unwrap(),
+                "{prefix}"
+            );
+        }
+
+        for prefix in [
+            "ext-",
+            "ms-win-",
+            "api-ms-win-core-sysinfo-l1-2-3-",
+            "api-ms-win-core-file",
+        ] {
+            assert!(
+                !namespace_entry
+                    .name_starts_with_ignore_case(prefix)
+                    .unwrap(),
+                "{prefix}"
+            );
+        }
+
+        // Before version 6, the name is stored without the "api-" prefix.
+        let bytes = build_legacy_map(
+            4,
+            &[("ms-win-core-sysinfo-l1-2-3", &[("", "kernelbase.dll")])],
+        );
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let namespace_entry = map.namespace_entry(0).unwrap().unwrap();
+        assert!(!namespace_entry
+            .name_starts_with_ignore_case("api-")
+            .unwrap());
+        assert!(namespace_entry
+            .name_starts_with_ignore_case("MS-WIN-")
+            .unwrap());
+        assert!(namespace_entry
+            .name_eq_ignore_case("ms-win-core-sysinfo-l1-2-3")
+            .unwrap());
+    }
+
+    #[test]
+    fn test_name_out_of_bounds() {
+        let mut bytes = build_map();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let name_offset = map.namespace_entry_offset() as usize + 4;
+        bytes[name_offset..name_offset + 4].copy_from_slice(&0xffff_0000u32.to_le_bytes());
+
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let namespace_entry = map.namespace_entry(0).unwrap().unwrap();
+        let error = namespace_entry.name().unwrap_err();
+        assert!(matches!(error, NtApiSetError::EntryNameOutOfBounds { .. }));
+        assert_eq!(
+            namespace_entry.name_eq_ignore_case(NAME).unwrap_err(),
+            error
+        );
+        assert_eq!(
+            namespace_entry
+                .name_starts_with_ignore_case("")
+                .unwrap_err(),
+            error
+        );
     }
 }
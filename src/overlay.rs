@@ -0,0 +1,612 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::fmt;
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use nt_string::u16strle::U16StrLe;
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+
+use crate::error::{NtApiSetError, Result};
+use crate::helpers::{strip_prefix_ignore_ascii_case, strip_suffix_ignore_ascii_case};
+use crate::resolver::ApiSetResolver;
+
+/// Whether an [`OverlaidResolver`] applies the rules of its [`RedirectOverlay`] before or after consulting the base resolver.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum OverlayPrecedence {
+    /// The rules override any mapping of the base resolver.
+    BeforeBase,
+    /// The rules only apply to API Sets that the base resolver cannot resolve.
+    AfterBase,
+}
+
+impl Default for OverlayPrecedence {
+    fn default() -> Self {
+        Self::BeforeBase
+    }
+}
+
+/// Kind of a [`RedirectRule`], which determines how its pattern is matched.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum RedirectRuleKind {
+    /// The pattern is a full API Set name, which must match the imported name case-insensitively.
+    /// A ".dll" extension is ignored on both sides.
+    Exact,
+    /// The pattern is the beginning of API Set names, e.g. `api-ms-win-core-registry-`.
+    /// It matches every imported name starting with it, compared case-insensitively.
+    Prefix,
+}
+
+/// A single rule of a [`RedirectOverlay`], which redirects matching API Sets to a host module.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RedirectRule {
+    kind: RedirectRuleKind,
+    pattern: String,
+    host_module: String,
+    /// `host_module` as UTF-16LE bytes, to be returned as a [`U16StrLe`].
+    host_module_utf16: Vec<u8>,
+}
+
+impl RedirectRule {
+    fn new(kind: RedirectRuleKind, pattern: &str, host_module: &str) -> Self {
+        Self {
+            kind,
+            pattern: pattern.to_string(),
+            host_module: host_module.to_string(),
+            host_module_utf16: host_module
+                .encode_utf16()
+                .flat_map(u16::to_le_bytes)
+                .collect(),
+        }
+    }
+
+    /// Returns the name of the host module that matching API Sets are redirected to.
+    pub fn host_module(&self) -> &str {
+        &self.host_module
+    }
+
+    /// Returns how the [`pattern`](Self::pattern) of this rule is matched.
+    pub const fn kind(&self) -> RedirectRuleKind {
+        self.kind
+    }
+
+    /// Returns the API Set name or prefix this rule matches.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Checks that the pattern looks like an API Set name (or the beginning of one) and the host module is a non-empty ASCII name.
+    fn is_valid(&self) -> bool {
+        let is_valid_name_char = |c: char| c.is_ascii_alphanumeric() || c == '-';
+
+        let pattern = match self.kind {
+            RedirectRuleKind::Exact => self.exact_pattern(),
+            RedirectRuleKind::Prefix => self.pattern.as_str(),
+        };
+        let name_without_prefix = match strip_prefix_ignore_ascii_case(pattern, "api-")
+            .or_else(|| strip_prefix_ignore_ascii_case(pattern, "ext-"))
+        {
+            Some(name_without_prefix) => name_without_prefix,
+            None => return false,
+        };
+
+        // An exact name needs a hyphen after its prefix, because NTDLL hashes it up to the last hyphen.
+        let is_complete = match self.kind {
+            RedirectRuleKind::Exact => {
+                matches!(name_without_prefix.rfind('-'), Some(position) if position > 0)
+            }
+            RedirectRuleKind::Prefix => true,
+        };
+
+        name_without_prefix.chars().all(is_valid_name_char)
+            && is_complete
+            && !self.host_module.is_empty()
+            && self.host_module.is_ascii()
+    }
+
+    /// Returns the pattern of an exact rule without its ".dll" extension.
+    fn exact_pattern(&self) -> &str {
+        strip_suffix_ignore_ascii_case(&self.pattern, ".dll").unwrap_or(&self.pattern)
+    }
+
+    /// Checks whether this rule has the same kind and pattern as `other`, ignoring case.
+    fn is_duplicate_of(&self, other: &RedirectRule) -> bool {
+        match (self.kind, other.kind) {
+            (RedirectRuleKind::Exact, RedirectRuleKind::Exact) => self
+                .exact_pattern()
+                .eq_ignore_ascii_case(other.exact_pattern()),
+            (RedirectRuleKind::Prefix, RedirectRuleKind::Prefix) => {
+                self.pattern.eq_ignore_ascii_case(&other.pattern)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// User-defined rules that redirect API Sets to other host modules at resolution time, e.g. to shim DLLs of a sandbox.
+///
+/// In contrast to an API Set Map extension, an overlay doesn't require crafting a `.apiset` section.
+/// It is applied on top of any [`ApiSetResolver`] via an [`OverlaidResolver`].
+///
+/// Exact rules take precedence over prefix rules, and a longer prefix takes precedence over a shorter one.
+/// Rules apply to every importing module.
+///
+/// Rules are added unchecked, and [`check`](Self::check) validates them.
+/// With the `serde` feature, an overlay can also be deserialized (e.g. from JSON), which checks it as well:
+///
+/// ```json
+/// {
+///     "precedence": "before_base",
+///     "rules": [
+///         { "kind": "exact", "pattern": "api-ms-win-core-registry-l1-1-0", "host_module": "shim.dll" },
+///         { "kind": "prefix", "pattern": "api-ms-win-core-registry-", "host_module": "shim.dll" }
+///     ]
+/// }
+/// ```
+///
+/// `precedence` is optional and defaults to `before_base`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize),
+    serde(try_from = "UncheckedRedirectOverlay")
+)]
+pub struct RedirectOverlay {
+    precedence: OverlayPrecedence,
+    rules: Vec<RedirectRule>,
+}
+
+impl RedirectOverlay {
+    /// Creates an empty [`RedirectOverlay`], whose rules are applied before consulting the base resolver.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a rule that redirects the API Set `apiset_name` to `host_module`.
+    ///
+    /// `apiset_name` may have a ".dll" extension and is compared case-insensitively.
+    pub fn add_exact_rule(mut self, apiset_name: &str, host_module: &str) -> Self {
+        self.rules.push(RedirectRule::new(
+            RedirectRuleKind::Exact,
+            apiset_name,
+            host_module,
+        ));
+        self
+    }
+
+    /// Adds a rule that redirects all API Sets whose names start with `prefix` to `host_module`.
+    ///
+    /// `prefix` is compared case-insensitively.
+    pub fn add_prefix_rule(mut self, prefix: &str, host_module: &str) -> Self {
+        self.rules.push(RedirectRule::new(
+            RedirectRuleKind::Prefix,
+            prefix,
+            host_module,
+        ));
+        self
+    }
+
+    /// Checks all rules of this overlay.
+    ///
+    /// A rule whose pattern doesn't begin with "api-" or "ext-", contains characters other than ASCII letters, digits, and hyphens,
+    /// or (for an exact rule) has no hyphen after that prefix, is reported as [`NtApiSetError::InvalidRedirectRule`].
+    /// The same is reported for a rule with an empty or non-ASCII host module.
+    /// Two rules of the same kind with the same pattern (ignoring case) are reported as [`NtApiSetError::DuplicateRedirectRule`].
+    pub fn check(&self) -> Result<()> {
+        for (index, rule) in self.rules.iter().enumerate() {
+            if !rule.is_valid() {
+                return Err(NtApiSetError::InvalidRedirectRule { index });
+            }
+
+            if let Some(previous_index) = self.rules[..index]
+                .iter()
+                .position(|previous_rule| rule.is_duplicate_of(previous_rule))
+            {
+                return Err(NtApiSetError::DuplicateRedirectRule {
+                    index,
+                    previous_index,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets whether the rules are applied before or after consulting the base resolver.
+    pub fn precedence(mut self, precedence: OverlayPrecedence) -> Self {
+        self.precedence = precedence;
+        self
+    }
+
+    /// Returns the rule that applies to the imported API Set `apiset_name`, if any.
+    ///
+    /// `apiset_name` may be taken directly from an import table, so it may have a ".dll" extension.
+    pub fn redirect(&self, apiset_name: &str) -> Option<&RedirectRule> {
+        let name = strip_suffix_ignore_ascii_case(apiset_name, ".dll").unwrap_or(apiset_name);
+
+        let exact_rule = self.rules.iter().find(|rule| {
+            rule.kind == RedirectRuleKind::Exact && rule.exact_pattern().eq_ignore_ascii_case(name)
+        });
+
+        exact_rule.or_else(|| {
+            self.rules
+                .iter()
+                .filter(|rule| {
+                    rule.kind == RedirectRuleKind::Prefix
+                        && strip_prefix_ignore_ascii_case(name, &rule.pattern).is_some()
+                })
+                .max_by_key(|rule| rule.pattern.len())
+        })
+    }
+
+    /// Returns all rules of this overlay in the order they have been added.
+    pub fn rules(&self) -> &[RedirectRule] {
+        &self.rules
+    }
+}
+
+/// Deserialized fields of a [`RedirectOverlay`] before they have been checked.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct UncheckedRedirectOverlay {
+    #[serde(default)]
+    precedence: OverlayPrecedence,
+    rules: Vec<UncheckedRedirectRule>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct UncheckedRedirectRule {
+    kind: RedirectRuleKind,
+    pattern: String,
+    host_module: String,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<UncheckedRedirectOverlay> for RedirectOverlay {
+    type Error = NtApiSetError;
+
+    fn try_from(unchecked: UncheckedRedirectOverlay) -> Result<Self> {
+        let overlay = Self {
+            precedence: unchecked.precedence,
+            rules: unchecked
+                .rules
+                .iter()
+                .map(|rule| RedirectRule::new(rule.kind, &rule.pattern, &rule.host_module))
+                .collect(),
+        };
+        overlay.check()?;
+
+        Ok(overlay)
+    }
+}
+
+/// An [`ApiSetResolver`] that applies the rules of a [`RedirectOverlay`] on top of a base resolver.
+///
+/// ```
+/// # use nt_apiset::{
+/// #     ApiSetMap, ApiSetMapBuilder, ApiSetNamespaceEntryBuilder, ApiSetNamespaceEntryFlags, ApiSetResolver,
+/// #     OverlaidResolver, RedirectOverlay,
+/// # };
+/// # let bytes = ApiSetMapBuilder::new()
+/// #     .add_namespace_entry(
+/// #         ApiSetNamespaceEntryBuilder::new("api-ms-win-core-registry-l1-1-0", ApiSetNamespaceEntryFlags::empty())
+/// #             .add_value_entry("", "kernelbase.dll"),
+/// #     )
+/// #     .build()
+/// #     .unwrap();
+/// let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+/// let overlay = RedirectOverlay::new().add_exact_rule("api-ms-win-core-registry-l1-1-0", "shim.dll");
+/// let resolver = OverlaidResolver::new(&map, overlay).unwrap();
+///
+/// let host = resolver.resolve("api-ms-win-core-registry-l1-1-0.dll", None).unwrap().unwrap();
+/// assert_eq!(host, "shim.dll");
+/// ```
+pub struct OverlaidResolver<'b> {
+    base: &'b dyn ApiSetResolver,
+    overlay: RedirectOverlay,
+}
+
+impl<'b> OverlaidResolver<'b> {
+    /// Creates an [`OverlaidResolver`] that applies `overlay` on top of `base`.
+    ///
+    /// The rules of `overlay` are checked first, see [`RedirectOverlay::check`].
+    pub fn new(base: &'b dyn ApiSetResolver, overlay: RedirectOverlay) -> Result<Self> {
+        overlay.check()?;
+        Ok(Self { base, overlay })
+    }
+
+    /// Returns the [`RedirectOverlay`] applied by this resolver.
+    pub const fn overlay(&self) -> &RedirectOverlay {
+        &self.overlay
+    }
+
+    fn redirect(&self, apiset_name: &str) -> Option<Result<U16StrLe<'_>>> {
+        let rule = self.overlay.redirect(apiset_name)?;
+        Some(Ok(U16StrLe(&rule.host_module_utf16)))
+    }
+}
+
+impl<'b> ApiSetResolver for OverlaidResolver<'b> {
+    fn resolve(
+        &self,
+        apiset_name: &str,
+        importing_module: Option<&str>,
+    ) -> Option<Result<U16StrLe<'_>>> {
+        match self.overlay.precedence {
+            OverlayPrecedence::BeforeBase => self
+                .redirect(apiset_name)
+                .or_else(|| self.base.resolve(apiset_name, importing_module)),
+            OverlayPrecedence::AfterBase => self
+                .base
+                .resolve(apiset_name, importing_module)
+                .or_else(|| self.redirect(apiset_name)),
+        }
+    }
+}
+
+impl<'b> fmt::Debug for OverlaidResolver<'b> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OverlaidResolver")
+            .field("overlay", &self.overlay)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+    use crate::map::ApiSetMap;
+    use crate::namespace_entry::ApiSetNamespaceEntryFlags;
+
+    fn build_map() -> Vec<u8> {
+        ApiSetMapBuilder::new()
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "api-ms-win-core-registry-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                )
+                .add_value_entry("", "kernelbase.dll")
+                .add_value_entry("kernel32.dll", "kernel32legacy.dll"),
+            )
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "api-ms-win-core-registry-l2-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                )
+                .add_value_entry("", "advapi32.dll"),
+            )
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "api-ms-win-core-sysinfo-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                )
+                .add_value_entry("", "kernelbase.dll"),
+            )
+            .build()
+            .unwrap()
+    }
+
+    fn resolve(
+        resolver: &dyn ApiSetResolver,
+        apiset_name: &str,
+        importing_module: Option<&str>,
+    ) -> Option<String> {
+        resolver
+            .resolve(apiset_name, importing_module)
+            .map(|host| host.unwrap().to_string().unwrap())
+    }
+
+    #[test]
+    fn test_exact_override() {
+        let bytes = build_map();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let overlay = RedirectOverlay::new()
+            .add_exact_rule("API-MS-WIN-CORE-REGISTRY-L1-1-0.dll", "shim.dll");
+        let resolver = OverlaidResolver::new(&map, overlay).unwrap();
+
+        // Every importing module is redirected, with or without ".dll" and in any case.
+        for (apiset_name, importing_module) in [
+            ("api-ms-win-core-registry-l1-1-0.dll", None),
+            ("api-ms-win-core-registry-l1-1-0", Some("kernel32.dll")),
+            ("Api-Ms-Win-Core-Registry-L1-1-0.DLL", Some("user32.dll")),
+        ] {
+            assert_eq!(
+                resolve(&resolver, apiset_name, importing_module).unwrap(),
+                "shim.dll"
+            );
+        }
+
+        // An exact rule doesn't match any other version of the API Set.
+        assert_eq!(
+            resolve(&resolver, "api-ms-win-core-registry-l2-1-0.dll", None).unwrap(),
+            "advapi32.dll"
+        );
+        assert_eq!(
+            resolve(&resolver, "api-ms-win-core-registry-l1-1-1.dll", None).unwrap(),
+            "kernelbase.dll"
+        );
+    }
+
+    #[test]
+    fn test_prefix_override() {
+        let bytes = build_map();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let overlay = RedirectOverlay::new()
+            .add_prefix_rule("api-ms-win-core-registry-", "shim.dll")
+            .add_prefix_rule("api-ms-win-core-registry-l2-", "shim2.dll")
+            .add_exact_rule("api-ms-win-core-registry-l2-1-0", "shim3.dll");
+        let resolver = OverlaidResolver::new(&map, overlay).unwrap();
+
+        assert_eq!(
+            resolve(
+                &resolver,
+                "api-ms-win-core-registry-l1-1-0.dll",
+                Some("kernel32.dll")
+            )
+            .unwrap(),
+            "shim.dll"
+        );
+
+        // A longer prefix wins over a shorter one, and an exact rule wins over both.
+        assert_eq!(
+            resolve(&resolver, "api-ms-win-core-registry-l2-2-0.dll", None).unwrap(),
+            "shim2.dll"
+        );
+        assert_eq!(
+            resolve(&resolver, "API-MS-WIN-CORE-REGISTRY-L2-1-0.DLL", None).unwrap(),
+            "shim3.dll"
+        );
+
+        // API Sets unknown to the base resolver are redirected as well.
+        assert_eq!(
+            resolve(&resolver, "api-ms-win-core-registry-l3-1-0.dll", None).unwrap(),
+            "shim.dll"
+        );
+    }
+
+    #[test]
+    fn test_passthrough() {
+        let bytes = build_map();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let overlay =
+            RedirectOverlay::new().add_prefix_rule("api-ms-win-core-registry-", "shim.dll");
+        let resolver = OverlaidResolver::new(&map, overlay).unwrap();
+
+        assert_eq!(
+            resolve(&resolver, "api-ms-win-core-sysinfo-l1-1-0.dll", None).unwrap(),
+            "kernelbase.dll"
+        );
+        assert!(resolve(&resolver, "api-ms-win-core-file-l1-1-0.dll", None).is_none());
+        assert!(resolve(&resolver, "kernel32.dll", None).is_none());
+
+        // An empty overlay passes everything through.
+        let resolver = OverlaidResolver::new(&map, RedirectOverlay::new()).unwrap();
+        assert_eq!(
+            resolve(
+                &resolver,
+                "api-ms-win-core-registry-l1-1-0.dll",
+                Some("kernel32.dll")
+            )
+            .unwrap(),
+            "kernel32legacy.dll"
+        );
+    }
+
+    #[test]
+    fn test_after_base() {
+        let bytes = build_map();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let overlay = RedirectOverlay::new()
+            .add_prefix_rule("api-ms-win-core-registry-", "shim.dll")
+            .precedence(OverlayPrecedence::AfterBase);
+        let resolver = OverlaidResolver::new(&map, overlay).unwrap();
+
+        // The base resolver wins for API Sets it knows, and the overlay fills the gaps.
+        assert_eq!(
+            resolve(&resolver, "api-ms-win-core-registry-l1-1-0.dll", None).unwrap(),
+            "kernelbase.dll"
+        );
+        assert_eq!(
+            resolve(&resolver, "api-ms-win-core-registry-l3-1-0.dll", None).unwrap(),
+            "shim.dll"
+        );
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        for overlay in [
+            RedirectOverlay::new().add_exact_rule("", "shim.dll"),
+            RedirectOverlay::new().add_exact_rule("kernel32.dll", "shim.dll"),
+            RedirectOverlay::new().add_exact_rule("api-registry", "shim.dll"),
+            RedirectOverlay::new().add_exact_rule("api-ms-win-core-registry-l1-1-0", ""),
+            RedirectOverlay::new()
+                .add_exact_rule("api-ms-win-core-registry-l1-1-0", "shim\u{e4}.dll"),
+            RedirectOverlay::new().add_prefix_rule("ms-win-core-", "shim.dll"),
+            RedirectOverlay::new().add_prefix_rule("api-ms-win-core-*", "shim.dll"),
+        ] {
+            assert_eq!(
+                overlay.check().unwrap_err(),
+                NtApiSetError::InvalidRedirectRule { index: 0 }
+            );
+        }
+
+        // A bare prefix is a valid prefix rule.
+        RedirectOverlay::new()
+            .add_prefix_rule("ext-", "shim.dll")
+            .check()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_duplicate_rules() {
+        let overlay = RedirectOverlay::new()
+            .add_exact_rule("api-ms-win-core-registry-l1-1-0", "shim.dll")
+            .add_prefix_rule("api-ms-win-core-registry-l1-1-0", "shim.dll")
+            .add_exact_rule("API-MS-WIN-CORE-REGISTRY-L1-1-0.dll", "shim2.dll");
+        assert_eq!(
+            overlay.check().unwrap_err(),
+            NtApiSetError::DuplicateRedirectRule {
+                index: 2,
+                previous_index: 0
+            }
+        );
+
+        let bytes = build_map();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let overlay = RedirectOverlay::new()
+            .add_prefix_rule("api-ms-win-core-", "shim.dll")
+            .add_prefix_rule("Api-Ms-Win-Core-", "shim2.dll");
+        assert_eq!(
+            OverlaidResolver::new(&map, overlay).unwrap_err(),
+            NtApiSetError::DuplicateRedirectRule {
+                index: 1,
+                previous_index: 0
+            }
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json() {
+        let json = r#"{
+            "precedence": "after_base",
+            "rules": [
+                { "kind": "exact", "pattern": "api-ms-win-core-registry-l1-1-0", "host_module": "shim.dll" },
+                { "kind": "prefix", "pattern": "api-ms-win-core-registry-", "host_module": "shim2.dll" }
+            ]
+        }"#;
+        let overlay: RedirectOverlay = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            overlay,
+            RedirectOverlay::new()
+                .add_exact_rule("api-ms-win-core-registry-l1-1-0", "shim.dll")
+                .add_prefix_rule("api-ms-win-core-registry-", "shim2.dll")
+                .precedence(OverlayPrecedence::AfterBase)
+        );
+
+        // The precedence is optional.
+        let json = r#"{ "rules": [] }"#;
+        let overlay: RedirectOverlay = serde_json::from_str(json).unwrap();
+        assert_eq!(overlay, RedirectOverlay::new());
+
+        // Invalid rules are rejected.
+        let json = r#"{ "rules": [{ "kind": "exact", "pattern": "kernel32.dll", "host_module": "shim.dll" }] }"#;
+        let error = serde_json::from_str::<RedirectOverlay>(json).unwrap_err();
+        assert!(error.to_string().contains("Redirect rule 0"));
+    }
+}
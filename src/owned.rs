@@ -0,0 +1,227 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::vec::Vec;
+
+use nt_string::u16strle::U16StrLe;
+
+use crate::error::Result;
+use crate::hash_entry::ApiSetHashEntries;
+use crate::helpers::try_reserve;
+use crate::map::ApiSetMap;
+use crate::namespace_entry::{ApiSetNamespaceEntries, ApiSetNamespaceEntry};
+
+/// An API Set Map that owns the bytes of its `.apiset` section.
+///
+/// In contrast to [`ApiSetMap`], this doesn't borrow the section bytes, so it can be stored in a long-lived struct
+/// (e.g. a service that loads the API Set Map once at startup) without any lifetime gymnastics.
+/// The header is parsed once upon creation.
+///
+/// All queries are performed by [`as_map`](Self::as_map), which cheaply re-borrows the owned bytes as an [`ApiSetMap`].
+/// The most common ones are also forwarded directly.
+#[derive(Debug)]
+pub struct OwnedApiSetMap {
+    section_bytes: Vec<u8>,
+    /// The parsed [`ApiSetMap`] with empty section bytes, to be rebound to `section_bytes`.
+    unbound_map: ApiSetMap<'static>,
+}
+
+impl OwnedApiSetMap {
+    /// Returns an [`ApiSetMap`] borrowing the section bytes of this [`OwnedApiSetMap`].
+    ///
+    /// This doesn't parse anything again and never fails.
+    pub fn as_map(&self) -> ApiSetMap<'_> {
+        self.unbound_map.rebind(&self.section_bytes)
+    }
+
+    /// Finds a namespace entry efficiently in the hash table of the API Set Map.
+    ///
+    /// See [`ApiSetMap::find_namespace_entry`].
+    pub fn find_namespace_entry(
+        &self,
+        namespace_entry_name: &str,
+    ) -> Option<Result<ApiSetNamespaceEntry<'_>>> {
+        self.as_map().find_namespace_entry(namespace_entry_name)
+    }
+
+    /// Returns an iterator over the Hash Entries of this API Set Map.
+    ///
+    /// See [`ApiSetMap::hash_entries`].
+    pub fn hash_entries(&self) -> Result<ApiSetHashEntries<'_>> {
+        self.as_map().hash_entries()
+    }
+
+    /// Returns the owned bytes of the `.apiset` section.
+    pub fn into_section_bytes(self) -> Vec<u8> {
+        self.section_bytes
+    }
+
+    /// Returns an iterator over the Namespace Entries of this API Set Map.
+    ///
+    /// See [`ApiSetMap::namespace_entries`].
+    pub fn namespace_entries(&self) -> Result<ApiSetNamespaceEntries<'_>> {
+        self.as_map().namespace_entries()
+    }
+
+    /// Resolves an imported API Set to the name of its host DLL, the same way the Windows loader does.
+    ///
+    /// See [`ApiSetMap::resolve`].
+    pub fn resolve(
+        &self,
+        apiset_name: &str,
+        importing_module: Option<&str>,
+    ) -> Option<Result<U16StrLe<'_>>> {
+        self.as_map().resolve(apiset_name, importing_module)
+    }
+
+    /// Resolves the name of an imported DLL to its namespace entry, the same way the Windows loader does.
+    ///
+    /// See [`ApiSetMap::resolve_import`].
+    pub fn resolve_import(&self, import_name: &str) -> Option<Result<ApiSetNamespaceEntry<'_>>> {
+        self.as_map().resolve_import(import_name)
+    }
+
+    /// Returns the raw bytes of the `.apiset` section.
+    pub fn section_bytes(&self) -> &[u8] {
+        &self.section_bytes
+    }
+
+    /// Checks that this API Set Map and `other` have the same content, regardless of their byte layout.
+    ///
+    /// See [`ApiSetMap::semantic_eq`].
+    /// Use [`as_map`](Self::as_map) to compare two [`OwnedApiSetMap`]s.
+    pub fn semantic_eq(&self, other: &ApiSetMap) -> Result<bool> {
+        self.as_map().semantic_eq(other)
+    }
+
+    /// Creates an [`OwnedApiSetMap`] by taking ownership of the raw bytes of the `.apiset` section of an API Set Map file.
+    ///
+    /// The bytes are parsed the same way as by [`ApiSetMap::try_from_apiset_section_bytes`].
+    pub fn try_from_apiset_section_bytes(section_bytes: Vec<u8>) -> Result<Self> {
+        let result = Self::parse_apiset_section_bytes(section_bytes);
+
+        #[cfg(feature = "metrics")]
+        crate::instrument::record_parse(&result);
+
+        result
+    }
+
+    /// Performs the parsing of [`try_from_apiset_section_bytes`](Self::try_from_apiset_section_bytes) without counting failures in the metrics.
+    pub(crate) fn parse_apiset_section_bytes(section_bytes: Vec<u8>) -> Result<Self> {
+        let unbound_map = ApiSetMap::parse_apiset_section_bytes(&section_bytes)?.rebind(&[]);
+
+        Ok(Self {
+            section_bytes,
+            unbound_map,
+        })
+    }
+
+    /// Creates an [`OwnedApiSetMap`] from a 32-bit or 64-bit API Set Map file opened via [`pelite::PeFile`].
+    ///
+    /// Only the `.apiset` section is copied, so the file bytes don't need to be kept afterwards.
+    /// Its location within the file is retained, see [`ApiSetMap::section_location`].
+    #[cfg(feature = "pelite")]
+    #[cfg_attr(docsrs, doc(cfg(all(feature = "alloc", feature = "pelite"))))]
+    pub fn try_from_pe_file(pe_file: pelite::PeFile) -> Result<Self> {
+        Ok(ApiSetMap::try_from_pe_file(pe_file)?.to_owned_map())
+    }
+}
+
+impl Clone for OwnedApiSetMap {
+    fn clone(&self) -> Self {
+        Self {
+            section_bytes: self.section_bytes.clone(),
+            unbound_map: self.unbound_map.rebind(&[]),
+        }
+    }
+}
+
+impl<'a> ApiSetMap<'a> {
+    /// Copies the section bytes of this [`ApiSetMap`] into an [`OwnedApiSetMap`].
+    ///
+    /// If the allocation fails, this aborts like any other allocating function.
+    /// Use [`try_to_owned_map`](Self::try_to_owned_map) to handle that case.
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn to_owned_map(&self) -> OwnedApiSetMap {
+        OwnedApiSetMap {
+            section_bytes: self.section_bytes().to_vec(),
+            unbound_map: self.rebind(&[]),
+        }
+    }
+
+    /// Performs the same as [`to_owned_map`](Self::to_owned_map), but returns [`NtApiSetError::AllocationFailed`](crate::NtApiSetError::AllocationFailed)
+    /// instead of aborting if the allocation fails.
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn try_to_owned_map(&self) -> Result<OwnedApiSetMap> {
+        let mut section_bytes = Vec::new();
+        try_reserve(&mut section_bytes, self.section_bytes().len())?;
+        section_bytes.extend_from_slice(self.section_bytes());
+
+        Ok(OwnedApiSetMap {
+            section_bytes,
+            unbound_map: self.rebind(&[]),
+        })
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+    use crate::map::SectionLocation;
+    use crate::namespace_entry::ApiSetNamespaceEntryFlags;
+
+    const NAME: &str = "api-ms-win-core-foo-l1-1-0.dll";
+
+    fn resolve(map: &OwnedApiSetMap) -> String {
+        map.resolve(NAME, None)
+            .unwrap()
+            .unwrap()
+            .to_string()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_move_to_thread() {
+        let bytes = ApiSetMapBuilder::new()
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "api-ms-win-core-foo-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                )
+                .add_value_entry("", "foo.dll"),
+            )
+            .build()
+            .unwrap();
+        let mut map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let section_location = SectionLocation::new(0x1000, 0x200, 0x400);
+        map.set_section_location(section_location);
+
+        let owned_map = map.to_owned_map();
+        let clone = owned_map.clone();
+
+        // Both the map and its clone are usable in another thread, and a clone made there outlives the original.
+        let (moved_clone, thread_clone) = thread::spawn(move || {
+            assert_eq!(resolve(&owned_map), "foo.dll");
+            assert_eq!(resolve(&clone), "foo.dll");
+
+            let thread_clone = owned_map.clone();
+            drop(owned_map);
+            (clone, thread_clone)
+        })
+        .join()
+        .unwrap();
+
+        for owned_map in [&moved_clone, &thread_clone] {
+            assert_eq!(owned_map.section_bytes(), &bytes[..]);
+            assert_eq!(resolve(owned_map), "foo.dll");
+
+            let as_map = owned_map.as_map();
+            assert!(as_map.semantic_eq(&map).unwrap());
+            assert_eq!(as_map.section_location(), Some(section_location));
+            assert_eq!(as_map.to_rva(0x10), Some(0x1010));
+        }
+    }
+}
@@ -0,0 +1,182 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::vec::Vec;
+
+use pelite::pe64::Pe;
+
+use crate::error::{NtApiSetError, Result};
+use crate::map::ApiSetMap;
+
+/// Extension trait to use this crate directly on PE files opened via the `pelite` crate.
+///
+/// This trait is implemented for every [`pelite::pe64::Pe`] type, such as [`pelite::pe64::PeFile`] and [`pelite::pe64::PeView`].
+/// Its methods are thin wrappers around the corresponding functions of this crate.
+///
+/// ```no_run
+/// # use nt_apiset::PeApiSetExt;
+/// # use pelite::pe64::PeFile;
+/// let schema_dll = std::fs::read("apisetschema.dll").unwrap();
+/// let map = PeFile::from_bytes(&schema_dll).unwrap().apiset_map().unwrap();
+///
+/// let program = std::fs::read("program.exe").unwrap();
+/// for import in PeFile::from_bytes(&program).unwrap().apiset_imports().unwrap() {
+///     println!("{import}");
+/// }
+/// ```
+#[cfg_attr(docsrs, doc(cfg(all(feature = "alloc", feature = "pelite"))))]
+pub trait PeApiSetExt<'a> {
+    /// Returns the names of all imported DLLs that are API Sets, i.e. begin with "api-" or "ext-".
+    ///
+    /// The names are returned as stored in the import directory, usually with a ".dll" file extension.
+    /// A PE file without an import directory has no API Set imports.
+    fn apiset_imports(&self) -> Result<Vec<&'a str>>;
+
+    /// Parses the `.apiset` section of this API Set Map file.
+    ///
+    /// This is the same as calling [`ApiSetMap::try_from_pe64`].
+    fn apiset_map(&self) -> Result<ApiSetMap<'a>>;
+}
+
+impl<'a, T> PeApiSetExt<'a> for T
+where
+    T: Pe<'a>,
+{
+    fn apiset_imports(&self) -> Result<Vec<&'a str>> {
+        let imports = match self.imports() {
+            Ok(imports) => imports,
+            Err(pelite::Error::Null) => return Ok(Vec::new()),
+            Err(_) => return Err(NtApiSetError::ImportDirectoryInvalid),
+        };
+
+        let mut apiset_imports = Vec::new();
+
+        for descriptor in imports {
+            let dll_name = descriptor
+                .dll_name()
+                .map_err(|_| NtApiSetError::ImportDirectoryInvalid)?;
+
+            // API Set names are plain ASCII, so anything else can't be an API Set.
+            let dll_name = match dll_name.to_str() {
+                Ok(dll_name) => dll_name,
+                Err(_) => continue,
+            };
+
+            let is_apiset = dll_name.get(..4).map_or(false, |prefix| {
+                prefix.eq_ignore_ascii_case("api-") || prefix.eq_ignore_ascii_case("ext-")
+            });
+
+            if is_apiset {
+                apiset_imports.push(dll_name);
+            }
+        }
+
+        Ok(apiset_imports)
+    }
+
+    fn apiset_map(&self) -> Result<ApiSetMap<'a>> {
+        ApiSetMap::try_from_pe64(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pelite::pe64::PeFile;
+
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+    use crate::fixtures::{build_pe_file, build_pe_file_with_imports, PE_SECTION_FILE_OFFSET};
+    use crate::namespace_entry::ApiSetNamespaceEntryFlags;
+
+    fn build_map() -> Vec<u8> {
+        ApiSetMapBuilder::new()
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "api-ms-win-core-sysinfo-l1-1-0",
+                    ApiSetNamespaceEntryFlags::SEALED,
+                )
+                .add_value_entry("", "kernelbase.dll"),
+            )
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_apiset_map() {
+        let section_bytes = build_map();
+        let file = build_pe_file(true, &section_bytes);
+        let pe_file = PeFile::from_bytes(&file).unwrap();
+
+        let map = pe_file.apiset_map().unwrap();
+        let expected = ApiSetMap::try_from_apiset_section_bytes(&section_bytes).unwrap();
+        assert!(map.semantic_eq(&expected).unwrap());
+        assert_eq!(
+            map.resolve("api-ms-win-core-sysinfo-l1-1-0.dll", None)
+                .unwrap()
+                .unwrap(),
+            "kernelbase.dll"
+        );
+
+        // An API Set Map file imports nothing.
+        assert!(pe_file.apiset_imports().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_apiset_imports() {
+        let file = build_pe_file_with_imports(
+            true,
+            &build_map(),
+            &[
+                "KERNEL32.dll",
+                "api-ms-win-core-sysinfo-l1-1-0.dll",
+                "ntdll.dll",
+                "EXT-MS-WIN-GDI-DRAW-L1-1-0.DLL",
+                "apiset.dll",
+            ],
+        );
+        let pe_file = PeFile::from_bytes(&file).unwrap();
+
+        // Only API Sets are returned, as stored and in the order of the import directory.
+        assert_eq!(
+            pe_file.apiset_imports().unwrap(),
+            [
+                "api-ms-win-core-sysinfo-l1-1-0.dll",
+                "EXT-MS-WIN-GDI-DRAW-L1-1-0.DLL"
+            ]
+        );
+
+        // The imports can be resolved with the API Set Map of the same file.
+        let map = pe_file.apiset_map().unwrap();
+        assert_eq!(
+            map.resolve(pe_file.apiset_imports().unwrap()[0], None)
+                .unwrap()
+                .unwrap(),
+            "kernelbase.dll"
+        );
+        assert!(map
+            .resolve(pe_file.apiset_imports().unwrap()[1], None)
+            .is_none());
+    }
+
+    #[test]
+    fn test_apiset_imports_invalid() {
+        let section_bytes = build_map();
+        let mut file = build_pe_file_with_imports(
+            true,
+            &section_bytes,
+            &["api-ms-win-core-sysinfo-l1-1-0.dll"],
+        );
+
+        // Let the name of the first import point outside the file.
+        let import_directory =
+            PE_SECTION_FILE_OFFSET as usize + (section_bytes.len() + 0x1ff) / 0x200 * 0x200;
+        file[import_directory + 12..import_directory + 16]
+            .copy_from_slice(&0xffff_0000u32.to_le_bytes());
+
+        let pe_file = PeFile::from_bytes(&file).unwrap();
+        assert_eq!(
+            pe_file.apiset_imports().unwrap_err(),
+            NtApiSetError::ImportDirectoryInvalid
+        );
+    }
+}
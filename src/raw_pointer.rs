@@ -0,0 +1,172 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// This is the only non-test module of nt-apiset that contains unsafe code.
+// All it does is turning a raw pointer into a slice of the size declared by the API Set Map header.
+// Everything else is parsed through the same bounds-checked slice API as for a `.apiset` section read from disk.
+
+#![allow(unsafe_code)]
+
+use core::slice;
+
+use crate::error::{NtApiSetError, Result};
+use crate::map::ApiSetMap;
+use crate::schema::Schema;
+
+impl ApiSetMap<'static> {
+    /// Creates an [`ApiSetMap`] from the API Set Map that the Windows loader has mapped into the current process.
+    ///
+    /// This is the map referenced by `PEB->ApiSetMap`, which is what the loader of the running system actually uses.
+    /// It may be more recent than the `apisetschema.dll` file on disk.
+    ///
+    /// The API Set Map of Windows 7 (version 2) doesn't declare its size and is therefore rejected with
+    /// [`NtApiSetError::UnsupportedVersion`].
+    #[cfg(windows)]
+    #[cfg_attr(docsrs, doc(cfg(all(windows, feature = "raw-pointer"))))]
+    pub fn try_from_current_process() -> Result<Self> {
+        #[link(name = "ntdll")]
+        extern "system" {
+            fn RtlGetCurrentPeb() -> *const u8;
+        }
+
+        // Offset of the `ApiSetMap` field in the PEB.
+        #[cfg(target_pointer_width = "64")]
+        const API_SET_MAP_OFFSET: usize = 0x68;
+        #[cfg(target_pointer_width = "32")]
+        const API_SET_MAP_OFFSET: usize = 0x38;
+
+        // SAFETY: The PEB of the current process is always mapped and `ApiSetMap` is a pointer-aligned field of it.
+        // The loader maps the API Set Map read-only before any user code runs and never unmaps it,
+        // so it stays valid and unmodified for the lifetime of the process.
+        unsafe {
+            let peb = RtlGetCurrentPeb();
+            let api_set_map = *(peb.add(API_SET_MAP_OFFSET) as *const *const u8);
+
+            Self::try_from_ptr(api_set_map)
+        }
+    }
+
+    /// Creates an [`ApiSetMap`] from a raw pointer to an API Set Map in memory.
+    ///
+    /// The version and header are read first, and the size declared by the header determines the length of the borrowed slice.
+    /// All further accesses are bounds-checked against that size, just like for [`try_from_apiset_section_bytes`](Self::try_from_apiset_section_bytes).
+    /// Hence, corrupted offsets in the API Set Map never cause reads beyond the declared size.
+    ///
+    /// API Set Maps of version 2 (Windows 7) don't declare their size and are rejected with [`NtApiSetError::UnsupportedVersion`].
+    /// A declared size that is smaller than the header is rejected with [`NtApiSetError::InvalidMapHeaderSize`].
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be non-null and valid for reads of the API Set Map header.
+    /// Furthermore, it must be valid for reads of as many bytes as declared by the `size` field of that header.
+    /// This memory must not be modified or freed for the rest of the program, as the returned [`ApiSetMap`] borrows it for `'static`.
+    #[cfg_attr(docsrs, doc(cfg(feature = "raw-pointer")))]
+    pub unsafe fn try_from_ptr(ptr: *const u8) -> Result<Self> {
+        let result = Self::parse_ptr(ptr);
+
+        #[cfg(feature = "metrics")]
+        crate::instrument::record_parse(&result);
+
+        result
+    }
+
+    unsafe fn parse_ptr(ptr: *const u8) -> Result<Self> {
+        // The version is the first field in every header.
+        let version = u32::from_le((ptr as *const u32).read_unaligned());
+        let schema = match Schema::from_version(version) {
+            Some(Schema::V2) | None => return Err(NtApiSetError::UnsupportedVersion { version }),
+            Some(schema) => schema,
+        };
+
+        let header_size = schema.map_header_size();
+        let header_bytes = slice::from_raw_parts(ptr, header_size);
+        let size = ApiSetMap::parse_apiset_section_bytes(header_bytes)?.size() as usize;
+
+        if size < header_size {
+            return Err(NtApiSetError::InvalidMapHeaderSize {
+                expected: header_size,
+                actual: size,
+            });
+        }
+
+        // `slice::from_raw_parts` requires the size to not exceed `isize::MAX`, which matters on 32-bit targets.
+        if size > isize::MAX as usize {
+            return Err(NtApiSetError::SectionTooLarge { size });
+        }
+
+        let section_bytes = slice::from_raw_parts(ptr, size);
+        ApiSetMap::parse_apiset_section_bytes(section_bytes)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+    use crate::fixtures::build_legacy_map;
+    use crate::namespace_entry::ApiSetNamespaceEntryFlags;
+
+    fn build() -> alloc::vec::Vec<u8> {
+        ApiSetMapBuilder::new()
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "api-ms-win-core-foo-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                )
+                .add_value_entry("", "foo.dll"),
+            )
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_try_from_ptr() {
+        // Trailing bytes beyond the declared size must not become part of the API Set Map.
+        let mut bytes = build();
+        let size = bytes.len();
+        bytes.extend_from_slice(&[0xff; 16]);
+        let bytes: &'static [u8] = bytes.leak();
+
+        // SAFETY: `bytes` is valid for reads of the declared size and leaked, so it lives for the rest of the program.
+        let map = unsafe { ApiSetMap::try_from_ptr(bytes.as_ptr()) }.unwrap();
+        let expected = ApiSetMap::try_from_apiset_section_bytes(&bytes[..size]).unwrap();
+
+        assert_eq!(map.size() as usize, size);
+        assert!(map.semantic_eq(&expected).unwrap());
+        assert_eq!(
+            map.resolve("api-ms-win-core-foo-l1-1-0.dll", None)
+                .unwrap()
+                .unwrap(),
+            "foo.dll"
+        );
+    }
+
+    #[test]
+    fn test_try_from_ptr_version_2() {
+        let bytes = build_legacy_map(2, &[("ms-win-core-foo-l1-1-0", &[("", "foo.dll")])]);
+
+        // SAFETY: `bytes` is valid for reads of the version field, and no map is returned that could outlive it.
+        let result = unsafe { ApiSetMap::try_from_ptr(bytes.as_ptr()) };
+        assert_eq!(
+            result.unwrap_err(),
+            NtApiSetError::UnsupportedVersion { version: 2 }
+        );
+    }
+
+    #[test]
+    fn test_try_from_ptr_undersized() {
+        let mut bytes = build();
+        let header_size = Schema::V6.map_header_size();
+        bytes[4..8].copy_from_slice(&(header_size as u32 - 1).to_le_bytes());
+
+        // SAFETY: `bytes` is valid for reads of the header, and no map is returned that could outlive it.
+        let result = unsafe { ApiSetMap::try_from_ptr(bytes.as_ptr()) };
+        assert_eq!(
+            result.unwrap_err(),
+            NtApiSetError::InvalidMapHeaderSize {
+                expected: header_size,
+                actual: header_size - 1,
+            }
+        );
+    }
+}
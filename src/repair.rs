@@ -0,0 +1,329 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::mem;
+
+use alloc::vec::Vec;
+
+use zerocopy::LayoutVerified;
+
+use crate::error::{NtApiSetError, Result};
+use crate::hash_entry::ApiSetHashEntryHeader;
+use crate::helpers::{entry_array_end, hash_name, u16_to_ascii_lowercase};
+use crate::map::{ApiSetMap, ApiSetMapHeader};
+use crate::namespace_entry::{ApiSetNamespaceEntry, ApiSetNamespaceEntryHeader};
+
+impl<'a> ApiSetMap<'a> {
+    /// Returns a copy of the `.apiset` section with a rebuilt hash table.
+    ///
+    /// This is useful for carved API Set Maps whose Namespace Entries are intact, but whose hash table is corrupted or missing,
+    /// which breaks [`find_namespace_entry`](Self::find_namespace_entry).
+    /// The hash of every Namespace Entry is recomputed from its hashed name prefix, and the sorted hash table is written back.
+    ///
+    /// The hash factor is taken from the header, unless `hash_factor` is given.
+    /// Pass the factor explicitly if the header value is implausible (all known API Set Maps use `0x1f`).
+    /// It is then also written to the header.
+    ///
+    /// The new hash table replaces the old one in place.
+    /// Only if the old hash table lies outside the section or overlaps the header or the Namespace Entries,
+    /// the new one is appended to the end of the section, and the header is updated accordingly.
+    /// All other bytes remain unchanged.
+    ///
+    /// An error is returned if the Namespace Entries or their names cannot be read.
+    /// API Set Maps before version 6 have no hash table, so [`NtApiSetError::UnsupportedVersion`] is returned for them.
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn repair_hash_table(&self, hash_factor: Option<u32>) -> Result<Vec<u8>> {
+        if !self.schema().has_hash_table() {
+            return Err(NtApiSetError::UnsupportedVersion {
+                version: self.version(),
+            });
+        }
+
+        let section_bytes = self.section_bytes();
+        let namespace_entries = self.namespace_entries()?;
+        let count = namespace_entries.len();
+
+        let (header, _) = LayoutVerified::<_, ApiSetMapHeader>::new_unaligned_from_prefix(
+            section_bytes,
+        )
+        .ok_or(NtApiSetError::InvalidMapHeaderSize {
+            expected: mem::size_of::<ApiSetMapHeader>(),
+            actual: section_bytes.len(),
+        })?;
+        let namespace_entries_start = header.namespace_entry_offset.get() as usize;
+        let namespace_entries_end = entry_array_end(
+            namespace_entries_start,
+            mem::size_of::<ApiSetNamespaceEntryHeader>(),
+            count,
+        );
+        let hash_factor = hash_factor.unwrap_or_else(|| header.hash_factor.get());
+
+        let mut hash_entries = Vec::with_capacity(count);
+        for (index, namespace_entry) in namespace_entries.enumerate() {
+            let hash = rehash(&namespace_entry, hash_factor)?;
+            hash_entries.push((hash, index as u32));
+        }
+        hash_entries.sort_unstable();
+
+        let hash_entry_size = mem::size_of::<ApiSetHashEntryHeader>();
+        let mut start = header.hash_entry_offset.get() as usize;
+        let mut end = entry_array_end(start, hash_entry_size, count);
+
+        let mut repaired = section_bytes.to_vec();
+
+        let in_bounds = end <= repaired.len();
+        let overlaps_header = start < mem::size_of::<ApiSetMapHeader>();
+        let overlaps_namespace_entries =
+            start < namespace_entries_end && namespace_entries_start < end;
+
+        if !in_bounds || overlaps_header || overlaps_namespace_entries {
+            // Append a new hash table at the next 4-byte boundary.
+            start = (repaired.len() + 3) & !3;
+            end = start + count * hash_entry_size;
+            repaired.resize(end, 0);
+        }
+
+        let offset = u32::try_from(start).map_err(|_| NtApiSetError::HashEntriesOutOfBounds {
+            range: start..end,
+            actual: section_bytes.len(),
+        })?;
+        let size =
+            u32::try_from(repaired.len()).map_err(|_| NtApiSetError::HashEntriesOutOfBounds {
+                range: start..end,
+                actual: section_bytes.len(),
+            })?;
+
+        for ((hash, index), entry_bytes) in hash_entries
+            .into_iter()
+            .zip(repaired[start..end].chunks_exact_mut(hash_entry_size))
+        {
+            // The chunk has exactly the size of the header, so this cannot fail.
+            let mut entry = LayoutVerified::<_, ApiSetHashEntryHeader>::new_unaligned(entry_bytes)
+                .ok_or(NtApiSetError::ApiSetSectionOutOfBounds)?;
+            entry.hash.set(hash);
+            entry.index.set(index);
+        }
+
+        // The header has been read from the same bytes before, so this cannot fail.
+        let (mut header, _) =
+            LayoutVerified::<_, ApiSetMapHeader>::new_unaligned_from_prefix(&mut repaired[..])
+                .ok_or(NtApiSetError::ApiSetSectionOutOfBounds)?;
+        header.hash_factor.set(hash_factor);
+
+        if header.hash_entry_offset.get() != offset {
+            header.hash_entry_offset.set(offset);
+            header.size.set(size);
+        }
+
+        Ok(repaired)
+    }
+}
+
+/// Recomputes the hash of `namespace_entry` from its name prefix, the same way [`ApiSetMap::find_namespace_entry`] hashes a name.
+///
+/// The prefix is the part before the last hyphen, as recorded in the hashed length of the entry.
+/// If the hashed length exceeds the name, the part before the last hyphen is determined from the name itself.
+fn rehash(namespace_entry: &ApiSetNamespaceEntry, hash_factor: u32) -> Result<u32> {
+    let name = namespace_entry.name()?;
+    let hashed_length = namespace_entry.hashed_length();
+
+    let prefix_length = if hashed_length <= name.0.len() {
+        hashed_length / mem::size_of::<u16>()
+    } else {
+        name.u16_iter()
+            .enumerate()
+            .filter(|(_, c)| *c == u16::from(b'-'))
+            .last()
+            .map_or(0, |(index, _)| index)
+    };
+
+    let chars = name
+        .u16_iter()
+        .take(prefix_length)
+        .map(|c| u32::from(u16_to_ascii_lowercase(c)));
+
+    Ok(hash_name(chars, hash_factor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+    use crate::fixtures::build_legacy_map;
+    use crate::namespace_entry::ApiSetNamespaceEntryFlags;
+
+    const NAMES: [&str; 6] = [
+        "api-ms-win-core-file-l1-1-0",
+        "api-ms-win-core-file-l1-2-0",
+        "api-ms-win-core-registry-l1-1-0",
+        "api-ms-win-core-sysinfo-l1-1-0",
+        "api-ms-win-core-sysinfo-l1-2-3",
+        "ext-ms-win-gdi-draw-l1-1-0",
+    ];
+    const HASH_FACTOR_OFFSET: usize = 24;
+    const HASH_ENTRY_SIZE: usize = 8;
+
+    fn build_map() -> Vec<u8> {
+        NAMES
+            .iter()
+            .enumerate()
+            .fold(ApiSetMapBuilder::new(), |builder, (i, name)| {
+                builder.add_namespace_entry(
+                    ApiSetNamespaceEntryBuilder::new(name, ApiSetNamespaceEntryFlags::empty())
+                        .add_value_entry("", &alloc::format!("host{i}.dll")),
+                )
+            })
+            .build()
+            .unwrap()
+    }
+
+    fn hash_entry_offset(bytes: &[u8], index: usize) -> usize {
+        let map = ApiSetMap::try_from_apiset_section_bytes(bytes).unwrap();
+        map.hash_entry_offset() as usize + index * HASH_ENTRY_SIZE
+    }
+
+    /// Checks that `bytes` pass validation and every name resolves to its host again.
+    fn check_repaired(bytes: &[u8]) {
+        let map = ApiSetMap::try_from_apiset_section_bytes(bytes).unwrap();
+        assert_eq!(map.validate().unwrap(), []);
+
+        for (i, name) in NAMES.iter().enumerate() {
+            let namespace_entry = map.find_namespace_entry(name).unwrap().unwrap();
+            assert_eq!(namespace_entry.name().unwrap(), *name);
+            assert_eq!(
+                map.resolve(&alloc::format!("{name}.dll"), None)
+                    .unwrap()
+                    .unwrap(),
+                alloc::format!("host{i}.dll").as_str()
+            );
+        }
+    }
+
+    /// Repairs the corrupted `bytes` with both the header factor and the known one,
+    /// and checks that both restore the original hash table.
+    fn check_repair(original: &[u8], bytes: &[u8]) {
+        let map = ApiSetMap::try_from_apiset_section_bytes(bytes).unwrap();
+        assert!(map.validate().is_err());
+
+        for hash_factor in [None, Some(0x1f)] {
+            let repaired = map.repair_hash_table(hash_factor).unwrap();
+            assert_eq!(repaired, original);
+            check_repaired(&repaired);
+        }
+    }
+
+    #[test]
+    fn test_reordered_hash_entries() {
+        let original = build_map();
+        let mut bytes = original.clone();
+
+        let first = hash_entry_offset(&bytes, 0);
+        let last = hash_entry_offset(&bytes, NAMES.len() - 1);
+        let first_entry = bytes[first..first + HASH_ENTRY_SIZE].to_vec();
+        bytes.copy_within(last..last + HASH_ENTRY_SIZE, first);
+        bytes[last..last + HASH_ENTRY_SIZE].copy_from_slice(&first_entry);
+
+        check_repair(&original, &bytes);
+    }
+
+    #[test]
+    fn test_mismatched_hashes() {
+        let original = build_map();
+        let mut bytes = original.clone();
+
+        for index in [1, 4] {
+            let hash = hash_entry_offset(&bytes, index);
+            bytes[hash..hash + 4].copy_from_slice(&(index as u32).to_le_bytes());
+        }
+
+        check_repair(&original, &bytes);
+    }
+
+    #[test]
+    fn test_index_out_of_bounds() {
+        let original = build_map();
+        let mut bytes = original.clone();
+
+        let index = hash_entry_offset(&bytes, 2) + 4;
+        bytes[index..index + 4].copy_from_slice(&100u32.to_le_bytes());
+
+        check_repair(&original, &bytes);
+    }
+
+    #[test]
+    fn test_wrong_hash_factor() {
+        let original = build_map();
+        let mut bytes = original.clone();
+        bytes[HASH_FACTOR_OFFSET..HASH_FACTOR_OFFSET + 4].copy_from_slice(&0x25u32.to_le_bytes());
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        assert!(map.validate().is_err());
+
+        // The known factor restores the original.
+        let repaired = map.repair_hash_table(Some(0x1f)).unwrap();
+        assert_eq!(repaired, original);
+        check_repaired(&repaired);
+
+        // Keeping the factor of the header rehashes all entries with it, which works just as well.
+        let repaired = map.repair_hash_table(None).unwrap();
+        assert_eq!(
+            repaired[HASH_FACTOR_OFFSET..HASH_FACTOR_OFFSET + 4],
+            0x25u32.to_le_bytes()
+        );
+        check_repaired(&repaired);
+    }
+
+    #[test]
+    fn test_hash_table_out_of_bounds() {
+        let original = build_map();
+        let mut bytes = original.clone();
+        let hash_entry_offset_field = 20;
+        bytes[hash_entry_offset_field..hash_entry_offset_field + 4]
+            .copy_from_slice(&0xffff_0000u32.to_le_bytes());
+
+        // The new hash table is appended to the section at the next 4-byte boundary.
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let repaired = map.repair_hash_table(None).unwrap();
+        let start = (original.len() + 3) & !3;
+        assert_eq!(repaired.len(), start + NAMES.len() * HASH_ENTRY_SIZE);
+
+        // Only the header has been changed in place.
+        let header_size = map.schema().map_header_size();
+        assert_eq!(
+            repaired[header_size..original.len()],
+            bytes[header_size..original.len()]
+        );
+
+        let repaired_map = ApiSetMap::try_from_apiset_section_bytes(&repaired).unwrap();
+        assert_eq!(repaired_map.hash_entry_offset() as usize, start);
+        check_repaired(&repaired);
+    }
+
+    #[test]
+    fn test_valid_map() {
+        let original = build_map();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&original).unwrap();
+
+        assert_eq!(map.repair_hash_table(None).unwrap(), original);
+        assert_eq!(map.repair_hash_table(Some(0x1f)).unwrap(), original);
+
+        // Another factor is written to the header along with the rehashed entries.
+        let repaired = map.repair_hash_table(Some(0x25)).unwrap();
+        assert_ne!(repaired, original);
+        assert_eq!(
+            repaired[HASH_FACTOR_OFFSET..HASH_FACTOR_OFFSET + 4],
+            0x25u32.to_le_bytes()
+        );
+        check_repaired(&repaired);
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let bytes = build_legacy_map(2, &[("ms-win-core-file-l1-1-0", &[("", "kernel32.dll")])]);
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+        assert_eq!(
+            map.repair_hash_table(None).unwrap_err(),
+            NtApiSetError::UnsupportedVersion { version: 2 }
+        );
+    }
+}
@@ -0,0 +1,129 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use nt_string::u16strle::U16StrLe;
+
+use crate::error::Result;
+use crate::extension::ApiSetMapView;
+#[cfg(feature = "alloc")]
+use crate::index::ApiSetIndex;
+use crate::map::ApiSetMap;
+#[cfg(feature = "alloc")]
+use crate::owned::OwnedApiSetMap;
+
+/// Common interface of all types that resolve an imported API Set to the name of its host DLL.
+///
+/// This allows code to be written once for an [`ApiSetMap`], an [`OwnedApiSetMap`], an [`ApiSetIndex`],
+/// or an [`ApiSetMapView`], and to hand out a resolver as a trait object, e.g. via `ApiSetMapCell::resolver`.
+///
+/// All implementations follow the semantics of [`ApiSetMap::resolve`].
+pub trait ApiSetResolver: Send + Sync {
+    /// Resolves an imported API Set to the name of its host DLL, the same way the Windows loader does.
+    ///
+    /// See [`ApiSetMap::resolve`].
+    fn resolve(
+        &self,
+        apiset_name: &str,
+        importing_module: Option<&str>,
+    ) -> Option<Result<U16StrLe<'_>>>;
+}
+
+impl<'a> ApiSetResolver for ApiSetMap<'a> {
+    fn resolve(
+        &self,
+        apiset_name: &str,
+        importing_module: Option<&str>,
+    ) -> Option<Result<U16StrLe<'_>>> {
+        ApiSetMap::resolve(self, apiset_name, importing_module)
+    }
+}
+
+impl<'m, 'a> ApiSetResolver for ApiSetMapView<'m, 'a> {
+    fn resolve(
+        &self,
+        apiset_name: &str,
+        importing_module: Option<&str>,
+    ) -> Option<Result<U16StrLe<'_>>> {
+        ApiSetMapView::resolve(self, apiset_name, importing_module)
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+impl<'a> ApiSetResolver for ApiSetIndex<'a> {
+    fn resolve(
+        &self,
+        apiset_name: &str,
+        importing_module: Option<&str>,
+    ) -> Option<Result<U16StrLe<'_>>> {
+        ApiSetIndex::resolve(self, apiset_name, importing_module).map(Ok)
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+impl ApiSetResolver for OwnedApiSetMap {
+    fn resolve(
+        &self,
+        apiset_name: &str,
+        importing_module: Option<&str>,
+    ) -> Option<Result<U16StrLe<'_>>> {
+        OwnedApiSetMap::resolve(self, apiset_name, importing_module)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+    use crate::map::ApiSetMapFlags;
+    use crate::namespace_entry::ApiSetNamespaceEntryFlags;
+
+    fn build(flags: ApiSetMapFlags, host: &str) -> Vec<u8> {
+        ApiSetMapBuilder::new()
+            .flags(flags)
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "api-ms-win-core-foo-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                )
+                .add_value_entry("", host)
+                .add_value_entry("kernel32.dll", "kernelbase.dll"),
+            )
+            .build()
+            .unwrap()
+    }
+
+    fn check(resolver: &dyn ApiSetResolver, default_host: &str) {
+        let name = "API-MS-WIN-CORE-FOO-L1-1-1.dll";
+        assert_eq!(resolver.resolve(name, None).unwrap().unwrap(), default_host);
+        assert_eq!(
+            resolver
+                .resolve(name, Some("kernel32.dll"))
+                .unwrap()
+                .unwrap(),
+            "kernelbase.dll"
+        );
+        assert!(resolver
+            .resolve("api-ms-win-core-bar-l1-1-0.dll", None)
+            .is_none());
+    }
+
+    #[test]
+    fn test_implementations() {
+        let bytes = build(ApiSetMapFlags::empty(), "foo.dll");
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        check(&map, "foo.dll");
+        check(&map.to_owned_map(), "foo.dll");
+        check(&map.build_index().unwrap(), "foo.dll");
+
+        let extension_bytes = build(ApiSetMapFlags::IS_EXTENSION, "foo_ext.dll");
+        let extension = ApiSetMap::try_from_apiset_section_bytes(&extension_bytes).unwrap();
+        check(
+            &ApiSetMapView::with_extension(&map, &extension).unwrap(),
+            "foo_ext.dll",
+        );
+    }
+}
@@ -0,0 +1,92 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// The internal structures are slightly different for older Windows versions.
+// See https://www.geoffchappell.com/studies/windows/win32/apisetschema/index.htm
+
+use core::mem;
+
+use crate::map::{ApiSetMapHeader, ApiSetMapHeaderV2, ApiSetMapHeaderV4};
+use crate::namespace_entry::{
+    ApiSetNamespaceEntryHeader, ApiSetNamespaceEntryHeaderV2, ApiSetNamespaceEntryHeaderV4,
+};
+use crate::value_entry::{
+    ApiSetValueArrayHeaderV2, ApiSetValueArrayHeaderV4, ApiSetValueEntryHeader,
+    ApiSetValueEntryHeaderV2,
+};
+
+/// On-disk layout of an API Set Map, as determined by its version.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Schema {
+    /// Version 2, used by Windows 7.
+    ///
+    /// There are no flags and no hash table.
+    /// Value Entries are preceded by a count.
+    V2,
+    /// Version 4, used by Windows 8.1.
+    ///
+    /// There is no hash table.
+    /// Value Entries are preceded by flags and a count.
+    V4,
+    /// Version 6, used by Windows 10 and later.
+    V6,
+}
+
+impl Schema {
+    pub(crate) const fn from_version(version: u32) -> Option<Self> {
+        match version {
+            2 => Some(Self::V2),
+            // Version 3 of Windows 8 has a layout of its own, which is not supported.
+            3 => None,
+            4 => Some(Self::V4),
+            6 => Some(Self::V6),
+            _ => None,
+        }
+    }
+
+    /// Returns whether Namespace Entries, Value Entries, and the API Set Map itself have flags.
+    pub(crate) const fn has_flags(self) -> bool {
+        !matches!(self, Self::V2)
+    }
+
+    /// Returns whether names of Namespace Entries are stored with their "api-" or "ext-" prefix.
+    pub(crate) const fn has_name_prefix(self) -> bool {
+        matches!(self, Self::V6)
+    }
+
+    pub(crate) const fn has_hash_table(self) -> bool {
+        matches!(self, Self::V6)
+    }
+
+    pub(crate) const fn map_header_size(self) -> usize {
+        match self {
+            Self::V2 => mem::size_of::<ApiSetMapHeaderV2>(),
+            Self::V4 => mem::size_of::<ApiSetMapHeaderV4>(),
+            Self::V6 => mem::size_of::<ApiSetMapHeader>(),
+        }
+    }
+
+    pub(crate) const fn namespace_entry_size(self) -> usize {
+        match self {
+            Self::V2 => mem::size_of::<ApiSetNamespaceEntryHeaderV2>(),
+            Self::V4 => mem::size_of::<ApiSetNamespaceEntryHeaderV4>(),
+            Self::V6 => mem::size_of::<ApiSetNamespaceEntryHeader>(),
+        }
+    }
+
+    /// Returns the size of the structure preceding the Value Entries of a Namespace Entry.
+    pub(crate) const fn value_array_header_size(self) -> usize {
+        match self {
+            Self::V2 => mem::size_of::<ApiSetValueArrayHeaderV2>(),
+            Self::V4 => mem::size_of::<ApiSetValueArrayHeaderV4>(),
+            Self::V6 => 0,
+        }
+    }
+
+    pub(crate) const fn value_entry_size(self) -> usize {
+        match self {
+            Self::V2 => mem::size_of::<ApiSetValueEntryHeaderV2>(),
+            Self::V4 | Self::V6 => mem::size_of::<ApiSetValueEntryHeader>(),
+        }
+    }
+}
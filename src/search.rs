@@ -0,0 +1,273 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::cmp::Ordering;
+use core::iter::FusedIterator;
+
+use crate::error::Result;
+use crate::helpers::{
+    cmp_ignore_ascii_case_str, contains_ignore_ascii_case_str, starts_with_ignore_ascii_case_str,
+};
+use crate::map::ApiSetMap;
+use crate::namespace_entry::{ApiSetNamespaceEntries, ApiSetNamespaceEntry};
+
+/// Iterator over the [`ApiSetNamespaceEntry`]s of an [`ApiSetMap`] whose names begin with a given prefix.
+///
+/// This iterator is returned by [`ApiSetMap::find_namespace_entries_by_prefix`].
+/// It starts at the first matching Namespace Entry and stops at the first one that doesn't match anymore.
+/// A Namespace Entry whose name cannot be read is returned as an error item.
+#[derive(Clone, Debug)]
+pub struct ApiSetPrefixMatches<'a, 'p> {
+    prefix: &'p str,
+    namespace_entries: ApiSetNamespaceEntries<'a>,
+    done: bool,
+}
+
+impl<'a, 'p> Iterator for ApiSetPrefixMatches<'a, 'p> {
+    type Item = Result<ApiSetNamespaceEntry<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let namespace_entry = self.namespace_entries.next()?;
+        let name = iter_try!(namespace_entry.name());
+
+        if starts_with_ignore_ascii_case_str(&name, self.prefix) {
+            Some(Ok(namespace_entry))
+        } else {
+            // All entries with that prefix are adjacent, so there won't be any further matches.
+            self.done = true;
+            None
+        }
+    }
+}
+
+impl<'a, 'p> FusedIterator for ApiSetPrefixMatches<'a, 'p> {}
+
+/// Iterator over the [`ApiSetNamespaceEntry`]s of an [`ApiSetMap`] whose names contain a given string.
+///
+/// This iterator is returned by [`ApiSetMap::find_namespace_entries_containing`].
+/// A Namespace Entry whose name cannot be read is returned as an error item.
+#[derive(Clone, Debug)]
+pub struct ApiSetSubstringMatches<'a, 'n> {
+    needle: &'n str,
+    namespace_entries: ApiSetNamespaceEntries<'a>,
+}
+
+impl<'a, 'n> Iterator for ApiSetSubstringMatches<'a, 'n> {
+    type Item = Result<ApiSetNamespaceEntry<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for namespace_entry in &mut self.namespace_entries {
+            let name = iter_try!(namespace_entry.name());
+
+            if contains_ignore_ascii_case_str(&name, self.needle) {
+                return Some(Ok(namespace_entry));
+            }
+        }
+
+        None
+    }
+}
+
+impl<'a, 'n> FusedIterator for ApiSetSubstringMatches<'a, 'n> {}
+
+impl<'a> ApiSetMap<'a> {
+    /// Returns an iterator over all Namespace Entries whose names begin with `prefix`, ignoring ASCII case.
+    ///
+    /// This is useful for queries like "all API Sets beginning with `api-ms-win-crt-`".
+    /// As the Namespace Entries are sorted case-insensitively by name, the first match is found via binary search,
+    /// and the iterator stops at the first Namespace Entry that doesn't match anymore.
+    /// Names are compared on their UTF-16 code units without allocating.
+    ///
+    /// An error is returned if a name cannot be read during the binary search.
+    /// Note that API Set Maps before version 6 store the names without the "api-" or "ext-" prefix.
+    pub fn find_namespace_entries_by_prefix<'p>(
+        &self,
+        prefix: &'p str,
+    ) -> Result<ApiSetPrefixMatches<'a, 'p>> {
+        let namespace_entries = self.namespace_entries()?;
+
+        // Find the first Namespace Entry whose name is not less than `prefix`.
+        let mut left = 0;
+        let mut right = namespace_entries.len();
+
+        while left < right {
+            let mid = left + (right - left) / 2;
+            let namespace_entry = match namespace_entries.clone().nth(mid) {
+                Some(namespace_entry) => namespace_entry,
+                None => break,
+            };
+
+            if cmp_ignore_ascii_case_str(&namespace_entry.name()?, prefix) == Ordering::Less {
+                left = mid + 1;
+            } else {
+                right = mid;
+            }
+        }
+
+        let count = namespace_entries.len();
+        let namespace_entries = namespace_entries.restrict(left..count)?;
+
+        Ok(ApiSetPrefixMatches {
+            prefix,
+            namespace_entries,
+            done: false,
+        })
+    }
+
+    /// Returns an iterator over all Namespace Entries whose names contain `needle`, ignoring ASCII case.
+    ///
+    /// This is useful for queries like "all API Sets containing `gaming`".
+    /// In contrast to [`find_namespace_entries_by_prefix`](Self::find_namespace_entries_by_prefix), this has to check every Namespace Entry.
+    /// Names are compared on their UTF-16 code units without allocating.
+    pub fn find_namespace_entries_containing<'n>(
+        &self,
+        needle: &'n str,
+    ) -> Result<ApiSetSubstringMatches<'a, 'n>> {
+        Ok(ApiSetSubstringMatches {
+            needle,
+            namespace_entries: self.namespace_entries()?,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+    use crate::fixtures::build_legacy_map;
+    use crate::namespace_entry::ApiSetNamespaceEntryFlags;
+
+    // Deliberately not sorted, the builder has to do that.
+    const NAMES: [&str; 8] = [
+        "ext-ms-win-gaming-tcui-l1-1-0",
+        "api-ms-win-core-file-l1-1-0",
+        "API-MS-WIN-CORE-FILE-L2-1-0",
+        "api-ms-win-core-fibers-l1-1-0",
+        "api-ms-win-crt-heap-l1-1-0",
+        "api-ms-win-core-sysinfo-l1-2-0",
+        "ext-ms-win-gaming-xinput-l1-1-0",
+        "api-ms-win-crt-string-l1-1-0",
+    ];
+
+    const PREFIXES: [&str; 14] = [
+        "",
+        "a",
+        "api-",
+        "API-MS-WIN-CORE-",
+        "api-ms-win-core-fi",
+        "Api-Ms-Win-Core-File-",
+        "api-ms-win-core-file-l1-1-0",
+        "api-ms-win-core-file-l1-1-0-extra",
+        "api-ms-win-crt-",
+        "api-ms-win-d",
+        "ext-ms-win-gaming-",
+        "0",
+        "zzz",
+        "ms-win-core-",
+    ];
+
+    const NEEDLES: [&str; 8] = [
+        "", "CORE", "-l1-1-", "L2", "gaming", "heap-l1", "-0", "nothing",
+    ];
+
+    fn names<'a>(
+        namespace_entries: impl Iterator<Item = Result<ApiSetNamespaceEntry<'a>>>,
+    ) -> Vec<String> {
+        namespace_entries
+            .map(|namespace_entry| {
+                namespace_entry
+                    .unwrap()
+                    .name()
+                    .unwrap()
+                    .to_string()
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    fn brute_force(map: &ApiSetMap, matches: impl Fn(&str) -> bool) -> Vec<String> {
+        names(map.namespace_entries().unwrap().map(Ok))
+            .into_iter()
+            .filter(|name| matches(&name.to_ascii_lowercase()))
+            .collect()
+    }
+
+    fn check_searches(map: &ApiSetMap) {
+        for prefix in PREFIXES {
+            let lowercase_prefix = prefix.to_ascii_lowercase();
+            assert_eq!(
+                names(map.find_namespace_entries_by_prefix(prefix).unwrap()),
+                brute_force(map, |name| name.starts_with(&lowercase_prefix)),
+                "prefix {prefix:?}"
+            );
+        }
+
+        for needle in NEEDLES {
+            let lowercase_needle = needle.to_ascii_lowercase();
+            assert_eq!(
+                names(map.find_namespace_entries_containing(needle).unwrap()),
+                brute_force(map, |name| name.contains(&lowercase_needle)),
+                "needle {needle:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_searches() {
+        let bytes = NAMES
+            .iter()
+            .fold(ApiSetMapBuilder::new(), |builder, name| {
+                builder.add_namespace_entry(
+                    ApiSetNamespaceEntryBuilder::new(name, ApiSetNamespaceEntryFlags::empty())
+                        .add_value_entry("", "foo.dll"),
+                )
+            })
+            .build()
+            .unwrap();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        check_searches(&map);
+
+        // Also check one result by hand, in case the brute force is wrong as well.
+        assert_eq!(
+            names(
+                map.find_namespace_entries_by_prefix("API-MS-WIN-CORE-FI")
+                    .unwrap()
+            ),
+            [
+                "api-ms-win-core-fibers-l1-1-0",
+                "api-ms-win-core-file-l1-1-0",
+                "API-MS-WIN-CORE-FILE-L2-1-0"
+            ]
+        );
+        assert_eq!(
+            names(map.find_namespace_entries_by_prefix("").unwrap()).len(),
+            NAMES.len()
+        );
+
+        let empty_bytes = ApiSetMapBuilder::new().build().unwrap();
+        check_searches(&ApiSetMap::try_from_apiset_section_bytes(&empty_bytes).unwrap());
+    }
+
+    #[test]
+    fn test_searches_legacy() {
+        let mut legacy_names = NAMES
+            .iter()
+            .map(|name| name.to_ascii_lowercase()[4..].to_string())
+            .collect::<Vec<_>>();
+        legacy_names.sort_unstable();
+        let entries = legacy_names
+            .iter()
+            .map(|name| (name.as_str(), &[("", "foo.dll")][..]))
+            .collect::<Vec<_>>();
+
+        let bytes = build_legacy_map(4, &entries);
+        check_searches(&ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap());
+    }
+}
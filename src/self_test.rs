@@ -0,0 +1,259 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::cmp::Ordering;
+
+use alloc::vec::Vec;
+
+use crate::error::Result;
+use crate::helpers::cmp_ignore_ascii_case;
+use crate::map::ApiSetMap;
+
+/// API Sets that have existed in every API Set Map since Windows 10 RTM.
+///
+/// These are probed by [`ApiSetMap::self_test`].
+/// The list is only compiled in with the `self-test` feature.
+const WELL_KNOWN_CONTRACTS: &[&str] = &[
+    "api-ms-win-core-file-l1-1-0",
+    "api-ms-win-core-handle-l1-1-0",
+    "api-ms-win-core-heap-l1-1-0",
+    "api-ms-win-core-memory-l1-1-0",
+    "api-ms-win-core-processthreads-l1-1-0",
+    "api-ms-win-core-synch-l1-1-0",
+    "api-ms-win-core-sysinfo-l1-1-0",
+];
+
+/// Result of [`ApiSetMap::self_test`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SelfTestReport {
+    namespace_entry_count: usize,
+    namespace_entries_sorted: bool,
+    hash_entries_sorted: bool,
+    hash_entry_indexes_valid: bool,
+    hashes_valid: bool,
+    failed_probes: Vec<&'static str>,
+}
+
+impl SelfTestReport {
+    /// Returns the well-known API Sets that could not be resolved to a non-empty host module.
+    pub fn failed_probes(&self) -> &[&'static str] {
+        &self.failed_probes
+    }
+
+    /// Returns `true` if every Hash Entry points to an existing Namespace Entry.
+    pub fn hash_entry_indexes_valid(&self) -> bool {
+        self.hash_entry_indexes_valid
+    }
+
+    /// Returns `true` if the hash of every Hash Entry matches the recomputed hash of the Namespace Entry it points to.
+    pub fn hashes_valid(&self) -> bool {
+        self.hashes_valid
+    }
+
+    /// Returns `true` if the Hash Entries are sorted by their hash value.
+    pub fn hash_entries_sorted(&self) -> bool {
+        self.hash_entries_sorted
+    }
+
+    /// Returns `true` if the Namespace Entries are sorted case-insensitively by name.
+    pub fn namespace_entries_sorted(&self) -> bool {
+        self.namespace_entries_sorted
+    }
+
+    /// Returns the number of Namespace Entries found in the API Set Map.
+    pub fn namespace_entry_count(&self) -> usize {
+        self.namespace_entry_count
+    }
+
+    /// Returns `true` if all checks have passed.
+    pub fn passed(&self) -> bool {
+        self.namespace_entry_count > 0
+            && self.namespace_entries_sorted
+            && self.hash_entries_sorted
+            && self.hash_entry_indexes_valid
+            && self.hashes_valid
+            && self.failed_probes.is_empty()
+    }
+}
+
+impl<'a> ApiSetMap<'a> {
+    /// Performs a cheap sanity check of this API Set Map.
+    ///
+    /// This checks that the map is non-empty, its Namespace Entries and Hash Entries are sorted,
+    /// all Hash Entries point to existing Namespace Entries with matching hashes, and a small built-in list of well-known API Sets
+    /// (which have existed since Windows 10 RTM) resolve to non-empty host modules.
+    ///
+    /// It is a smoke test to run before trusting a map with many resolutions, not a complete validation.
+    /// Failed checks are reported in the returned [`SelfTestReport`].
+    /// An error is only returned if the entry arrays or their names cannot be read at all.
+    #[cfg_attr(docsrs, doc(cfg(feature = "self-test")))]
+    pub fn self_test(&self) -> Result<SelfTestReport> {
+        let namespace_entries = self.namespace_entries()?;
+        let namespace_entry_count = namespace_entries.len();
+
+        let mut namespace_entries_sorted = true;
+        let mut previous_name = None;
+
+        for namespace_entry in namespace_entries {
+            let name = namespace_entry.name()?;
+
+            if let Some(previous_name) = previous_name {
+                if cmp_ignore_ascii_case(&previous_name, &name) == Ordering::Greater {
+                    namespace_entries_sorted = false;
+                }
+            }
+
+            previous_name = Some(name);
+        }
+
+        let mut hash_entries_sorted = true;
+        let mut hash_entry_indexes_valid = true;
+        let mut hashes_valid = true;
+        let mut previous_hash = None;
+
+        for joined in self.hash_joined()? {
+            let (hash_entry, namespace_entry) = match joined {
+                Ok(joined) => joined,
+                Err(_) => {
+                    hash_entry_indexes_valid = false;
+                    continue;
+                }
+            };
+
+            let hash = hash_entry.hash();
+            if matches!(previous_hash, Some(previous_hash) if previous_hash > hash) {
+                hash_entries_sorted = false;
+            }
+
+            previous_hash = Some(hash);
+
+            if self.recompute_hash(&namespace_entry) != Some(hash) {
+                hashes_valid = false;
+            }
+        }
+
+        let mut failed_probes = Vec::new();
+
+        for contract in WELL_KNOWN_CONTRACTS {
+            if !self.probe(contract) {
+                failed_probes.push(*contract);
+            }
+        }
+
+        Ok(SelfTestReport {
+            namespace_entry_count,
+            namespace_entries_sorted,
+            hash_entries_sorted,
+            hash_entry_indexes_valid,
+            hashes_valid,
+            failed_probes,
+        })
+    }
+
+    fn probe(&self, contract: &str) -> bool {
+        let namespace_entry = match self.find_namespace_entry(contract) {
+            Some(Ok(namespace_entry)) => namespace_entry,
+            _ => return false,
+        };
+
+        let mut value_entries = match namespace_entry.value_entries() {
+            Ok(value_entries) => value_entries,
+            Err(_) => return false,
+        };
+
+        matches!(
+            value_entries.next().map(|value_entry| value_entry.value()),
+            Some(Ok(value)) if !value.is_empty()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+    use crate::namespace_entry::ApiSetNamespaceEntryFlags;
+
+    fn build_map(contracts: &[(&str, &str)]) -> Vec<u8> {
+        contracts
+            .iter()
+            .fold(ApiSetMapBuilder::new(), |builder, (name, host)| {
+                builder.add_namespace_entry(
+                    ApiSetNamespaceEntryBuilder::new(name, ApiSetNamespaceEntryFlags::SEALED)
+                        .add_value_entry("", host),
+                )
+            })
+            .build()
+            .unwrap()
+    }
+
+    fn build_fixture() -> Vec<u8> {
+        let contracts = WELL_KNOWN_CONTRACTS
+            .iter()
+            .map(|contract| (*contract, "kernelbase.dll"))
+            .collect::<Vec<_>>();
+        build_map(&contracts)
+    }
+
+    #[test]
+    fn test_fixture() {
+        let bytes = build_fixture();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let report = map.self_test().unwrap();
+
+        assert_eq!(report.namespace_entry_count(), WELL_KNOWN_CONTRACTS.len());
+        assert!(report.namespace_entries_sorted());
+        assert!(report.hash_entries_sorted());
+        assert!(report.hash_entry_indexes_valid());
+        assert!(report.hashes_valid());
+        assert!(report.failed_probes().is_empty());
+        assert!(report.passed());
+    }
+
+    #[test]
+    fn test_gutted_map() {
+        // Only two contracts are left, and one of them has lost its host.
+        let bytes = build_map(&[
+            ("api-ms-win-core-file-l1-1-0", "kernelbase.dll"),
+            ("api-ms-win-core-heap-l1-1-0", ""),
+        ]);
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let report = map.self_test().unwrap();
+
+        assert!(report.hashes_valid());
+        assert_eq!(
+            report.failed_probes(),
+            &[
+                "api-ms-win-core-handle-l1-1-0",
+                "api-ms-win-core-heap-l1-1-0",
+                "api-ms-win-core-memory-l1-1-0",
+                "api-ms-win-core-processthreads-l1-1-0",
+                "api-ms-win-core-synch-l1-1-0",
+                "api-ms-win-core-sysinfo-l1-1-0",
+            ]
+        );
+        assert!(!report.passed());
+    }
+
+    #[test]
+    fn test_corrupted_hash() {
+        let mut bytes = build_fixture();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let hash_entry = map.hash_entries().unwrap().next_back().unwrap();
+        let offset = hash_entry.offset();
+
+        // Keep the Hash Entries sorted, so that only the hash check can notice.
+        let corrupted_hash = hash_entry.hash().wrapping_add(1);
+        bytes[offset..offset + 4].copy_from_slice(&corrupted_hash.to_le_bytes());
+
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let report = map.self_test().unwrap();
+
+        assert!(report.hash_entries_sorted());
+        assert!(report.hash_entry_indexes_valid());
+        assert!(!report.hashes_valid());
+        assert!(!report.passed());
+    }
+}
@@ -0,0 +1,328 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::ops::Range;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use nt_string::u16strle::U16StrLe;
+use serde::{Deserialize, Serialize};
+
+use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+use crate::control::OpControl;
+use crate::error::{NtApiSetError, Result};
+use crate::map::{ApiSetMap, ApiSetMapFlags};
+use crate::namespace_entry::ApiSetNamespaceEntryFlags;
+use crate::owned::OwnedApiSetMap;
+use crate::value_entry::ApiSetValueEntryFlags;
+
+/// Owned copy of an entire [`ApiSetMap`], returned by [`ApiSetMap::to_snapshot`].
+///
+/// In contrast to [`ApiSetMap`], this doesn't borrow the section bytes and can be serialized and deserialized via `serde`,
+/// e.g. to export an API Set Map as JSON and compare it between Windows builds.
+///
+/// Deserialization rejects snapshots that [`to_owned_map`](Self::to_owned_map) could not turn into an API Set Map,
+/// in particular duplicate Namespace Entry names and duplicate importing modules within a Namespace Entry.
+///
+/// ```
+/// # use nt_apiset::ApiSetMapSnapshot;
+/// let json = r#"{
+///     "version": 6,
+///     "flags": 1,
+///     "namespace_entries": [{
+///         "name": "api-ms-win-core-sysinfo-l1-1-0",
+///         "flags": 1,
+///         "value_entries": [{ "name": "", "value": "kernelbase.dll", "flags": 0 }]
+///     }]
+/// }"#;
+/// let snapshot: ApiSetMapSnapshot = serde_json::from_str(json).unwrap();
+///
+/// let map = snapshot.to_owned_map().unwrap();
+/// let host = map.resolve("api-ms-win-core-sysinfo-l1-1-0.dll", None).unwrap().unwrap();
+/// assert_eq!(host, "kernelbase.dll");
+/// ```
+#[cfg_attr(docsrs, doc(cfg(all(feature = "alloc", feature = "serde"))))]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[serde(try_from = "UncheckedApiSetMapSnapshot")]
+pub struct ApiSetMapSnapshot {
+    /// See [`ApiSetMap::version`].
+    pub version: u32,
+    /// Raw bits of the [`ApiSetMapFlags`](crate::map::ApiSetMapFlags).
+    pub flags: u32,
+    /// All Namespace Entries in the order they are stored.
+    pub namespace_entries: Vec<ApiSetNamespaceEntrySnapshot>,
+}
+
+/// Owned copy of an [`ApiSetNamespaceEntry`](crate::namespace_entry::ApiSetNamespaceEntry), part of an [`ApiSetMapSnapshot`].
+#[cfg_attr(docsrs, doc(cfg(all(feature = "alloc", feature = "serde"))))]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct ApiSetNamespaceEntrySnapshot {
+    /// See [`ApiSetNamespaceEntry::name`](crate::namespace_entry::ApiSetNamespaceEntry::name).
+    pub name: String,
+    /// Raw bits of the [`ApiSetNamespaceEntryFlags`](crate::namespace_entry::ApiSetNamespaceEntryFlags).
+    pub flags: u32,
+    /// All Value Entries in the order they are stored, beginning with the default entry.
+    pub value_entries: Vec<ApiSetValueEntrySnapshot>,
+}
+
+/// Owned copy of an [`ApiSetValueEntry`](crate::value_entry::ApiSetValueEntry), part of an [`ApiSetNamespaceEntrySnapshot`].
+#[cfg_attr(docsrs, doc(cfg(all(feature = "alloc", feature = "serde"))))]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct ApiSetValueEntrySnapshot {
+    /// See [`ApiSetValueEntry::name`](crate::value_entry::ApiSetValueEntry::name).
+    pub name: String,
+    /// See [`ApiSetValueEntry::value`](crate::value_entry::ApiSetValueEntry::value).
+    pub value: String,
+    /// Raw bits of the [`ApiSetValueEntryFlags`](crate::value_entry::ApiSetValueEntryFlags).
+    pub flags: u32,
+}
+
+impl ApiSetMapSnapshot {
+    /// Returns an [`ApiSetMapBuilder`] that builds a version 6 API Set Map with the contents of this snapshot.
+    ///
+    /// Names are taken as they are.
+    /// Note that snapshots of API Set Maps before version 6 have Namespace Entry names without the "api-" or "ext-" prefix.
+    pub fn to_builder(&self) -> ApiSetMapBuilder {
+        self.namespace_entries.iter().fold(
+            ApiSetMapBuilder::new().flags(ApiSetMapFlags::from_bits_retain(self.flags)),
+            |builder, namespace_entry| builder.add_namespace_entry(namespace_entry.to_builder()),
+        )
+    }
+
+    /// Builds a queryable [`OwnedApiSetMap`] from this snapshot, see [`to_builder`](Self::to_builder).
+    pub fn to_owned_map(&self) -> Result<OwnedApiSetMap> {
+        let section_bytes = self.to_builder().build()?;
+        OwnedApiSetMap::try_from_apiset_section_bytes(section_bytes)
+    }
+}
+
+impl ApiSetNamespaceEntrySnapshot {
+    fn to_builder(&self) -> ApiSetNamespaceEntryBuilder {
+        self.value_entries.iter().fold(
+            ApiSetNamespaceEntryBuilder::new(
+                &self.name,
+                ApiSetNamespaceEntryFlags::from_bits_retain(self.flags),
+            ),
+            |builder, value_entry| {
+                builder.add_value_entry_with_flags(
+                    &value_entry.name,
+                    &value_entry.value,
+                    ApiSetValueEntryFlags::from_bits_retain(value_entry.flags),
+                )
+            },
+        )
+    }
+}
+
+/// Deserialized fields of an [`ApiSetMapSnapshot`] before they have been checked.
+#[derive(Deserialize)]
+struct UncheckedApiSetMapSnapshot {
+    version: u32,
+    flags: u32,
+    namespace_entries: Vec<ApiSetNamespaceEntrySnapshot>,
+}
+
+impl TryFrom<UncheckedApiSetMapSnapshot> for ApiSetMapSnapshot {
+    type Error = NtApiSetError;
+
+    fn try_from(unchecked: UncheckedApiSetMapSnapshot) -> Result<Self> {
+        let snapshot = Self {
+            version: unchecked.version,
+            flags: unchecked.flags,
+            namespace_entries: unchecked.namespace_entries,
+        };
+        snapshot.to_builder().check()?;
+
+        Ok(snapshot)
+    }
+}
+
+impl<'a> ApiSetMap<'a> {
+    /// Copies all Namespace Entries and Value Entries of this API Set Map into an owned [`ApiSetMapSnapshot`].
+    ///
+    /// An error is returned if any entry cannot be read or any string is not valid UTF-16.
+    #[cfg_attr(docsrs, doc(cfg(all(feature = "alloc", feature = "serde"))))]
+    pub fn to_snapshot(&self) -> Result<ApiSetMapSnapshot> {
+        self.to_snapshot_with(&OpControl::new())
+    }
+
+    /// Performs the same as [`to_snapshot`](Self::to_snapshot), but reports progress and checks for cancellation via `control`.
+    ///
+    /// Every Namespace Entry (along with its Value Entries) counts as one step.
+    /// If the operation is cancelled, [`NtApiSetError::Cancelled`] is returned.
+    #[cfg_attr(docsrs, doc(cfg(all(feature = "alloc", feature = "serde"))))]
+    pub fn to_snapshot_with(&self, control: &OpControl) -> Result<ApiSetMapSnapshot> {
+        let namespace_entries_iter = self.namespace_entries()?;
+        let mut progress = control.start(namespace_entries_iter.len());
+        let mut namespace_entries = Vec::new();
+
+        for namespace_entry in namespace_entries_iter {
+            progress.check()?;
+            let mut value_entries = Vec::new();
+
+            for value_entry in namespace_entry.value_entries()? {
+                value_entries.push(ApiSetValueEntrySnapshot {
+                    name: to_string(&value_entry.name()?, value_entry.name_range())?,
+                    value: to_string(&value_entry.value()?, value_entry.value_range())?,
+                    flags: value_entry.raw_flags(),
+                });
+            }
+
+            namespace_entries.push(ApiSetNamespaceEntrySnapshot {
+                name: to_string(&namespace_entry.name()?, namespace_entry.name_range())?,
+                flags: namespace_entry.flags().bits(),
+                value_entries,
+            });
+            progress.advance();
+        }
+
+        Ok(ApiSetMapSnapshot {
+            version: self.version(),
+            flags: self.flags().bits(),
+            namespace_entries,
+        })
+    }
+}
+
+/// Returns a JSON Schema document describing the JSON serialization of an [`ApiSetMapSnapshot`].
+///
+/// This allows consumers in other languages to validate exported snapshots and generate code for them.
+#[cfg(feature = "json-schema")]
+#[cfg_attr(docsrs, doc(cfg(feature = "json-schema")))]
+pub fn export_json_schema() -> String {
+    let schema = schemars::schema_for!(ApiSetMapSnapshot);
+    serde_json::to_string_pretty(&schema).expect("a JSON Schema can always be serialized")
+}
+
+fn to_string(string: &U16StrLe, range: Range<usize>) -> Result<String> {
+    string
+        .to_string()
+        .map_err(|_| NtApiSetError::InvalidUtf16 { range })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_map() -> Vec<u8> {
+        ApiSetMapBuilder::new()
+            .flags(ApiSetMapFlags::SEALED)
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "api-ms-win-core-processthreads-l1-1-2",
+                    ApiSetNamespaceEntryFlags::SEALED,
+                )
+                .add_value_entry("", "kernelbase.dll")
+                .add_value_entry_with_flags(
+                    "kernel32.dll",
+                    "kernel32legacy.dll",
+                    ApiSetValueEntryFlags::SEALED,
+                ),
+            )
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "ext-ms-win-gdi-draw-l1-1-0",
+                    ApiSetNamespaceEntryFlags::IS_EXTENSION,
+                )
+                .add_value_entry("", "gdi32full.dll"),
+            )
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let bytes = build_map();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let snapshot = map.to_snapshot().unwrap();
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let loaded: ApiSetMapSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, snapshot);
+
+        let owned_map = loaded.to_owned_map().unwrap();
+        let loaded_map = owned_map.as_map();
+        assert_eq!(loaded_map.to_snapshot().unwrap(), snapshot);
+        assert!(owned_map.semantic_eq(&map).unwrap());
+
+        for (apiset_name, importing_module, expected) in [
+            (
+                "api-ms-win-core-processthreads-l1-1-2.dll",
+                None,
+                "kernelbase.dll",
+            ),
+            (
+                "api-ms-win-core-processthreads-l1-1-2.dll",
+                Some("kernel32.dll"),
+                "kernel32legacy.dll",
+            ),
+            ("ext-ms-win-gdi-draw-l1-1-0.dll", None, "gdi32full.dll"),
+        ] {
+            let host = owned_map
+                .resolve(apiset_name, importing_module)
+                .unwrap()
+                .unwrap();
+            assert_eq!(host, expected);
+        }
+    }
+
+    #[test]
+    fn test_malformed_json() {
+        let bytes = build_map();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let json = serde_json::to_string(&map.to_snapshot().unwrap()).unwrap();
+
+        // Truncated JSON and a missing field.
+        assert!(serde_json::from_str::<ApiSetMapSnapshot>(&json[..json.len() - 1]).is_err());
+        assert!(
+            serde_json::from_str::<ApiSetMapSnapshot>(r#"{"version": 6, "flags": 0}"#).is_err()
+        );
+
+        // Duplicate Namespace Entry names.
+        let json = r#"{"version": 6, "flags": 0, "namespace_entries": [
+            {"name": "api-ms-win-core-foo-l1-1-0", "flags": 0, "value_entries": []},
+            {"name": "API-MS-WIN-CORE-FOO-L1-1-0", "flags": 0, "value_entries": []}
+        ]}"#;
+        let error = serde_json::from_str::<ApiSetMapSnapshot>(json).unwrap_err();
+        assert!(error.to_string().contains("same name"), "{error}");
+
+        // Duplicate importing modules within a Namespace Entry.
+        let json = r#"{"version": 6, "flags": 0, "namespace_entries": [
+            {"name": "api-ms-win-core-foo-l1-1-0", "flags": 0, "value_entries": [
+                {"name": "", "value": "foo.dll", "flags": 0},
+                {"name": "kernel32.dll", "value": "foo1.dll", "flags": 0},
+                {"name": "Kernel32.dll", "value": "foo2.dll", "flags": 0}
+            ]}
+        ]}"#;
+        let error = serde_json::from_str::<ApiSetMapSnapshot>(json).unwrap_err();
+        assert!(
+            error.to_string().contains("same importing module"),
+            "{error}"
+        );
+    }
+
+    #[cfg(feature = "json-schema")]
+    #[test]
+    fn test_json_schema() {
+        let schema: serde_json::Value = serde_json::from_str(&export_json_schema()).unwrap();
+        let validator = jsonschema::validator_for(&schema).unwrap();
+
+        let bytes = build_map();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let instance = serde_json::to_value(map.to_snapshot().unwrap()).unwrap();
+        assert!(validator.is_valid(&instance));
+
+        // A missing field and a negative number are rejected.
+        let mut invalid = instance.clone();
+        invalid.as_object_mut().unwrap().remove("flags");
+        assert!(!validator.is_valid(&invalid));
+
+        let mut invalid = instance;
+        invalid["namespace_entries"][0]["value_entries"][0]["flags"] = serde_json::json!(-1);
+        assert!(!validator.is_valid(&invalid));
+    }
+}
@@ -0,0 +1,211 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+use crate::namespace_entry::ApiSetNamespaceEntryFlags;
+
+/// Number of Namespace Entries of [`synthetic_map`], which is roughly the size of the API Set Map of a current Windows release.
+#[cfg_attr(docsrs, doc(cfg(feature = "test-utils")))]
+pub const SYNTHETIC_MAP_NAMESPACE_ENTRIES: usize = 2500;
+
+/// Highest number of Namespace Entries supported by [`synthetic_map_builder`].
+#[cfg_attr(docsrs, doc(cfg(feature = "test-utils")))]
+pub const SYNTHETIC_MAP_MAX_NAMESPACE_ENTRIES: usize = 20_000;
+
+const FAMILIES: &[&str] = &[
+    "appmodel", "base", "core", "devices", "eventing", "gaming", "gdi", "mm", "net", "ntuser",
+    "ole", "power", "rtcore", "security", "service", "shell", "shcore", "storage", "ui", "winrt",
+];
+
+const COMPONENTS: &[&str] = &[
+    "apiquery",
+    "com",
+    "console",
+    "datetime",
+    "debug",
+    "delayload",
+    "errorhandling",
+    "fibers",
+    "file",
+    "handle",
+    "heap",
+    "interlocked",
+    "io",
+    "libraryloader",
+    "localization",
+    "memory",
+    "namedpipe",
+    "path",
+    "processenvironment",
+    "processthreads",
+    "profile",
+    "registry",
+    "rtlsupport",
+    "string",
+    "synch",
+    "sysinfo",
+    "threadpool",
+    "timezone",
+    "util",
+    "version",
+];
+
+const HOSTS: &[&str] = &[
+    "kernelbase.dll",
+    "kernel32.dll",
+    "ntdll.dll",
+    "user32.dll",
+    "gdi32.dll",
+    "advapi32.dll",
+    "sechost.dll",
+    "combase.dll",
+    "shcore.dll",
+    "ucrtbase.dll",
+    "win32u.dll",
+    "rpcrt4.dll",
+];
+
+/// Generates the bytes of a version 6 `.apiset` section with [`SYNTHETIC_MAP_NAMESPACE_ENTRIES`] Namespace Entries.
+///
+/// This is a shortcut for building the result of [`synthetic_map_builder`].
+#[cfg_attr(docsrs, doc(cfg(feature = "test-utils")))]
+pub fn synthetic_map(seed: u64) -> Vec<u8> {
+    synthetic_map_builder(seed, SYNTHETIC_MAP_NAMESPACE_ENTRIES)
+        .build()
+        .expect("the synthetic map only contains valid names")
+}
+
+/// Returns an [`ApiSetMapBuilder`] for a synthetic but realistic API Set Map with `namespace_entries` Namespace Entries.
+///
+/// The map mirrors the shape of a real `apisetschema.dll`: Namespace Entries are named like `api-ms-win-core-synch-l1-2-0`
+/// or `ext-ms-win-shell-file-l1-1-0`, most of them are mapped to a single host, some have an additional host for a specific
+/// importing module, and a few have no Value Entries at all.
+/// It doesn't contain any data from Microsoft binaries and can therefore be redistributed freely.
+///
+/// The same `seed` always generates the same map, so it is suited for tests and benchmarks.
+///
+/// # Panics
+///
+/// Panics if `namespace_entries` exceeds [`SYNTHETIC_MAP_MAX_NAMESPACE_ENTRIES`].
+#[cfg_attr(docsrs, doc(cfg(feature = "test-utils")))]
+pub fn synthetic_map_builder(seed: u64, namespace_entries: usize) -> ApiSetMapBuilder {
+    assert!(
+        namespace_entries <= SYNTHETIC_MAP_MAX_NAMESPACE_ENTRIES,
+        "a synthetic map can have at most {SYNTHETIC_MAP_MAX_NAMESPACE_ENTRIES} Namespace Entries"
+    );
+
+    let mut rng = XorShift64::new(seed);
+    let mut names = BTreeSet::new();
+    let mut builder = ApiSetMapBuilder::new();
+
+    while names.len() < namespace_entries {
+        let is_extension = rng.below(4) == 0;
+        let prefix = if is_extension {
+            "ext-ms-win"
+        } else {
+            "api-ms-win"
+        };
+        let family = rng.pick(FAMILIES);
+        let component = rng.pick(COMPONENTS);
+        let variant = match rng.below(10) {
+            0 => String::new(),
+            n => format!("{}", n + 1),
+        };
+        let name = format!(
+            "{prefix}-{family}-{component}{variant}-l{}-{}-{}",
+            rng.below(2) + 1,
+            rng.below(3) + 1,
+            rng.below(4)
+        );
+
+        if !names.insert(name.clone()) {
+            continue;
+        }
+
+        let flags = if is_extension {
+            ApiSetNamespaceEntryFlags::IS_EXTENSION
+        } else if family == "core" {
+            ApiSetNamespaceEntryFlags::SEALED
+        } else {
+            ApiSetNamespaceEntryFlags::empty()
+        };
+        let mut namespace_entry = ApiSetNamespaceEntryBuilder::new(&name, flags);
+
+        // About 5% of all API Sets have no host at all.
+        if rng.below(20) != 0 {
+            let host = if rng.below(3) == 0 {
+                format!("{component}.dll")
+            } else {
+                String::from(rng.pick(HOSTS))
+            };
+            namespace_entry = namespace_entry.add_value_entry("", &host);
+
+            // About 10% of them redirect a specific importing module to a different host.
+            if rng.below(10) == 0 {
+                namespace_entry = namespace_entry.add_value_entry(&host, "kernel32.dll");
+            }
+        }
+
+        builder = builder.add_namespace_entry(namespace_entry);
+    }
+
+    builder
+}
+
+/// Minimal deterministic pseudo-random number generator, which is sufficient for generating test data.
+struct XorShift64(u64);
+
+impl XorShift64 {
+    const fn new(seed: u64) -> Self {
+        // A state of zero would only ever produce zeros.
+        Self(seed ^ 0x9e37_79b9_7f4a_7c15)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+
+    fn pick<'s>(&mut self, items: &[&'s str]) -> &'s str {
+        items[self.below(items.len())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::ApiSetMap;
+
+    #[test]
+    fn test_synthetic_map() {
+        let bytes = synthetic_map(1);
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+        assert_eq!(map.count() as usize, SYNTHETIC_MAP_NAMESPACE_ENTRIES);
+        map.validate().unwrap();
+
+        let mut value_entries = 0;
+
+        for namespace_entry in map.namespace_entries().unwrap() {
+            let name = namespace_entry.name().unwrap().to_string().unwrap();
+            let found = map.find_namespace_entry(&name).unwrap().unwrap();
+            assert_eq!(found.offset(), namespace_entry.offset());
+            value_entries += namespace_entry.value_count().unwrap();
+        }
+
+        assert!(value_entries > SYNTHETIC_MAP_NAMESPACE_ENTRIES * 9 / 10);
+    }
+
+    #[test]
+    fn test_deterministic() {
+        assert_eq!(synthetic_map(1), synthetic_map(1));
+        assert_ne!(synthetic_map(1), synthetic_map(2));
+    }
+}
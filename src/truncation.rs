@@ -0,0 +1,244 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::iter::FusedIterator;
+use core::mem;
+
+use crate::error::{NtApiSetError, Result};
+use crate::hash_entry::{ApiSetHashEntries, ApiSetHashEntryHeader};
+use crate::helpers::clamp_entry_array;
+use crate::map::ApiSetMap;
+use crate::namespace_entry::{ApiSetNamespaceEntries, ApiSetNamespaceEntry};
+use crate::value_entry::ApiSetValueEntries;
+
+/// Iterator over an entry array of an [`ApiSetMap`] that reports entries cut off by the end of the `.apiset` section.
+///
+/// This iterator is returned by [`ApiSetMap::try_namespace_entries`], [`ApiSetMap::try_hash_entries`],
+/// and [`ApiSetNamespaceEntry::try_value_entries`].
+/// It first yields all entries that fit entirely into the section.
+/// If the array declares more entries than that, a single [`NtApiSetError::TruncatedEntry`] follows for the first missing entry.
+#[derive(Clone, Debug)]
+pub struct ApiSetTryEntries<I> {
+    entries: I,
+    index: usize,
+    count: usize,
+    array_start: usize,
+    entry_size: usize,
+}
+
+impl<I> ApiSetTryEntries<I> {
+    const fn new(entries: I, array_start: usize, entry_size: usize, count: usize) -> Self {
+        Self {
+            entries,
+            index: 0,
+            count,
+            array_start,
+            entry_size,
+        }
+    }
+}
+
+impl<I> Iterator for ApiSetTryEntries<I>
+where
+    I: Iterator,
+{
+    type Item = Result<I::Item>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(entry) = self.entries.next() {
+            self.index += 1;
+            return Some(Ok(entry));
+        }
+
+        if self.index >= self.count {
+            return None;
+        }
+
+        // All further entries are cut off as well, so only report the first one.
+        let index = self.index;
+        self.index = self.count;
+
+        Some(Err(NtApiSetError::TruncatedEntry {
+            index,
+            offset: self
+                .array_start
+                .saturating_add(index.saturating_mul(self.entry_size)),
+        }))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.entries.size_hint();
+        let truncated = usize::from(self.index.saturating_add(lower) < self.count);
+        (
+            lower.saturating_add(truncated),
+            upper.and_then(|upper| upper.checked_add(truncated)),
+        )
+    }
+}
+
+impl<I> FusedIterator for ApiSetTryEntries<I> where I: FusedIterator {}
+
+impl<'a> ApiSetMap<'a> {
+    /// Returns an iterator over the [`ApiSetHashEntry`]s of this [`ApiSetMap`] that reports truncation.
+    ///
+    /// In contrast to [`hash_entries`](Self::hash_entries), this never fails upfront if the Hash Entry array exceeds the `.apiset` section.
+    /// Instead, all intact Hash Entries are returned, followed by an [`NtApiSetError::TruncatedEntry`] for the first one that is cut off.
+    ///
+    /// API Set Maps before version 6 have no hash table, so the returned iterator is empty for them.
+    ///
+    /// [`ApiSetHashEntry`]: crate::hash_entry::ApiSetHashEntry
+    pub fn try_hash_entries(&self) -> ApiSetTryEntries<ApiSetHashEntries<'a>> {
+        let (start, count) = self.hash_entry_array();
+        let entry_size = mem::size_of::<ApiSetHashEntryHeader>();
+        let range = clamp_entry_array(start, entry_size, count, self.section_bytes().len());
+
+        ApiSetTryEntries::new(
+            ApiSetHashEntries::new(self.section_bytes(), range),
+            start,
+            entry_size,
+            count,
+        )
+    }
+
+    /// Returns an iterator over the [`ApiSetNamespaceEntry`] elements of this [`ApiSetMap`] that reports truncation.
+    ///
+    /// In contrast to [`namespace_entries`](Self::namespace_entries), this never fails upfront if the Namespace Entry array exceeds the `.apiset` section.
+    /// Instead, all intact Namespace Entries are returned, followed by an [`NtApiSetError::TruncatedEntry`] for the first one that is cut off.
+    pub fn try_namespace_entries(&self) -> ApiSetTryEntries<ApiSetNamespaceEntries<'a>> {
+        let (start, count) = self.namespace_entry_array();
+        let entry_size = self.schema().namespace_entry_size();
+        let range = clamp_entry_array(start, entry_size, count, self.section_bytes().len());
+
+        ApiSetTryEntries::new(
+            ApiSetNamespaceEntries::new(
+                self.section_bytes(),
+                self.schema(),
+                range,
+                self.is_lenient(),
+            ),
+            start,
+            entry_size,
+            count,
+        )
+    }
+}
+
+impl<'a> ApiSetNamespaceEntry<'a> {
+    /// Returns an iterator over the [`ApiSetValueEntry`]s of this [`ApiSetNamespaceEntry`] that reports truncation.
+    ///
+    /// In contrast to [`value_entries`](Self::value_entries), this doesn't fail if the Value Entry array exceeds the `.apiset` section.
+    /// Instead, all intact Value Entries are returned, followed by an [`NtApiSetError::TruncatedEntry`] for the first one that is cut off.
+    /// An error is only returned if the Value Entry array header of an API Set Map before version 6 cannot be read.
+    ///
+    /// [`ApiSetValueEntry`]: crate::value_entry::ApiSetValueEntry
+    pub fn try_value_entries(&self) -> Result<ApiSetTryEntries<ApiSetValueEntries<'a>>> {
+        let (start, count) = self.value_entry_array()?;
+
+        Ok(ApiSetTryEntries::new(
+            self.clamped_value_entries(start, count),
+            start,
+            self.schema().value_entry_size(),
+            count,
+        ))
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+    use crate::namespace_entry::ApiSetNamespaceEntryFlags;
+
+    fn build() -> Vec<u8> {
+        ["bar", "foo", "qux"]
+            .iter()
+            .fold(ApiSetMapBuilder::new(), |builder, name| {
+                builder.add_namespace_entry(
+                    ApiSetNamespaceEntryBuilder::new(
+                        &alloc::format!("api-ms-win-core-{name}-l1-1-0"),
+                        ApiSetNamespaceEntryFlags::SEALED,
+                    )
+                    .add_value_entry("", &alloc::format!("{name}.dll"))
+                    .add_value_entry("kernel32.dll", "kernelbase.dll")
+                    .add_value_entry("user32.dll", "win32u.dll"),
+                )
+            })
+            .build()
+            .unwrap()
+    }
+
+    /// Cuts off `bytes` in the middle of the third entry of the array at `start`
+    /// and checks that `try_entries` returns the first two entries, followed by exactly one [`NtApiSetError::TruncatedEntry`].
+    fn check_truncation<F>(bytes: &[u8], start: usize, entry_size: usize, try_entries: F)
+    where
+        F: Fn(&ApiSetMap) -> Vec<Result<usize>>,
+    {
+        let truncated = &bytes[..start + 2 * entry_size + entry_size / 2];
+        let map = ApiSetMap::try_from_apiset_section_bytes(truncated).unwrap();
+
+        assert_eq!(
+            try_entries(&map),
+            [
+                Ok(start),
+                Ok(start + entry_size),
+                Err(NtApiSetError::TruncatedEntry {
+                    index: 2,
+                    offset: start + 2 * entry_size,
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_try_namespace_entries() {
+        let bytes = build();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let start = map.namespace_entry_offset() as usize;
+
+        check_truncation(&bytes, start, map.schema().namespace_entry_size(), |map| {
+            map.try_namespace_entries()
+                .map(|entry| entry.map(|entry| entry.offset()))
+                .collect()
+        });
+    }
+
+    #[test]
+    fn test_try_hash_entries() {
+        let bytes = build();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let start = map.hash_entry_offset() as usize;
+
+        check_truncation(
+            &bytes,
+            start,
+            mem::size_of::<ApiSetHashEntryHeader>(),
+            |map| {
+                map.try_hash_entries()
+                    .map(|entry| entry.map(|entry| entry.offset()))
+                    .collect()
+            },
+        );
+    }
+
+    #[test]
+    fn test_try_value_entries() {
+        let bytes = build();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let last = map.namespace_entries().unwrap().nth(2).unwrap();
+        let start = last.value_entries().unwrap().next().unwrap().offset();
+
+        // The Namespace Entry must remain intact for its Value Entries to be found.
+        assert!(last.offset() + map.schema().namespace_entry_size() <= start);
+
+        check_truncation(&bytes, start, map.schema().value_entry_size(), |map| {
+            let namespace_entry = map.try_namespace_entries().nth(2).unwrap().unwrap();
+            namespace_entry
+                .try_value_entries()
+                .unwrap()
+                .map(|entry| entry.map(|entry| entry.offset()))
+                .collect()
+        });
+    }
+}
@@ -0,0 +1,757 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::mem;
+use core::ops::Range;
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use displaydoc::Display;
+
+use crate::control::{OpControl, Progress};
+use crate::error::{NtApiSetError, Result};
+use crate::hash_entry::ApiSetHashEntryHeader;
+use crate::helpers::{hash_name, u16_to_ascii_lowercase};
+use crate::map::ApiSetMap;
+use crate::namespace_entry::ApiSetNamespaceEntry;
+
+/// Noteworthy, but valid property of an API Set Map, as reported by [`ApiSetMap::validate`].
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+#[derive(Clone, Debug, Display, Eq, PartialEq)]
+pub enum ValidationInfo {
+    /// The API Set Map has no namespace entries, so every lookup misses
+    NoNamespaceEntries,
+    /// Namespace entry {index} has no value entries, so it doesn't resolve to any host module
+    NoValueEntries {
+        /// Index of the namespace entry.
+        index: usize,
+    },
+}
+
+impl<'a> ApiSetMap<'a> {
+    /// Eagerly checks the entire API Set Map for structural damage and returns all findings.
+    ///
+    /// In contrast to the lookup functions, which only notice malformed data when they happen to touch it,
+    /// this walks the header, every Hash Entry, every Namespace Entry, every Value Entry, and every string.
+    /// It checks that:
+    ///
+    /// * the size declared by the header fits into the `.apiset` section (versions 4 and 6),
+    /// * all entry arrays and strings lie within that declared size,
+    /// * all strings have an even length, as required for UTF-16,
+    /// * no Value Entry array overlaps a string,
+    /// * and for version 6: the hashed length of each Namespace Entry doesn't exceed its name,
+    ///   the Hash Entries are sorted, point to existing Namespace Entries, cover each Namespace Entry exactly once,
+    ///   and their hashes match the recomputed hashes of the hashed name prefixes.
+    ///
+    /// This is meant for forensic use, e.g. on sections recovered from memory dumps.
+    /// The walk continues after a finding wherever possible, so a single call reports everything that is damaged.
+    /// Entry arrays of a [lenient](Self::try_from_apiset_section_bytes_lenient) API Set Map are checked as declared, not as clamped.
+    ///
+    /// An API Set Map without any damage may still be empty or contain API Sets without a mapping.
+    /// This is valid, so it is reported as [`ValidationInfo`] instead.
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn validate(&self) -> Result<Vec<ValidationInfo>, Vec<NtApiSetError>> {
+        self.validate_with(&OpControl::new())
+    }
+
+    /// Performs the same as [`validate`](Self::validate), but reports progress and checks for cancellation via `control`.
+    ///
+    /// Every Namespace Entry (along with its Value Entries) and every Hash Entry counts as one step.
+    /// If the operation is cancelled, the findings so far are returned, followed by [`NtApiSetError::Cancelled`].
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn validate_with(
+        &self,
+        control: &OpControl,
+    ) -> Result<Vec<ValidationInfo>, Vec<NtApiSetError>> {
+        // Report truncated entry arrays instead of silently checking only their clamped parts.
+        if self.is_lenient() {
+            return self.strict().validate_with(control);
+        }
+
+        let mut findings = Vec::new();
+        let limit = self.validation_limit(&mut findings);
+
+        let namespace_entry_count = self.namespace_entries().map_or(0, |e| e.len());
+        let hash_entry_count = if self.schema().has_hash_table() {
+            self.hash_entries().map_or(0, |e| e.len())
+        } else {
+            0
+        };
+        let mut progress = control.start(namespace_entry_count + hash_entry_count);
+
+        let mut string_ranges = Vec::new();
+        let mut value_array_ranges = Vec::new();
+        let result = self
+            .validate_namespace_entries(
+                limit,
+                &mut progress,
+                &mut findings,
+                &mut string_ranges,
+                &mut value_array_ranges,
+            )
+            .and_then(|namespace_entry_count| match namespace_entry_count {
+                Some(namespace_entry_count) if self.schema().has_hash_table() => self
+                    .validate_hash_entries(
+                        limit,
+                        namespace_entry_count,
+                        &mut progress,
+                        &mut findings,
+                    ),
+                _ => Ok(()),
+            });
+
+        if let Err(e) = result {
+            findings.push(e);
+            return Err(findings);
+        }
+
+        validate_no_overlap(&mut string_ranges, &value_array_ranges, &mut findings);
+
+        if findings.is_empty() {
+            Ok(self.validation_info())
+        } else {
+            Err(findings)
+        }
+    }
+
+    /// Collects the [`ValidationInfo`] of an API Set Map that has passed validation.
+    fn validation_info(&self) -> Vec<ValidationInfo> {
+        let mut info = Vec::new();
+        let namespace_entries = match self.namespace_entries() {
+            Ok(namespace_entries) => namespace_entries,
+            Err(_) => return info,
+        };
+
+        if namespace_entries.len() == 0 {
+            info.push(ValidationInfo::NoNamespaceEntries);
+        }
+
+        for (index, namespace_entry) in namespace_entries.enumerate() {
+            if matches!(namespace_entry.value_count(), Ok(0)) {
+                info.push(ValidationInfo::NoValueEntries { index });
+            }
+        }
+
+        info
+    }
+
+    /// Returns the number of bytes that entries and strings may occupy.
+    ///
+    /// This is the size declared by the header if it fits into the `.apiset` section, and the section size otherwise.
+    fn validation_limit(&self, findings: &mut Vec<NtApiSetError>) -> usize {
+        let actual = self.section_bytes().len();
+        let size = self.size() as usize;
+
+        if self.version() == 2 {
+            // Version 2 doesn't declare a size.
+            actual
+        } else if size > actual {
+            findings.push(NtApiSetError::DeclaredSizeOutOfBounds { size, actual });
+            actual
+        } else {
+            size
+        }
+    }
+
+    /// Validates all Namespace Entries along with their Value Entries and strings.
+    ///
+    /// Returns the number of Namespace Entries, or `None` if the Namespace Entry array cannot be read at all.
+    /// An error is only returned if the operation has been cancelled.
+    fn validate_namespace_entries(
+        &self,
+        limit: usize,
+        progress: &mut Progress,
+        findings: &mut Vec<NtApiSetError>,
+        string_ranges: &mut Vec<Range<usize>>,
+        value_array_ranges: &mut Vec<Range<usize>>,
+    ) -> Result<Option<usize>> {
+        let namespace_entries = match self.namespace_entries() {
+            Ok(namespace_entries) => namespace_entries,
+            Err(e) => {
+                findings.push(e);
+                return Ok(None);
+            }
+        };
+        let count = namespace_entries.len();
+
+        if let Some(first) = namespace_entries.clone().next() {
+            let start = first.offset();
+            let end = start + count * self.schema().namespace_entry_size();
+
+            if end > limit {
+                findings.push(NtApiSetError::NamespaceEntriesOutOfBounds {
+                    range: start..end,
+                    actual: limit,
+                });
+            }
+        }
+
+        for namespace_entry in namespace_entries {
+            progress.check()?;
+            self.validate_namespace_entry(
+                &namespace_entry,
+                limit,
+                findings,
+                string_ranges,
+                value_array_ranges,
+            );
+            progress.advance();
+        }
+
+        Ok(Some(count))
+    }
+
+    /// Validates a single Namespace Entry along with its Value Entries and strings.
+    fn validate_namespace_entry(
+        &self,
+        namespace_entry: &ApiSetNamespaceEntry,
+        limit: usize,
+        findings: &mut Vec<NtApiSetError>,
+        string_ranges: &mut Vec<Range<usize>>,
+        value_array_ranges: &mut Vec<Range<usize>>,
+    ) {
+        let name_range = namespace_entry.name_range();
+        validate_string(
+            name_range.clone(),
+            namespace_entry.offset(),
+            limit,
+            findings,
+        );
+        string_ranges.push(name_range.clone());
+
+        if self.schema().has_hash_table() && namespace_entry.hashed_length() > name_range.len() {
+            findings.push(NtApiSetError::HashedLengthOutOfBounds {
+                hashed_length: namespace_entry.hashed_length(),
+                name_length: name_range.len(),
+                entry_offset: namespace_entry.offset(),
+            });
+        }
+
+        let value_entries = match namespace_entry.value_entries() {
+            Ok(value_entries) => value_entries,
+            Err(e) => {
+                findings.push(e);
+                return;
+            }
+        };
+
+        if let Some(first) = value_entries.clone().next() {
+            let start = first.offset();
+            let end = start + value_entries.len() * self.schema().value_entry_size();
+
+            if end > limit {
+                findings.push(NtApiSetError::ValueEntriesOutOfBounds {
+                    range: start..end,
+                    actual: limit,
+                });
+            }
+
+            value_array_ranges.push(start..end);
+        }
+
+        for value_entry in value_entries {
+            for range in [value_entry.name_range(), value_entry.value_range()] {
+                validate_string(range.clone(), value_entry.offset(), limit, findings);
+                string_ranges.push(range);
+            }
+        }
+    }
+
+    /// Validates the hash table of a version 6 API Set Map.
+    ///
+    /// An error is only returned if the operation has been cancelled.
+    fn validate_hash_entries(
+        &self,
+        limit: usize,
+        namespace_entry_count: usize,
+        progress: &mut Progress,
+        findings: &mut Vec<NtApiSetError>,
+    ) -> Result<()> {
+        let hash_entries = match self.hash_entries() {
+            Ok(hash_entries) => hash_entries,
+            Err(e) => {
+                findings.push(e);
+                return Ok(());
+            }
+        };
+
+        if let Some(first) = hash_entries.clone().next() {
+            let start = first.offset();
+            let end = start + hash_entries.len() * mem::size_of::<ApiSetHashEntryHeader>();
+
+            if end > limit {
+                findings.push(NtApiSetError::HashEntriesOutOfBounds {
+                    range: start..end,
+                    actual: limit,
+                });
+            }
+        }
+
+        // Checked by `validate_namespace_entries`, so this cannot fail.
+        let namespace_entries = match self.namespace_entries() {
+            Ok(namespace_entries) => namespace_entries,
+            Err(_) => return Ok(()),
+        };
+
+        let mut referenced = vec![false; namespace_entry_count];
+        let mut previous_hash = None;
+
+        for (index, hash_entry) in hash_entries.enumerate() {
+            progress.check()?;
+            let hash = hash_entry.hash();
+            if matches!(previous_hash, Some(previous_hash) if previous_hash > hash) {
+                findings.push(NtApiSetError::HashEntriesNotSorted { index });
+            }
+            previous_hash = Some(hash);
+
+            let namespace_entry_index = hash_entry.index() as usize;
+            let namespace_entry = match namespace_entries.clone().nth(namespace_entry_index) {
+                Some(namespace_entry) => namespace_entry,
+                None => {
+                    findings.push(NtApiSetError::NamespaceEntryIndexOutOfBounds {
+                        index: namespace_entry_index,
+                        count: namespace_entry_count,
+                    });
+                    progress.advance();
+                    continue;
+                }
+            };
+            referenced[namespace_entry_index] = true;
+
+            // Unreadable names and excessive hashed lengths have already been reported for the Namespace Entry.
+            if let Some(expected) = self.recompute_hash(&namespace_entry) {
+                if expected != hash {
+                    findings.push(NtApiSetError::HashMismatch {
+                        index,
+                        expected,
+                        actual: hash,
+                    });
+                }
+            }
+
+            progress.advance();
+        }
+
+        let referenced = referenced.iter().filter(|referenced| **referenced).count();
+        if referenced != namespace_entry_count {
+            findings.push(NtApiSetError::CountMismatch {
+                referenced,
+                count: namespace_entry_count,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Recomputes the hash of the hashed name prefix of `namespace_entry`, or returns `None` if that prefix cannot be read.
+    pub(crate) fn recompute_hash(&self, namespace_entry: &ApiSetNamespaceEntry) -> Option<u32> {
+        let name = namespace_entry.name().ok()?;
+        let hashed_name = name.0.get(..namespace_entry.hashed_length())?;
+        let chars = hashed_name
+            .chunks_exact(mem::size_of::<u16>())
+            .map(|c| u32::from(u16_to_ascii_lowercase(u16::from_le_bytes([c[0], c[1]]))));
+
+        Some(hash_name(chars, self.hash_factor()))
+    }
+}
+
+/// Checks that the string at `range`, referenced by the entry at `entry_offset`, lies within `limit` and has an even length.
+fn validate_string(
+    range: Range<usize>,
+    entry_offset: usize,
+    limit: usize,
+    findings: &mut Vec<NtApiSetError>,
+) {
+    if range.end > limit {
+        findings.push(NtApiSetError::EntryNameOutOfBounds {
+            name_range: range.clone(),
+            entry_offset,
+            actual: limit,
+        });
+    }
+
+    if range.len() % mem::size_of::<u16>() != 0 {
+        findings.push(NtApiSetError::OddStringLength { range });
+    }
+}
+
+/// Checks that none of the `value_array_ranges` overlaps any of the `string_ranges`.
+fn validate_no_overlap(
+    string_ranges: &mut Vec<Range<usize>>,
+    value_array_ranges: &[Range<usize>],
+    findings: &mut Vec<NtApiSetError>,
+) {
+    string_ranges.retain(|range| !range.is_empty());
+    string_ranges.sort_unstable_by_key(|range| range.start);
+
+    // For every prefix of the sorted strings, remember the string reaching furthest.
+    // A Value Entry array overlaps a string if one of the strings starting before its end reaches beyond its start.
+    let mut furthest = Vec::<usize>::with_capacity(string_ranges.len());
+    for (index, range) in string_ranges.iter().enumerate() {
+        match furthest.last() {
+            Some(&previous) if string_ranges[previous].end >= range.end => furthest.push(previous),
+            _ => furthest.push(index),
+        }
+    }
+
+    for range in value_array_ranges {
+        let candidates =
+            string_ranges.partition_point(|string_range| string_range.start < range.end);
+
+        if let Some(&index) = candidates.checked_sub(1).and_then(|i| furthest.get(i)) {
+            let string_range = &string_ranges[index];
+
+            if string_range.end > range.start {
+                findings.push(NtApiSetError::ValueEntriesOverlapString {
+                    range: range.clone(),
+                    string_range: string_range.clone(),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+    use crate::namespace_entry::{ApiSetNamespaceEntryFlags, ApiSetNamespaceEntryHeader};
+    use crate::value_entry::ApiSetValueEntryHeader;
+
+    // Byte offsets of fields within the version 6 structures.
+    const MAP_SIZE: usize = 4;
+    const MAP_NAMESPACE_ENTRY_OFFSET: usize = 16;
+    const MAP_HASH_ENTRY_OFFSET: usize = 20;
+    const NAMESPACE_ENTRY_HASHED_LENGTH: usize = 12;
+    const NAMESPACE_ENTRY_ARRAY_OFFSET: usize = 16;
+    const VALUE_ENTRY_VALUE_OFFSET: usize = 12;
+    const VALUE_ENTRY_VALUE_LENGTH: usize = 16;
+    const HASH_ENTRY_HASH: usize = 0;
+    const HASH_ENTRY_INDEX: usize = 4;
+
+    /// Builds a valid API Set Map with 3 Namespace Entries and returns its bytes.
+    fn build() -> Vec<u8> {
+        let bytes = ApiSetMapBuilder::new()
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "api-ms-win-core-bar-l1-1-0",
+                    ApiSetNamespaceEntryFlags::SEALED,
+                )
+                .add_value_entry("", "bar.dll"),
+            )
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "api-ms-win-core-foo-l1-1-0",
+                    ApiSetNamespaceEntryFlags::SEALED,
+                )
+                .add_value_entry("", "foo.dll")
+                .add_value_entry("kernel32.dll", "kernelbase.dll"),
+            )
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "ext-ms-win-qux-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                )
+                .add_value_entry("", "qux.dll"),
+            )
+            .build()
+            .unwrap();
+
+        map(&bytes).validate().unwrap();
+        bytes
+    }
+
+    fn map(bytes: &[u8]) -> ApiSetMap<'_> {
+        ApiSetMap::try_from_apiset_section_bytes(bytes).unwrap()
+    }
+
+    fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn write_u32(bytes: &mut [u8], offset: usize, value: u32) {
+        bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn namespace_entry_offset(bytes: &[u8], index: usize) -> usize {
+        map(bytes)
+            .namespace_entries()
+            .unwrap()
+            .nth(index)
+            .unwrap()
+            .offset()
+    }
+
+    /// Returns the offsets of the `index`-th Value Entry of the `namespace_entry_index`-th Namespace Entry.
+    fn value_entry_offset(bytes: &[u8], namespace_entry_index: usize, index: usize) -> usize {
+        map(bytes)
+            .namespace_entries()
+            .unwrap()
+            .nth(namespace_entry_index)
+            .unwrap()
+            .value_entries()
+            .unwrap()
+            .nth(index)
+            .unwrap()
+            .offset()
+    }
+
+    fn hash_entry_offset(bytes: &[u8], index: usize) -> usize {
+        map(bytes)
+            .hash_entries()
+            .unwrap()
+            .nth(index)
+            .unwrap()
+            .offset()
+    }
+
+    fn findings(bytes: &[u8]) -> Vec<NtApiSetError> {
+        map(bytes).validate().unwrap_err()
+    }
+
+    #[test]
+    fn test_count_mismatch() {
+        let mut bytes = build();
+        let first = hash_entry_offset(&bytes, 0);
+        let second = hash_entry_offset(&bytes, 1);
+
+        // Let the first two Hash Entries point to the same Namespace Entry, but keep their hashes intact.
+        let index = read_u32(&bytes, first + HASH_ENTRY_INDEX);
+        write_u32(&mut bytes, second + HASH_ENTRY_INDEX, index);
+
+        assert_eq!(
+            findings(&bytes),
+            [
+                NtApiSetError::HashMismatch {
+                    index: 1,
+                    expected: read_u32(&bytes, first + HASH_ENTRY_HASH),
+                    actual: read_u32(&bytes, second + HASH_ENTRY_HASH),
+                },
+                NtApiSetError::CountMismatch {
+                    referenced: 2,
+                    count: 3,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_declared_size_out_of_bounds() {
+        let mut bytes = build();
+        let actual = bytes.len();
+        write_u32(&mut bytes, MAP_SIZE, actual as u32 + 8);
+
+        assert_eq!(
+            findings(&bytes),
+            [NtApiSetError::DeclaredSizeOutOfBounds {
+                size: actual + 8,
+                actual,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_hash_entries_not_sorted() {
+        let mut bytes = build();
+        let first = hash_entry_offset(&bytes, 0);
+        let second = hash_entry_offset(&bytes, 1);
+
+        // Swap the first two Hash Entries.
+        let first_entry = bytes[first..second].to_vec();
+        bytes.copy_within(second..second + first_entry.len(), first);
+        bytes[second..second + first_entry.len()].copy_from_slice(&first_entry);
+
+        assert_eq!(
+            findings(&bytes),
+            [NtApiSetError::HashEntriesNotSorted { index: 1 }]
+        );
+    }
+
+    #[test]
+    fn test_hash_mismatch() {
+        let mut bytes = build();
+        let last = hash_entry_offset(&bytes, 2);
+        let expected = read_u32(&bytes, last + HASH_ENTRY_HASH);
+
+        // The largest hash can be increased without breaking the sort order.
+        write_u32(&mut bytes, last + HASH_ENTRY_HASH, u32::MAX);
+
+        assert_eq!(
+            findings(&bytes),
+            [NtApiSetError::HashMismatch {
+                index: 2,
+                expected,
+                actual: u32::MAX,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_odd_string_length() {
+        let mut bytes = build();
+        let value_entry = value_entry_offset(&bytes, 0, 0);
+        let value_offset = read_u32(&bytes, value_entry + VALUE_ENTRY_VALUE_OFFSET) as usize;
+        let value_length = read_u32(&bytes, value_entry + VALUE_ENTRY_VALUE_LENGTH) as usize;
+        write_u32(
+            &mut bytes,
+            value_entry + VALUE_ENTRY_VALUE_LENGTH,
+            value_length as u32 - 1,
+        );
+
+        assert_eq!(
+            findings(&bytes),
+            [NtApiSetError::OddStringLength {
+                range: value_offset..value_offset + value_length - 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_value_entries_overlap_string() {
+        let mut bytes = build();
+        let namespace_entry = namespace_entry_offset(&bytes, 0);
+        let array_offset =
+            read_u32(&bytes, namespace_entry + NAMESPACE_ENTRY_ARRAY_OFFSET) as usize;
+        let value_entry = value_entry_offset(&bytes, 0, 0);
+        let value_length = read_u32(&bytes, value_entry + VALUE_ENTRY_VALUE_LENGTH) as usize;
+
+        // Let the host module name point into the Value Entry array it is stored in.
+        write_u32(
+            &mut bytes,
+            value_entry + VALUE_ENTRY_VALUE_OFFSET,
+            array_offset as u32,
+        );
+
+        assert_eq!(
+            findings(&bytes),
+            [NtApiSetError::ValueEntriesOverlapString {
+                range: array_offset..array_offset + mem::size_of::<ApiSetValueEntryHeader>(),
+                string_range: array_offset..array_offset + value_length,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_entry_name_out_of_bounds() {
+        let mut bytes = build();
+        let actual = bytes.len();
+        let value_entry = value_entry_offset(&bytes, 1, 1);
+        let value_length = read_u32(&bytes, value_entry + VALUE_ENTRY_VALUE_LENGTH) as usize;
+
+        // Let the host module name start within the section, but end beyond it.
+        let value_offset = actual - 2;
+        write_u32(
+            &mut bytes,
+            value_entry + VALUE_ENTRY_VALUE_OFFSET,
+            value_offset as u32,
+        );
+
+        assert_eq!(
+            findings(&bytes),
+            [NtApiSetError::EntryNameOutOfBounds {
+                name_range: value_offset..value_offset + value_length,
+                entry_offset: value_entry,
+                actual,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_hashed_length_out_of_bounds() {
+        let mut bytes = build();
+        let namespace_entry = namespace_entry_offset(&bytes, 1);
+        let name_length = map(&bytes)
+            .namespace_entries()
+            .unwrap()
+            .nth(1)
+            .unwrap()
+            .name_range()
+            .len();
+        write_u32(
+            &mut bytes,
+            namespace_entry + NAMESPACE_ENTRY_HASHED_LENGTH,
+            name_length as u32 + 2,
+        );
+
+        assert_eq!(
+            findings(&bytes),
+            [NtApiSetError::HashedLengthOutOfBounds {
+                hashed_length: name_length + 2,
+                name_length,
+                entry_offset: namespace_entry,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_namespace_entry_index_out_of_bounds() {
+        let mut bytes = build();
+        let first = hash_entry_offset(&bytes, 0);
+        write_u32(&mut bytes, first + HASH_ENTRY_INDEX, 7);
+
+        assert_eq!(
+            findings(&bytes),
+            [
+                NtApiSetError::NamespaceEntryIndexOutOfBounds { index: 7, count: 3 },
+                NtApiSetError::CountMismatch {
+                    referenced: 2,
+                    count: 3,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_namespace_entries_out_of_bounds() {
+        let mut bytes = build();
+        let actual = bytes.len();
+        let start = actual - 8;
+        write_u32(&mut bytes, MAP_NAMESPACE_ENTRY_OFFSET, start as u32);
+
+        assert_eq!(
+            findings(&bytes),
+            [NtApiSetError::NamespaceEntriesOutOfBounds {
+                range: start..start + 3 * mem::size_of::<ApiSetNamespaceEntryHeader>(),
+                actual,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_hash_entries_out_of_bounds() {
+        let mut bytes = build();
+        let actual = bytes.len();
+        let start = actual - 8;
+        write_u32(&mut bytes, MAP_HASH_ENTRY_OFFSET, start as u32);
+
+        assert_eq!(
+            findings(&bytes),
+            [NtApiSetError::HashEntriesOutOfBounds {
+                range: start..start + 3 * mem::size_of::<ApiSetHashEntryHeader>(),
+                actual,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_value_entries_out_of_bounds() {
+        let mut bytes = build();
+        let actual = bytes.len();
+        let namespace_entry = namespace_entry_offset(&bytes, 1);
+        let start = actual - 8;
+        write_u32(
+            &mut bytes,
+            namespace_entry + NAMESPACE_ENTRY_ARRAY_OFFSET,
+            start as u32,
+        );
+
+        assert_eq!(
+            findings(&bytes),
+            [NtApiSetError::ValueEntriesOutOfBounds {
+                range: start..start + 2 * mem::size_of::<ApiSetValueEntryHeader>(),
+                actual,
+            }]
+        );
+    }
+}
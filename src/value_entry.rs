@@ -2,25 +2,145 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use core::iter::FusedIterator;
-use core::mem;
 use core::ops::Range;
 
 use nt_string::u16strle::U16StrLe;
-use zerocopy::{FromBytes, LayoutVerified, LittleEndian, Unaligned, U32};
+use zerocopy::{AsBytes, FromBytes, LayoutVerified, LittleEndian, Unaligned, U32};
 
 use crate::error::{NtApiSetError, Result};
+#[cfg(feature = "heapless")]
+use crate::helpers::to_fixed_string;
+use crate::helpers::{entry_subrange, eq_ignore_ascii_case_str};
+use crate::schema::Schema;
 
-#[allow(dead_code)]
-#[derive(Debug, FromBytes, Unaligned)]
+/// Maximum length in UTF-8 bytes of a host module name returned by [`ApiSetValueEntry::value`].
+///
+/// Host modules are file names, which Windows limits to 255 UTF-16 code units.
+/// Each of them takes at most 3 bytes in UTF-8 (surrogate pairs take 4 bytes for 2 code units),
+/// so this is a safe capacity for `ApiSetValueEntry::value_to_fixed`.
+/// All host modules found in API Set Maps so far are plain ASCII names far below that limit (e.g. `kernelbase.dll`).
+pub const MAX_HOST_NAME_LEN: usize = 3 * 255;
+
+/// Value Entry of version 4 and 6.
+#[derive(AsBytes, Debug, FromBytes, Unaligned)]
 #[repr(packed)]
 pub(crate) struct ApiSetValueEntryHeader {
-    flags: U32<LittleEndian>,
+    pub(crate) flags: U32<LittleEndian>,
+    pub(crate) name_offset: U32<LittleEndian>,
+    pub(crate) name_length: U32<LittleEndian>,
+    pub(crate) value_offset: U32<LittleEndian>,
+    pub(crate) value_length: U32<LittleEndian>,
+}
+
+#[derive(Debug, FromBytes, Unaligned)]
+#[repr(packed)]
+pub(crate) struct ApiSetValueEntryHeaderV2 {
     name_offset: U32<LittleEndian>,
     name_length: U32<LittleEndian>,
     value_offset: U32<LittleEndian>,
     value_length: U32<LittleEndian>,
 }
 
+/// Precedes the Value Entries of a Namespace Entry in version 2.
+#[derive(Debug, FromBytes, Unaligned)]
+#[repr(packed)]
+pub(crate) struct ApiSetValueArrayHeaderV2 {
+    count: U32<LittleEndian>,
+}
+
+/// Precedes the Value Entries of a Namespace Entry in version 4.
+#[allow(dead_code)]
+#[derive(Debug, FromBytes, Unaligned)]
+#[repr(packed)]
+pub(crate) struct ApiSetValueArrayHeaderV4 {
+    flags: U32<LittleEndian>,
+    count: U32<LittleEndian>,
+}
+
+/// Fields of a Value Entry, independent of the [`Schema`] it has been read from.
+#[derive(Clone, Copy, Debug)]
+struct ValueEntryFields {
+    flags: u32,
+    name_offset: u32,
+    name_length: u32,
+    value_offset: u32,
+    value_length: u32,
+}
+
+impl ValueEntryFields {
+    fn read(schema: Schema, bytes: &[u8]) -> Option<Self> {
+        let fields = match schema {
+            Schema::V2 => {
+                let (header, _) =
+                    LayoutVerified::<_, ApiSetValueEntryHeaderV2>::new_unaligned_from_prefix(
+                        bytes,
+                    )?;
+
+                Self {
+                    flags: 0,
+                    name_offset: header.name_offset.get(),
+                    name_length: header.name_length.get(),
+                    value_offset: header.value_offset.get(),
+                    value_length: header.value_length.get(),
+                }
+            }
+            Schema::V4 | Schema::V6 => {
+                let (header, _) =
+                    LayoutVerified::<_, ApiSetValueEntryHeader>::new_unaligned_from_prefix(bytes)?;
+
+                Self {
+                    flags: header.flags.get(),
+                    name_offset: header.name_offset.get(),
+                    name_length: header.name_length.get(),
+                    value_offset: header.value_offset.get(),
+                    value_length: header.value_length.get(),
+                }
+            }
+        };
+
+        Some(fields)
+    }
+}
+
+/// Reads the structure preceding the Value Entries at `offset` in versions 2 and 4.
+///
+/// Returns the byte offset of the first Value Entry and the number of Value Entries.
+pub(crate) fn read_value_array_header(
+    section_bytes: &[u8],
+    schema: Schema,
+    offset: usize,
+) -> Result<(usize, usize)> {
+    let header_size = schema.value_array_header_size();
+    let end = offset.saturating_add(header_size);
+    let out_of_bounds = || NtApiSetError::ValueEntriesOutOfBounds {
+        range: offset..end,
+        actual: section_bytes.len(),
+    };
+    let bytes = section_bytes.get(offset..end).ok_or_else(out_of_bounds)?;
+
+    let count = match schema {
+        Schema::V2 => LayoutVerified::<_, ApiSetValueArrayHeaderV2>::new_unaligned(bytes)
+            .ok_or_else(out_of_bounds)?
+            .count
+            .get(),
+        Schema::V4 => LayoutVerified::<_, ApiSetValueArrayHeaderV4>::new_unaligned(bytes)
+            .ok_or_else(out_of_bounds)?
+            .count
+            .get(),
+        Schema::V6 => 0,
+    };
+
+    Ok((end, count as usize))
+}
+
+flags! {
+    /// Flags returned by [`ApiSetValueEntry::flags`].
+    pub struct ApiSetValueEntryFlags: u32 {
+        /// This API Set Value Entry is sealed (`API_SET_SCHEMA_ENTRY_FLAGS_SEALED`).
+        const SEALED = 1 << 0;
+    }
+}
+
 /// Iterator over the [`ApiSetValueEntry`]s of an [`ApiSetNamespaceEntry`].
 ///
 /// This iterator is returned by [`ApiSetNamespaceEntry::value_entries`].
@@ -33,40 +153,88 @@ pub(crate) struct ApiSetValueEntryHeader {
 #[derive(Clone, Debug)]
 pub struct ApiSetValueEntries<'a> {
     section_bytes: &'a [u8],
+    schema: Schema,
+    array_start: usize,
     range: Range<usize>,
+    parent_position: usize,
+    parent_name_range: Range<usize>,
 }
 
 impl<'a> ApiSetValueEntries<'a> {
-    pub(crate) const fn new(section_bytes: &'a [u8], range: Range<usize>) -> Self {
+    pub(crate) const fn new(
+        section_bytes: &'a [u8],
+        schema: Schema,
+        range: Range<usize>,
+        parent_position: usize,
+        parent_name_range: Range<usize>,
+    ) -> Self {
         Self {
             section_bytes,
+            schema,
+            array_start: range.start,
             range,
+            parent_position,
+            parent_name_range,
         }
     }
+
+    pub(crate) fn restrict(mut self, index_range: Range<usize>) -> Result<Self> {
+        self.range = entry_subrange(&self.range, self.schema.value_entry_size(), index_range)?;
+        Ok(self)
+    }
+
+    fn entry_at(&self, position: usize) -> Option<ApiSetValueEntry<'a>> {
+        let entry_size = self.schema.value_entry_size();
+        let bytes = self.section_bytes.get(position..self.range.end)?;
+        let header = ValueEntryFields::read(self.schema, bytes)?;
+
+        Some(ApiSetValueEntry {
+            section_bytes: self.section_bytes,
+            position,
+            header,
+            array_index: (position - self.array_start) / entry_size,
+            parent_position: self.parent_position,
+            parent_name_range: self.parent_name_range.clone(),
+        })
+    }
 }
 
 impl<'a> Iterator for ApiSetValueEntries<'a> {
     type Item = ApiSetValueEntry<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let (entry_header, _) =
-            LayoutVerified::<_, ApiSetValueEntryHeader>::new_unaligned_from_prefix(
-                self.section_bytes.get(self.range.clone())?,
-            )?;
-        let entry = ApiSetValueEntry {
-            section_bytes: self.section_bytes,
-            position: self.range.start,
-            header: entry_header,
-        };
-        self.range.start += mem::size_of::<ApiSetValueEntryHeader>();
+        let entry = self.entry_at(self.range.start)?;
+        self.range.start += self.schema.value_entry_size();
 
         Some(entry)
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let size = self.range.len() / mem::size_of::<ApiSetValueEntryHeader>();
+        let size = self.range.len() / self.schema.value_entry_size();
         (size, Some(size))
     }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        // `n` is arbitrary and usize, so we may hit boundaries here. Check that!
+        let bytes_to_skip = n.checked_mul(self.schema.value_entry_size())?;
+        self.range.start = self.range.start.checked_add(bytes_to_skip)?;
+        self.next()
+    }
+}
+
+impl<'a> DoubleEndedIterator for ApiSetValueEntries<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let entry_size = self.schema.value_entry_size();
+        if self.range.len() < entry_size {
+            return None;
+        }
+
+        let position = self.range.end - entry_size;
+        let entry = self.entry_at(position)?;
+        self.range.end = position;
+
+        Some(entry)
+    }
 }
 
 impl<'a> ExactSizeIterator for ApiSetValueEntries<'a> {}
@@ -81,15 +249,68 @@ impl<'a> FusedIterator for ApiSetValueEntries<'a> {}
 pub struct ApiSetValueEntry<'a> {
     section_bytes: &'a [u8],
     position: usize,
-    header: LayoutVerified<&'a [u8], ApiSetValueEntryHeader>,
+    header: ValueEntryFields,
+    array_index: usize,
+    parent_position: usize,
+    parent_name_range: Range<usize>,
 }
 
 impl<'a> ApiSetValueEntry<'a> {
-    /// Returns flags set for this [`ApiSetValueEntry`].
+    /// Returns the position of this [`ApiSetValueEntry`] within the value entry array of its [`ApiSetNamespaceEntry`].
+    ///
+    /// Index 0 is the default entry.
+    ///
+    /// [`ApiSetNamespaceEntry`]: crate::namespace_entry::ApiSetNamespaceEntry
+    pub const fn array_index(&self) -> usize {
+        self.array_index
+    }
+
+    /// Returns flags set for this [`ApiSetValueEntry`] as specified by [`ApiSetValueEntryFlags`].
+    ///
+    /// Bits unknown to this crate are retained and can be inspected via [`ApiSetValueEntryFlags::bits`].
+    /// API Set Maps of version 2 (Windows 7) have no flags, so they are always empty for them.
+    pub fn flags(&self) -> ApiSetValueEntryFlags {
+        ApiSetValueEntryFlags::from_bits_retain(self.header.flags)
+    }
+
+    /// Returns the flags field of this [`ApiSetValueEntry`] exactly as stored, including any unknown bits.
+    ///
+    /// API Set Maps of version 2 (Windows 7) have no flags, so 0 is returned for them.
+    pub const fn raw_flags(&self) -> u32 {
+        self.header.flags
+    }
+
+    /// Returns the byte offset of this [`ApiSetValueEntry`] within the `.apiset` section.
+    pub const fn offset(&self) -> usize {
+        self.position
+    }
+
+    /// Returns the name of the [`ApiSetNamespaceEntry`] this [`ApiSetValueEntry`] belongs to.
+    ///
+    /// This is the same as calling [`ApiSetNamespaceEntry::name`] on the parent entry, but doesn't require you to keep track of it.
+    ///
+    /// [`ApiSetNamespaceEntry`]: crate::namespace_entry::ApiSetNamespaceEntry
+    /// [`ApiSetNamespaceEntry::name`]: crate::namespace_entry::ApiSetNamespaceEntry::name
+    pub fn parent_name(&self) -> Result<U16StrLe<'a>> {
+        let range = self.parent_name_range.clone();
+
+        let bytes =
+            self.section_bytes
+                .get(range.clone())
+                .ok_or(NtApiSetError::EntryNameOutOfBounds {
+                    name_range: range,
+                    entry_offset: self.parent_position,
+                    actual: self.section_bytes.len(),
+                })?;
+
+        Ok(U16StrLe(bytes))
+    }
+
+    /// Returns the byte offset of the [`ApiSetNamespaceEntry`] this [`ApiSetValueEntry`] belongs to within the `.apiset` section.
     ///
-    /// These flags are currently unknown, so a plain [`u32`] is returned.
-    pub fn flags(&self) -> u32 {
-        self.header.flags.get()
+    /// [`ApiSetNamespaceEntry`]: crate::namespace_entry::ApiSetNamespaceEntry
+    pub const fn parent_offset(&self) -> usize {
+        self.parent_position
     }
 
     /// Returns the name of the importing module for this mapping.
@@ -101,10 +322,7 @@ impl<'a> ApiSetValueEntry<'a> {
     ///
     /// [`ApiSetNamespaceEntry`]: crate::namespace_entry::ApiSetNamespaceEntry
     pub fn name(&self) -> Result<U16StrLe<'a>> {
-        let start = self.header.name_offset.get() as usize;
-        let length = self.header.name_length.get() as usize;
-        let end = start + length;
-        let range = start..end;
+        let range = self.name_range();
 
         let bytes =
             self.section_bytes
@@ -122,10 +340,7 @@ impl<'a> ApiSetValueEntry<'a> {
     ///
     /// It ends with the file extension of the host module.
     pub fn value(&self) -> Result<U16StrLe<'a>> {
-        let start = self.header.value_offset.get() as usize;
-        let length = self.header.value_length.get() as usize;
-        let end = start + length;
-        let range = start..end;
+        let range = self.value_range();
 
         let bytes =
             self.section_bytes
@@ -138,4 +353,337 @@ impl<'a> ApiSetValueEntry<'a> {
 
         Ok(U16StrLe(bytes))
     }
+
+    /// Checks whether the [`name`](Self::name) of the importing module equals `other`, ignoring ASCII case.
+    ///
+    /// This compares the UTF-16 code units directly and doesn't allocate.
+    pub fn name_eq_ignore_case(&self, other: &str) -> Result<bool> {
+        Ok(eq_ignore_ascii_case_str(&self.name()?, other))
+    }
+
+    /// Checks whether the [`value`](Self::value) (the host module name) equals `other`, ignoring ASCII case.
+    ///
+    /// This compares the UTF-16 code units directly and doesn't allocate.
+    pub fn value_eq_ignore_case(&self, other: &str) -> Result<bool> {
+        Ok(eq_ignore_ascii_case_str(&self.value()?, other))
+    }
+
+    /// Returns a copy of the [`name`](Self::name) of the importing module in a fixed-capacity string.
+    ///
+    /// This allows to keep the name beyond the lifetime of the section bytes without allocating.
+    /// `N` is the capacity in UTF-8 bytes.
+    /// If the name doesn't fit, [`NtApiSetError::StringTooLong`] is returned.
+    #[cfg(feature = "heapless")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "heapless")))]
+    pub fn name_to_fixed<const N: usize>(&self) -> Result<heapless::String<N>> {
+        to_fixed_string(&self.name()?, self.name_range())
+    }
+
+    /// Returns a copy of the [`value`](Self::value) (the host module name) in a fixed-capacity string.
+    ///
+    /// This allows to keep the name beyond the lifetime of the section bytes without allocating.
+    /// `N` is the capacity in UTF-8 bytes, and [`MAX_HOST_NAME_LEN`] is a safe choice for it.
+    /// If the name doesn't fit, [`NtApiSetError::StringTooLong`] is returned.
+    #[cfg(feature = "heapless")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "heapless")))]
+    pub fn value_to_fixed<const N: usize>(&self) -> Result<heapless::String<N>> {
+        to_fixed_string(&self.value()?, self.value_range())
+    }
+
+    pub(crate) fn name_range(&self) -> Range<usize> {
+        let start = self.header.name_offset as usize;
+        let length = self.header.name_length as usize;
+        start..start.saturating_add(length)
+    }
+
+    pub(crate) fn value_range(&self) -> Range<usize> {
+        let start = self.header.value_offset as usize;
+        let length = self.header.value_length as usize;
+        start..start.saturating_add(length)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+    #[cfg(feature = "heapless")]
+    use crate::helpers::to_fixed_string;
+    use crate::map::ApiSetMap;
+    use crate::namespace_entry::ApiSetNamespaceEntryFlags;
+
+    #[test]
+    fn test_raw_flags() {
+        let bytes = ApiSetMapBuilder::new()
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "api-ms-win-core-foo-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                )
+                .add_value_entry("", "foo.dll")
+                .add_value_entry_with_flags(
+                    "advapi32.dll",
+                    "advapi32_foo.dll",
+                    ApiSetValueEntryFlags::SEALED,
+                )
+                .add_value_entry_with_flags(
+                    "kernel32.dll",
+                    "kernelbase.dll",
+                    ApiSetValueEntryFlags::SEALED
+                        | ApiSetValueEntryFlags::from_bits_retain(0x4000_0010),
+                ),
+            )
+            .build()
+            .unwrap();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let value_entries = map
+            .namespace_entry(0)
+            .unwrap()
+            .unwrap()
+            .value_entries()
+            .unwrap()
+            .collect::<alloc::vec::Vec<_>>();
+
+        let raw_flags = value_entries
+            .iter()
+            .map(ApiSetValueEntry::raw_flags)
+            .collect::<alloc::vec::Vec<_>>();
+        assert_eq!(raw_flags, [0, 0x1, 0x4000_0011]);
+
+        // Unknown bits are kept, both in the raw field and in the parsed flags.
+        assert_eq!(value_entries[2].flags().bits(), 0x4000_0011);
+        assert!(value_entries[2]
+            .flags()
+            .contains(ApiSetValueEntryFlags::SEALED));
+    }
+
+    #[test]
+    fn test_parent_and_array_index() {
+        let names = ["api-ms-win-core-bar-l1-1-0", "api-ms-win-core-foo-l1-1-0"];
+        // Host-specific Value Entries are sorted by their importing module when building.
+        let hosts = [
+            ("", "foo.dll"),
+            ("advapi32.dll", "foo_advapi.dll"),
+            ("kernel32.dll", "foo_kernel.dll"),
+            ("user32.dll", "foo_user.dll"),
+        ];
+        let bytes = ApiSetMapBuilder::new()
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(names[0], ApiSetNamespaceEntryFlags::empty())
+                    .add_value_entry("", "bar.dll"),
+            )
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(names[1], ApiSetNamespaceEntryFlags::empty())
+                    .add_value_entry(hosts[0].0, hosts[0].1)
+                    .add_value_entry(hosts[3].0, hosts[3].1)
+                    .add_value_entry(hosts[1].0, hosts[1].1)
+                    .add_value_entry(hosts[2].0, hosts[2].1),
+            )
+            .build()
+            .unwrap();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+        for (name, namespace_entry) in names.iter().zip(map.namespace_entries().unwrap()) {
+            for (index, value_entry) in namespace_entry.value_entries().unwrap().enumerate() {
+                assert_eq!(value_entry.array_index(), index);
+                assert_eq!(value_entry.parent_name().unwrap(), *name);
+                assert_eq!(value_entry.parent_offset(), namespace_entry.offset());
+            }
+        }
+
+        let namespace_entry = map.find_namespace_entry(names[1]).unwrap().unwrap();
+        assert_eq!(namespace_entry.value_entries().unwrap().len(), hosts.len());
+
+        for (index, (importing_module, host_module)) in hosts.iter().enumerate() {
+            let value_entry = namespace_entry.value_entry(index).unwrap().unwrap();
+            assert_eq!(value_entry.array_index(), index);
+            assert_eq!(value_entry.name().unwrap(), *importing_module);
+            assert_eq!(value_entry.value().unwrap(), *host_module);
+
+            // An entry found by resolving knows its position and parent as well.
+            let importing_module = Some(*importing_module).filter(|name| !name.is_empty());
+            let value_entry = namespace_entry
+                .resolve_value_entry(importing_module)
+                .unwrap()
+                .unwrap();
+            assert_eq!(value_entry.array_index(), index);
+            assert_eq!(value_entry.parent_name().unwrap(), names[1]);
+            assert_eq!(value_entry.parent_offset(), namespace_entry.offset());
+        }
+
+        // Iterating from the back yields the same positions.
+        let indexes = namespace_entry
+            .value_entries()
+            .unwrap()
+            .rev()
+            .map(|value_entry| value_entry.array_index())
+            .collect::<alloc::vec::Vec<_>>();
+        assert_eq!(indexes, [3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn test_eq_ignore_case() {
+        let bytes = ApiSetMapBuilder::new()
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "api-ms-win-core-foo-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                )
+                .add_value_entry("", "foo.dll")
+                .add_value_entry("Kernel32.dll", "KernelBase.dll"),
+            )
+            .build()
+            .unwrap();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let namespace_entry = map.namespace_entry(0).unwrap().unwrap();
+        let default_value_entry = namespace_entry.default_value_entry().unwrap().unwrap();
+        let value_entry = namespace_entry.value_entry(1).unwrap().unwrap();
+
+        // The default Value Entry has an empty name.
+        assert!(default_value_entry.name_eq_ignore_case("").unwrap());
+        assert!(!default_value_entry
+            .name_eq_ignore_case("kernel32.dll")
+            .unwrap());
+        assert!(default_value_entry.value_eq_ignore_case("FOO.DLL").unwrap());
+
+        for name in ["Kernel32.dll", "kernel32.dll", "KERNEL32.DLL"] {
+            assert!(value_entry.name_eq_ignore_case(name).unwrap(), "{name}");
+        }
+        for name in ["", "kernel32", "kernel32.dll ", "kernelbase.dll"] {
+            assert!(!value_entry.name_eq_ignore_case(name).unwrap(), "{name}");
+        }
+
+        for value in ["KernelBase.dll", "kernelbase.dll", "KERNELBASE.DLL"] {
+            assert!(value_entry.value_eq_ignore_case(value).unwrap(), "{value}");
+        }
+        for value in ["", "kernelbase", "kernel32.dll", "k\u{e9}rnelbase.dll"] {
+            assert!(!value_entry.value_eq_ignore_case(value).unwrap(), "{value}");
+        }
+    }
+
+    #[test]
+    fn test_eq_ignore_case_out_of_bounds() {
+        let mut bytes = ApiSetMapBuilder::new()
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "api-ms-win-core-foo-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                )
+                .add_value_entry("kernel32.dll", "foo.dll"),
+            )
+            .build()
+            .unwrap();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let value_entry_offset = map
+            .namespace_entry(0)
+            .unwrap()
+            .unwrap()
+            .default_value_entry()
+            .unwrap()
+            .unwrap()
+            .offset();
+
+        // Let the value point outside the section, but keep the name intact.
+        let value_offset = value_entry_offset + 12;
+        bytes[value_offset..value_offset + 4].copy_from_slice(&0xffff_0000u32.to_le_bytes());
+
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let value_entry = map
+            .namespace_entry(0)
+            .unwrap()
+            .unwrap()
+            .default_value_entry()
+            .unwrap()
+            .unwrap();
+        assert!(value_entry.name_eq_ignore_case("KERNEL32.DLL").unwrap());
+        assert!(matches!(
+            value_entry.value_eq_ignore_case("foo.dll").unwrap_err(),
+            NtApiSetError::EntryNameOutOfBounds { .. }
+        ));
+    }
+
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn test_to_fixed() {
+        let bytes = ApiSetMapBuilder::new()
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "api-ms-win-core-foo-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                )
+                .add_value_entry("kernel32.dll", "foo.dll"),
+            )
+            .build()
+            .unwrap();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let namespace_entry = map
+            .find_namespace_entry("api-ms-win-core-foo-l1-1-0")
+            .unwrap()
+            .unwrap();
+        let value_entry = namespace_entry.value_entries().unwrap().next().unwrap();
+
+        // Exact fit
+        assert_eq!(value_entry.value_to_fixed::<7>().unwrap(), "foo.dll");
+        assert_eq!(value_entry.name_to_fixed::<12>().unwrap(), "kernel32.dll");
+        assert_eq!(
+            namespace_entry.name_to_fixed::<26>().unwrap(),
+            "api-ms-win-core-foo-l1-1-0"
+        );
+
+        // Overflow by a single byte
+        assert_eq!(
+            value_entry.value_to_fixed::<6>().unwrap_err(),
+            NtApiSetError::StringTooLong {
+                range: value_entry.value_range(),
+                capacity: 6,
+            }
+        );
+        assert_eq!(
+            value_entry.name_to_fixed::<11>().unwrap_err(),
+            NtApiSetError::StringTooLong {
+                range: value_entry.name_range(),
+                capacity: 11,
+            }
+        );
+        assert_eq!(
+            namespace_entry.name_to_fixed::<25>().unwrap_err(),
+            NtApiSetError::StringTooLong {
+                range: namespace_entry.name_range(),
+                capacity: 25,
+            }
+        );
+    }
+
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn test_max_host_name_len() {
+        // A file name of 255 UTF-16 code units that all take 3 bytes in UTF-8.
+        let bytes = "\u{20ac}"
+            .repeat(255)
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect::<alloc::vec::Vec<u8>>();
+        let range = 0..bytes.len();
+
+        let fixed = to_fixed_string::<MAX_HOST_NAME_LEN>(&U16StrLe(&bytes), range.clone()).unwrap();
+        assert_eq!(fixed.len(), MAX_HOST_NAME_LEN);
+        assert_eq!(
+            to_fixed_string::<{ MAX_HOST_NAME_LEN - 1 }>(&U16StrLe(&bytes), range.clone())
+                .unwrap_err(),
+            NtApiSetError::StringTooLong {
+                range,
+                capacity: MAX_HOST_NAME_LEN - 1,
+            }
+        );
+
+        // Every host module of the bundled API Set Map fits as well.
+        let bytes = include_bytes!("../examples-nostd/src/nostd_lookup.apiset");
+        let map = ApiSetMap::try_from_apiset_section_bytes(bytes).unwrap();
+
+        for namespace_entry in map.namespace_entries().unwrap() {
+            for value_entry in namespace_entry.value_entries().unwrap() {
+                value_entry.value_to_fixed::<MAX_HOST_NAME_LEN>().unwrap();
+            }
+        }
+    }
 }
@@ -0,0 +1,311 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::ops::ControlFlow;
+
+use crate::error::{NtApiSetError, Result};
+use crate::hash_entry::ApiSetHashEntry;
+use crate::map::ApiSetMap;
+use crate::namespace_entry::{ApiSetNamespaceEntry, ApiSetNamespaceEntryFlags};
+use crate::value_entry::ApiSetValueEntry;
+
+/// Callbacks invoked by [`ApiSetMap::accept`] while walking an API Set Map.
+///
+/// All methods have empty default implementations, so a visitor only needs to implement the callbacks it is interested in.
+pub trait ApiSetVisitor<'a> {
+    /// Called once before any entry is visited.
+    fn map_header(&mut self, _map: &ApiSetMap<'a>) {}
+
+    /// Called for every [`ApiSetNamespaceEntry`] in the order of the namespace entry array.
+    ///
+    /// Return [`ControlFlow::Break`] to stop the entire walk after this entry.
+    /// Its value entries are not visited in that case.
+    fn namespace_entry(
+        &mut self,
+        _index: usize,
+        _namespace_entry: &ApiSetNamespaceEntry<'a>,
+    ) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// Called for every [`ApiSetValueEntry`] right after the [`ApiSetNamespaceEntry`] it belongs to.
+    fn value_entry(
+        &mut self,
+        _namespace_entry_index: usize,
+        _index: usize,
+        _value_entry: &ApiSetValueEntry<'a>,
+    ) {
+    }
+
+    /// Called for every [`ApiSetHashEntry`] after all namespace entries have been visited.
+    fn hash_entry(&mut self, _index: usize, _hash_entry: &ApiSetHashEntry<'a>) {}
+
+    /// Called for every error encountered while walking an entry.
+    ///
+    /// The walk continues with the next entry afterwards.
+    fn error(&mut self, _error: NtApiSetError) {}
+}
+
+impl<'a> ApiSetMap<'a> {
+    /// Walks all structures of this API Set Map exactly once, invoking the callbacks of `visitor`.
+    ///
+    /// The order is: [`ApiSetVisitor::map_header`], then every namespace entry followed by its value entries,
+    /// and finally every hash entry.
+    ///
+    /// Errors of individual entries are reported via [`ApiSetVisitor::error`] and don't abort the walk.
+    /// An error is only returned if the namespace entry array or hash entry array cannot be read at all.
+    pub fn accept<V>(&self, visitor: &mut V) -> Result<()>
+    where
+        V: ApiSetVisitor<'a>,
+    {
+        visitor.map_header(self);
+
+        for (i, namespace_entry) in self.namespace_entries()?.enumerate() {
+            if let ControlFlow::Break(()) = visitor.namespace_entry(i, &namespace_entry) {
+                return Ok(());
+            }
+
+            match namespace_entry.value_entries() {
+                Ok(value_entries) => {
+                    for (j, value_entry) in value_entries.enumerate() {
+                        visitor.value_entry(i, j, &value_entry);
+                    }
+                }
+                Err(e) => visitor.error(e),
+            }
+        }
+
+        for (i, hash_entry) in self.hash_entries()?.enumerate() {
+            visitor.hash_entry(i, &hash_entry);
+        }
+
+        Ok(())
+    }
+}
+
+/// Statistics about an API Set Map, collected in a single pass via [`ApiSetMap::stats`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ApiSetStats {
+    namespace_entries: usize,
+    sealed_namespace_entries: usize,
+    extension_namespace_entries: usize,
+    value_entries: usize,
+    max_value_entries: usize,
+    hash_entries: usize,
+    errors: usize,
+}
+
+impl ApiSetStats {
+    /// Returns the number of errors encountered while collecting the statistics.
+    ///
+    /// The entries affected by these errors are not counted.
+    pub fn errors(&self) -> usize {
+        self.errors
+    }
+
+    /// Returns the number of Namespace Entries flagged as [`IS_EXTENSION`](ApiSetNamespaceEntryFlags::IS_EXTENSION).
+    pub fn extension_namespace_entries(&self) -> usize {
+        self.extension_namespace_entries
+    }
+
+    /// Returns the number of Hash Entries.
+    pub fn hash_entries(&self) -> usize {
+        self.hash_entries
+    }
+
+    /// Returns the highest number of Value Entries of a single Namespace Entry.
+    pub fn max_value_entries(&self) -> usize {
+        self.max_value_entries
+    }
+
+    /// Returns the number of Namespace Entries.
+    pub fn namespace_entries(&self) -> usize {
+        self.namespace_entries
+    }
+
+    /// Returns the number of Namespace Entries flagged as [`SEALED`](ApiSetNamespaceEntryFlags::SEALED).
+    pub fn sealed_namespace_entries(&self) -> usize {
+        self.sealed_namespace_entries
+    }
+
+    /// Returns the total number of Value Entries of all Namespace Entries.
+    pub fn value_entries(&self) -> usize {
+        self.value_entries
+    }
+}
+
+impl<'a> ApiSetVisitor<'a> for ApiSetStats {
+    fn namespace_entry(
+        &mut self,
+        _index: usize,
+        namespace_entry: &ApiSetNamespaceEntry<'a>,
+    ) -> ControlFlow<()> {
+        let flags = namespace_entry.flags();
+        self.namespace_entries += 1;
+        self.sealed_namespace_entries +=
+            usize::from(flags.contains(ApiSetNamespaceEntryFlags::SEALED));
+        self.extension_namespace_entries +=
+            usize::from(flags.contains(ApiSetNamespaceEntryFlags::IS_EXTENSION));
+
+        ControlFlow::Continue(())
+    }
+
+    fn value_entry(
+        &mut self,
+        _namespace_entry_index: usize,
+        index: usize,
+        _value_entry: &ApiSetValueEntry<'a>,
+    ) {
+        self.value_entries += 1;
+        self.max_value_entries = self.max_value_entries.max(index + 1);
+    }
+
+    fn hash_entry(&mut self, _index: usize, _hash_entry: &ApiSetHashEntry<'a>) {
+        self.hash_entries += 1;
+    }
+
+    fn error(&mut self, _error: NtApiSetError) {
+        self.errors += 1;
+    }
+}
+
+impl<'a> ApiSetMap<'a> {
+    /// Collects [`ApiSetStats`] about this API Set Map in a single pass via [`ApiSetMap::accept`].
+    pub fn stats(&self) -> Result<ApiSetStats> {
+        let mut stats = ApiSetStats::default();
+        self.accept(&mut stats)?;
+        Ok(stats)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::builder::{ApiSetMapBuilder, ApiSetNamespaceEntryBuilder};
+
+    fn fixture() -> Vec<u8> {
+        ApiSetMapBuilder::new()
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "api-ms-foo-l1-1-0",
+                    ApiSetNamespaceEntryFlags::SEALED,
+                )
+                .add_value_entry("", "foo.dll"),
+            )
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "api-ms-bar-l1-1-0",
+                    ApiSetNamespaceEntryFlags::empty(),
+                )
+                .add_value_entry("", "bar.dll")
+                .add_value_entry("kernel32.dll", "bar_legacy.dll"),
+            )
+            .add_namespace_entry(
+                ApiSetNamespaceEntryBuilder::new(
+                    "ext-ms-baz-l1-1-0",
+                    ApiSetNamespaceEntryFlags::IS_EXTENSION,
+                )
+                .add_value_entry("", "baz.dll"),
+            )
+            .build()
+            .unwrap()
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        MapHeader,
+        NamespaceEntry(usize),
+        ValueEntry(usize, usize),
+        HashEntry(usize),
+    }
+
+    struct BreakingVisitor {
+        break_at: usize,
+        events: Vec<Event>,
+    }
+
+    impl<'a> ApiSetVisitor<'a> for BreakingVisitor {
+        fn map_header(&mut self, _map: &ApiSetMap<'a>) {
+            self.events.push(Event::MapHeader);
+        }
+
+        fn namespace_entry(
+            &mut self,
+            index: usize,
+            _namespace_entry: &ApiSetNamespaceEntry<'a>,
+        ) -> ControlFlow<()> {
+            self.events.push(Event::NamespaceEntry(index));
+
+            if index == self.break_at {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        }
+
+        fn value_entry(
+            &mut self,
+            namespace_entry_index: usize,
+            index: usize,
+            _value_entry: &ApiSetValueEntry<'a>,
+        ) {
+            self.events
+                .push(Event::ValueEntry(namespace_entry_index, index));
+        }
+
+        fn hash_entry(&mut self, index: usize, _hash_entry: &ApiSetHashEntry<'a>) {
+            self.events.push(Event::HashEntry(index));
+        }
+    }
+
+    #[test]
+    fn test_stats() {
+        let bytes = fixture();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+        let stats = map.stats().unwrap();
+
+        assert_eq!(stats.namespace_entries(), 3);
+        assert_eq!(stats.sealed_namespace_entries(), 1);
+        assert_eq!(stats.extension_namespace_entries(), 1);
+        assert_eq!(stats.value_entries(), 4);
+        assert_eq!(stats.max_value_entries(), 2);
+        assert_eq!(stats.hash_entries(), 3);
+        assert_eq!(stats.errors(), 0);
+    }
+
+    #[test]
+    fn test_break() {
+        let bytes = fixture();
+        let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+        // The builder sorts the Namespace Entries, so "api-ms-bar-l1-1-0" comes first.
+        let mut visitor = BreakingVisitor {
+            break_at: 1,
+            events: Vec::new(),
+        };
+        map.accept(&mut visitor).unwrap();
+
+        assert_eq!(
+            visitor.events,
+            [
+                Event::MapHeader,
+                Event::NamespaceEntry(0),
+                Event::ValueEntry(0, 0),
+                Event::ValueEntry(0, 1),
+                Event::NamespaceEntry(1),
+            ]
+        );
+
+        // Without a break, the walk continues with all Hash Entries.
+        let mut visitor = BreakingVisitor {
+            break_at: usize::MAX,
+            events: Vec::new(),
+        };
+        map.accept(&mut visitor).unwrap();
+
+        assert_eq!(visitor.events.len(), 1 + 3 + 4 + 3);
+        assert_eq!(visitor.events.last(), Some(&Event::HashEntry(2)));
+    }
+}
@@ -0,0 +1,86 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Race test for the thread-safety guarantees documented in the crate docs:
+// Many threads resolve against the same API Set Map concurrently, both directly and via an `ApiSetIndex`,
+// and must always get the same results as a single thread.
+
+use std::thread;
+
+use nt_apiset::{synthetic_map, ApiSetMap, OwnedApiSetMap};
+
+const THREADS: usize = 16;
+const ITERATIONS: usize = 10;
+
+/// Returns the names to resolve: every Namespace Entry as an imported DLL name, plus one miss per entry.
+fn import_names(map: &ApiSetMap) -> Vec<String> {
+    let mut names = Vec::new();
+
+    for namespace_entry in map.namespace_entries().unwrap() {
+        let name = namespace_entry.name().unwrap().to_string().unwrap();
+        names.push(format!("{name}.dll"));
+        names.push(format!("{name}-missing.dll"));
+    }
+
+    names
+}
+
+fn resolve_all(
+    names: &[String],
+    importing_module: Option<&str>,
+    resolve: impl Fn(&str, Option<&str>) -> Option<String>,
+) -> Vec<Option<String>> {
+    names
+        .iter()
+        .map(|name| resolve(name, importing_module))
+        .collect()
+}
+
+#[test]
+fn test_concurrent_resolve() {
+    let map = OwnedApiSetMap::try_from_apiset_section_bytes(synthetic_map(1)).unwrap();
+    let names = import_names(&map.as_map());
+
+    // Compute the expected results on a single thread first.
+    let resolve_map = |map: &OwnedApiSetMap, name: &str, importing_module: Option<&str>| {
+        map.resolve(name, importing_module)
+            .map(|host| host.unwrap().to_string().unwrap())
+    };
+    let expected = [None, Some("kernelbase.dll")].map(|importing_module| {
+        resolve_all(&names, importing_module, |name, importing_module| {
+            resolve_map(&map, name, importing_module)
+        })
+    });
+
+    let map_view = map.as_map();
+    let index = map_view.build_index().unwrap();
+
+    thread::scope(|scope| {
+        for thread_index in 0..THREADS {
+            let (map, names, expected, index) = (&map, &names, &expected, &index);
+
+            scope.spawn(move || {
+                for iteration in 0..ITERATIONS {
+                    // Alternate between the importing modules and between the uncached and the indexed path,
+                    // so that different threads perform different kinds of lookups at the same time.
+                    let variant = (thread_index + iteration) % 2;
+                    let importing_module = [None, Some("kernelbase.dll")][variant];
+
+                    let actual = if (thread_index + iteration / 2) % 2 == 0 {
+                        resolve_all(names, importing_module, |name, importing_module| {
+                            resolve_map(map, name, importing_module)
+                        })
+                    } else {
+                        resolve_all(names, importing_module, |name, importing_module| {
+                            index
+                                .resolve(name, importing_module)
+                                .map(|host| host.to_string().unwrap())
+                        })
+                    };
+
+                    assert_eq!(actual, expected[variant]);
+                }
+            });
+        }
+    });
+}
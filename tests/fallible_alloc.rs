@@ -0,0 +1,166 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Checks that every `try_` path reports a failing allocator as `NtApiSetError::AllocationFailed` instead of aborting.
+// The global allocator of this test binary fails as soon as the current thread has exhausted its byte budget.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use nt_apiset::{
+    ApiSetMap, ApiSetMapBuilder, ApiSetNamespaceEntryBuilder, ApiSetNamespaceEntryFlags, MemRead,
+    MemReadError, NtApiSetError, Result,
+};
+
+thread_local! {
+    /// Number of bytes the current thread may still allocate.
+    static BUDGET: Cell<usize> = const { Cell::new(usize::MAX) };
+}
+
+struct FailingAllocator;
+
+impl FailingAllocator {
+    fn take(size: usize) -> bool {
+        BUDGET.with(|budget| match budget.get().checked_sub(size) {
+            Some(remaining) => {
+                budget.set(remaining);
+                true
+            }
+            None => false,
+        })
+    }
+}
+
+unsafe impl GlobalAlloc for FailingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if Self::take(layout.size()) {
+            System.alloc(layout)
+        } else {
+            std::ptr::null_mut()
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if Self::take(new_size) {
+            System.realloc(ptr, layout, new_size)
+        } else {
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: FailingAllocator = FailingAllocator;
+
+struct Memory<'a>(&'a [u8]);
+
+impl<'a> MemRead for Memory<'a> {
+    fn read(&self, va: u64, buf: &mut [u8]) -> core::result::Result<(), MemReadError> {
+        let bytes = self
+            .0
+            .get(va as usize..va as usize + buf.len())
+            .ok_or(MemReadError::Unreadable)?;
+        buf.copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+fn build_map() -> Vec<u8> {
+    ApiSetMapBuilder::new()
+        .add_namespace_entry(
+            ApiSetNamespaceEntryBuilder::new(
+                "api-ms-win-core-bar-l1-1-0",
+                ApiSetNamespaceEntryFlags::SEALED,
+            )
+            .add_value_entry("", "bar.dll"),
+        )
+        .add_namespace_entry(
+            ApiSetNamespaceEntryBuilder::new(
+                "api-ms-win-core-foo-l1-1-0",
+                ApiSetNamespaceEntryFlags::SEALED,
+            )
+            .add_value_entry("", "foo.dll")
+            .add_value_entry("kernelbase.dll", "kernel32.dll")
+            .add_value_entry("foo.dll", "kernelbase.dll"),
+        )
+        .build()
+        .unwrap()
+}
+
+/// Runs `f` with a budget of 0, 1, 2, ... bytes until it succeeds.
+/// Every failed run must report [`NtApiSetError::AllocationFailed`].
+/// Returns the number of failed runs.
+fn run_with_increasing_budget<T, F>(f: F) -> usize
+where
+    F: Fn() -> Result<T>,
+{
+    for budget in 0.. {
+        BUDGET.with(|b| b.set(budget));
+        let result = f();
+        BUDGET.with(|b| b.set(usize::MAX));
+
+        match result {
+            Ok(_) => return budget,
+            Err(NtApiSetError::AllocationFailed { .. }) => (),
+            Err(e) => panic!("unexpected error with a budget of {budget} bytes: {e}"),
+        }
+    }
+
+    unreachable!()
+}
+
+#[test]
+fn test_try_to_owned_map() {
+    let bytes = build_map();
+    let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+    let failures = run_with_increasing_budget(|| map.try_to_owned_map());
+    assert_eq!(failures, bytes.len());
+}
+
+#[test]
+fn test_try_build_index() {
+    let bytes = build_map();
+    let map = ApiSetMap::try_from_apiset_section_bytes(&bytes).unwrap();
+
+    let failures = run_with_increasing_budget(|| map.try_build_index());
+    assert!(failures > 0);
+
+    let index = map.try_build_index().unwrap();
+    assert_eq!(
+        index
+            .resolve("api-ms-win-core-foo-l1-1-0.dll", Some("kernelbase.dll"))
+            .unwrap(),
+        "kernel32.dll"
+    );
+}
+
+#[test]
+fn test_read_from_memory() {
+    let bytes = build_map();
+    let memory = Memory(&bytes);
+
+    let failures = run_with_increasing_budget(|| ApiSetMap::read_from_memory(&memory, 0));
+    assert!(failures >= bytes.len());
+}
+
+#[test]
+fn test_read_from_memory_oversized() {
+    // A corrupted header declaring a size of almost 4 GiB must not abort the process.
+    let mut bytes = build_map();
+    bytes[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+    let memory = Memory(&bytes);
+
+    BUDGET.with(|b| b.set(1024 * 1024));
+    let result = ApiSetMap::read_from_memory(&memory, 0);
+    BUDGET.with(|b| b.set(usize::MAX));
+
+    assert!(matches!(
+        result,
+        Err(NtApiSetError::AllocationFailed { .. })
+    ));
+}